@@ -1,7 +1,15 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use crate::config::parse_profile_name;
+use crate::device::{parse_pid, DeviceSelector};
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::error::ErrorKind;
+use clap::{Arg, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use librazer::error::ErrorCode;
+use librazer::state::Field;
 use librazer::types::{
-    BatteryCare, CpuBoost, GpuBoost, LightsAlwaysOn, LogoMode, MaxFanSpeedMode, PerfMode,
+    BatteryCare, Brightness, ChargeLimit, CpuBoost, FanRpm, GpuBoost, LightsAlwaysOn, LogoMode,
+    MaxFanSpeedMode, PerfMode,
 };
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "blade_helper")]
@@ -16,6 +24,47 @@ pub struct Cli {
     #[arg(long, global = true)]
     pub json: bool,
 
+    /// Device to use, by index from `devices` or by PID (e.g. 0x029f)
+    #[arg(long, global = true, value_name = "INDEX|PID")]
+    pub device: Option<DeviceSelector>,
+
+    /// Connect to the device with this USB PID, skipping model detection
+    #[arg(long, global = true, value_name = "PID", value_parser = parse_pid, conflicts_with = "device")]
+    pub pid: Option<u16>,
+
+    /// Open this HID path directly, bypassing enumeration
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "device")]
+    pub hid_path: Option<String>,
+
+    /// Open models missing from the supported list by probing their features
+    #[arg(long, global = true)]
+    pub allow_unsupported: bool,
+
+    /// Treat this laptop as the supported model with this number prefix
+    #[arg(long, global = true, value_name = "PREFIX", conflicts_with_all = ["device", "pid", "hid_path"])]
+    pub force_model: Option<String>,
+
+    /// Write every HID report and response to this JSON file
+    #[arg(long, global = true, value_name = "PATH")]
+    pub record: Option<PathBuf>,
+
+    /// Log every HID packet in full, serial numbers included (see RAZER_LOG_PACKETS)
+    #[arg(long, global = true)]
+    pub trace_hid: bool,
+
+    /// Print HID send counts and latencies when the command completes
+    #[arg(long, global = true)]
+    pub timings: bool,
+
+    /// Send without the lock that keeps other razer-ctl processes out (see RAZER_LOCK_FILE)
+    #[arg(long, global = true)]
+    pub no_lock: bool,
+
+    /// Go through the razer-dbusd service instead of opening the device (status and set only)
+    #[cfg(feature = "dbus")]
+    #[arg(long, global = true, conflicts_with_all = ["device", "pid", "hid_path", "force_model", "record", "timings", "trace_hid", "no_lock"])]
+    pub via_dbus: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -23,7 +72,24 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     /// Show current device status (all settings)
-    Status,
+    Status {
+        /// Include HID send counts and latencies
+        #[arg(long)]
+        stats: bool,
+
+        /// Show the last known state, marked stale, when the device can't be read
+        #[arg(long)]
+        cached_ok: bool,
+
+        /// Read only these settings, e.g. fan_rpm,perf_mode, sending fewer commands
+        #[arg(
+            long,
+            value_name = "FIELD,...",
+            value_delimiter = ',',
+            conflicts_with = "cached_ok"
+        )]
+        fields: Vec<Field>,
+    },
 
     /// Get a specific setting value
     Get {
@@ -38,22 +104,133 @@ pub enum Commands {
         setting: SetCommand,
     },
 
+    /// Step a setting to its next supported value, wrapping around (for keyboard shortcuts)
+    Cycle {
+        /// The setting to advance
+        #[arg(value_enum)]
+        setting: CycleSetting,
+    },
+
     /// Show device information
     Info,
 
+    /// List connected supported devices
+    Devices {
+        /// List every model blade-helper supports instead
+        #[arg(long)]
+        supported: bool,
+    },
+
+    /// Print what device detection sees, for bug reports
+    Doctor,
+
     /// Manage configuration
     Config {
         #[command(subcommand)]
         action: ConfigCommand,
     },
+
+    /// Save the current settings for `restore`
+    Save {
+        /// Also write the settings as JSON to this file, e.g. for razer-dbusd --restore
+        #[arg(long, value_name = "PATH")]
+        file: Option<PathBuf>,
+    },
+
+    /// Write back the settings kept by `save`, e.g. after a cold boot
+    Restore,
+
+    /// Save, apply and manage named sets of settings
+    Profile {
+        #[command(subcommand)]
+        action: ProfileCommand,
+    },
+
+    /// Run a command with temporary settings, restoring them when it exits
+    Run {
+        #[command(flatten)]
+        settings: RunSettings,
+
+        /// Command to run, after `--`
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+
+    /// Decode a file written with --record and read the status from it
+    #[command(hide = true)]
+    Replay {
+        /// Recording to replay
+        path: PathBuf,
+    },
+}
+
+impl Cli {
+    /// Parses the command line. With `perf_modes`, the performance mode
+    /// arguments accept and list only those modes.
+    pub fn parse_for(perf_modes: Option<&[PerfMode]>) -> Cli {
+        let mut command = Cli::command();
+        if let Some(modes) = perf_modes {
+            command = command
+                .mut_subcommand("set", |set| {
+                    set.mut_subcommand("perf", |perf| {
+                        perf.mut_arg("mode", |arg| restrict_perf_modes(arg, modes))
+                    })
+                })
+                .mut_subcommand("run", |run| {
+                    run.mut_arg("perf", |arg| restrict_perf_modes(arg, modes))
+                });
+        }
+        let mut matches = command.try_get_matches().unwrap_or_else(|e| exit_usage(e));
+        let cli = Cli::from_arg_matches_mut(&mut matches).unwrap_or_else(|e| exit_usage(e));
+        if let Commands::Set { setting } = &cli.command {
+            if let Err(message) = setting.check() {
+                exit_usage(Cli::command().error(ErrorKind::ArgumentConflict, message));
+            }
+        }
+        cli
+    }
+}
+
+/// Exits like clap does, except that usage errors exit with [`ErrorCode::Other`]
+/// instead of 2, which means no device was found.
+fn exit_usage(error: clap::Error) -> ! {
+    if !error.use_stderr() {
+        error.exit();
+    }
+    let _ = error.print();
+    std::process::exit(ErrorCode::Other.get().into())
+}
+
+fn restrict_perf_modes(arg: Arg, modes: &[PerfMode]) -> Arg {
+    let values = modes.iter().filter_map(ValueEnum::to_possible_value);
+    arg.value_parser(
+        PossibleValuesParser::new(values)
+            .map(|name| name.parse::<PerfMode>().expect("listed mode parses")),
+    )
 }
 
 #[derive(Subcommand)]
 pub enum SetCommand {
-    /// Set performance mode (balanced, silent, custom)
+    /// Set performance mode (balanced, silent, custom; `info` lists the device's)
     Perf {
         #[arg(value_enum)]
         mode: PerfMode,
+
+        /// CPU boost level to set after switching (custom only)
+        #[arg(long, value_enum)]
+        cpu: Option<CpuBoost>,
+
+        /// GPU boost level to set after switching (custom only)
+        #[arg(long, value_enum)]
+        gpu: Option<GpuBoost>,
+
+        /// Max fan speed mode to set after switching (custom only)
+        #[arg(long, value_enum)]
+        max_fan: Option<MaxFanSpeedMode>,
+
+        /// Manual fan speed in RPM to set after switching (balanced only)
+        #[arg(long)]
+        rpm: Option<FanRpm>,
     },
 
     /// Set CPU boost level (requires custom perf mode)
@@ -74,11 +251,10 @@ pub enum SetCommand {
         action: FanCommand,
     },
 
-    /// Set keyboard backlight brightness (0-255)
+    /// Set keyboard backlight brightness (0-255, or a percentage like 50%)
     Keyboard {
-        /// Brightness level (0-255)
-        #[arg(value_parser = clap::value_parser!(u8))]
-        brightness: u8,
+        /// Brightness level (0-255, or a percentage like 50%)
+        brightness: Brightness,
     },
 
     /// Set lid logo mode
@@ -93,6 +269,12 @@ pub enum SetCommand {
         mode: BatteryCare,
     },
 
+    /// Limit battery charging to a percentage (50-100, 100 for no limit)
+    ChargeLimit {
+        /// Highest charge, rounded to 5% steps (e.g. 80 or 80%)
+        limit: ChargeLimit,
+    },
+
     /// Set lights always on mode
     LightsAlwaysOn {
         #[arg(value_enum)]
@@ -100,6 +282,67 @@ pub enum SetCommand {
     },
 }
 
+impl SetCommand {
+    /// Fails if `set perf` is given settings its mode doesn't take.
+    fn check(&self) -> Result<(), String> {
+        let SetCommand::Perf {
+            mode,
+            cpu,
+            gpu,
+            max_fan,
+            rpm,
+        } = self
+        else {
+            return Ok(());
+        };
+        let custom_only = [
+            ("--cpu", cpu.is_some()),
+            ("--gpu", gpu.is_some()),
+            ("--max-fan", max_fan.is_some()),
+        ];
+        if *mode != PerfMode::Custom {
+            if let Some((flag, _)) = custom_only.iter().find(|(_, given)| *given) {
+                return Err(format!(
+                    "'{}' can only be used with 'set perf custom'",
+                    flag
+                ));
+            }
+        }
+        if *mode != PerfMode::Balanced && rpm.is_some() {
+            return Err("'--rpm' can only be used with 'set perf balanced'".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Settings applied for the duration of `run`.
+#[derive(Args)]
+pub struct RunSettings {
+    /// Performance mode
+    #[arg(long, value_enum)]
+    pub perf: Option<PerfMode>,
+
+    /// CPU boost level (requires custom perf mode)
+    #[arg(long, value_enum)]
+    pub cpu: Option<CpuBoost>,
+
+    /// GPU boost level (requires custom perf mode)
+    #[arg(long, value_enum)]
+    pub gpu: Option<GpuBoost>,
+
+    /// Manual fan speed in RPM (range depends on the model, see `info`; requires balanced perf mode)
+    #[arg(long)]
+    pub fan_rpm: Option<FanRpm>,
+
+    /// Max fan speed mode
+    #[arg(long, value_enum)]
+    pub max_fan: Option<MaxFanSpeedMode>,
+
+    /// Keyboard backlight brightness (0-255, or a percentage like 50%)
+    #[arg(long)]
+    pub keyboard: Option<Brightness>,
+}
+
 #[derive(Subcommand)]
 pub enum FanCommand {
     /// Set fan to automatic mode
@@ -107,9 +350,8 @@ pub enum FanCommand {
 
     /// Set fan to manual mode with specific RPM
     Manual {
-        /// Fan speed in RPM (2000-5000)
-        #[arg(value_parser = clap::value_parser!(u16).range(2000..=5000))]
-        rpm: u16,
+        /// Fan speed in RPM (range depends on the model, see `info`)
+        rpm: FanRpm,
     },
 
     /// Enable or disable max fan speed mode
@@ -119,6 +361,51 @@ pub enum FanCommand {
     },
 }
 
+#[derive(Subcommand)]
+pub enum ProfileCommand {
+    /// Save the current settings as a profile, replacing one with the same name
+    Save {
+        /// Profile name (letters, digits, '-' and '_')
+        #[arg(value_parser = parse_profile_name)]
+        name: String,
+    },
+
+    /// Apply a saved profile, performance mode first
+    Apply {
+        /// Profile name
+        name: String,
+    },
+
+    /// List saved profiles and their settings
+    List,
+
+    /// Write a profile as JSON, to stdout or a file, to share or keep in dotfiles
+    Export {
+        /// Profile name
+        name: String,
+
+        /// Write to this file instead of stdout
+        #[arg(long, value_name = "PATH")]
+        file: Option<PathBuf>,
+    },
+
+    /// Add a profile written by `profile export`, leaving out settings this laptop lacks
+    Import {
+        /// File written by `profile export`
+        path: PathBuf,
+
+        /// Save it under this name instead of the one in the file
+        #[arg(long = "as", value_name = "NAME", value_parser = parse_profile_name)]
+        name: Option<String>,
+    },
+
+    /// Delete a saved profile
+    Delete {
+        /// Profile name
+        name: String,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum ConfigCommand {
     /// Show current configuration
@@ -130,13 +417,34 @@ pub enum ConfigCommand {
         profile: String,
     },
 
-    /// Clear cached device PID
+    /// Pin the device to connect to (run without flags to unpin)
+    SetDevice {
+        /// USB PID to connect to, skipping model detection
+        #[arg(long, value_parser = parse_pid)]
+        pid: Option<u16>,
+
+        /// HID path to open directly
+        #[arg(long)]
+        hid_path: Option<String>,
+    },
+
+    /// Clear cached device PID, path and probed features
     ClearCache,
 
     /// Show configuration file path
     Path,
 }
 
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum CycleSetting {
+    /// Performance mode: silent, balanced, custom
+    Perf,
+    /// CPU boost level (requires custom perf mode)
+    Cpu,
+    /// GPU boost level (requires custom perf mode)
+    Gpu,
+}
+
 #[derive(Clone, Copy, Debug, ValueEnum)]
 pub enum SettingName {
     /// Performance mode
@@ -155,6 +463,8 @@ pub enum SettingName {
     Logo,
     /// Battery care mode
     BatteryCare,
+    /// Battery charge limit
+    ChargeLimit,
     /// Lights always on mode
     LightsAlwaysOn,
 }
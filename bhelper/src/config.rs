@@ -1,8 +1,16 @@
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::settings::{CachedState, SavedState};
+use librazer::descriptor;
+use librazer::profile::Profile;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 const APP_NAME: &str = "blade-helper";
+const DESCRIPTORS_FILE: &str = "descriptors.toml";
+
+/// Longest profile name `profile save` accepts.
+const MAX_PROFILE_NAME: usize = 32;
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Config {
@@ -10,13 +18,81 @@ pub struct Config {
     pub device: DeviceConfig,
     #[serde(default)]
     pub settings: SettingsConfig,
+    /// Named settings written by `profile save`, in the order they were first saved.
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct DeviceConfig {
     pub cached_pid: Option<u16>,
+    /// HID path of the interface that last worked, opened before searching by PID.
+    pub cached_path: Option<String>,
     pub model: Option<String>,
     pub model_prefix: Option<String>,
+    /// PID chosen by the user, used instead of model detection.
+    pub pid_override: Option<u16>,
+    /// HID path chosen by the user, opened directly.
+    pub hid_path: Option<String>,
+    /// PID of an unsupported model opened with `--allow-unsupported`.
+    pub probed_pid: Option<u16>,
+    /// Features found by probing `probed_pid`, reused instead of probing again.
+    pub probed_features: Option<Vec<String>>,
+    /// Descriptor used instead of model detection, for unlisted models.
+    pub force_descriptor: Option<ForcedDescriptor>,
+    /// State of the last successful read, shown by `status --cached-ok` while the
+    /// device can't be reached.
+    pub last_state: Option<CachedState>,
+    /// Settings written by `save` for `restore`; kept by `config clear-cache`.
+    pub saved_state: Option<SavedState>,
+}
+
+/// `device.force_descriptor`: a supported model number prefix, or a whole
+/// descriptor table.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ForcedDescriptor {
+    Model(String),
+    Inline(Box<descriptor::DescriptorSpec>),
+}
+
+impl std::fmt::Display for ForcedDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ForcedDescriptor::Model(model) => write!(f, "{}", model),
+            ForcedDescriptor::Inline(spec) => write!(
+                f,
+                "{} ({}, PID {:#06x})",
+                spec.name,
+                spec.model_number_prefixes.join("/"),
+                spec.pid
+            ),
+        }
+    }
+}
+
+/// Checks a name for `profile save`: letters, digits, `-` and `_`, up to 32 of
+/// them, so it can be typed without quoting.
+pub fn parse_profile_name(s: &str) -> std::result::Result<String, String> {
+    if s.is_empty() {
+        return Err("profile name can't be empty".to_string());
+    }
+    if s.len() > MAX_PROFILE_NAME {
+        return Err(format!(
+            "profile name is longer than {} characters",
+            MAX_PROFILE_NAME
+        ));
+    }
+    if let Some(c) = s
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_'))
+    {
+        return Err(format!(
+            "'{}' isn't allowed in profile names, use letters, digits, '-' and '_'",
+            c
+        ));
+    }
+    Ok(s.to_string())
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -58,21 +134,175 @@ impl ConfigManager {
         Ok(path)
     }
 
+    /// Path of the file with extra device descriptors, next to the config file.
+    pub fn descriptors_path() -> Result<PathBuf> {
+        Ok(Self::config_path()?.with_file_name(DESCRIPTORS_FILE))
+    }
+
+    /// Registers the device descriptors in [`descriptors_path`](Self::descriptors_path)
+    /// if the file exists. A broken file is reported and otherwise ignored.
+    pub fn load_descriptors() {
+        let Ok(path) = Self::descriptors_path() else {
+            return;
+        };
+        if !path.exists() {
+            return;
+        }
+        match descriptor::load_extra(&path) {
+            Ok(descriptors) => {
+                for added in descriptor::register(descriptors) {
+                    info!(
+                        "Added descriptor {} (PID {:#06x}, model {}) from {}",
+                        added.name,
+                        added.pid,
+                        added.model_number_prefixes.join("/"),
+                        path.display()
+                    );
+                }
+            }
+            Err(e) => warn!("Ignoring extra descriptors: {}", e),
+        }
+    }
+
     pub fn get_cached_pid(&self) -> Option<u16> {
         self.config.device.cached_pid
     }
 
-    pub fn set_cached_device(&mut self, pid: u16, model: &str, model_prefix: &str) -> Result<()> {
+    /// Model number prefix the cached device was detected with.
+    pub fn get_cached_model_prefix(&self) -> Option<&str> {
+        self.config.device.model_prefix.as_deref()
+    }
+
+    pub fn get_cached_path(&self) -> Option<&str> {
+        self.config.device.cached_path.as_deref()
+    }
+
+    pub fn set_cached_device(
+        &mut self,
+        pid: u16,
+        model: &str,
+        model_prefix: &str,
+        path: Option<String>,
+    ) -> Result<()> {
         self.config.device.cached_pid = Some(pid);
+        self.config.device.cached_path = path;
         self.config.device.model = Some(model.to_string());
         self.config.device.model_prefix = Some(model_prefix.to_string());
         self.save()
     }
 
+    pub fn set_pinned_device(&mut self, pid: Option<u16>, hid_path: Option<String>) -> Result<()> {
+        self.config.device.pid_override = pid;
+        self.config.device.hid_path = hid_path;
+        self.save()
+    }
+
+    pub fn get_probed_features(&self) -> Option<(u16, &[String])> {
+        let device = &self.config.device;
+        Some((device.probed_pid?, device.probed_features.as_deref()?))
+    }
+
+    pub fn set_probed_features(&mut self, pid: u16, features: &[&str]) -> Result<()> {
+        self.config.device.probed_pid = Some(pid);
+        self.config.device.probed_features = Some(features.iter().map(|f| f.to_string()).collect());
+        self.save()
+    }
+
+    pub fn get_last_state(&self) -> Option<&CachedState> {
+        self.config.device.last_state.as_ref()
+    }
+
+    pub fn set_last_state(&mut self, state: CachedState) -> Result<()> {
+        self.config.device.last_state = Some(state);
+        self.save()
+    }
+
+    pub fn get_saved_state(&self) -> Option<&SavedState> {
+        self.config.device.saved_state.as_ref()
+    }
+
+    pub fn set_saved_state(&mut self, state: SavedState) -> Result<()> {
+        self.config.device.saved_state = Some(state);
+        self.save()
+    }
+
+    pub fn profiles(&self) -> &[Profile] {
+        &self.config.profiles
+    }
+
+    /// Returns the profile called `name`, failing with [`Error::UnknownProfile`].
+    pub fn get_profile(&self, name: &str) -> Result<&Profile> {
+        self.config
+            .profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .ok_or_else(|| self.unknown_profile(name))
+    }
+
+    /// Stores `profile`, replacing the one with the same name.
+    pub fn set_profile(&mut self, profile: Profile) -> Result<()> {
+        let profiles = &mut self.config.profiles;
+        match profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => profiles.push(profile),
+        }
+        self.save()
+    }
+
+    /// Removes and returns the profile called `name`.
+    pub fn delete_profile(&mut self, name: &str) -> Result<Profile> {
+        let index = self
+            .config
+            .profiles
+            .iter()
+            .position(|profile| profile.name == name)
+            .ok_or_else(|| self.unknown_profile(name))?;
+        let profile = self.config.profiles.remove(index);
+        self.save()?;
+        Ok(profile)
+    }
+
+    fn unknown_profile(&self, name: &str) -> Error {
+        Error::UnknownProfile {
+            name: name.to_string(),
+            known: self
+                .config
+                .profiles
+                .iter()
+                .map(|p| p.name.clone())
+                .collect(),
+        }
+    }
+
+    pub fn clear_cached_path(&mut self) -> Result<()> {
+        self.config.device.cached_path = None;
+        self.save()
+    }
+
     pub fn clear_cache(&mut self) -> Result<()> {
         self.config.device.cached_pid = None;
+        self.config.device.cached_path = None;
         self.config.device.model = None;
         self.config.device.model_prefix = None;
+        self.config.device.probed_pid = None;
+        self.config.device.probed_features = None;
+        self.config.device.last_state = None;
         self.save()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_names() {
+        for name in ["gaming", "meeting-2", "Night_Mode", "a"] {
+            assert_eq!(parse_profile_name(name).as_deref(), Ok(name));
+        }
+        let too_long = "x".repeat(MAX_PROFILE_NAME + 1);
+        for name in ["", "my profile", "../gaming", "café", too_long.as_str()] {
+            assert!(parse_profile_name(name).is_err(), "{}", name);
+        }
+    }
+}
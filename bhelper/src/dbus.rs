@@ -0,0 +1,90 @@
+//! `--via-dbus`: reads and changes settings through the razer-dbusd service,
+//! for users who may not open the HID device themselves.
+
+use crate::cli::Commands;
+use crate::display;
+use crate::error::{Error, Result};
+use crate::settings::{DeviceState, SettingValue};
+use librazer::error::ErrorCode;
+
+/// Prefix of the error names razer-dbusd replies with, followed by the
+/// [`ErrorCode`] variant.
+const ERROR_PREFIX: &str = "org.razerctl.Error.";
+
+#[zbus::proxy(
+    interface = "org.razerctl.Laptop1",
+    default_service = "org.razerctl",
+    default_path = "/org/razerctl/Laptop1"
+)]
+trait Laptop {
+    fn get_state(&self) -> zbus::Result<String>;
+
+    fn apply_profile(&self, profile: &str) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn name(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn model(&self) -> zbus::Result<String>;
+}
+
+/// Runs `status` or `set` against the service.
+pub fn run(command: Commands, json: bool) -> Result<()> {
+    let connection = zbus::blocking::Connection::system()?;
+    let laptop = LaptopProxyBlocking::new(&connection)?;
+    match command {
+        Commands::Status { fields, .. } if !fields.is_empty() => {
+            return Err(Error::NotViaDbus("status --fields"))
+        }
+        Commands::Status { .. } => {
+            let state: DeviceState = serde_json::from_str(&laptop.get_state()?)
+                .map_err(|e| Error::ServiceUnavailable(e.to_string()))?;
+            let (name, model) = (laptop.name()?, laptop.model()?);
+            if json {
+                display::print_service_status_json(&name, &model, &state);
+            } else {
+                display::print_status(&name, &model, &state);
+            }
+        }
+        Commands::Set { setting } => {
+            let steps = crate::set_values(setting);
+            let values: Vec<SettingValue> = steps.iter().map(|(_, value)| value.clone()).collect();
+            let profile =
+                SettingValue::profile_of(&values).ok_or(Error::NotViaDbus("The charge limit"))?;
+            let profile = serde_json::to_string(&profile).unwrap();
+            laptop.apply_profile(&profile)?;
+            crate::print_set(&steps, json);
+        }
+        _ => return Err(Error::NotViaDbus("This command")),
+    }
+    Ok(())
+}
+
+impl From<zbus::Error> for Error {
+    fn from(error: zbus::Error) -> Self {
+        service_error(error)
+    }
+}
+
+/// Maps the service's error names back to their [`ErrorCode`].
+fn service_error(error: zbus::Error) -> Error {
+    let zbus::Error::MethodError(name, message, _) = &error else {
+        return Error::ServiceUnavailable(error.to_string());
+    };
+    let Some(kind) = name.as_str().strip_prefix(ERROR_PREFIX) else {
+        return Error::ServiceUnavailable(error.to_string());
+    };
+    let code = match kind {
+        "DeviceNotFound" => ErrorCode::DeviceNotFound,
+        "Permission" | "AccessDenied" => ErrorCode::Permission,
+        "Unsupported" => ErrorCode::Unsupported,
+        "Precondition" => ErrorCode::Precondition,
+        "Busy" => ErrorCode::Busy,
+        "Protocol" => ErrorCode::Protocol,
+        _ => ErrorCode::Other,
+    };
+    Error::Service {
+        code,
+        message: message.clone().unwrap_or_else(|| kind.to_string()),
+    }
+}
@@ -1,76 +1,368 @@
-use crate::config::ConfigManager;
+use crate::config::{ConfigManager, ForcedDescriptor};
 use crate::error::{Error, Result};
-use crate::settings::{DeviceState, Setting, SettingValue};
-use librazer::{command, descriptor, device, types};
-use log::debug;
-
-/// Check if a Razer USB device is physically connected (Linux only).
-/// This checks /sys directly, bypassing hidapi permissions.
-#[cfg(target_os = "linux")]
-fn razer_device_exists() -> bool {
-    use std::fs;
-    use std::path::Path;
-
-    let usb_devices = Path::new("/sys/bus/usb/devices");
-    if let Ok(entries) = fs::read_dir(usb_devices) {
-        for entry in entries.flatten() {
-            let vendor_path = entry.path().join("idVendor");
-            if let Ok(vendor) = fs::read_to_string(&vendor_path) {
-                if vendor.trim().eq_ignore_ascii_case("1532") {
-                    return true;
-                }
-            }
+use crate::settings::{CachedState, DeviceState, SavedState, Setting, SettingValue};
+use colored::Colorize;
+use librazer::error::RazerError;
+use librazer::persist::FieldOutcome;
+use librazer::profile::Profile;
+use librazer::replay::{Recorder, Recording, ReplayTransport};
+use librazer::state::{Field, PartialState};
+use librazer::{descriptor, device, types, RazerLaptop};
+use log::{debug, warn};
+use std::ffi::CString;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Asks which device to use when several supported ones are connected. Without a
+/// terminal to ask on, the first one, matching the laptop model, is used.
+fn prompt_for_device(candidates: &[descriptor::Descriptor]) -> usize {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    if !std::io::stdin().is_terminal() {
+        warn!(
+            "Several supported devices are connected, using {}. Pass --device to choose.",
+            candidates[0].name
+        );
+        return 0;
+    }
+
+    eprintln!("Several supported devices are connected:");
+    for (index, desc) in candidates.iter().enumerate() {
+        eprintln!("  [{}] {} (PID {:#06x})", index, desc.name, desc.pid);
+    }
+    let mut stdin = std::io::stdin().lock();
+    loop {
+        eprint!("Select device [0]: ");
+        let _ = std::io::stderr().flush();
+        let mut line = String::new();
+        if stdin.read_line(&mut line).unwrap_or(0) == 0 {
+            return 0;
+        }
+        match line.trim() {
+            "" => return 0,
+            input => match input.parse::<usize>() {
+                Ok(index) if index < candidates.len() => return index,
+                _ => eprintln!("Enter a number from 0 to {}", candidates.len() - 1),
+            },
+        }
+    }
+}
+
+/// Selects one of several connected devices, by list index or USB PID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceSelector {
+    Index(usize),
+    Pid(u16),
+}
+
+/// Parses a hex USB PID such as `0x029f`.
+pub fn parse_pid(s: &str) -> std::result::Result<u16, String> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u16::from_str_radix(hex, 16).map_err(|e| format!("invalid PID '{}': {}", s, e))
+}
+
+impl std::str::FromStr for DeviceSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.starts_with("0x") || s.starts_with("0X") {
+            parse_pid(s).map(DeviceSelector::Pid)
+        } else {
+            s.parse::<usize>().map(DeviceSelector::Index).map_err(|_| {
+                format!(
+                    "expected a device index or a hex PID like 0x029f, got '{}'",
+                    s
+                )
+            })
+        }
+    }
+}
+
+impl std::fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceSelector::Index(index) => write!(f, "#{}", index),
+            DeviceSelector::Pid(pid) => write!(f, "PID {:#06x}", pid),
         }
     }
-    false
 }
 
-#[cfg(not(target_os = "linux"))]
-fn razer_device_exists() -> bool {
-    false
+/// How the user asked to reach the device, from CLI flags.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectOptions {
+    pub selector: Option<DeviceSelector>,
+    pub pid: Option<u16>,
+    pub hid_path: Option<String>,
+    pub allow_unsupported: bool,
+    /// Model number prefix of the supported model to treat this laptop as.
+    pub force_model: Option<String>,
+    /// File to write the session's HID exchanges to.
+    pub record: Option<PathBuf>,
+    /// Print send statistics when the device is closed.
+    pub timings: bool,
+    /// Send without the cross-process device lock.
+    pub no_lock: bool,
 }
 
+/// A [`RazerLaptop`] with the CLI's connection, caching and recording around it.
 pub struct BladeDevice {
-    inner: device::Device,
+    laptop: RazerLaptop,
+    recording: Option<(Recorder, PathBuf)>,
+    timings: bool,
+    /// Save every state read as the config's last state. Off for replays.
+    cache_state: bool,
+}
+
+/// Result of [`BladeDevice::read_state_cached`].
+pub enum StateReading {
+    Live(Box<BladeDevice>, DeviceState),
+    /// The device couldn't be read, this is what it last reported.
+    Stale(CachedState),
 }
 
 impl BladeDevice {
+    /// Retry policy for CLI use: a stray HID hiccup shouldn't fail the whole command.
+    const SEND_OPTIONS: device::SendOptions = device::SendOptions {
+        retries: 2,
+        timeout: Duration::from_secs(2),
+        ..device::SendOptions::DEFAULT
+    };
+
+    /// Upper bound for auto-detection, so a stalled USB hub fails instead of hanging.
+    const DETECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn wrap(mut inner: device::Device) -> Self {
+        inner.set_send_options(Self::SEND_OPTIONS);
+        Self {
+            laptop: RazerLaptop::new(inner),
+            recording: None,
+            timings: false,
+            cache_state: false,
+        }
+    }
+
     pub fn detect() -> Result<Self> {
-        let inner = device::Device::detect().map_err(|e| {
-            let err_msg = e.to_string().to_lowercase();
+        let options = device::DetectOptions {
+            timeout: Some(Self::DETECT_TIMEOUT),
+            ..Default::default()
+        }
+        .on_progress(|step| debug!("{}", step))
+        .on_select(prompt_for_device);
+        let inner = device::Device::detect_with(options).map_err(|e| match e {
+            // Keep the details, they are what a bug report needs
+            RazerError::UnsupportedModel { .. }
+            | RazerError::AmbiguousDevice { .. }
+            | RazerError::DetectTimeout(_)
+            | RazerError::PermissionDenied { .. } => Error::Device(e),
+            _ => Error::DeviceNotFound,
+        })?;
+        Ok(Self::wrap(inner))
+    }
+
+    /// Connects using the CLI options, then any device pinned in the config,
+    /// and finally auto-detection.
+    pub fn connect(options: &ConnectOptions) -> Result<Self> {
+        let mut device = Self::open(options)?;
+        if options.no_lock {
+            device.laptop.device_mut().set_lock(None);
+        }
+        if let Some(path) = &options.record {
+            debug!("Recording HID exchanges to {}", path.display());
+            device.recording = Some((device.laptop.device_mut().start_recording(), path.clone()));
+        }
+        device.timings = options.timings;
+        device.cache_state = true;
+        Ok(device)
+    }
 
-            // Skip permission check if the error is about invalid arguments (protocol issue)
-            if err_msg.contains("einval") || err_msg.contains("invalid argument") {
-                return Error::DeviceNotFound;
+    /// Opens a device that answers from a recorded session.
+    pub fn replay(recording: &Recording) -> Self {
+        let mut desc = device::Device::descriptor_for_pid(recording.pid);
+        if desc.features.is_empty() {
+            // unknown model: decode whatever was recorded
+            desc = desc.with_features(librazer::feature::ALL_FEATURES);
+        }
+        Self::wrap(device::Device::with_transport(
+            ReplayTransport::new(recording),
+            desc,
+        ))
+    }
+
+    fn open(options: &ConnectOptions) -> Result<Self> {
+        #[cfg(feature = "simulation")]
+        if librazer::simulation::enabled() {
+            return Ok(Self::wrap(librazer::simulation::device()));
+        }
+
+        if let Some(path) = &options.hid_path {
+            return Self::open_path(path, options.pid);
+        }
+        if let Some(pid) = options.pid {
+            return Self::open_pid(pid);
+        }
+        if let Some(selector) = options.selector {
+            return Self::select(selector);
+        }
+        if let Some(model) = &options.force_model {
+            return Self::force(ForcedDescriptor::Model(model.clone()));
+        }
+
+        if let Ok(config_mgr) = ConfigManager::load() {
+            let device_config = &config_mgr.config().device;
+            if let Some(path) = &device_config.hid_path {
+                debug!("Using HID path from config: {}", path);
+                return Self::open_path(path, device_config.pid_override);
+            }
+            if let Some(pid) = device_config.pid_override {
+                debug!("Using PID from config: {:#06x}", pid);
+                return Self::open_pid(pid);
             }
+            if let Some(forced) = &device_config.force_descriptor {
+                debug!("Using descriptor from config: {:?}", forced);
+                return Self::force(forced.clone());
+            }
+        }
+
+        match Self::detect_with_cache() {
+            Err(e) if options.allow_unsupported => {
+                debug!("Detection failed ({}), trying unsupported models", e);
+                Self::detect_unsupported()
+            }
+            result => result,
+        }
+    }
 
-            // Check for permission-related errors
-            if err_msg.contains("permission")
-                || err_msg.contains("access denied")
-                || err_msg.contains("operation not permitted")
-            {
-                return Error::PermissionDenied;
+    /// Opens the forced descriptor regardless of the laptop model.
+    fn force(forced: ForcedDescriptor) -> Result<Self> {
+        let desc = match forced {
+            ForcedDescriptor::Model(model) => {
+                descriptor::find_by_model(&model).ok_or(Error::UnknownModel(model))?
             }
+            ForcedDescriptor::Inline(spec) => spec.build()?,
+        };
+        eprintln!(
+            "{} forcing {} ({}, PID {:#06x}). This model is not verified for this \
+             laptop; settings may misbehave.",
+            "Warning:".yellow().bold(),
+            desc.name,
+            desc.model_number_prefixes.join("/"),
+            desc.pid
+        );
+        let inner = device::Device::detect_as(desc)?;
+        Ok(Self::wrap(inner))
+    }
+
+    /// Opens a model missing from the supported list, reusing probed features
+    /// from the config when available.
+    pub fn detect_unsupported() -> Result<Self> {
+        let mut config_mgr = ConfigManager::load().ok();
+        let cached = config_mgr
+            .as_ref()
+            .and_then(|c| c.get_probed_features())
+            .map(|(pid, features)| (pid, features.to_vec()));
+        if let Some((pid, features)) = cached {
+            debug!("Trying probed PID {:#06x} with cached features", pid);
+            let desc = descriptor::generic(pid).with_features(&features);
+            if let Ok(inner) = device::Device::new(desc) {
+                return Ok(Self::wrap(inner));
+            }
+        }
 
-            // On Linux, if device exists in /sys but hidapi can't see it, likely permissions
-            if razer_device_exists() {
-                return Error::PermissionDenied;
+        let inner = device::Device::detect_unsupported()?;
+        let pid = inner.info.pid;
+        if descriptor::find_by_pid(pid).is_none() {
+            if let Some(config_mgr) = config_mgr.as_mut() {
+                let _ = config_mgr.set_probed_features(pid, inner.info.features);
             }
+        }
+        Ok(Self::wrap(inner))
+    }
 
-            Error::DeviceNotFound
+    pub fn open_pid(pid: u16) -> Result<Self> {
+        let inner = device::Device::new_with_pid(pid)?;
+        Ok(Self::wrap(inner))
+    }
+
+    /// Opens a HID path directly. Without a PID, the descriptor is taken from
+    /// the enumerated interface at that path.
+    pub fn open_path(path: &str, pid: Option<u16>) -> Result<Self> {
+        let c_path = CString::new(path).map_err(|_| Error::InvalidHidPath {
+            path: path.to_string(),
+            reason: "path contains a NUL byte".to_string(),
         })?;
-        Ok(Self { inner })
+
+        let desc = match pid {
+            Some(pid) => device::Device::descriptor_for_pid(pid),
+            None => Self::list()?
+                .into_iter()
+                .find(|(_, info)| info.path == c_path)
+                .map(|(desc, _)| desc)
+                .ok_or_else(|| Error::InvalidHidPath {
+                    path: path.to_string(),
+                    reason: "no supported device at this path, pass --pid to force one".to_string(),
+                })?,
+        };
+
+        let inner = device::Device::new_at_path(&c_path, desc)?;
+        Ok(Self::wrap(inner))
+    }
+
+    /// Lists every connected interface that belongs to a supported device.
+    pub fn list() -> Result<Vec<(descriptor::Descriptor, device::DevicePathInfo)>> {
+        Ok(device::Device::enumerate_supported()?)
+    }
+
+    pub fn select(selector: DeviceSelector) -> Result<Self> {
+        debug!("Selecting device {}", selector);
+        let devices = Self::list()?;
+        let inner = match selector {
+            DeviceSelector::Index(index) => {
+                let (_, info) = devices
+                    .get(index)
+                    .ok_or(Error::NoMatchingDevice(selector))?;
+                device::Device::open(info)?
+            }
+            DeviceSelector::Pid(pid) => {
+                let (desc, _) = devices
+                    .iter()
+                    .find(|(desc, _)| desc.pid == pid)
+                    .ok_or(Error::NoMatchingDevice(selector))?;
+                device::Device::new(desc.clone())?
+            }
+        };
+        Ok(Self::wrap(inner))
     }
 
     pub fn detect_with_cache() -> Result<Self> {
-        // Try to load config and use cached PID
-        if let Ok(config_mgr) = ConfigManager::load() {
+        // Try the cached HID path first, then the cached PID
+        if let Ok(mut config_mgr) = ConfigManager::load() {
             if let Some(cached_pid) = config_mgr.get_cached_pid() {
-                debug!("Trying cached PID: {:#06x}", cached_pid);
-                if let Some(desc) = descriptor::SUPPORTED.iter().find(|d| d.pid == cached_pid) {
+                if let Some(mut desc) = descriptor::find_by_pid(cached_pid) {
+                    if let Some(prefix) = config_mgr.get_cached_model_prefix() {
+                        desc = desc.for_model(prefix);
+                    }
+                    if let Some(path) = config_mgr.get_cached_path().map(str::to_string) {
+                        debug!("Trying cached path: {}", path);
+                        let opened = CString::new(path)
+                            .ok()
+                            .and_then(|path| device::Device::new_at_path(&path, desc.clone()).ok());
+                        if let Some(inner) = opened {
+                            debug!("Successfully connected using cached path");
+                            return Ok(Self::wrap(inner));
+                        }
+                        debug!("Cached path failed, invalidating it");
+                        let _ = config_mgr.clear_cached_path();
+                    }
+
+                    debug!("Trying cached PID: {:#06x}", cached_pid);
                     if let Ok(inner) = device::Device::new(desc.clone()) {
                         debug!("Successfully connected using cached PID");
-                        return Ok(Self { inner });
+                        let device = Self::wrap(inner);
+                        device.update_cache();
+                        return Ok(device);
                     }
                 }
                 debug!("Cached PID failed, falling back to full detection");
@@ -79,185 +371,561 @@ impl BladeDevice {
 
         // Fall back to full detection
         let device = Self::detect()?;
+        device.update_cache();
+        Ok(device)
+    }
 
-        // Cache the detected device
+    /// Remembers this device so the next start can open it directly.
+    fn update_cache(&self) {
         if let Ok(mut config_mgr) = ConfigManager::load() {
-            let _ = config_mgr.set_cached_device(device.pid(), device.name(), device.model());
+            let path = self
+                .laptop
+                .device()
+                .hid_path()
+                .map(|path| path.to_string_lossy().into_owned());
+            let _ = config_mgr.set_cached_device(self.pid(), self.name(), self.model(), path);
         }
-
-        Ok(device)
     }
 
     pub fn name(&self) -> &str {
-        self.inner.info.name
+        self.laptop.info().name
     }
 
     pub fn model(&self) -> &str {
-        self.inner.info.model_number_prefix
+        self.laptop.info().model_number_prefix()
     }
 
     pub fn pid(&self) -> u16 {
-        self.inner.info.pid
+        self.laptop.info().pid
     }
 
     pub fn features(&self) -> &[&str] {
-        self.inner.info.features
+        self.laptop.features()
     }
 
-    pub fn supports(&self, feature: &str) -> bool {
-        self.inner.info.features.contains(&feature)
+    /// Manual fan speeds this model accepts, in RPM.
+    pub fn fan_rpm_range(&self) -> &std::ops::RangeInclusive<u16> {
+        &self.laptop.info().fan_rpm_range
     }
 
-    pub fn read_state(&self) -> Result<DeviceState> {
-        let mut state = DeviceState::default();
+    pub fn perf_modes(&self) -> &[types::PerfMode] {
+        self.laptop.info().perf_modes
+    }
 
-        // Performance mode
-        if let Ok((perf_mode, fan_mode)) = command::get_perf_mode(&self.inner) {
-            state.perf_mode = Some(perf_mode);
-            state.fan_mode = Some(fan_mode);
+    pub fn cpu_boosts(&self) -> &[types::CpuBoost] {
+        self.laptop.info().cpu_boosts
+    }
 
-            if perf_mode == types::PerfMode::Custom {
-                state.cpu_boost = command::get_cpu_boost(&self.inner).ok();
-                state.gpu_boost = command::get_gpu_boost(&self.inner).ok();
-            }
+    pub fn gpu_boosts(&self) -> &[types::GpuBoost] {
+        self.laptop.info().gpu_boosts
+    }
+
+    /// Keyboard lighting controls, `None` without a keyboard backlight.
+    pub fn lighting(&self) -> Option<descriptor::LightingCapability> {
+        self.supports("kbd-backlight")
+            .then_some(self.laptop.info().lighting)
+    }
+
+    /// Where the descriptor's PID and command behavior came from.
+    pub fn source(&self) -> (&'static str, &'static str) {
+        (self.laptop.info().notes, self.laptop.info().source_url)
+    }
+
+    pub fn quirks(&self) -> Vec<(&'static str, String)> {
+        self.laptop.info().active_quirks()
+    }
+
+    pub fn interface(&self) -> Option<&device::DevicePathInfo> {
+        self.laptop.device().interface()
+    }
+
+    pub fn stats(&self) -> device::DeviceStats {
+        self.laptop.device().stats()
+    }
 
-            if fan_mode == types::FanMode::Manual {
-                state.fan_rpm = command::get_fan_rpm(&self.inner, types::FanZone::Zone1).ok();
+    pub fn health(&self) -> device::DeviceHealth {
+        self.laptop.device().health()
+    }
+
+    pub fn identity(&self) -> &device::Identity {
+        self.laptop.device().identity()
+    }
+
+    pub fn supports(&self, feature: &str) -> bool {
+        self.laptop.supports(feature)
+    }
+
+    /// Reads every setting. Unless nothing could be read, the state is also
+    /// saved for [`read_state_cached`](Self::read_state_cached).
+    pub fn read_state(&self) -> Result<DeviceState> {
+        let state = self.laptop.snapshot();
+        if self.cache_state && state != DeviceState::default() {
+            if let Ok(mut config_mgr) = ConfigManager::load() {
+                let cached = CachedState::new(self.name(), self.model(), state.clone());
+                if let Err(e) = config_mgr.set_last_state(cached) {
+                    debug!("Failed to cache the device state: {}", e);
+                }
             }
         }
+        Ok(state)
+    }
 
-        // Max fan speed mode
-        state.max_fan_speed = command::get_max_fan_speed_mode(&self.inner).ok();
-
-        // Keyboard brightness
-        if self.supports("kbd-backlight") {
-            state.keyboard_brightness = command::get_keyboard_brightness(&self.inner).ok();
+    /// Connects and reads every setting, falling back to the last state read when
+    /// the device can't be opened or doesn't answer any read. A cached state older
+    /// than `max_age` isn't used; the live error or empty state is returned instead.
+    pub fn read_state_cached(options: &ConnectOptions, max_age: Duration) -> Result<StateReading> {
+        let live = Self::connect(options).and_then(|device| {
+            let state = device.read_state()?;
+            Ok((device, state))
+        });
+        let reachable = matches!(&live, Ok((_, state)) if *state != DeviceState::default());
+        if !reachable {
+            let cached = ConfigManager::load()
+                .ok()
+                .and_then(|config_mgr| config_mgr.get_last_state().cloned())
+                .filter(|cached| cached.age() <= max_age);
+            if let Some(cached) = cached {
+                match &live {
+                    Err(e) => debug!("Device unavailable ({}), using the cached state", e),
+                    Ok(_) => debug!("No setting could be read, using the cached state"),
+                }
+                return Ok(StateReading::Stale(cached));
+            }
         }
+        let (device, state) = live?;
+        Ok(StateReading::Live(Box::new(device), state))
+    }
 
-        // Battery care
-        if self.supports("battery-care") {
-            state.battery_care = command::get_battery_care(&self.inner).ok();
+    /// Reads every setting and keeps it in the config for
+    /// [`restore_state`](Self::restore_state). Fails if nothing could be read.
+    pub fn save_state(&self, config_mgr: &mut ConfigManager) -> Result<SavedState> {
+        let state = self.laptop.snapshot();
+        if state == DeviceState::default() {
+            return Err(RazerError::Other("No setting could be read".to_string()).into());
         }
+        let saved = SavedState::new(self.name(), self.model(), &state);
+        config_mgr.set_saved_state(saved.clone())?;
+        Ok(saved)
+    }
 
-        // Logo mode
-        if self.supports("lid-logo") {
-            state.logo_mode = command::get_logo_mode(&self.inner).ok();
+    /// Writes back the settings [`save_state`](Self::save_state) kept, reporting
+    /// each one; see [`SavedSettings::restore_to`](librazer::persist::SavedSettings::restore_to)
+    /// for the order and what is skipped.
+    pub fn restore_state(&self, config_mgr: &ConfigManager) -> Result<Vec<FieldOutcome>> {
+        let saved = config_mgr.get_saved_state().ok_or(Error::NoSavedState)?;
+        if !saved.model.is_empty() && saved.model != self.model() {
+            warn!(
+                "Restoring settings saved on {} ({}) to {} ({})",
+                saved.name,
+                saved.model,
+                self.name(),
+                self.model()
+            );
         }
+        Ok(saved.settings.restore_to(self.laptop.device()))
+    }
 
-        // Lights always on
-        if self.supports("lights-always-on") {
-            state.lights_always_on = command::get_lights_always_on(&self.inner).ok();
+    /// Reads every setting into a profile called `name`. Fails if nothing could
+    /// be read.
+    pub fn capture_profile(&self, name: &str) -> Result<Profile> {
+        let profile = Profile::capture(name, self.laptop.device());
+        if profile.settings.is_empty() {
+            return Err(RazerError::Other("No setting could be read".to_string()).into());
         }
+        Ok(profile)
+    }
 
-        Ok(state)
+    /// Applies `profile` like [`restore_state`](Self::restore_state), reporting
+    /// each setting.
+    pub fn apply_profile(&self, profile: &Profile) -> Vec<FieldOutcome> {
+        self.laptop.apply(profile)
+    }
+
+    /// Removes the settings of `profile` this model can't take, e.g. one
+    /// captured on another model, and reports why each was removed.
+    pub fn fit_profile(&self, profile: &mut Profile) -> Vec<FieldOutcome> {
+        profile.strip_unsupported(self.laptop.info())
+    }
+
+    /// Reads only `fields`; unlike [`read_state`](Self::read_state) this isn't cached.
+    pub fn read_fields(&self, fields: &[Field]) -> PartialState {
+        self.laptop.read_fields(fields)
     }
 
     pub fn get_setting(&self, setting: Setting) -> Result<SettingValue> {
-        match setting {
+        let laptop = &self.laptop;
+        Ok(match setting {
             Setting::PerfMode => {
-                let (mode, fan_mode) = command::get_perf_mode(&self.inner)?;
-                Ok(SettingValue::PerfMode { mode, fan_mode })
-            }
-            Setting::CpuBoost => {
-                let boost = command::get_cpu_boost(&self.inner)?;
-                Ok(SettingValue::CpuBoost(boost))
-            }
-            Setting::GpuBoost => {
-                let boost = command::get_gpu_boost(&self.inner)?;
-                Ok(SettingValue::GpuBoost(boost))
+                let (mode, fan_mode) = laptop.perf_mode()?;
+                SettingValue::PerfMode { mode, fan_mode }
             }
+            Setting::CpuBoost => SettingValue::CpuBoost(laptop.cpu_boost()?.into_known()?),
+            Setting::GpuBoost => SettingValue::GpuBoost(laptop.gpu_boost()?.into_known()?),
             Setting::FanMode => {
-                let (_, fan_mode) = command::get_perf_mode(&self.inner)?;
-                let rpm = if fan_mode == types::FanMode::Manual {
-                    Some(command::get_fan_rpm(&self.inner, types::FanZone::Zone1)?)
+                let mode = laptop.fan_mode()?;
+                let rpm = if mode == types::FanMode::Manual {
+                    Some(laptop.fan_rpm()?)
                 } else {
                     None
                 };
-                Ok(SettingValue::Fan {
-                    mode: fan_mode,
-                    rpm,
-                })
-            }
-            Setting::MaxFanSpeed => {
-                let mode = command::get_max_fan_speed_mode(&self.inner)?;
-                Ok(SettingValue::MaxFanSpeed(mode))
+                SettingValue::Fan { mode, rpm }
             }
+            Setting::MaxFanSpeed => SettingValue::MaxFanSpeed(laptop.max_fan_speed()?),
             Setting::KeyboardBrightness => {
-                if !self.supports("kbd-backlight") {
-                    return Err(Error::FeatureNotSupported("kbd-backlight".to_string()));
-                }
-                let brightness = command::get_keyboard_brightness(&self.inner)?;
-                Ok(SettingValue::KeyboardBrightness(brightness))
+                SettingValue::KeyboardBrightness(laptop.keyboard_brightness()?)
             }
-            Setting::LogoMode => {
-                if !self.supports("lid-logo") {
-                    return Err(Error::FeatureNotSupported("lid-logo".to_string()));
-                }
-                let mode = command::get_logo_mode(&self.inner)?;
-                Ok(SettingValue::LogoMode(mode))
+            Setting::LogoMode => SettingValue::LogoMode(laptop.logo_mode()?),
+            Setting::BatteryCare => SettingValue::BatteryCare(laptop.battery_care()?),
+            Setting::ChargeLimit => SettingValue::ChargeLimit(laptop.charge_limit()?),
+            Setting::LightsAlwaysOn => SettingValue::LightsAlwaysOn(laptop.lights_always_on()?),
+        })
+    }
+
+    /// Applies `changes` until the returned guard is dropped, which puts the
+    /// affected settings back the way they were.
+    ///
+    /// The settings are read before anything is changed. Restoring is best-effort:
+    /// failures are logged, not returned.
+    pub fn scoped(&self, changes: Vec<SettingValue>) -> Result<RestoreGuard<'_>> {
+        for change in &changes {
+            self.check_setting(change)?;
+        }
+        let mut settings = Vec::new();
+        for change in &changes {
+            let setting = change.setting();
+            // set_perf_mode resets the fan to auto, so remember it too
+            if setting == Setting::PerfMode && self.supports("fan") {
+                settings.push(Setting::FanMode);
             }
-            Setting::BatteryCare => {
-                if !self.supports("battery-care") {
-                    return Err(Error::FeatureNotSupported("battery-care".to_string()));
-                }
-                let care = command::get_battery_care(&self.inner)?;
-                Ok(SettingValue::BatteryCare(care))
+            settings.push(setting);
+        }
+        let mut snapshot: Vec<SettingValue> = Vec::new();
+        for setting in settings {
+            if !snapshot.iter().any(|value| value.setting() == setting) {
+                snapshot.push(self.get_setting(setting)?);
             }
-            Setting::LightsAlwaysOn => {
-                if !self.supports("lights-always-on") {
-                    return Err(Error::FeatureNotSupported("lights-always-on".to_string()));
+        }
+        debug!("Saved settings to restore: {:?}", snapshot);
+
+        let guard = RestoreGuard {
+            device: self,
+            snapshot,
+        };
+        for change in changes {
+            self.apply_setting(change)?;
+        }
+        Ok(guard)
+    }
+
+    /// Fails if this model cannot take `value`, before anything is sent.
+    fn check_setting(&self, value: &SettingValue) -> Result<()> {
+        match *value {
+            SettingValue::PerfMode { mode, .. } => self.laptop.info().check_perf_mode(mode)?,
+            SettingValue::CpuBoost(boost) => self.laptop.info().check_cpu_boost(boost)?,
+            SettingValue::GpuBoost(boost) => self.laptop.info().check_gpu_boost(boost)?,
+            SettingValue::Fan { rpm: Some(rpm), .. } => self.laptop.info().check_fan_rpm(rpm)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Applies `steps` in order, each after the one before has been written, all
+    /// checked before the first. If one fails, the performance mode, fan and boosts
+    /// are set back the way they were and the error names the step.
+    pub fn apply_steps(&self, steps: &[(&'static str, SettingValue)]) -> Result<()> {
+        for (_, value) in steps {
+            self.check_setting(value)?;
+        }
+        let before = self
+            .laptop
+            .read_fields(&[
+                Field::PerfMode,
+                Field::FanMode,
+                Field::FanRpm,
+                Field::CpuBoost,
+                Field::GpuBoost,
+                Field::MaxFanSpeed,
+            ])
+            .into_state();
+        debug!("Settings before the steps: {:?}", before);
+
+        for (index, (name, value)) in steps.iter().enumerate() {
+            if let Err(source) = self.apply_setting(value.clone()) {
+                let restored = before.apply_to(self.laptop.device());
+                if let Err(e) = &restored {
+                    warn!("Failed to set back the settings before the steps: {}", e);
                 }
-                let lights = command::get_lights_always_on(&self.inner)?;
-                Ok(SettingValue::LightsAlwaysOn(lights))
+                return Err(Error::StepFailed {
+                    step: index + 1,
+                    total: steps.len(),
+                    setting: format!("{} to {}", name, value),
+                    restored: restored.is_ok(),
+                    source: Box::new(source),
+                });
             }
         }
+        Ok(())
     }
 
+    /// Applies `value`; settings this model lacks or can't take fail before
+    /// anything is sent.
     pub fn apply_setting(&self, value: SettingValue) -> Result<()> {
+        let laptop = &self.laptop;
         match value {
-            SettingValue::PerfMode { mode, .. } => {
-                command::set_perf_mode(&self.inner, mode)?;
-            }
-            SettingValue::CpuBoost(boost) => {
-                command::set_cpu_boost(&self.inner, boost)?;
-            }
-            SettingValue::GpuBoost(boost) => {
-                command::set_gpu_boost(&self.inner, boost)?;
-            }
+            SettingValue::PerfMode { mode, .. } => laptop.set_perf_mode(mode)?,
+            SettingValue::CpuBoost(boost) => laptop.set_cpu_boost(boost)?,
+            SettingValue::GpuBoost(boost) => laptop.set_gpu_boost(boost)?,
             SettingValue::Fan { mode, rpm } => {
-                command::set_fan_mode(&self.inner, mode)?;
+                // check the speed before the mode switch is sent
+                self.check_setting(&value)?;
+                laptop.set_fan_mode(mode)?;
                 if let Some(rpm) = rpm {
-                    command::set_fan_rpm(&self.inner, rpm)?;
+                    laptop.set_fan_rpm(rpm)?;
                 }
             }
-            SettingValue::MaxFanSpeed(mode) => {
-                command::set_max_fan_speed_mode(&self.inner, mode)?;
-            }
+            SettingValue::MaxFanSpeed(mode) => laptop.set_max_fan_speed(mode)?,
             SettingValue::KeyboardBrightness(brightness) => {
-                if !self.supports("kbd-backlight") {
-                    return Err(Error::FeatureNotSupported("kbd-backlight".to_string()));
-                }
-                command::set_keyboard_brightness(&self.inner, brightness)?;
-            }
-            SettingValue::LogoMode(mode) => {
-                if !self.supports("lid-logo") {
-                    return Err(Error::FeatureNotSupported("lid-logo".to_string()));
-                }
-                command::set_logo_mode(&self.inner, mode)?;
+                laptop.set_keyboard_brightness(brightness)?
             }
-            SettingValue::BatteryCare(care) => {
-                if !self.supports("battery-care") {
-                    return Err(Error::FeatureNotSupported("battery-care".to_string()));
-                }
-                command::set_battery_care(&self.inner, care)?;
+            SettingValue::LogoMode(mode) => laptop.set_logo_mode(mode)?,
+            SettingValue::BatteryCare(care) => laptop.set_battery_care(care)?,
+            SettingValue::ChargeLimit(limit) => laptop.set_charge_limit(limit)?,
+            SettingValue::LightsAlwaysOn(lights) => laptop.set_lights_always_on(lights)?,
+        }
+        Ok(())
+    }
+}
+
+/// Settings saved by [`BladeDevice::scoped`], restored on drop.
+pub struct RestoreGuard<'a> {
+    device: &'a BladeDevice,
+    snapshot: Vec<SettingValue>,
+}
+
+impl Drop for RestoreGuard<'_> {
+    fn drop(&mut self) {
+        // newest first, so e.g. boosts are restored while still in custom mode
+        for value in self.snapshot.drain(..).rev() {
+            debug!("Restoring {:?}", value);
+            if let Err(e) = self.device.apply_setting(value.clone()) {
+                warn!("Failed to restore {:?}: {}", value, e);
             }
-            SettingValue::LightsAlwaysOn(lights) => {
-                if !self.supports("lights-always-on") {
-                    return Err(Error::FeatureNotSupported("lights-always-on".to_string()));
-                }
-                command::set_lights_always_on(&self.inner, lights)?;
+        }
+    }
+}
+
+impl Drop for BladeDevice {
+    fn drop(&mut self) {
+        if self.timings {
+            crate::display::print_timings(&self.laptop.device().stats());
+        }
+        if let Some((recorder, path)) = &self.recording {
+            match recorder.save(path) {
+                Ok(()) => debug!("Saved recording to {}", path.display()),
+                Err(e) => log::warn!("Failed to save recording: {}", e),
             }
         }
-        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use librazer::command::cmd;
+    use librazer::descriptor::SUPPORTED;
+    use librazer::feature;
+    use librazer::transport::MockTransport;
+    use librazer::types::{Brightness, FanMode, FanRpm};
+
+    /// A BladeDevice on a mock that echoes every report unless told otherwise.
+    fn mocked() -> (BladeDevice, MockTransport) {
+        let mock = MockTransport::new();
+        let desc = SUPPORTED[0].with_features(feature::ALL_FEATURES);
+        let device = BladeDevice::wrap(device::Device::with_transport(mock.clone(), desc));
+        (device, mock)
+    }
+
+    fn commands(mock: &MockTransport) -> Vec<u16> {
+        mock.sent_commands()
+            .into_iter()
+            .map(|(command, _)| command)
+            .collect()
+    }
+
+    #[test]
+    fn test_fan_mode_set_before_rpm() {
+        let (device, mock) = mocked();
+        // the first checks and the mode switch are echoed, then the device is in Manual
+        for _ in 0..4 {
+            mock.push_response(&[]);
+        }
+        mock.push_response(&[0, 1, 0, 1]);
+        mock.push_response(&[0, 2, 0, 1]);
+
+        device
+            .apply_setting(SettingValue::Fan {
+                mode: FanMode::Manual,
+                rpm: Some(FanRpm::new(4000).unwrap()),
+            })
+            .unwrap();
+        assert_eq!(
+            commands(&mock),
+            [
+                cmd::GET_PERF_MODE,
+                cmd::GET_PERF_MODE,
+                cmd::SET_PERF_MODE,
+                cmd::SET_PERF_MODE,
+                cmd::GET_PERF_MODE,
+                cmd::GET_PERF_MODE,
+                cmd::SET_FAN_RPM,
+                cmd::SET_FAN_RPM,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scoped_restores_on_drop() {
+        let (device, mock) = mocked();
+        mock.push_response(&[1, 5, 80]);
+
+        let guard = device
+            .scoped(vec![SettingValue::KeyboardBrightness(Brightness::new(200))])
+            .unwrap();
+        assert_eq!(
+            mock.sent_commands().last().unwrap(),
+            &(cmd::SET_KBD_BRIGHTNESS, vec![1, 5, 200])
+        );
+        drop(guard);
+        assert_eq!(
+            mock.sent_commands().last().unwrap(),
+            &(cmd::SET_KBD_BRIGHTNESS, vec![1, 5, 80])
+        );
+    }
+
+    #[test]
+    fn test_failed_step_is_named_and_undone() {
+        let (device, mock) = mocked();
+        let steps = [
+            (
+                "Performance Mode",
+                SettingValue::PerfMode {
+                    mode: types::PerfMode::Custom,
+                    fan_mode: FanMode::Auto,
+                },
+            ),
+            ("CPU Boost", SettingValue::CpuBoost(types::CpuBoost::Boost)),
+        ];
+        // read before and switched, then the boost is refused while in Custom
+        for _ in 0..5 {
+            mock.push_response(&[]);
+        }
+        mock.push_response(&[0, 1, 4, 0]);
+        mock.push_response(&[0, 2, 4, 0]);
+        mock.push_status(0x03); // boost rejected
+        mock.push_response(&[0, 1, 4, 0]);
+        mock.push_response(&[0, 2, 4, 0]);
+
+        let error = device.apply_steps(&steps).unwrap_err();
+        let Error::StepFailed {
+            step,
+            total,
+            restored,
+            ..
+        } = &error
+        else {
+            panic!("{}", error);
+        };
+        assert_eq!((*step, *total, *restored), (2, 2, true));
+        assert!(
+            error.to_string().contains("CPU Boost to Boost"),
+            "{}",
+            error
+        );
+        // back to Balanced, which was read before the steps
+        let sent = mock.sent_commands();
+        assert_eq!(
+            sent[sent.len() - 2..],
+            [
+                (cmd::SET_PERF_MODE, vec![1, 1, 0, 0]),
+                (cmd::SET_PERF_MODE, vec![1, 2, 0, 0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejected_before_sending() {
+        let (device, mock) = mocked();
+        let error = device
+            .apply_setting(SettingValue::Fan {
+                mode: FanMode::Manual,
+                rpm: Some(FanRpm::new(9000).unwrap()),
+            })
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Device(RazerError::FanRpmOutOfRange { .. })
+        ));
+        assert!(mock.sent().is_empty());
+    }
+
+    #[test]
+    fn test_fit_profile_leaves_out_missing_features() {
+        let mock = MockTransport::new();
+        let desc = SUPPORTED[0].with_features(&[feature::PERF, feature::KBDBACKLIGHT]);
+        let device = BladeDevice::wrap(device::Device::with_transport(mock.clone(), desc));
+        let mut profile = Profile::new(
+            "meeting",
+            &DeviceState {
+                perf_mode: Some(types::PerfMode::Silent),
+                keyboard_brightness: Some(Brightness::new(20)),
+                battery_care: Some(types::BatteryCare::Enable),
+                ..Default::default()
+            },
+        );
+
+        let removed = device.fit_profile(&mut profile);
+        let removed: Vec<&str> = removed.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(removed, ["battery_care"]);
+        assert_eq!(profile.settings.len(), 2);
+        assert!(mock.sent().is_empty());
+    }
+
+    #[cfg(feature = "simulation")]
+    #[test]
+    fn test_profile_export_import_apply_round_trip() {
+        use crate::settings::ProfileDocument;
+        use librazer::simulation;
+        use librazer::types::{
+            BatteryCare, CpuBoost, GpuBoost, LogoMode, MaxFanSpeedMode, PerfMode,
+        };
+
+        let source = BladeDevice::wrap(simulation::device());
+        for value in [
+            SettingValue::PerfMode {
+                mode: PerfMode::Custom,
+                fan_mode: FanMode::Auto,
+            },
+            SettingValue::CpuBoost(CpuBoost::Boost),
+            SettingValue::GpuBoost(GpuBoost::High),
+            SettingValue::MaxFanSpeed(MaxFanSpeedMode::Enable),
+            SettingValue::KeyboardBrightness(Brightness::new(40)),
+            SettingValue::LogoMode(LogoMode::Breathing),
+            SettingValue::BatteryCare(BatteryCare::Enable),
+        ] {
+            source.apply_setting(value).unwrap();
+        }
+        let exported = source.capture_profile("tuned").unwrap();
+        let json = serde_json::to_string_pretty(&ProfileDocument::new(&exported)).unwrap();
+
+        let target = BladeDevice::wrap(simulation::device());
+        let document = ProfileDocument::parse(&json).unwrap();
+        assert_eq!(document.model, "SIMULATED");
+        let mut imported = document.into_profile("shared".to_string());
+        assert_eq!(target.fit_profile(&mut imported), []);
+        assert_eq!(imported.settings, exported.settings);
+
+        let report = target.apply_profile(&imported);
+        assert!(report.iter().all(|e| e.outcome.is_ok()), "{:?}", report);
+        assert_eq!(target.read_state().unwrap(), source.read_state().unwrap());
     }
 }
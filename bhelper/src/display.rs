@@ -1,38 +1,237 @@
 use crate::device::BladeDevice;
+use crate::error::Error;
 use crate::settings::{
-    DeviceState, JsonDeviceInfo, JsonDeviceState, JsonSettingValue, SettingValue,
+    CachedState, DeviceState, JsonDeviceInfo, JsonDeviceListEntry, JsonInterfaceInfo,
+    JsonSettingValue, JsonStats, JsonUsbInfo, SavedState, SettingValue,
 };
 use colored::*;
-use librazer::types::PerfMode;
+use librazer::command::CommandId;
+use librazer::descriptor::{Descriptor, SupportedModel};
+use librazer::device::{DeviceHealth, DevicePathInfo, DeviceStats};
+use librazer::persist::{FieldOutcome, Restored, SavedSettings};
+use librazer::profile::Profile;
+use librazer::replay::Recording;
+use librazer::state::{Field, PartialState};
+use librazer::types::{BatteryCare, Brightness, PerfMode};
 
-pub fn print_device_info(device: &BladeDevice) {
+/// How to fix thermal zones that report different modes.
+pub const RESYNC_HINT: &str = "run `blade_helper set perf balanced` to resync";
+
+pub fn print_device_info(device: &BladeDevice, verbose: bool) {
     println!("{}", "Device Information".bold().cyan());
     println!("  {}     {}", "Name:".dimmed(), device.name());
     println!("  {}    {}", "Model:".dimmed(), device.model());
     println!("  {}      {:#06x}", "PID:".dimmed(), device.pid());
+    let identity = device.identity();
+    if let Some((major, minor)) = identity.firmware {
+        println!("  {} {}.{}", "Firmware:".dimmed(), major, minor);
+    }
+    if let Some(serial) = &identity.serial {
+        println!("  {}   {}", "Serial:".dimmed(), serial);
+    }
+    if let Some(interface) = device.interface() {
+        println!(
+            "  {} {} (usage page {:#06x}, usage {:#06x})",
+            "Interface:".dimmed(),
+            interface.interface_number,
+            interface.usage_page,
+            interface.usage
+        );
+    }
+    if let Some(usb) = &identity.usb_strings {
+        let unknown = || "unknown".to_string();
+        println!();
+        println!("{}", "USB Device:".bold().cyan());
+        println!(
+            "  {} {}",
+            "Manufacturer:".dimmed(),
+            usb.manufacturer.clone().unwrap_or_else(unknown)
+        );
+        println!(
+            "  {}      {}",
+            "Product:".dimmed(),
+            usb.product.clone().unwrap_or_else(unknown)
+        );
+        println!(
+            "  {}       {}",
+            "Serial:".dimmed(),
+            usb.serial_number.clone().unwrap_or_else(unknown)
+        );
+        println!("  {}    {}", "Interface:".dimmed(), usb.interface_number);
+    }
     println!();
     println!("{}", "Supported Features:".bold().cyan());
     for feature in device.features() {
         println!("  {} {}", "•".green(), feature);
     }
+    if device.supports("perf") || device.supports("fan") || device.lighting().is_some() {
+        println!();
+    }
+    if device.supports("perf") {
+        let modes: Vec<String> = device.perf_modes().iter().map(|m| m.to_string()).collect();
+        let cpu: Vec<String> = device.cpu_boosts().iter().map(|b| b.to_string()).collect();
+        let gpu: Vec<String> = device.gpu_boosts().iter().map(|b| b.to_string()).collect();
+        println!("  {}       {}", "Perf modes:".dimmed(), modes.join(", "));
+        println!("  {}        {}", "CPU boost:".dimmed(), cpu.join(", "));
+        println!("  {}        {}", "GPU boost:".dimmed(), gpu.join(", "));
+    }
+    if device.supports("fan") {
+        let range = device.fan_rpm_range();
+        println!(
+            "  {} {}-{} RPM",
+            "Manual fan range:".dimmed(),
+            range.start(),
+            range.end()
+        );
+    }
+    if let Some(lighting) = device.lighting() {
+        println!("  {}         {}", "Keyboard:".dimmed(), lighting);
+    }
+    let quirks = device.quirks();
+    if !quirks.is_empty() {
+        println!();
+        println!("{}", "Quirks:".bold().cyan());
+        for (name, value) in quirks {
+            println!("  {} {}", format!("{}:", name).dimmed(), value);
+        }
+    }
+    let (notes, source_url) = device.source();
+    if verbose && !(notes.is_empty() && source_url.is_empty()) {
+        println!();
+        println!("{}", "Descriptor Source:".bold().cyan());
+        if !notes.is_empty() {
+            println!("  {}  {}", "Notes:".dimmed(), notes);
+        }
+        if !source_url.is_empty() {
+            println!("  {} {}", "Source:".dimmed(), source_url);
+        }
+    }
 }
 
-pub fn print_device_info_json(device: &BladeDevice) {
-    let info = JsonDeviceInfo {
+fn json_device_info(device: &BladeDevice) -> JsonDeviceInfo {
+    let identity = device.identity();
+    JsonDeviceInfo {
         name: device.name().to_string(),
         model: device.model().to_string(),
         pid: format!("{:#06x}", device.pid()),
         features: device.features().iter().map(|s| s.to_string()).collect(),
-    };
+        fan_rpm_range: [
+            *device.fan_rpm_range().start(),
+            *device.fan_rpm_range().end(),
+        ],
+        perf_modes: device.perf_modes().to_vec(),
+        cpu_boosts: device.cpu_boosts().to_vec(),
+        gpu_boosts: device.gpu_boosts().to_vec(),
+        lighting: device.lighting(),
+        quirks: device
+            .quirks()
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect(),
+        firmware: identity
+            .firmware
+            .map(|(major, minor)| format!("{}.{}", major, minor)),
+        serial: identity.serial.clone(),
+        interface: device.interface().map(|info| JsonInterfaceInfo {
+            number: info.interface_number,
+            usage_page: format!("{:#06x}", info.usage_page),
+            usage: format!("{:#06x}", info.usage),
+        }),
+        usb: identity.usb_strings.as_ref().map(|usb| JsonUsbInfo {
+            manufacturer: usb.manufacturer.clone(),
+            product: usb.product.clone(),
+            serial_number: usb.serial_number.clone(),
+            interface: usb.interface_number,
+        }),
+    }
+}
+
+pub fn print_device_info_json(device: &BladeDevice) {
+    let info = json_device_info(device);
     println!("{}", serde_json::to_string_pretty(&info).unwrap());
 }
 
-pub fn print_status(device: &BladeDevice, state: &DeviceState) {
+pub fn print_devices(devices: &[(Descriptor, DevicePathInfo)]) {
+    if devices.is_empty() {
+        println!("{}", "No supported devices connected".dimmed());
+        return;
+    }
+    println!("{}", "Connected Devices".bold().cyan());
+    for (index, (desc, info)) in devices.iter().enumerate() {
+        println!(
+            "  {} {} {} {}",
+            format!("[{}]", index).bold(),
+            desc.name,
+            format!("({:#06x}, interface {})", info.pid, info.interface_number).dimmed(),
+            info.path.to_string_lossy().dimmed()
+        );
+    }
+}
+
+pub fn print_devices_json(devices: &[(Descriptor, DevicePathInfo)]) {
+    let entries: Vec<_> = devices
+        .iter()
+        .enumerate()
+        .map(|(index, (desc, info))| JsonDeviceListEntry {
+            index,
+            name: desc.name.to_string(),
+            pid: format!("{:#06x}", info.pid),
+            interface: info.interface_number,
+            path: info.path.to_string_lossy().into_owned(),
+        })
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&entries).unwrap());
+}
+
+pub fn print_supported(models: &[SupportedModel]) {
+    println!("{}", "Supported Models".bold().cyan());
+    let name_width = models.iter().map(|m| m.name.len()).max().unwrap_or(0);
+    let prefixes: Vec<String> = models.iter().map(|m| m.model_prefixes.join(", ")).collect();
+    let prefix_width = prefixes.iter().map(String::len).max().unwrap_or(0);
+    for (model, prefixes) in models.iter().zip(&prefixes) {
+        println!(
+            "  {:name_width$}  {:prefix_width$}  {}  {}",
+            model.name,
+            prefixes,
+            format!("{:#06x}", model.pid).dimmed(),
+            model.features.join(", ").dimmed(),
+        );
+    }
+}
+
+/// Prints every field of the supported descriptors, for support matrices.
+pub fn print_supported_json() {
+    println!("{}", librazer::descriptor::to_json());
+}
+
+pub fn print_recording(recording: &Recording) {
     println!(
-        "{} {}",
-        device.name().bold(),
-        format!("({})", device.model()).dimmed()
+        "{} {} {}",
+        "Recording of".bold().cyan(),
+        recording.device.bold(),
+        format!("({:#06x})", recording.pid).dimmed()
     );
+    for exchange in &recording.exchanges {
+        let command = exchange
+            .command()
+            .map(CommandId::describe)
+            .unwrap_or_else(|| "?".to_string());
+        let outcome = match (&exchange.error, exchange.status) {
+            (Some(error), _) => error.red().to_string(),
+            (None, Some(status)) => format!("status {:#04x}", status),
+            (None, None) => "no response".dimmed().to_string(),
+        };
+        println!(
+            "  {} {} {}",
+            format!("{:>6}ms", exchange.elapsed_ms).dimmed(),
+            command,
+            outcome
+        );
+    }
+}
+
+pub fn print_status(name: &str, model: &str, state: &DeviceState) {
+    println!("{} {}", name.bold(), format!("({})", model).dimmed());
     println!("{}", "─".repeat(40).dimmed());
 
     if let Some(perf_mode) = state.perf_mode {
@@ -40,29 +239,37 @@ pub fn print_status(device: &BladeDevice, state: &DeviceState) {
             PerfMode::Silent => "Silent".green(),
             PerfMode::Balanced => "Balanced".yellow(),
             PerfMode::Custom => "Custom".red(),
+            PerfMode::Unknown(_) => perf_mode.to_string().normal(),
         };
         print!("{} {}", "Performance:".dimmed(), mode_color);
         if let Some(fan_mode) = state.fan_mode {
-            print!(" (Fan: {:?}", fan_mode);
+            print!(" (Fan: {}", fan_mode);
             if let Some(rpm) = state.fan_rpm {
-                print!(" @ {} RPM", rpm.to_string().cyan());
+                print!(" @ {} RPM", rpm.get().to_string().cyan());
             }
             print!(")");
         }
         println!();
+        if state.zone_mismatch {
+            println!(
+                "  {} {}",
+                "Zones out of sync:".yellow(),
+                RESYNC_HINT.dimmed()
+            );
+        }
 
         if perf_mode == PerfMode::Custom {
             if let Some(cpu) = state.cpu_boost {
-                println!("  {} {:?}", "CPU Boost:".dimmed(), cpu);
+                println!("  {} {}", "CPU Boost:".dimmed(), cpu);
             }
             if let Some(gpu) = state.gpu_boost {
-                println!("  {} {:?}", "GPU Boost:".dimmed(), gpu);
+                println!("  {} {}", "GPU Boost:".dimmed(), gpu);
             }
         }
     }
 
     if let Some(max_fan) = state.max_fan_speed {
-        println!("{} {:?}", "Max Fan:".dimmed(), max_fan);
+        println!("{} {}", "Max Fan:".dimmed(), max_fan);
     }
 
     if let Some(brightness) = state.keyboard_brightness {
@@ -71,12 +278,12 @@ pub fn print_status(device: &BladeDevice, state: &DeviceState) {
     }
 
     if let Some(logo) = state.logo_mode {
-        println!("{} {:?}", "Logo:".dimmed(), logo);
+        println!("{} {}", "Logo:".dimmed(), logo);
     }
 
     if let Some(care) = state.battery_care {
-        let status = format!("{:?}", care);
-        let colored_status = if status == "Enable" {
+        let status = care.to_string();
+        let colored_status = if care == BatteryCare::Enable {
             status.green()
         } else {
             status.normal()
@@ -85,29 +292,324 @@ pub fn print_status(device: &BladeDevice, state: &DeviceState) {
     }
 
     if let Some(lights) = state.lights_always_on {
-        println!("{} {:?}", "Lights On:".dimmed(), lights);
+        println!("{} {}", "Lights On:".dimmed(), lights);
     }
 }
 
-pub fn print_status_json(device: &BladeDevice, state: &DeviceState) {
+pub fn print_status_json(device: &BladeDevice, state: &DeviceState, stats: Option<DeviceStats>) {
     #[derive(serde::Serialize)]
-    struct StatusOutput {
+    struct StatusOutput<'a> {
         device: JsonDeviceInfo,
-        state: JsonDeviceState,
+        state: &'a DeviceState,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stats: Option<JsonStats>,
     }
 
     let output = StatusOutput {
-        device: JsonDeviceInfo {
-            name: device.name().to_string(),
-            model: device.model().to_string(),
-            pid: format!("{:#06x}", device.pid()),
-            features: device.features().iter().map(|s| s.to_string()).collect(),
-        },
-        state: JsonDeviceState::from(state),
+        device: json_device_info(device),
+        state,
+        stats: stats.as_ref().map(JsonStats::from),
+    };
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+/// Prints the fields `status --fields` read, `-` for those that couldn't be.
+pub fn print_fields(name: &str, model: &str, state: &PartialState) {
+    println!("{} {}", name.bold(), format!("({})", model).dimmed());
+    println!("{}", "─".repeat(40).dimmed());
+    for (field, value) in state.iter() {
+        let value = value.map_or_else(|| "-".dimmed(), |value| value.to_string().normal());
+        println!("{} {}", format!("{}:", field).dimmed(), value);
+    }
+    if state.zone_mismatch() {
+        println!("{} {}", "Zones out of sync:".yellow(), RESYNC_HINT.dimmed());
+    }
+}
+
+/// Prints the fields like `status --json`, with only the requested fields in `state`.
+pub fn print_fields_json(device: &BladeDevice, state: &PartialState, stats: Option<DeviceStats>) {
+    #[derive(serde::Serialize)]
+    struct FieldsOutput<'a> {
+        device: JsonDeviceInfo,
+        state: &'a PartialState,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        zone_mismatch: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stats: Option<JsonStats>,
+    }
+
+    let output = FieldsOutput {
+        device: json_device_info(device),
+        state,
+        zone_mismatch: state.zone_mismatch(),
+        stats: stats.as_ref().map(JsonStats::from),
+    };
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+/// Prints the state a device last reported, under a "stale as of" line.
+pub fn print_stale_status(cached: &CachedState) {
+    println!(
+        "{} {}",
+        "Stale as of".yellow(),
+        format!(
+            "{} ({} ago), the device can't be read",
+            format_utc(cached.read_at),
+            format_age(cached.age())
+        )
+        .yellow()
+    );
+    print_status(&cached.name, &cached.model, &cached.state);
+}
+
+/// Prints a cached state as `{"device": {"name", "model"}, "state", "stale_as_of"}`,
+/// `stale_as_of` in seconds since the Unix epoch.
+pub fn print_stale_status_json(cached: &CachedState) {
+    let output = serde_json::json!({
+        "device": { "name": cached.name, "model": cached.model },
+        "state": cached.state,
+        "stale_as_of": cached.read_at,
+    });
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+/// Lists the settings `save` kept.
+pub fn print_saved(saved: &SavedState) {
+    println!(
+        "{} Saved {} settings of {} {}",
+        "✓".green(),
+        saved.settings.len(),
+        saved.name.bold(),
+        format!("({})", saved.model).dimmed()
+    );
+    print_settings(&saved.settings);
+}
+
+/// One indented line per setting that parses.
+fn print_settings(settings: &SavedSettings) {
+    let (state, _) = settings.parse();
+    for field in Field::ALL {
+        if let Some(value) = state.get(*field) {
+            println!("  {} {}", format!("{}:", field).dimmed(), value);
+        }
+    }
+}
+
+/// Prints `{"device": {"name", "model"}, "saved_at", "settings"}`, `saved_at` in
+/// seconds since the Unix epoch.
+pub fn print_saved_json(saved: &SavedState) {
+    let output = serde_json::json!({
+        "device": { "name": saved.name, "model": saved.model },
+        "saved_at": saved.saved_at,
+        "settings": saved.settings,
+    });
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+/// Prints one line per saved setting with what `restore` did with it.
+pub fn print_restore_report(report: &[FieldOutcome]) {
+    for entry in report {
+        let (mark, detail) = match &entry.outcome {
+            Restored::Restored => ("✓".green(), "".normal()),
+            Restored::Skipped(reason) => ("-".yellow(), format!(" skipped: {}", reason).dimmed()),
+            Restored::Failed(reason) => ("✗".red(), format!(" failed: {}", reason).red()),
+        };
+        println!("{} {}{}", mark, entry.field.cyan(), detail);
+    }
+}
+
+/// Prints `{"success", "fields": [{"field", "outcome", "reason"}]}`.
+pub fn print_restore_report_json(report: &[FieldOutcome]) {
+    let output = serde_json::json!({
+        "success": report.iter().all(|entry| entry.outcome.is_ok()),
+        "fields": report,
+    });
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+/// Lists the settings `profile save` kept.
+pub fn print_profile_saved(profile: &Profile) {
+    println!(
+        "{} Saved {} settings as profile '{}'",
+        "✓".green(),
+        profile.settings.len(),
+        profile.name.cyan()
+    );
+    print_settings(&profile.settings);
+}
+
+/// Lists every profile with its settings.
+pub fn print_profiles(profiles: &[Profile]) {
+    if profiles.is_empty() {
+        println!("No profiles, create one with 'blade_helper profile save <name>'");
+        return;
+    }
+    for profile in profiles {
+        println!("{}", profile.name.cyan().bold());
+        print_settings(&profile.settings);
+    }
+}
+
+/// Prints a profile as `{"name", "settings"}`.
+pub fn print_profile_json(profile: &Profile) {
+    println!("{}", serde_json::to_string_pretty(profile).unwrap());
+}
+
+/// Prints the profiles as `[{"name", "settings"}]`.
+pub fn print_profiles_json(profiles: &[Profile]) {
+    println!("{}", serde_json::to_string_pretty(profiles).unwrap());
+}
+
+/// Lists the settings of an imported profile, and the ones left out with why.
+pub fn print_profile_imported(profile: &Profile, removed: &[FieldOutcome]) {
+    println!(
+        "{} Imported profile '{}' with {} settings",
+        "✓".green(),
+        profile.name.cyan(),
+        profile.settings.len()
+    );
+    print_settings(&profile.settings);
+    if !removed.is_empty() {
+        println!(
+            "Left out {} settings this laptop can't take:",
+            removed.len()
+        );
+        print_restore_report(removed);
+    }
+}
+
+/// Prints `{"profile": {"name", "settings"}, "removed": [{"field", "outcome", "reason"}]}`.
+pub fn print_profile_imported_json(profile: &Profile, removed: &[FieldOutcome]) {
+    let output = serde_json::json!({
+        "profile": profile,
+        "removed": removed,
+    });
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+/// Prints `{"profile", "success", "fields": [{"field", "outcome", "reason"}]}`.
+pub fn print_profile_applied_json(name: &str, report: &[FieldOutcome]) {
+    let output = serde_json::json!({
+        "profile": name,
+        "success": report.iter().all(|entry| entry.outcome.is_ok()),
+        "fields": report,
+    });
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+/// Formats seconds since the Unix epoch as `2024-03-01 14:05 UTC`.
+fn format_utc(secs: u64) -> String {
+    // days to civil date, see https://howardhinnant.github.io/date_algorithms.html
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    let minutes = secs % 86_400 / 60;
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+/// Formats an age in its largest whole unit, like `5 min`.
+fn format_age(age: std::time::Duration) -> String {
+    match age.as_secs() {
+        secs if secs < 60 => format!("{} s", secs),
+        secs if secs < 3600 => format!("{} min", secs / 60),
+        secs => format!("{} h", secs / 3600),
+    }
+}
+
+/// Prints the status a service reported, as `{"device": {"name", "model"}, "state"}`.
+#[cfg(any(feature = "dbus", windows))]
+pub fn print_service_status_json(name: &str, model: &str, state: &DeviceState) {
+    let output = serde_json::json!({
+        "device": { "name": name, "model": model },
+        "state": state,
+    });
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+/// Prints a failure as `{"success": false, "error": {"code", "kind", "message", ...}}`
+/// on stdout, where scripts read the rest of the JSON output.
+pub fn print_error_json(error: &Error) {
+    #[derive(serde::Serialize)]
+    struct ErrorOutput<'a> {
+        success: bool,
+        error: &'a Error,
+    }
+
+    let output = ErrorOutput {
+        success: false,
+        error,
     };
     println!("{}", serde_json::to_string_pretty(&output).unwrap());
 }
 
+/// Prints send statistics to stderr, so they don't mix with command output.
+pub fn print_timings(stats: &DeviceStats) {
+    let classes: Vec<String> = stats
+        .by_class
+        .iter()
+        .map(|(class, count)| format!("{:#04x}×{}", class, count))
+        .collect();
+    eprintln!("{}", "Timings".bold().cyan());
+    eprintln!(
+        "  {}    {} ({} failed, {} reopens)",
+        "Sends:".dimmed(),
+        stats.succeeded + stats.failed,
+        stats.failed,
+        stats.reopens
+    );
+    eprintln!(
+        "  {}  p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+        "Latency:".dimmed(),
+        stats.latency.p50,
+        stats.latency.p90,
+        stats.latency.p99,
+        stats.latency.max
+    );
+    eprintln!("  {}  {}", "Classes:".dimmed(), classes.join(", "));
+}
+
+/// Prints how the sends made so far went, for `doctor`.
+pub fn print_health(health: &DeviceHealth) {
+    println!("{}", "Device health:".bold().cyan());
+    println!(
+        "  {}   {} of the last {} failed",
+        "Sends:".dimmed(),
+        health.recent_failures,
+        health.recent_sends
+    );
+    if health.failures.is_empty() {
+        return;
+    }
+    let failures: Vec<String> = health
+        .failures
+        .iter()
+        .map(|(code, count)| format!("{}×{}", code, count))
+        .collect();
+    println!("  {} {}", "Failures:".dimmed(), failures.join(", "));
+    println!("  {}   {} in a row", "Streak:".dimmed(), health.streak);
+    if let Some(elapsed) = health.last_failure.and_then(|time| time.elapsed().ok()) {
+        println!(
+            "  {} {:.1}s ago",
+            "Last failure:".dimmed(),
+            elapsed.as_secs_f64()
+        );
+    }
+}
+
 pub fn print_setting(name: &str, value: &SettingValue) {
     println!("{}: {}", name.cyan(), value);
 }
@@ -129,24 +631,39 @@ pub fn print_setting_changed(name: &str, value: &SettingValue) {
     );
 }
 
-pub fn print_setting_changed_json(name: &str, value: &SettingValue) {
-    #[derive(serde::Serialize)]
-    struct ChangeOutput {
-        success: bool,
-        setting: String,
-        value: String,
+#[derive(serde::Serialize)]
+struct ChangeOutput {
+    success: bool,
+    setting: String,
+    value: String,
+}
+
+impl ChangeOutput {
+    fn new(name: &str, value: &SettingValue) -> Self {
+        ChangeOutput {
+            success: true,
+            setting: name.to_string(),
+            value: value.to_string(),
+        }
     }
+}
 
-    let output = ChangeOutput {
-        success: true,
-        setting: name.to_string(),
-        value: value.to_string(),
-    };
+pub fn print_setting_changed_json(name: &str, value: &SettingValue) {
+    let output = ChangeOutput::new(name, value);
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+/// Prints the settings one command changed as an array of changes, in order.
+pub fn print_settings_changed_json(changes: &[(&str, SettingValue)]) {
+    let output: Vec<ChangeOutput> = changes
+        .iter()
+        .map(|(name, value)| ChangeOutput::new(name, value))
+        .collect();
     println!("{}", serde_json::to_string_pretty(&output).unwrap());
 }
 
-fn format_brightness_bar(brightness: u8) -> String {
-    let filled = (brightness as usize * 10) / 255;
+fn format_brightness_bar(brightness: Brightness) -> String {
+    let filled = brightness.as_percent() as usize / 10;
     let empty = 10 - filled;
     format!(
         "[{}{}]",
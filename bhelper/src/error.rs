@@ -1,3 +1,6 @@
+use crate::device::DeviceSelector;
+use librazer::error::{ErrorCode, ErrorReport};
+use serde::{Serialize, Serializer};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -5,17 +8,145 @@ pub enum Error {
     #[error("No Razer device found. Make sure your device is connected and supported.")]
     DeviceNotFound,
 
-    #[error("Permission denied accessing USB device. On Linux, install udev rules: see README for details.")]
-    PermissionDenied,
+    #[error("No supported device matches {0}. Run 'devices' to list connected devices.")]
+    NoMatchingDevice(DeviceSelector),
 
-    #[error("Feature '{0}' is not supported on this device")]
-    FeatureNotSupported(String),
+    #[error("No supported model matches '{0}'. Check the model number prefix, e.g. RZ09-0482.")]
+    UnknownModel(String),
+
+    #[error("Cannot open HID path '{path}': {reason}")]
+    InvalidHidPath { path: String, reason: String },
+
+    #[error("Failed to run '{command}': {reason}")]
+    RunFailed { command: String, reason: String },
+
+    #[error("No saved state, run 'blade_helper save' first")]
+    NoSavedState,
+
+    #[error("Failed to read saved state from {path}: {reason}")]
+    SavedStateFile { path: String, reason: String },
+
+    #[error("{failed} of {total} saved settings couldn't be restored")]
+    RestoreIncomplete { failed: usize, total: usize },
+
+    #[error("No profile named '{name}'. {}", known_profiles(.known))]
+    UnknownProfile { name: String, known: Vec<String> },
+
+    #[error("Profile file {path}: {reason}")]
+    ProfileFile { path: String, reason: String },
+
+    #[error("{failed} of {total} settings of profile '{name}' couldn't be applied")]
+    ProfileIncomplete {
+        name: String,
+        failed: usize,
+        total: usize,
+    },
+
+    #[error("Step {step} of {total}, {setting}, failed: {source}. {}", undone(*.restored))]
+    StepFailed {
+        step: usize,
+        total: usize,
+        setting: String,
+        restored: bool,
+        source: Box<Error>,
+    },
 
     #[error("Configuration error: {0}")]
     Config(#[from] confy::ConfyError),
 
     #[error("Device error: {0}")]
     Device(#[from] librazer::error::RazerError),
+
+    #[cfg(feature = "dbus")]
+    #[error("Cannot reach the razer-dbusd service: {0}")]
+    ServiceUnavailable(String),
+
+    #[cfg(feature = "dbus")]
+    #[error("Service error: {message}")]
+    Service { code: ErrorCode, message: String },
+
+    #[cfg(feature = "dbus")]
+    #[error("{0} isn't available with --via-dbus")]
+    NotViaDbus(&'static str),
+}
+
+impl Error {
+    /// Category of the error, which `main` exits with.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::DeviceNotFound | Error::NoMatchingDevice(_) | Error::InvalidHidPath { .. } => {
+                ErrorCode::DeviceNotFound
+            }
+            Error::UnknownModel(_) => ErrorCode::Unsupported,
+            Error::RunFailed { .. }
+            | Error::Config(_)
+            | Error::NoSavedState
+            | Error::SavedStateFile { .. }
+            | Error::RestoreIncomplete { .. }
+            | Error::UnknownProfile { .. }
+            | Error::ProfileFile { .. }
+            | Error::ProfileIncomplete { .. } => ErrorCode::Other,
+            Error::Device(e) => e.code(),
+            Error::StepFailed { source, .. } => source.code(),
+            #[cfg(feature = "dbus")]
+            Error::ServiceUnavailable(_) | Error::NotViaDbus(_) => ErrorCode::Other,
+            #[cfg(feature = "dbus")]
+            Error::Service { code, .. } => *code,
+        }
+    }
+
+    /// Whether running the command again may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Device(e) => e.is_retryable(),
+            Error::StepFailed { source, .. } => source.is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// Summary for JSON output, with this error's message.
+    pub fn report(&self) -> ErrorReport {
+        let mut report = match self {
+            Error::Device(e) => ErrorReport::from(e),
+            Error::StepFailed { source, .. } => source.report(),
+            _ => ErrorReport::new(self.code(), String::new()),
+        };
+        report.message = self.to_string();
+        report
+    }
+
+    /// Whether the device was refused to this user.
+    pub fn is_permission(&self) -> bool {
+        match self {
+            Error::Device(e) => e.is_permission(),
+            Error::StepFailed { source, .. } => source.is_permission(),
+            _ => false,
+        }
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.report().serialize(serializer)
+    }
+}
+
+/// What to do instead of using a profile that doesn't exist.
+fn known_profiles(known: &[String]) -> String {
+    if known.is_empty() {
+        "Create one with 'blade_helper profile save <name>'".to_string()
+    } else {
+        format!("Saved profiles: {}", known.join(", "))
+    }
+}
+
+/// Whether the steps before a failed one were undone.
+fn undone(restored: bool) -> &'static str {
+    if restored {
+        "The settings changed before it were set back"
+    } else {
+        "The settings changed before it couldn't all be set back"
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
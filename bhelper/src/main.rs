@@ -1,69 +1,260 @@
 mod cli;
 mod config;
+#[cfg(feature = "dbus")]
+mod dbus;
 mod device;
 mod display;
 mod error;
+#[cfg(windows)]
+mod pipe;
 mod settings;
 
-use clap::Parser;
+use clap::ValueEnum;
 use colored::*;
+use librazer::error::{RazerError, Remedy};
+use librazer::packet_log::{self, PacketLogMode};
+use librazer::permission::PermissionDetails;
+use librazer::replay::Recording;
+use librazer::state::Field;
 use librazer::types::FanMode;
 use log::debug;
+use std::path::Path;
+use std::time::Duration;
 
-use cli::{Cli, Commands, ConfigCommand, FanCommand, SetCommand, SettingName};
+use cli::{
+    Cli, Commands, ConfigCommand, CycleSetting, FanCommand, ProfileCommand, RunSettings,
+    SetCommand, SettingName,
+};
 use config::ConfigManager;
-use device::BladeDevice;
-use error::Result;
-use settings::{Setting, SettingValue};
+use device::{BladeDevice, ConnectOptions, StateReading};
+use error::{Error, Result};
+use settings::{JsonHealth, ProfileDocument, Setting, SettingValue};
+
+/// Oldest cached state `status --cached-ok` shows.
+const CACHED_STATE_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
 
 fn main() {
-    let cli = Cli::parse();
+    // the cached device's descriptor narrows the modes offered, without opening it
+    let perf_modes = ConfigManager::load()
+        .ok()
+        .and_then(|config_mgr| config_mgr.get_cached_pid())
+        .and_then(librazer::descriptor::find_by_pid)
+        .map(|descriptor| descriptor.perf_modes);
+    let cli = Cli::parse_for(perf_modes);
+    let verbose = cli.verbose;
+    let json = cli.json;
 
     if let Err(e) = run(cli) {
-        if std::env::var("NO_COLOR").is_ok() {
+        if json {
+            display::print_error_json(&e);
+        } else if std::env::var("NO_COLOR").is_ok() {
             eprintln!("Error: {}", e);
         } else {
             eprintln!("{} {}", "Error:".red().bold(), e);
         }
-        std::process::exit(1);
+        if let Some(hint) = error_hint(&e) {
+            eprintln!("  {}", hint);
+        }
+        if let Some(details) = permission_details(&e) {
+            for line in details.lines() {
+                eprintln!("  {}", line);
+            }
+        }
+        if verbose {
+            print_error_detail(&e);
+        }
+        std::process::exit(e.code().get().into());
+    }
+}
+
+/// A next step for errors whose message doesn't already suggest one.
+fn error_hint(error: &Error) -> Option<String> {
+    match error {
+        Error::StepFailed { source, .. } => error_hint(source),
+        Error::Device(RazerError::ModeRequired { remedy, .. }) => Some(remedy_hint(*remedy)),
+        Error::Device(RazerError::ZoneMismatch { .. }) => Some(display::RESYNC_HINT.to_string()),
+        // the message carries advice for this system already
+        Error::Device(RazerError::PermissionDenied(_)) => None,
+        e if e.is_permission() => Some("Install the udev rules, see the README".to_string()),
+        e if e.is_retryable() => Some("The device may just be busy, try again".to_string()),
+        _ => None,
+    }
+}
+
+/// What was found out about a refused device.
+fn permission_details(error: &Error) -> Option<&PermissionDetails> {
+    match error {
+        Error::Device(e) => match e.root() {
+            RazerError::PermissionDenied(details) => Some(details),
+            _ => None,
+        },
+        Error::StepFailed { source, .. } => permission_details(source),
+        _ => None,
+    }
+}
+
+/// The command that applies a [`Remedy`].
+fn remedy_hint(remedy: Remedy) -> String {
+    let command = match remedy {
+        Remedy::PerfMode(mode) => match mode.to_possible_value() {
+            Some(value) => format!("set perf {}", value.get_name()),
+            None => return format!("{} first", remedy),
+        },
+        Remedy::FanMode(FanMode::Auto) => "set fan auto".to_string(),
+        Remedy::FanMode(FanMode::Manual) => "set fan manual <RPM>".to_string(),
+    };
+    format!("run `blade_helper {}` first", command)
+}
+
+/// Prints what `--verbose` adds to an error: the command that failed and the
+/// start of a mismatched response.
+fn print_error_detail(error: &Error) {
+    let Error::Device(error) = error else {
+        return;
+    };
+    if let Some(context) = error.context() {
+        eprintln!("  Command: {}", context);
+    }
+    if let RazerError::UnexpectedResponse(mismatch) = error.root() {
+        eprintln!("  Response: {}", mismatch.response_hex());
+    }
+}
+
+/// Initializes logging based on verbosity. With the `tracing` feature, `--verbose`
+/// prints librazer's spans instead, and `log` records through the same subscriber.
+///
+/// `--trace-hid` logs every packet in full, even without `--verbose`.
+fn init_logging(verbose: bool, trace_hid: bool) {
+    if trace_hid {
+        packet_log::set_mode(PacketLogMode::Full);
     }
+
+    #[cfg(feature = "tracing")]
+    if verbose {
+        tracing_subscriber::fmt()
+            .compact()
+            .without_time()
+            .with_max_level(tracing_subscriber::filter::LevelFilter::DEBUG)
+            .with_writer(std::io::stderr)
+            .init();
+        return;
+    }
+
+    let log_level = if verbose { "debug" } else { "warn" };
+    let mut builder =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level));
+    if trace_hid {
+        builder.filter_module(packet_log::TARGET, log::LevelFilter::Debug);
+    }
+    builder.format_timestamp(None).init();
 }
 
 fn run(cli: Cli) -> Result<()> {
-    // Initialize logging based on verbosity
-    let log_level = if cli.verbose { "debug" } else { "warn" };
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level))
-        .format_timestamp(None)
-        .init();
+    init_logging(cli.verbose, cli.trace_hid);
+
+    ConfigManager::load_descriptors();
 
     debug!("Parsed CLI arguments");
 
     let json = cli.json;
+    let connect = ConnectOptions {
+        selector: cli.device,
+        pid: cli.pid,
+        hid_path: cli.hid_path,
+        allow_unsupported: cli.allow_unsupported,
+        force_model: cli.force_model,
+        record: cli.record,
+        timings: cli.timings,
+        no_lock: cli.no_lock,
+    };
 
-    match cli.command {
-        Commands::Status => cmd_status(json)?,
-        Commands::Get { setting } => cmd_get(setting, json)?,
-        Commands::Set { setting } => cmd_set(setting, json)?,
-        Commands::Info => cmd_info(json)?,
+    #[cfg(feature = "dbus")]
+    if cli.via_dbus {
+        return dbus::run(cli.command, json);
+    }
+
+    #[cfg(windows)]
+    let command = match pipe::try_run(cli.command, &connect, json) {
+        Ok(result) => return result,
+        Err(command) => command,
+    };
+    #[cfg(not(windows))]
+    let command = cli.command;
+
+    match command {
+        Commands::Status { stats, fields, .. } if !fields.is_empty() => {
+            cmd_status_fields(&connect, &fields, stats, json)?
+        }
+        Commands::Status {
+            stats, cached_ok, ..
+        } => cmd_status(&connect, stats, cached_ok, json)?,
+        Commands::Get { setting } => cmd_get(&connect, setting, json)?,
+        Commands::Set { setting } => cmd_set(&connect, setting, json)?,
+        Commands::Cycle { setting } => cmd_cycle(&connect, setting, json)?,
+        Commands::Info => cmd_info(&connect, json, cli.verbose)?,
+        Commands::Devices { supported } => cmd_devices(supported, json)?,
+        Commands::Doctor => cmd_doctor(&connect, json),
         Commands::Config { action } => cmd_config(action, json)?,
+        Commands::Save { file } => cmd_save(&connect, file.as_deref(), json)?,
+        Commands::Restore => cmd_restore(&connect, json)?,
+        Commands::Profile { action } => cmd_profile(&connect, action, json)?,
+        Commands::Run { settings, command } => cmd_run(&connect, settings, command)?,
+        Commands::Replay { path } => cmd_replay(&path, json)?,
     }
 
     Ok(())
 }
 
-fn cmd_status(json: bool) -> Result<()> {
-    let device = BladeDevice::detect_with_cache()?;
-    let state = device.read_state()?;
+fn cmd_status(connect: &ConnectOptions, stats: bool, cached_ok: bool, json: bool) -> Result<()> {
+    let (device, state) = if cached_ok {
+        match BladeDevice::read_state_cached(connect, CACHED_STATE_MAX_AGE)? {
+            StateReading::Live(device, state) => (*device, state),
+            StateReading::Stale(cached) => {
+                if json {
+                    display::print_stale_status_json(&cached);
+                } else {
+                    display::print_stale_status(&cached);
+                }
+                return Ok(());
+            }
+        }
+    } else {
+        let device = BladeDevice::connect(connect)?;
+        let state = device.read_state()?;
+        (device, state)
+    };
+    if json {
+        display::print_status_json(&device, &state, stats.then(|| device.stats()));
+    } else {
+        display::print_status(device.name(), device.model(), &state);
+        if stats {
+            display::print_timings(&device.stats());
+        }
+    }
+    Ok(())
+}
+
+fn cmd_status_fields(
+    connect: &ConnectOptions,
+    fields: &[Field],
+    stats: bool,
+    json: bool,
+) -> Result<()> {
+    let device = BladeDevice::connect(connect)?;
+    let state = device.read_fields(fields);
     if json {
-        display::print_status_json(&device, &state);
+        display::print_fields_json(&device, &state, stats.then(|| device.stats()));
     } else {
-        display::print_status(&device, &state);
+        display::print_fields(device.name(), device.model(), &state);
+        if stats {
+            display::print_timings(&device.stats());
+        }
     }
     Ok(())
 }
 
-fn cmd_get(setting: SettingName, json: bool) -> Result<()> {
-    let device = BladeDevice::detect_with_cache()?;
+fn cmd_get(connect: &ConnectOptions, setting: SettingName, json: bool) -> Result<()> {
+    let device = BladeDevice::connect(connect)?;
 
     let (name, setting_type) = match setting {
         SettingName::Perf => ("Performance Mode", Setting::PerfMode),
@@ -74,6 +265,7 @@ fn cmd_get(setting: SettingName, json: bool) -> Result<()> {
         SettingName::Keyboard => ("Keyboard Brightness", Setting::KeyboardBrightness),
         SettingName::Logo => ("Logo Mode", Setting::LogoMode),
         SettingName::BatteryCare => ("Battery Care", Setting::BatteryCare),
+        SettingName::ChargeLimit => ("Charge Limit", Setting::ChargeLimit),
         SettingName::LightsAlwaysOn => ("Lights Always On", Setting::LightsAlwaysOn),
     };
 
@@ -86,17 +278,267 @@ fn cmd_get(setting: SettingName, json: bool) -> Result<()> {
     Ok(())
 }
 
-fn cmd_set(setting: SetCommand, json: bool) -> Result<()> {
-    let device = BladeDevice::detect_with_cache()?;
+fn run_changes(settings: RunSettings) -> Vec<SettingValue> {
+    let mut changes = Vec::new();
+    if let Some(mode) = settings.perf {
+        changes.push(SettingValue::PerfMode {
+            mode,
+            fan_mode: FanMode::Auto,
+        });
+    }
+    if let Some(rpm) = settings.fan_rpm {
+        changes.push(SettingValue::Fan {
+            mode: FanMode::Manual,
+            rpm: Some(rpm),
+        });
+    }
+    if let Some(mode) = settings.max_fan {
+        changes.push(SettingValue::MaxFanSpeed(mode));
+    }
+    if let Some(boost) = settings.cpu {
+        changes.push(SettingValue::CpuBoost(boost));
+    }
+    if let Some(boost) = settings.gpu {
+        changes.push(SettingValue::GpuBoost(boost));
+    }
+    if let Some(brightness) = settings.keyboard {
+        changes.push(SettingValue::KeyboardBrightness(brightness));
+    }
+    changes
+}
 
-    let (name, value) = match setting {
-        SetCommand::Perf { mode } => (
-            "Performance Mode",
-            SettingValue::PerfMode {
-                mode,
-                fan_mode: FanMode::Auto,
-            },
-        ),
+fn cmd_save(connect: &ConnectOptions, file: Option<&Path>, json: bool) -> Result<()> {
+    let device = BladeDevice::connect(connect)?;
+    let mut config_mgr = ConfigManager::load()?;
+    let saved = device.save_state(&mut config_mgr)?;
+    if let Some(path) = file {
+        let contents = serde_json::to_string_pretty(&saved.settings).unwrap() + "\n";
+        std::fs::write(path, contents).map_err(|e| Error::SavedStateFile {
+            path: path.display().to_string(),
+            reason: e.to_string(),
+        })?;
+    }
+    if json {
+        display::print_saved_json(&saved);
+    } else {
+        display::print_saved(&saved);
+    }
+    Ok(())
+}
+
+fn cmd_restore(connect: &ConnectOptions, json: bool) -> Result<()> {
+    let device = BladeDevice::connect(connect)?;
+    let config_mgr = ConfigManager::load()?;
+    let report = device.restore_state(&config_mgr)?;
+    if json {
+        display::print_restore_report_json(&report);
+    } else {
+        display::print_restore_report(&report);
+    }
+    let failed = report.iter().filter(|entry| !entry.outcome.is_ok()).count();
+    if failed > 0 {
+        return Err(Error::RestoreIncomplete {
+            failed,
+            total: report.len(),
+        });
+    }
+    Ok(())
+}
+
+fn cmd_profile(connect: &ConnectOptions, action: ProfileCommand, json: bool) -> Result<()> {
+    let mut config_mgr = ConfigManager::load()?;
+    match action {
+        ProfileCommand::Save { name } => {
+            let device = BladeDevice::connect(connect)?;
+            let profile = device.capture_profile(&name)?;
+            config_mgr.set_profile(profile.clone())?;
+            if json {
+                display::print_profile_json(&profile);
+            } else {
+                display::print_profile_saved(&profile);
+            }
+        }
+        ProfileCommand::Apply { name } => {
+            let profile = config_mgr.get_profile(&name)?;
+            let device = BladeDevice::connect(connect)?;
+            let report = device.apply_profile(profile);
+            if json {
+                display::print_profile_applied_json(&name, &report);
+            } else {
+                display::print_restore_report(&report);
+            }
+            let failed = report.iter().filter(|entry| !entry.outcome.is_ok()).count();
+            if failed > 0 {
+                return Err(Error::ProfileIncomplete {
+                    name,
+                    failed,
+                    total: report.len(),
+                });
+            }
+        }
+        ProfileCommand::List => {
+            if json {
+                display::print_profiles_json(config_mgr.profiles());
+            } else {
+                display::print_profiles(config_mgr.profiles());
+            }
+        }
+        ProfileCommand::Export { name, file } => {
+            let document = ProfileDocument::new(config_mgr.get_profile(&name)?);
+            let contents = serde_json::to_string_pretty(&document).unwrap() + "\n";
+            let Some(path) = file else {
+                print!("{}", contents);
+                return Ok(());
+            };
+            std::fs::write(&path, contents).map_err(|e| Error::ProfileFile {
+                path: path.display().to_string(),
+                reason: e.to_string(),
+            })?;
+            if json {
+                let output = serde_json::json!({
+                    "success": true,
+                    "profile": name,
+                    "path": path,
+                });
+                println!("{}", serde_json::to_string_pretty(&output).unwrap());
+            } else {
+                println!(
+                    "{} Exported profile '{}' to {}",
+                    "✓".green(),
+                    name.cyan(),
+                    path.display()
+                );
+            }
+        }
+        ProfileCommand::Import { path, name } => {
+            let file_error = |reason: String| Error::ProfileFile {
+                path: path.display().to_string(),
+                reason,
+            };
+            let contents = std::fs::read_to_string(&path).map_err(|e| file_error(e.to_string()))?;
+            let document = ProfileDocument::parse(&contents).map_err(file_error)?;
+            let name = match name {
+                Some(name) => name,
+                None => config::parse_profile_name(&document.name)
+                    .map_err(|e| file_error(format!("{}, pass --as to rename it", e)))?,
+            };
+            let device = BladeDevice::connect(connect)?;
+            if !document.model.is_empty() && document.model != device.model() {
+                eprintln!(
+                    "{} profile '{}' was captured on {}, this laptop is {} ({})",
+                    "Warning:".yellow().bold(),
+                    document.name,
+                    document.model,
+                    device.model(),
+                    device.name()
+                );
+            }
+            let mut profile = document.into_profile(name);
+            let removed = device.fit_profile(&mut profile);
+            config_mgr.set_profile(profile.clone())?;
+            if json {
+                display::print_profile_imported_json(&profile, &removed);
+            } else {
+                display::print_profile_imported(&profile, &removed);
+            }
+        }
+        ProfileCommand::Delete { name } => {
+            let profile = config_mgr.delete_profile(&name)?;
+            if json {
+                display::print_profile_json(&profile);
+            } else {
+                println!("{} Deleted profile '{}'", "✓".green(), profile.name.cyan());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cmd_run(connect: &ConnectOptions, settings: RunSettings, command: Vec<String>) -> Result<()> {
+    let device = BladeDevice::connect(connect)?;
+
+    // Ctrl-C also reaches the child; staying alive until it exits is what lets
+    // the previous settings be restored
+    if let Err(e) = ctrlc::set_handler(|| debug!("Interrupted, waiting for the command to exit")) {
+        log::warn!("Failed to install Ctrl-C handler: {}", e);
+    }
+
+    let status = {
+        let _guard = device.scoped(run_changes(settings))?;
+        debug!("Running {:?}", command);
+        std::process::Command::new(&command[0])
+            .args(&command[1..])
+            .status()
+    };
+    drop(device);
+
+    let status = status.map_err(|e| Error::RunFailed {
+        command: command[0].clone(),
+        reason: e.to_string(),
+    })?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
+fn cmd_set(connect: &ConnectOptions, setting: SetCommand, json: bool) -> Result<()> {
+    let device = BladeDevice::connect(connect)?;
+    let steps = set_values(setting);
+
+    match steps.as_slice() {
+        [(_, value)] => device.apply_setting(value.clone())?,
+        _ => device.apply_steps(&steps)?,
+    }
+    print_set(&steps, json);
+    Ok(())
+}
+
+/// Prints what `set` changed: one change, or a list of them when `set perf`
+/// also set the boosts or fan.
+fn print_set(steps: &[(&'static str, SettingValue)], json: bool) {
+    match (steps, json) {
+        ([(name, value)], true) => display::print_setting_changed_json(name, value),
+        ([(name, value)], false) => display::print_setting_changed(name, value),
+        (_, true) => display::print_settings_changed_json(steps),
+        (_, false) => {
+            for (name, value) in steps {
+                display::print_setting_changed(name, value);
+            }
+        }
+    }
+}
+
+/// The display names and values a `set` subcommand asks for, in the order they
+/// are applied.
+fn set_values(setting: SetCommand) -> Vec<(&'static str, SettingValue)> {
+    let step = match setting {
+        SetCommand::Perf {
+            mode,
+            cpu,
+            gpu,
+            max_fan,
+            rpm,
+        } => {
+            let mut steps = vec![(
+                "Performance Mode",
+                SettingValue::PerfMode {
+                    mode,
+                    fan_mode: FanMode::Auto,
+                },
+            )];
+            steps.extend(cpu.map(|boost| ("CPU Boost", SettingValue::CpuBoost(boost))));
+            steps.extend(gpu.map(|boost| ("GPU Boost", SettingValue::GpuBoost(boost))));
+            steps.extend(max_fan.map(|mode| ("Max Fan Speed", SettingValue::MaxFanSpeed(mode))));
+            steps.extend(rpm.map(|rpm| {
+                let value = SettingValue::Fan {
+                    mode: FanMode::Manual,
+                    rpm: Some(rpm),
+                };
+                ("Fan", value)
+            }));
+            return steps;
+        }
         SetCommand::Cpu { boost } => ("CPU Boost", SettingValue::CpuBoost(boost)),
         SetCommand::Gpu { boost } => ("GPU Boost", SettingValue::GpuBoost(boost)),
         SetCommand::Fan { action } => {
@@ -124,10 +566,43 @@ fn cmd_set(setting: SetCommand, json: bool) -> Result<()> {
         ),
         SetCommand::Logo { mode } => ("Logo Mode", SettingValue::LogoMode(mode)),
         SetCommand::BatteryCare { mode } => ("Battery Care", SettingValue::BatteryCare(mode)),
+        SetCommand::ChargeLimit { limit } => ("Charge Limit", SettingValue::ChargeLimit(limit)),
         SetCommand::LightsAlwaysOn { mode } => {
             ("Lights Always On", SettingValue::LightsAlwaysOn(mode))
         }
     };
+    vec![step]
+}
+
+fn cmd_cycle(connect: &ConnectOptions, setting: CycleSetting, json: bool) -> Result<()> {
+    let device = BladeDevice::connect(connect)?;
+
+    let (name, value) = match setting {
+        CycleSetting::Perf => match device.get_setting(Setting::PerfMode)? {
+            SettingValue::PerfMode { mode, .. } => (
+                "Performance Mode",
+                SettingValue::PerfMode {
+                    mode: mode.cycle_in(device.perf_modes()),
+                    fan_mode: FanMode::Auto,
+                },
+            ),
+            _ => unreachable!("performance mode reads as a performance mode"),
+        },
+        CycleSetting::Cpu => match device.get_setting(Setting::CpuBoost)? {
+            SettingValue::CpuBoost(boost) => (
+                "CPU Boost",
+                SettingValue::CpuBoost(boost.cycle_in(device.cpu_boosts())),
+            ),
+            _ => unreachable!("CPU boost reads as a CPU boost"),
+        },
+        CycleSetting::Gpu => match device.get_setting(Setting::GpuBoost)? {
+            SettingValue::GpuBoost(boost) => (
+                "GPU Boost",
+                SettingValue::GpuBoost(boost.cycle_in(device.gpu_boosts())),
+            ),
+            _ => unreachable!("GPU boost reads as a GPU boost"),
+        },
+    };
 
     device.apply_setting(value.clone())?;
     if json {
@@ -138,12 +613,78 @@ fn cmd_set(setting: SetCommand, json: bool) -> Result<()> {
     Ok(())
 }
 
-fn cmd_info(json: bool) -> Result<()> {
-    let device = BladeDevice::detect_with_cache()?;
+fn cmd_info(connect: &ConnectOptions, json: bool, verbose: bool) -> Result<()> {
+    let device = BladeDevice::connect(connect)?;
     if json {
         display::print_device_info_json(&device);
     } else {
-        display::print_device_info(&device);
+        display::print_device_info(&device, verbose);
+    }
+    Ok(())
+}
+
+fn cmd_devices(supported: bool, json: bool) -> Result<()> {
+    if supported {
+        if json {
+            display::print_supported_json();
+        } else {
+            display::print_supported(&librazer::descriptor::supported_models());
+        }
+        return Ok(());
+    }
+    let devices = BladeDevice::list()?;
+    if json {
+        display::print_devices_json(&devices);
+    } else {
+        display::print_devices(&devices);
+    }
+    Ok(())
+}
+
+fn cmd_doctor(connect: &ConnectOptions, json: bool) {
+    let report = librazer::device::Device::enumeration_report();
+    // read every setting once, so the health shows how the device answers
+    let health = BladeDevice::connect(connect).map(|device| {
+        let _ = device.read_state();
+        device.health()
+    });
+    if json {
+        let (health, error) = match &health {
+            Ok(health) => (Some(JsonHealth::from(health)), None),
+            Err(e) => (None, Some(e)),
+        };
+        println!(
+            "{}",
+            serde_json::json!({ "report": report, "health": health, "error": error })
+        );
+    } else {
+        print!("{}", report);
+        println!();
+        match health {
+            Ok(health) => display::print_health(&health),
+            Err(e) => {
+                println!("{} unavailable ({})", "Device health:".bold().cyan(), e);
+                for line in permission_details(&e)
+                    .map(|d| d.lines())
+                    .unwrap_or_default()
+                {
+                    println!("  {}", line);
+                }
+            }
+        }
+    }
+}
+
+fn cmd_replay(path: &Path, json: bool) -> Result<()> {
+    let recording = Recording::load(path)?;
+    let device = BladeDevice::replay(&recording);
+    let state = device.read_state()?;
+    if json {
+        display::print_status_json(&device, &state, None);
+    } else {
+        display::print_recording(&recording);
+        println!();
+        display::print_status(device.name(), device.model(), &state);
     }
     Ok(())
 }
@@ -164,8 +705,14 @@ fn cmd_config(action: ConfigCommand, json: bool) -> Result<()> {
                 #[derive(serde::Serialize)]
                 struct DeviceCacheOutput {
                     pid: Option<String>,
+                    path: Option<String>,
                     model: Option<String>,
                     model_prefix: Option<String>,
+                    pid_override: Option<String>,
+                    hid_path: Option<String>,
+                    probed_pid: Option<String>,
+                    probed_features: Option<Vec<String>>,
+                    force_descriptor: Option<String>,
                 }
                 #[derive(serde::Serialize)]
                 struct SettingsOutput {
@@ -176,8 +723,18 @@ fn cmd_config(action: ConfigCommand, json: bool) -> Result<()> {
                     path: config_mgr.path().display().to_string(),
                     device_cache: DeviceCacheOutput {
                         pid: config.device.cached_pid.map(|p| format!("{:#06x}", p)),
+                        path: config.device.cached_path.clone(),
                         model: config.device.model.clone(),
                         model_prefix: config.device.model_prefix.clone(),
+                        pid_override: config.device.pid_override.map(|p| format!("{:#06x}", p)),
+                        hid_path: config.device.hid_path.clone(),
+                        probed_pid: config.device.probed_pid.map(|p| format!("{:#06x}", p)),
+                        probed_features: config.device.probed_features.clone(),
+                        force_descriptor: config
+                            .device
+                            .force_descriptor
+                            .as_ref()
+                            .map(|forced| forced.to_string()),
                     },
                     settings: SettingsOutput {
                         default_profile: config.settings.default_profile.clone(),
@@ -196,6 +753,9 @@ fn cmd_config(action: ConfigCommand, json: bool) -> Result<()> {
                 println!("{}", "Device Cache:".bold().cyan());
                 if let Some(pid) = config.device.cached_pid {
                     println!("  {} {:#06x}", "PID:".dimmed(), pid);
+                    if let Some(path) = &config.device.cached_path {
+                        println!("  {} {}", "Path:".dimmed(), path);
+                    }
                     if let Some(model) = &config.device.model {
                         println!("  {} {}", "Model:".dimmed(), model);
                     }
@@ -205,6 +765,23 @@ fn cmd_config(action: ConfigCommand, json: bool) -> Result<()> {
                 } else {
                     println!("  {}", "(no cached device)".dimmed());
                 }
+                if let Some(pid) = config.device.pid_override {
+                    println!("  {} {:#06x}", "Pinned PID:".dimmed(), pid);
+                }
+                if let Some(path) = &config.device.hid_path {
+                    println!("  {} {}", "Pinned Path:".dimmed(), path);
+                }
+                if let Some((pid, features)) = config_mgr.get_probed_features() {
+                    println!(
+                        "  {} {:#06x} ({})",
+                        "Probed:".dimmed(),
+                        pid,
+                        features.join(", ")
+                    );
+                }
+                if let Some(forced) = &config.device.force_descriptor {
+                    println!("  {} {}", "Forced:".dimmed(), forced);
+                }
                 println!();
 
                 println!("{}", "Settings:".bold().cyan());
@@ -229,6 +806,33 @@ fn cmd_config(action: ConfigCommand, json: bool) -> Result<()> {
                 );
             }
         }
+        ConfigCommand::SetDevice { pid, hid_path } => {
+            let mut config_mgr = ConfigManager::load()?;
+            config_mgr.set_pinned_device(pid, hid_path.clone())?;
+            if json {
+                #[derive(serde::Serialize)]
+                struct SetDeviceOutput {
+                    success: bool,
+                    pid: Option<String>,
+                    hid_path: Option<String>,
+                }
+                let output = SetDeviceOutput {
+                    success: true,
+                    pid: pid.map(|p| format!("{:#06x}", p)),
+                    hid_path,
+                };
+                println!("{}", serde_json::to_string_pretty(&output).unwrap());
+            } else if pid.is_none() && hid_path.is_none() {
+                println!("{} Pinned device cleared", "✓".green());
+            } else {
+                if let Some(pid) = pid {
+                    println!("{} Pinned PID set to {:#06x}", "✓".green(), pid);
+                }
+                if let Some(path) = hid_path {
+                    println!("{} Pinned HID path set to '{}'", "✓".green(), path.cyan());
+                }
+            }
+        }
         ConfigCommand::ClearCache => {
             let mut config_mgr = ConfigManager::load()?;
             config_mgr.clear_cache()?;
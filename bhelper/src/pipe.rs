@@ -0,0 +1,75 @@
+//! Routes `status` and `set` through the razer-pipesvc service when it is
+//! running, so they work without administrator rights.
+
+use crate::cli::{Commands, SetCommand};
+use crate::device::ConnectOptions;
+use crate::display;
+use crate::error::Result;
+use crate::settings::SettingValue;
+use librazer::ipc::{self, Client};
+use log::debug;
+use std::fs::File;
+
+/// Runs `command` through the service if it can, or hands it back to be run
+/// against the device when the service isn't running or the command needs the
+/// device itself.
+pub fn try_run(
+    command: Commands,
+    connect: &ConnectOptions,
+    json: bool,
+) -> std::result::Result<Result<()>, Commands> {
+    let routable = match &command {
+        Commands::Status {
+            stats,
+            cached_ok,
+            fields,
+        } => !stats && !cached_ok && fields.is_empty(),
+        Commands::Set { setting } => !matches!(setting, SetCommand::ChargeLimit { .. }),
+        _ => false,
+    };
+    if !routable || !uses_default_device(connect) {
+        return Err(command);
+    }
+    let mut client = match Client::connect() {
+        Ok(client) => client,
+        Err(e) => {
+            debug!("Opening the device directly: {}", e);
+            return Err(command);
+        }
+    };
+    debug!("Using the service at {}", ipc::PIPE_NAME);
+    Ok(run(&mut client, command, json))
+}
+
+/// Whether the options leave the device to the service's choosing.
+fn uses_default_device(connect: &ConnectOptions) -> bool {
+    connect.selector.is_none()
+        && connect.pid.is_none()
+        && connect.hid_path.is_none()
+        && connect.force_model.is_none()
+        && connect.record.is_none()
+        && !connect.timings
+}
+
+fn run(client: &mut Client<File>, command: Commands, json: bool) -> Result<()> {
+    match command {
+        Commands::Status { .. } => {
+            let status = client.status()?;
+            if json {
+                display::print_service_status_json(&status.name, &status.model, &status.state);
+            } else {
+                display::print_status(&status.name, &status.model, &status.state);
+            }
+        }
+        Commands::Set { setting } => {
+            let steps = crate::set_values(setting);
+            let values: Vec<SettingValue> = steps.iter().map(|(_, value)| value.clone()).collect();
+            let profile = SettingValue::profile_of(&values)
+                .expect("the charge limit isn't routed to the service");
+            client.apply(&profile)?;
+            crate::print_set(&steps, json);
+        }
+        _ => unreachable!("only status and set are routed to the service"),
+    }
+    Ok(())
+}
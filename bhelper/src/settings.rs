@@ -1,9 +1,131 @@
+use librazer::descriptor::LightingCapability;
+use librazer::device::{DeviceHealth, DeviceStats};
+use librazer::persist::SavedSettings;
+use librazer::profile::Profile;
 use librazer::types::{
-    BatteryCare, CpuBoost, FanMode, GpuBoost, LightsAlwaysOn, LogoMode, MaxFanSpeedMode, PerfMode,
+    BatteryCare, Brightness, ChargeLimit, CpuBoost, FanMode, FanRpm, GpuBoost, LightsAlwaysOn,
+    LogoMode, MaxFanSpeedMode, PerfMode,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Clone, Copy, Debug)]
+pub use librazer::state::DeviceState;
+
+/// The last state read from a device, kept in the config for `status --cached-ok`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CachedState {
+    pub name: String,
+    pub model: String,
+    /// Seconds since the Unix epoch.
+    pub read_at: u64,
+    pub state: DeviceState,
+}
+
+impl CachedState {
+    pub fn new(name: &str, model: &str, state: DeviceState) -> Self {
+        CachedState {
+            name: name.to_string(),
+            model: model.to_string(),
+            read_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+            state,
+        }
+    }
+
+    /// How long ago the state was read, zero if the clock went backwards.
+    pub fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH + Duration::from_secs(self.read_at))
+            .unwrap_or_default()
+    }
+}
+
+/// Settings kept by `save` for `restore`, since the EC forgets the manual fan
+/// speed and the boosts on a cold boot. Every member has a default so a damaged
+/// entry doesn't stop the config from loading.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedState {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub model: String,
+    /// Seconds since the Unix epoch.
+    #[serde(default)]
+    pub saved_at: u64,
+    #[serde(default)]
+    pub settings: SavedSettings,
+}
+
+impl SavedState {
+    pub fn new(name: &str, model: &str, state: &DeviceState) -> Self {
+        SavedState {
+            name: name.to_string(),
+            model: model.to_string(),
+            saved_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+            settings: SavedSettings::from_state(state),
+        }
+    }
+}
+
+/// A profile as `profile export` writes it, to share or keep in dotfiles.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProfileDocument {
+    /// Version of this format, [`ProfileDocument::SCHEMA`] when written.
+    pub schema: u32,
+    pub name: String,
+    /// Model number prefix of the laptop the profile was captured on, empty if
+    /// unknown.
+    #[serde(default)]
+    pub model: String,
+    pub settings: SavedSettings,
+}
+
+impl ProfileDocument {
+    /// The format version this blade_helper writes and reads.
+    pub const SCHEMA: u32 = 1;
+
+    pub fn new(profile: &Profile) -> Self {
+        ProfileDocument {
+            schema: Self::SCHEMA,
+            name: profile.name.clone(),
+            model: profile.model.clone(),
+            settings: profile.settings.clone(),
+        }
+    }
+
+    /// Parses an exported profile, failing with the reason if it isn't one or
+    /// has a schema version this blade_helper doesn't read.
+    pub fn parse(json: &str) -> std::result::Result<Self, String> {
+        let value: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        match value.get("schema").map(serde_json::Value::as_u64) {
+            None => return Err("not an exported profile, it has no schema version".to_string()),
+            Some(Some(schema)) if schema == u64::from(Self::SCHEMA) => {}
+            Some(schema) => {
+                return Err(format!(
+                    "schema version {} isn't supported, this blade_helper reads version {}",
+                    schema.map_or_else(|| value["schema"].to_string(), |s| s.to_string()),
+                    Self::SCHEMA
+                ))
+            }
+        }
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+
+    /// Returns the profile, called `name`.
+    pub fn into_profile(self, name: String) -> Profile {
+        Profile {
+            name,
+            model: self.model,
+            settings: self.settings,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Setting {
     PerfMode,
     CpuBoost,
@@ -13,6 +135,7 @@ pub enum Setting {
     KeyboardBrightness,
     LogoMode,
     BatteryCare,
+    ChargeLimit,
     LightsAlwaysOn,
 }
 
@@ -21,56 +144,58 @@ pub enum SettingValue {
     PerfMode { mode: PerfMode, fan_mode: FanMode },
     CpuBoost(CpuBoost),
     GpuBoost(GpuBoost),
-    Fan { mode: FanMode, rpm: Option<u16> },
+    Fan { mode: FanMode, rpm: Option<FanRpm> },
     MaxFanSpeed(MaxFanSpeedMode),
-    KeyboardBrightness(u8),
+    KeyboardBrightness(Brightness),
     LogoMode(LogoMode),
     BatteryCare(BatteryCare),
+    ChargeLimit(ChargeLimit),
     LightsAlwaysOn(LightsAlwaysOn),
 }
 
-#[derive(Clone, Debug, Default)]
-pub struct DeviceState {
-    pub perf_mode: Option<PerfMode>,
-    pub fan_mode: Option<FanMode>,
-    pub cpu_boost: Option<CpuBoost>,
-    pub gpu_boost: Option<GpuBoost>,
-    pub fan_rpm: Option<u16>,
-    pub max_fan_speed: Option<MaxFanSpeedMode>,
-    pub keyboard_brightness: Option<u8>,
-    pub logo_mode: Option<LogoMode>,
-    pub battery_care: Option<BatteryCare>,
-    pub lights_always_on: Option<LightsAlwaysOn>,
-}
+impl SettingValue {
+    /// Returns which setting this value is for.
+    pub fn setting(&self) -> Setting {
+        match self {
+            SettingValue::PerfMode { .. } => Setting::PerfMode,
+            SettingValue::CpuBoost(_) => Setting::CpuBoost,
+            SettingValue::GpuBoost(_) => Setting::GpuBoost,
+            SettingValue::Fan { .. } => Setting::FanMode,
+            SettingValue::MaxFanSpeed(_) => Setting::MaxFanSpeed,
+            SettingValue::KeyboardBrightness(_) => Setting::KeyboardBrightness,
+            SettingValue::LogoMode(_) => Setting::LogoMode,
+            SettingValue::BatteryCare(_) => Setting::BatteryCare,
+            SettingValue::ChargeLimit(_) => Setting::ChargeLimit,
+            SettingValue::LightsAlwaysOn(_) => Setting::LightsAlwaysOn,
+        }
+    }
 
-#[derive(Clone, Debug, Serialize)]
-pub struct JsonDeviceState {
-    pub perf_mode: Option<String>,
-    pub fan_mode: Option<String>,
-    pub cpu_boost: Option<String>,
-    pub gpu_boost: Option<String>,
-    pub fan_rpm: Option<u16>,
-    pub max_fan_speed: Option<String>,
-    pub keyboard_brightness: Option<u8>,
-    pub logo_mode: Option<String>,
-    pub battery_care: Option<String>,
-    pub lights_always_on: Option<String>,
-}
-
-impl From<&DeviceState> for JsonDeviceState {
-    fn from(state: &DeviceState) -> Self {
-        Self {
-            perf_mode: state.perf_mode.map(|m| format!("{:?}", m)),
-            fan_mode: state.fan_mode.map(|m| format!("{:?}", m)),
-            cpu_boost: state.cpu_boost.map(|m| format!("{:?}", m)),
-            gpu_boost: state.gpu_boost.map(|m| format!("{:?}", m)),
-            fan_rpm: state.fan_rpm,
-            max_fan_speed: state.max_fan_speed.map(|m| format!("{:?}", m)),
-            keyboard_brightness: state.keyboard_brightness,
-            logo_mode: state.logo_mode.map(|m| format!("{:?}", m)),
-            battery_care: state.battery_care.map(|m| format!("{:?}", m)),
-            lights_always_on: state.lights_always_on.map(|m| format!("{:?}", m)),
+    /// The profile a service applies to set `values` at once, or `None` if one
+    /// is the charge limit, which [`DeviceState`] doesn't cover. The service
+    /// orders the writes.
+    #[cfg(any(feature = "dbus", windows))]
+    pub fn profile_of(values: &[SettingValue]) -> Option<DeviceState> {
+        let mut profile = DeviceState::default();
+        for value in values {
+            match *value {
+                SettingValue::PerfMode { mode, .. } => profile.perf_mode = Some(mode),
+                SettingValue::CpuBoost(boost) => profile.cpu_boost = Some(boost.into()),
+                SettingValue::GpuBoost(boost) => profile.gpu_boost = Some(boost.into()),
+                SettingValue::Fan { mode, rpm } => {
+                    profile.fan_mode = Some(mode);
+                    profile.fan_rpm = rpm;
+                }
+                SettingValue::MaxFanSpeed(mode) => profile.max_fan_speed = Some(mode),
+                SettingValue::KeyboardBrightness(brightness) => {
+                    profile.keyboard_brightness = Some(brightness)
+                }
+                SettingValue::LogoMode(mode) => profile.logo_mode = Some(mode),
+                SettingValue::BatteryCare(mode) => profile.battery_care = Some(mode),
+                SettingValue::LightsAlwaysOn(mode) => profile.lights_always_on = Some(mode),
+                SettingValue::ChargeLimit(_) => return None,
+            }
         }
+        Some(profile)
     }
 }
 
@@ -80,6 +205,117 @@ pub struct JsonDeviceInfo {
     pub model: String,
     pub pid: String,
     pub features: Vec<String>,
+    pub fan_rpm_range: [u16; 2],
+    pub perf_modes: Vec<PerfMode>,
+    pub cpu_boosts: Vec<CpuBoost>,
+    pub gpu_boosts: Vec<GpuBoost>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lighting: Option<LightingCapability>,
+    /// Protocol deviations configured for the model, by name.
+    pub quirks: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub firmware: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface: Option<JsonInterfaceInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usb: Option<JsonUsbInfo>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct JsonStats {
+    pub reopens: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    /// Sends per command class, keyed by hex class.
+    pub by_class: BTreeMap<String, u64>,
+    pub latency_ms: JsonLatency,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct JsonLatency {
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl From<&DeviceStats> for JsonStats {
+    fn from(stats: &DeviceStats) -> Self {
+        let ms = |d: std::time::Duration| d.as_secs_f64() * 1000.0;
+        JsonStats {
+            reopens: stats.reopens,
+            succeeded: stats.succeeded,
+            failed: stats.failed,
+            by_class: stats
+                .by_class
+                .iter()
+                .map(|(class, count)| (format!("{:#04x}", class), *count))
+                .collect(),
+            latency_ms: JsonLatency {
+                p50: ms(stats.latency.p50),
+                p90: ms(stats.latency.p90),
+                p99: ms(stats.latency.p99),
+                max: ms(stats.latency.max),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct JsonHealth {
+    /// Failed sends, keyed by error kind.
+    pub failures: BTreeMap<String, u64>,
+    /// Seconds since the Unix epoch.
+    pub last_failure: Option<u64>,
+    pub streak: u64,
+    pub recent_failures: u32,
+    pub recent_sends: u32,
+}
+
+impl From<&DeviceHealth> for JsonHealth {
+    fn from(health: &DeviceHealth) -> Self {
+        JsonHealth {
+            failures: health
+                .failures
+                .iter()
+                .map(|(code, count)| (code.kind().to_string(), *count))
+                .collect(),
+            last_failure: health.last_failure.and_then(|time| {
+                time.duration_since(std::time::UNIX_EPOCH)
+                    .ok()
+                    .map(|since| since.as_secs())
+            }),
+            streak: health.streak,
+            recent_failures: health.recent_failures,
+            recent_sends: health.recent_sends,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct JsonUsbInfo {
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+    pub interface: i32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct JsonInterfaceInfo {
+    pub number: i32,
+    pub usage_page: String,
+    pub usage: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct JsonDeviceListEntry {
+    pub index: usize,
+    pub name: String,
+    pub pid: String,
+    pub interface: i32,
+    pub path: String,
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -92,20 +328,62 @@ impl std::fmt::Display for SettingValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             SettingValue::PerfMode { mode, fan_mode } => {
-                write!(f, "{:?} (Fan: {:?})", mode, fan_mode)
+                write!(f, "{} (Fan: {})", mode, fan_mode)
             }
-            SettingValue::CpuBoost(boost) => write!(f, "{:?}", boost),
-            SettingValue::GpuBoost(boost) => write!(f, "{:?}", boost),
+            SettingValue::CpuBoost(boost) => write!(f, "{}", boost),
+            SettingValue::GpuBoost(boost) => write!(f, "{}", boost),
             SettingValue::Fan { mode, rpm } => match (mode, rpm) {
                 (FanMode::Auto, _) => write!(f, "Auto"),
-                (FanMode::Manual, Some(rpm)) => write!(f, "Manual @ {} RPM", rpm),
+                (FanMode::Manual, Some(rpm)) => write!(f, "Manual @ {}", rpm),
                 (FanMode::Manual, None) => write!(f, "Manual"),
             },
-            SettingValue::MaxFanSpeed(mode) => write!(f, "{:?}", mode),
+            SettingValue::MaxFanSpeed(mode) => write!(f, "{}", mode),
             SettingValue::KeyboardBrightness(b) => write!(f, "{}", b),
-            SettingValue::LogoMode(mode) => write!(f, "{:?}", mode),
-            SettingValue::BatteryCare(care) => write!(f, "{:?}", care),
-            SettingValue::LightsAlwaysOn(lights) => write!(f, "{:?}", lights),
+            SettingValue::LogoMode(mode) => write!(f, "{}", mode),
+            SettingValue::BatteryCare(care) => write!(f, "{}", care),
+            SettingValue::ChargeLimit(limit) => write!(f, "{}", limit),
+            SettingValue::LightsAlwaysOn(lights) => write!(f, "{}", lights),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_document_schema() {
+        let profile = Profile {
+            name: "gaming".to_string(),
+            model: "RZ09-0483T".to_string(),
+            ..Profile::new(
+                "",
+                &DeviceState {
+                    perf_mode: Some(PerfMode::Custom),
+                    ..Default::default()
+                },
+            )
+        };
+        let json = serde_json::to_string(&ProfileDocument::new(&profile)).unwrap();
+        let document = ProfileDocument::parse(&json).unwrap();
+        assert_eq!(document.schema, ProfileDocument::SCHEMA);
+        assert_eq!(document.into_profile("gaming".to_string()), profile);
+
+        for (json, reason) in [
+            (r#"{"name": "x", "settings": {}}"#, "no schema version"),
+            (
+                r#"{"schema": 2, "name": "x", "settings": {}}"#,
+                "version 2 isn't",
+            ),
+            (
+                r#"{"schema": "1", "name": "x", "settings": {}}"#,
+                "version \"1\" isn't",
+            ),
+            (r#"{"schema": 1, "settings": {}}"#, "missing field `name`"),
+            ("[", "EOF"),
+        ] {
+            let error = ProfileDocument::parse(json).unwrap_err();
+            assert!(error.contains(reason), "{}: {}", json, error);
         }
     }
 }
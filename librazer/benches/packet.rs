@@ -0,0 +1,33 @@
+//! Serialization of a report before it is sent.
+//!
+//! Run with `cargo bench -p librazer --bench packet`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use librazer::limits::REPORT_SIZE;
+use librazer::packet::Packet;
+
+fn serialize(c: &mut Criterion) {
+    let packet = Packet::new(0x0d02, &[0x01, 0x01, 0x02, 0x00]);
+
+    // what Device::write_report did before: a Vec for the packet, then another
+    // with the report id in front
+    c.bench_function("vec", |b| {
+        b.iter(|| {
+            let report: Vec<u8> = std::iter::once(0x00)
+                .chain(Vec::<u8>::from(black_box(&packet)))
+                .collect();
+            black_box(report)
+        })
+    });
+
+    c.bench_function("write_report", |b| {
+        b.iter(|| {
+            let mut buf = [0x00; REPORT_SIZE];
+            black_box(&packet).write_report(0x00, &mut buf);
+            black_box(&buf);
+        })
+    });
+}
+
+criterion_group!(benches, serialize);
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+//! Serial and batched application of a profile, with the real protocol delays.
+//!
+//! Run with `cargo bench -p librazer --bench send_many`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use librazer::descriptor::SUPPORTED;
+use librazer::device::Device;
+use librazer::error::Result;
+use librazer::packet::Packet;
+use librazer::transport::{MockTransport, Transport};
+
+/// A mock that sleeps through the delays instead of recording them.
+struct Sleeping(MockTransport);
+
+impl Transport for Sleeping {
+    fn send_feature_report(&self, data: &[u8]) -> Result<()> {
+        self.0.send_feature_report(data)
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize> {
+        self.0.get_feature_report(buf)
+    }
+}
+
+/// The reports of applying a profile: perf mode, boosts, lighting and a read.
+fn profile_reports() -> Vec<Packet> {
+    vec![
+        Packet::new(0x0d02, &[1, 1, 4, 0]),
+        Packet::new(0x0d02, &[1, 2, 4, 0]),
+        Packet::new(0x0d07, &[0, 1, 3]),
+        Packet::new(0x0d07, &[0, 2, 2]),
+        Packet::new(0x0303, &[1, 5, 128]),
+        Packet::new(0x0300, &[1, 4, 1]),
+        Packet::new(0x0712, &[0xd0]),
+        Packet::new(0x0004, &[0x03, 0]),
+    ]
+}
+
+fn send_many(c: &mut Criterion) {
+    let reports = profile_reports();
+    let device = Device::with_transport(Sleeping(MockTransport::new()), SUPPORTED[0].clone());
+
+    let mut group = c.benchmark_group("profile");
+    group.sample_size(20);
+    group.bench_function("serial", |b| {
+        b.iter(|| {
+            for report in &reports {
+                device.send(report.clone()).unwrap();
+            }
+        })
+    });
+    group.bench_function("send_many", |b| {
+        b.iter(|| device.send_many(&reports).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, send_many);
+criterion_main!(benches);
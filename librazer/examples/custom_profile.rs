@@ -0,0 +1,63 @@
+//! Switches to Custom mode and sets the highest boosts the model allows.
+//!
+//! Without hardware, run it against the simulated laptop:
+//!
+//! ```text
+//! RAZER_SIMULATE=1 cargo run -p librazer --features simulation --example custom_profile
+//! ```
+
+use librazer::command;
+use librazer::device::Device;
+use librazer::error::{ErrorCode, RazerError, Remedy, Result};
+use librazer::types::CpuBoost;
+
+fn main() -> anyhow::Result<()> {
+    let device = Device::detect()?;
+
+    // Overclock isn't on every model, so fall back to the best level it lists
+    let cpu = match device.info.check_cpu_boost(CpuBoost::Overclock) {
+        Ok(()) => CpuBoost::Overclock,
+        Err(RazerError::UnsupportedLevel {
+            level,
+            device: name,
+        }) => {
+            let best = *device.info.cpu_boosts.iter().max().expect("no CPU boosts");
+            println!("{} isn't available on {}, using {:?}", level, name, best);
+            best
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let gpu = *device.info.gpu_boosts.iter().max().expect("no GPU boosts");
+
+    with_mode(&device, |device| command::set_cpu_boost(device, cpu))?;
+    with_mode(&device, |device| command::set_gpu_boost(device, gpu))?;
+
+    let (cpu, gpu) = command::get_boosts(&device)?;
+    println!("CPU boost {}, GPU boost {}", cpu, gpu);
+    Ok(())
+}
+
+/// Runs `set`, first putting the device into the modes it asks for and retrying
+/// once if the firmware was busy.
+fn with_mode(device: &Device, set: impl Fn(&Device) -> Result<()>) -> Result<()> {
+    let Err(e) = set(device) else {
+        return Ok(());
+    };
+    match e.root() {
+        // the error says how to get there; boosts need Custom mode
+        RazerError::ModeRequired { remedy, .. } => {
+            println!("Switching first: {}", remedy);
+            match *remedy {
+                Remedy::PerfMode(mode) => command::set_perf_mode(device, mode)?,
+                Remedy::FanMode(mode) => command::set_fan_mode(device, mode)?,
+            }
+            set(device)
+        }
+        _ if e.is_retryable() => set(device),
+        _ if e.code() == ErrorCode::Unsupported => {
+            eprintln!("Not supported on {}: {}", device.info.name, e);
+            Err(e)
+        }
+        _ => Err(e),
+    }
+}
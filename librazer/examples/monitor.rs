@@ -0,0 +1,56 @@
+//! Prints the performance mode and fan speed once a second.
+//!
+//! Takes the number of samples as its argument, 10 by default. The protocol
+//! has no temperature command, so only the fans are shown. Without hardware,
+//! run it against the simulated laptop:
+//!
+//! ```text
+//! RAZER_SIMULATE=1 cargo run -p librazer --features simulation --example monitor -- 5
+//! ```
+
+use librazer::command;
+use librazer::device::Device;
+use librazer::error::RazerError;
+use librazer::types::FanZone;
+use std::thread;
+use std::time::Duration;
+
+fn main() -> anyhow::Result<()> {
+    let samples: u32 = match std::env::args().nth(1) {
+        Some(arg) => arg.parse()?,
+        None => 10,
+    };
+    let device = Device::detect()?;
+    println!("Monitoring {}", device.info.name);
+
+    for sample in 0..samples {
+        if sample > 0 {
+            thread::sleep(Duration::from_secs(1));
+        }
+        match sample_fans(&device) {
+            Ok(line) => println!("{}", line),
+            Err(e) => match e.root() {
+                // a zone being switched mid-read; the next sample sees it settled
+                RazerError::ZoneMismatch { .. } => println!("zones changing: {}", e),
+                // Device already re-opened it once; it's really gone
+                RazerError::Disconnected => return Err(e.into()),
+                _ if e.is_retryable() => println!("skipped: {}", e),
+                _ => return Err(e.into()),
+            },
+        }
+    }
+    Ok(())
+}
+
+fn sample_fans(device: &Device) -> librazer::error::Result<String> {
+    let (perf_mode, fan_mode) = command::get_perf_mode(device)?;
+    let zone1 = command::get_fan_rpm(device, FanZone::Zone1)?;
+    let zone2 = command::get_fan_rpm(device, FanZone::Zone2)?;
+    Ok(format!(
+        "{}: fans {} / {} RPM ({})",
+        perf_mode,
+        zone1.get(),
+        zone2.get(),
+        fan_mode
+    ))
+}
@@ -0,0 +1,55 @@
+//! Detects the laptop and prints every setting it reports.
+//!
+//! Without hardware, run it against the simulated laptop:
+//!
+//! ```text
+//! RAZER_SIMULATE=1 cargo run -p librazer --features simulation --example status
+//! ```
+
+use librazer::command;
+use librazer::device::Device;
+use librazer::error::RazerError;
+use librazer::state::{DeviceState, Field};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let device = match Device::detect() {
+        Ok(device) => device,
+        Err(e) => {
+            // root() looks through the context detection adds
+            match e.root() {
+                RazerError::NoDevicesFound => {
+                    eprintln!(
+                        "No Razer laptop found. Set RAZER_SIMULATE=1 to try the simulated one."
+                    )
+                }
+                RazerError::PermissionDenied(_) => eprintln!("{}", e),
+                RazerError::UnsupportedModel { model, .. } => {
+                    eprintln!("{} isn't supported yet: {}", model, e)
+                }
+                _ => eprintln!("Detection failed: {}", e),
+            }
+            // the same numbers blade_helper exits with
+            return ExitCode::from(e.code().get());
+        }
+    };
+
+    println!("{} ({:#06x})", device.info.name, device.info.pid);
+    match command::get_firmware_version(&device) {
+        Ok((major, minor)) => println!("firmware: {}.{}", major, minor),
+        Err(e) => println!("firmware: unknown ({})", e),
+    }
+
+    // reads only what the model declares; settings that fail to read stay None
+    let state = DeviceState::read_from(&device, device.info.features);
+    for field in Field::ALL {
+        match state.get(*field) {
+            Some(value) => println!("{}: {}", field, value),
+            None => println!("{}: -", field),
+        }
+    }
+    if state.zone_mismatch {
+        println!("The thermal zones disagree; set the performance mode again to resync them.");
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,225 @@
+//! Async wrapper around [`Device`], enabled by the `async` feature.
+//!
+//! HID I/O, and the protocol delays with it, runs on tokio's blocking pool, so an
+//! async UI loop is never blocked. [`AsyncDevice::send`] runs [`Device::send`] and
+//! the command methods run the corresponding [`command`] function there. The
+//! sync API is unaffected.
+
+use crate::command;
+use crate::descriptor::Descriptor;
+use crate::device::{Device, SharedDevice};
+use crate::error::{RazerError, Result};
+use crate::packet::Packet;
+use crate::types::{
+    BatteryCare, Brightness, ChargeLimit, CpuBoost, FanMode, FanRpm, FanZone, GpuBoost,
+    LightsAlwaysOn, LogoMode, MaxFanSpeedMode, MaybeKnown, PerfMode,
+};
+use std::sync::Arc;
+
+/// Generates async methods that forward to the sync functions in [`command`].
+macro_rules! async_commands {
+    ($($(#[$meta:meta])* $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)+) => {
+        $(
+            $(#[$meta])*
+            pub async fn $name(&self, $($arg: $ty),*) -> Result<$ret> {
+                self.run(move |device| command::$name(device, $($arg),*)).await
+            }
+        )+
+    };
+}
+
+/// Cloneable async handle to a [`Device`].
+///
+/// Clones share the device; requests are serialized by an internal lock that is
+/// held across the awaits of a whole exchange.
+#[derive(Clone)]
+pub struct AsyncDevice {
+    device: SharedDevice,
+    exchange: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl AsyncDevice {
+    /// Wraps an opened device.
+    pub fn new(device: Device) -> Self {
+        AsyncDevice {
+            device: SharedDevice::new(device),
+            exchange: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    /// Auto-detects and connects to a supported Razer laptop without blocking the runtime.
+    pub async fn detect() -> Result<Self> {
+        tokio::task::spawn_blocking(Device::detect)
+            .await
+            .map_err(join_error)?
+            .map(AsyncDevice::new)
+    }
+
+    /// Returns the device descriptor.
+    pub fn info(&self) -> &Descriptor {
+        self.device.info()
+    }
+
+    /// Runs `f` with the device on the blocking thread pool.
+    pub async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Device) -> Result<T> + Send + 'static,
+    {
+        let _guard = self.exchange.lock().await;
+        self.blocking(f).await
+    }
+
+    /// Like [`run`](Self::run), for callers already holding the exchange lock.
+    async fn blocking<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Device) -> Result<T> + Send + 'static,
+    {
+        let device = self.device.clone();
+        tokio::task::spawn_blocking(move || f(&device.lock()))
+            .await
+            .map_err(join_error)?
+    }
+
+    /// Sends a USB HID feature report and returns the response.
+    ///
+    /// Async counterpart of [`Device::send`], run on the blocking pool like the
+    /// commands, so the device lock, rate limit, quirks and retry policy apply.
+    pub async fn send(&self, report: Packet) -> Result<Packet> {
+        self.run(move |device| device.send(report)).await
+    }
+
+    async_commands! {
+        /// Async [`command::set_perf_mode`].
+        set_perf_mode(perf_mode: PerfMode) -> ();
+        /// Async [`command::get_perf_mode`].
+        get_perf_mode() -> (PerfMode, FanMode);
+        /// Async [`command::set_cpu_boost`].
+        set_cpu_boost(boost: CpuBoost) -> ();
+        /// Async [`command::set_gpu_boost`].
+        set_gpu_boost(boost: GpuBoost) -> ();
+        /// Async [`command::get_cpu_boost`].
+        get_cpu_boost() -> MaybeKnown<CpuBoost>;
+        /// Async [`command::get_gpu_boost`].
+        get_gpu_boost() -> MaybeKnown<GpuBoost>;
+        /// Async [`command::get_boosts`].
+        get_boosts() -> (MaybeKnown<CpuBoost>, MaybeKnown<GpuBoost>);
+        /// Async [`command::set_fan_rpm`].
+        set_fan_rpm(rpm: FanRpm) -> ();
+        /// Async [`command::get_fan_rpm`].
+        get_fan_rpm(fan_zone: FanZone) -> FanRpm;
+        /// Async [`command::set_max_fan_speed_mode`].
+        set_max_fan_speed_mode(mode: MaxFanSpeedMode) -> ();
+        /// Async [`command::get_max_fan_speed_mode`].
+        get_max_fan_speed_mode() -> MaxFanSpeedMode;
+        /// Async [`command::set_fan_mode`].
+        set_fan_mode(mode: FanMode) -> ();
+        /// Async [`command::get_logo_mode`].
+        get_logo_mode() -> LogoMode;
+        /// Async [`command::set_logo_mode`].
+        set_logo_mode(mode: LogoMode) -> ();
+        /// Async [`command::get_keyboard_brightness`].
+        get_keyboard_brightness() -> Brightness;
+        /// Async [`command::set_keyboard_brightness`].
+        set_keyboard_brightness(brightness: Brightness) -> ();
+        /// Async [`command::get_lights_always_on`].
+        get_lights_always_on() -> LightsAlwaysOn;
+        /// Async [`command::set_lights_always_on`].
+        set_lights_always_on(lights_always_on: LightsAlwaysOn) -> ();
+        /// Async [`command::get_firmware_version`].
+        get_firmware_version() -> (u8, u8);
+        /// Async [`command::get_serial`].
+        get_serial() -> String;
+        /// Async [`command::get_battery_care`].
+        get_battery_care() -> BatteryCare;
+        /// Async [`command::set_battery_care`].
+        set_battery_care(mode: BatteryCare) -> ();
+        /// Async [`command::get_charge_limit`].
+        get_charge_limit() -> ChargeLimit;
+        /// Async [`command::set_charge_limit`].
+        set_charge_limit(limit: ChargeLimit) -> ();
+    }
+
+    /// Async [`command::custom_command`].
+    pub async fn custom_command(&self, command: u16, args: Vec<u8>) -> Result<()> {
+        self.run(move |device| command::custom_command(device, command, &args))
+            .await
+    }
+}
+
+fn join_error(e: tokio::task::JoinError) -> RazerError {
+    RazerError::Other(format!("Blocking task failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::SUPPORTED;
+    use crate::lock::DeviceLock;
+    use crate::transport::MockTransport;
+    use std::time;
+
+    fn mock_device() -> (AsyncDevice, MockTransport) {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        (AsyncDevice::new(device), mock)
+    }
+
+    #[tokio::test]
+    async fn test_async_send() {
+        let (device, mock) = mock_device();
+        mock.push_response(&[0xd0]);
+        let response = device.send(Packet::new(0x0792, &[0])).await.unwrap();
+        assert_eq!(response.get_args(), &[0xd0]);
+        assert_eq!(mock.sent_commands(), vec![(0x0792, vec![0])]);
+    }
+
+    #[tokio::test]
+    async fn test_async_send_waits_for_the_device_lock() {
+        let path =
+            std::env::temp_dir().join(format!("razer-ctl-test-async-{}.lock", std::process::id()));
+        let holder = DeviceLock::open(&path).unwrap();
+        let guard = holder.acquire().unwrap();
+
+        let mock = MockTransport::new();
+        let mut device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        device.set_lock(Some(
+            DeviceLock::open(&path)
+                .unwrap()
+                .with_wait(time::Duration::from_millis(30)),
+        ));
+        let device = AsyncDevice::new(device);
+        let error = device.send(Packet::new(0x0792, &[0])).await.unwrap_err();
+        assert!(
+            matches!(error, RazerError::DeviceLocked { .. }),
+            "{}",
+            error
+        );
+        assert!(mock.sent().is_empty());
+
+        drop(guard);
+        device.send(Packet::new(0x0792, &[0])).await.unwrap();
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_async_commands() {
+        let (device, mock) = mock_device();
+        mock.push_response(&[0, 0x01, 0x04]);
+        mock.push_response(&[0, 0x02, 0x02]);
+        assert_eq!(
+            device.get_boosts().await.unwrap(),
+            (CpuBoost::Overclock.into(), GpuBoost::High.into())
+        );
+
+        device
+            .set_keyboard_brightness(Brightness::new(42))
+            .await
+            .unwrap();
+        assert_eq!(
+            mock.sent_commands().last().unwrap(),
+            &(0x0303, vec![1, 5, 42])
+        );
+    }
+}
@@ -1,55 +1,181 @@
-use crate::device::Device;
-use crate::error::{RazerError, Result};
+use crate::device::CommandTarget;
+use crate::error::{RazerError, Remedy, Result};
+use crate::feature;
 use crate::packet::Packet;
+use crate::state::{DeviceState, Field, PartialState};
 use crate::types::{
-    BatteryCare, Cluster, CpuBoost, FanMode, FanZone, GpuBoost, LightsAlwaysOn, LogoMode,
-    MaxFanSpeedMode, PerfMode, ThermalZone,
+    BatteryCare, Brightness, ChargeLimit, Cluster, CpuBoost, FanMode, FanRpm, FanZone, GpuBoost,
+    LightsAlwaysOn, LogoMode, MaxFanSpeedMode, MaybeKnown, PerfMode, ThermalZone,
 };
 use log::{debug, trace};
 
-// USB HID command codes - see data/README.md for protocol details
-mod cmd {
+/// Declares [`CommandId`] and the matching raw codes in [`cmd`].
+macro_rules! commands {
+    ($($(#[$meta:meta])* $variant:ident = $raw:literal, $name:ident;)*) => {
+        /// A known USB HID command.
+        ///
+        /// The high byte of the raw code is the command class, the low byte the
+        /// command ID.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[repr(u16)]
+        pub enum CommandId {
+            $($(#[$meta])* $variant = $raw,)*
+        }
+
+        impl CommandId {
+            /// Every known command.
+            pub const ALL: &'static [CommandId] = &[$(CommandId::$variant,)*];
+
+            /// Returns the protocol name, e.g. `SET_PERF_MODE`.
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $(CommandId::$variant => stringify!($name),)*
+                }
+            }
+        }
+
+        /// Raw USB HID command codes of [`CommandId`], for matching on `u16`s.
+        /// See data/README.md for protocol details.
+        pub mod cmd {
+            $(pub const $name: u16 = $raw;)*
+        }
+    };
+}
+
+commands! {
+    // Device information
+    GetFirmwareVersion = 0x0081, GET_FIRMWARE_VERSION;
+    GetSerial = 0x0082, GET_SERIAL;
+
     // Performance mode commands
-    pub const SET_PERF_MODE: u16 = 0x0d02;
-    pub const GET_PERF_MODE: u16 = 0x0d82;
-    pub const SET_BOOST: u16 = 0x0d07;
-    pub const GET_BOOST: u16 = 0x0d87;
+    SetPerfMode = 0x0d02, SET_PERF_MODE;
+    GetPerfMode = 0x0d82, GET_PERF_MODE;
+    SetBoost = 0x0d07, SET_BOOST;
+    GetBoost = 0x0d87, GET_BOOST;
 
     // Fan commands
-    pub const SET_FAN_RPM: u16 = 0x0d01;
-    pub const GET_FAN_RPM: u16 = 0x0d81;
-    pub const SET_MAX_FAN_SPEED: u16 = 0x070f;
-    pub const GET_MAX_FAN_SPEED: u16 = 0x078f;
+    SetFanRpm = 0x0d01, SET_FAN_RPM;
+    GetFanRpm = 0x0d81, GET_FAN_RPM;
+    SetMaxFanSpeed = 0x070f, SET_MAX_FAN_SPEED;
+    GetMaxFanSpeed = 0x078f, GET_MAX_FAN_SPEED;
 
     // Logo commands
-    pub const SET_LOGO_POWER: u16 = 0x0300;
-    pub const GET_LOGO_POWER: u16 = 0x0380;
-    pub const SET_LOGO_MODE: u16 = 0x0302;
-    pub const GET_LOGO_MODE: u16 = 0x0382;
+    SetLogoPower = 0x0300, SET_LOGO_POWER;
+    GetLogoPower = 0x0380, GET_LOGO_POWER;
+    SetLogoMode = 0x0302, SET_LOGO_MODE;
+    GetLogoMode = 0x0382, GET_LOGO_MODE;
 
     // Keyboard commands
-    pub const SET_KBD_BRIGHTNESS: u16 = 0x0303;
-    pub const GET_KBD_BRIGHTNESS: u16 = 0x0383;
+    SetKbdBrightness = 0x0303, SET_KBD_BRIGHTNESS;
+    GetKbdBrightness = 0x0383, GET_KBD_BRIGHTNESS;
+    SetCustomFrame = 0x0f03, SET_CUSTOM_FRAME;
 
     // Lights always on
-    pub const SET_LIGHTS_ALWAYS_ON: u16 = 0x0004;
-    pub const GET_LIGHTS_ALWAYS_ON: u16 = 0x0084;
+    SetLightsAlwaysOn = 0x0004, SET_LIGHTS_ALWAYS_ON;
+    GetLightsAlwaysOn = 0x0084, GET_LIGHTS_ALWAYS_ON;
 
     // Battery care
-    pub const SET_BATTERY_CARE: u16 = 0x0712;
-    pub const GET_BATTERY_CARE: u16 = 0x0792;
+    SetBatteryCare = 0x0712, SET_BATTERY_CARE;
+    GetBatteryCare = 0x0792, GET_BATTERY_CARE;
+}
+
+impl CommandId {
+    /// Returns the raw 16-bit command code.
+    pub const fn raw(self) -> u16 {
+        self as u16
+    }
+
+    /// Returns the command class (high byte).
+    pub const fn class(self) -> u8 {
+        (self.raw() >> 8) as u8
+    }
+
+    /// Returns the command ID within its class (low byte).
+    pub const fn id(self) -> u8 {
+        (self.raw() & 0xff) as u8
+    }
+
+    /// Looks up a known command by its raw code.
+    pub fn from_raw(raw: u16) -> Option<CommandId> {
+        CommandId::ALL.iter().copied().find(|c| c.raw() == raw)
+    }
+
+    /// Formats a raw code for traces: `0x0d02 SET_PERF_MODE`, or just `0x1234`
+    /// for unknown commands.
+    pub fn describe(raw: u16) -> String {
+        match CommandId::from_raw(raw) {
+            Some(command) => command.to_string(),
+            None => format!("0x{:04x}", raw),
+        }
+    }
+
+    /// Whether responses carry data that identifies the laptop, like its serial
+    /// number. [`packet_log`](crate::packet_log) masks their arguments.
+    pub const fn identifies_device(self) -> bool {
+        matches!(self, CommandId::GetSerial)
+    }
+
+    /// Performance mode the firmware requires before accepting this command.
+    pub const fn required_perf_mode(self) -> Option<PerfMode> {
+        match self {
+            CommandId::SetBoost | CommandId::SetMaxFanSpeed => Some(PerfMode::Custom),
+            CommandId::SetFanRpm => Some(PerfMode::Balanced),
+            _ => None,
+        }
+    }
+}
+
+impl From<CommandId> for u16 {
+    fn from(command: CommandId) -> u16 {
+        command.raw()
+    }
+}
+
+impl std::fmt::Display for CommandId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{:04x} {}", self.raw(), self.name())
+    }
+}
+
+/// Runs `body`, naming `name` in the context of its protocol errors.
+fn operation<T>(name: &'static str, body: impl FnOnce() -> Result<T>) -> Result<T> {
+    body().map_err(|e| e.in_operation(name))
 }
 
-fn send_command(device: &Device, command: u16, args: &[u8]) -> Result<Packet> {
-    trace!("Sending command 0x{:04X} with args {:02X?}", command, args);
-    let response = device.send(Packet::new(command, args))?;
+fn send_command(device: &impl CommandTarget, command: u16, args: &[u8]) -> Result<Packet> {
+    trace!(
+        "Sending command {} with args {:02X?}",
+        CommandId::describe(command),
+        args
+    );
+    let response = device.send(Packet::try_new(command, args)?)?;
     if !response.get_args().starts_with(args) {
         return Err(RazerError::ResponseMismatch);
     }
     Ok(response)
 }
 
-fn set_perf_mode_internal(device: &Device, perf_mode: PerfMode, fan_mode: FanMode) -> Result<()> {
+/// Sends `reports` with [`CommandTarget::send_many`] and checks that every
+/// response echoes its arguments.
+fn send_batch(device: &impl CommandTarget, reports: &[Packet]) -> Result<()> {
+    trace!("Sending batch of {} reports", reports.len());
+    let responses = device.send_many(reports)?;
+    if reports
+        .iter()
+        .zip(&responses)
+        .any(|(report, response)| !response.get_args().starts_with(report.get_args()))
+    {
+        return Err(RazerError::ResponseMismatch);
+    }
+    Ok(())
+}
+
+fn set_perf_mode_internal(
+    device: &impl CommandTarget,
+    perf_mode: PerfMode,
+    fan_mode: FanMode,
+) -> Result<()> {
+    device.info().check_perf_mode(perf_mode)?;
     if (fan_mode == FanMode::Manual) && (perf_mode != PerfMode::Balanced) {
         return Err(RazerError::PreconditionFailed(format!(
             "{:?} allowed only in {:?}",
@@ -58,174 +184,330 @@ fn set_perf_mode_internal(device: &Device, perf_mode: PerfMode, fan_mode: FanMod
         )));
     }
 
-    ThermalZone::ALL.into_iter().try_for_each(|zone| {
-        send_command(
-            device,
-            cmd::SET_PERF_MODE,
-            &[0x01, zone as u8, perf_mode as u8, fan_mode as u8],
-        )
-        .map(|_| ())
+    let reports = ThermalZone::ALL
+        .into_iter()
+        .map(|zone| {
+            Packet::builder(cmd::SET_PERF_MODE)
+                .arg_u8(0x01)
+                .arg_u8(zone as u8)
+                .arg_u8(perf_mode.raw())
+                .arg_u8(fan_mode as u8)
+                .build()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    send_batch(device, &reports)
+}
+
+/// Fails with [`RazerError::ModeRequired`] unless the device is in `perf_mode`
+/// and, if given, `fan_mode`. The remedy fixes the performance mode first, since
+/// setting it resets the fan mode.
+fn require_modes(
+    device: &impl CommandTarget,
+    perf_mode: PerfMode,
+    fan_mode: Option<FanMode>,
+) -> Result<()> {
+    let actual = get_perf_mode(device)?;
+    let remedy = match fan_mode {
+        _ if actual.0 != perf_mode => Remedy::PerfMode(perf_mode),
+        Some(fan_mode) if actual.1 != fan_mode => Remedy::FanMode(fan_mode),
+        _ => return Ok(()),
+    };
+    Err(RazerError::ModeRequired {
+        required: (perf_mode, fan_mode),
+        actual,
+        remedy,
     })
 }
 
-fn set_boost_internal(device: &Device, cluster: Cluster, boost: u8) -> Result<()> {
-    let args = &[0, cluster as u8, boost];
-    if get_perf_mode(device)? != (PerfMode::Custom, FanMode::Auto) {
-        return Err(RazerError::PreconditionFailed(format!(
-            "Performance mode must be {:?}",
-            PerfMode::Custom
-        )));
-    }
-    let response = device.send(Packet::new(cmd::SET_BOOST, args))?;
-    if !response.get_args().starts_with(args) {
+fn set_boost_internal(device: &impl CommandTarget, cluster: Cluster, boost: u8) -> Result<()> {
+    let report = Packet::builder(cmd::SET_BOOST)
+        .arg_u8(0)
+        .arg_u8(cluster as u8)
+        .arg_u8(boost)
+        .build()?;
+    require_modes(device, PerfMode::Custom, Some(FanMode::Auto))?;
+    let response = device.send(report.clone())?;
+    if !response.get_args().starts_with(report.get_args()) {
         return Err(RazerError::ResponseMismatch);
     }
     Ok(())
 }
 
-fn get_boost_internal(device: &Device, cluster: Cluster) -> Result<u8> {
-    let response = device.send(Packet::new(cmd::GET_BOOST, &[0, cluster as u8, 0]))?;
-    if response.get_args()[1] != cluster as u8 {
+fn get_boost_internal(device: &impl CommandTarget, cluster: Cluster) -> Result<u8> {
+    let report = Packet::builder(cmd::GET_BOOST)
+        .arg_u8(0)
+        .arg_u8(cluster as u8)
+        .arg_u8(0)
+        .build()?;
+    let response = device.send(report)?;
+    if Cluster::try_from(response.arg(1)?)? != cluster {
         return Err(RazerError::ResponseMismatch);
     }
-    Ok(response.get_args()[2])
+    response.arg(2)
 }
 
 /// Sets the laptop's performance mode (Silent, Balanced, or Custom).
 ///
 /// Fan mode is automatically set to Auto. Use [`set_fan_mode`] to switch to manual fan control.
-pub fn set_perf_mode(device: &Device, perf_mode: PerfMode) -> Result<()> {
-    debug!("Setting performance mode to {:?}", perf_mode);
-    set_perf_mode_internal(device, perf_mode, FanMode::Auto)
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn set_perf_mode(device: &impl CommandTarget, perf_mode: PerfMode) -> Result<()> {
+    operation("set_perf_mode", || {
+        debug!("Setting performance mode to {:?}", perf_mode);
+        set_perf_mode_internal(device, perf_mode, FanMode::Auto)
+    })
 }
 
 /// Gets the current performance mode and fan mode.
 ///
-/// Queries both thermal zones and ensures they match.
-pub fn get_perf_mode(device: &Device) -> Result<(PerfMode, FanMode)> {
-    let results: Vec<_> = ThermalZone::ALL
-        .into_iter()
-        .map(|zone| {
-            let response = device.send(Packet::new(cmd::GET_PERF_MODE, &[0, zone as u8, 0, 0]))?;
-            Ok((
-                PerfMode::try_from(response.get_args()[2])?,
-                FanMode::try_from(response.get_args()[3])?,
-            ))
-        })
-        .collect::<Result<Vec<_>>>()?;
+/// Queries both thermal zones and fails with [`RazerError::ZoneMismatch`] unless they match.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn get_perf_mode(device: &impl CommandTarget) -> Result<(PerfMode, FanMode)> {
+    operation("get_perf_mode", || {
+        let results: Vec<_> = ThermalZone::ALL
+            .into_iter()
+            .map(|zone| {
+                let report = Packet::builder(cmd::GET_PERF_MODE)
+                    .arg_u8(0)
+                    .arg_u8(zone as u8)
+                    .args(&[0, 0])
+                    .build()?;
+                let response = device.send(report)?;
+                Ok((
+                    PerfMode::from_raw(response.arg(2)?),
+                    FanMode::try_from(response.arg(3)?)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-    if results[0] != results[1] {
-        return Err(RazerError::Other(format!(
-            "Modes do not match between zones: {:?} vs {:?}",
-            results[0], results[1]
-        )));
-    }
+        if results[0] != results[1] {
+            return Err(RazerError::ZoneMismatch {
+                zone1: results[0],
+                zone2: results[1],
+            });
+        }
+
+        Ok(results[0])
+    })
+}
+
+/// Sets the CPU boost level. Requires Custom performance mode and a level
+/// listed in the descriptor's `cpu_boosts`.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn set_cpu_boost(device: &impl CommandTarget, boost: CpuBoost) -> Result<()> {
+    operation("set_cpu_boost", || {
+        device.info().check_cpu_boost(boost)?;
+        debug!("Setting CPU boost to {:?}", boost);
+        set_boost_internal(device, Cluster::Cpu, boost as u8)
+    })
+}
 
-    Ok(results[0])
+/// Sets the GPU boost level. Requires Custom performance mode and a level
+/// listed in the descriptor's `gpu_boosts`.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn set_gpu_boost(device: &impl CommandTarget, boost: GpuBoost) -> Result<()> {
+    operation("set_gpu_boost", || {
+        device.info().check_gpu_boost(boost)?;
+        debug!("Setting GPU boost to {:?}", boost);
+        set_boost_internal(device, Cluster::Gpu, boost as u8)
+    })
 }
 
-/// Sets the CPU boost level. Requires Custom performance mode.
-pub fn set_cpu_boost(device: &Device, boost: CpuBoost) -> Result<()> {
-    debug!("Setting CPU boost to {:?}", boost);
-    set_boost_internal(device, Cluster::Cpu, boost as u8)
+/// Gets the current CPU boost level, keeping levels this crate doesn't know.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn get_cpu_boost(device: &impl CommandTarget) -> Result<MaybeKnown<CpuBoost>> {
+    operation("get_cpu_boost", || {
+        let raw = get_boost_internal(device, Cluster::Cpu)?;
+        Ok(MaybeKnown::from_raw(raw))
+    })
 }
 
-/// Sets the GPU boost level. Requires Custom performance mode.
-pub fn set_gpu_boost(device: &Device, boost: GpuBoost) -> Result<()> {
-    debug!("Setting GPU boost to {:?}", boost);
-    set_boost_internal(device, Cluster::Gpu, boost as u8)
+/// Gets the current GPU boost level, keeping levels this crate doesn't know.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn get_gpu_boost(device: &impl CommandTarget) -> Result<MaybeKnown<GpuBoost>> {
+    operation("get_gpu_boost", || {
+        let raw = get_boost_internal(device, Cluster::Gpu)?;
+        Ok(MaybeKnown::from_raw(raw))
+    })
 }
 
-/// Gets the current CPU boost level.
-pub fn get_cpu_boost(device: &Device) -> Result<CpuBoost> {
-    CpuBoost::try_from(get_boost_internal(device, Cluster::Cpu)?)
+/// Gets both the CPU and GPU boost levels.
+///
+/// Issues the two boost queries back-to-back without checking the performance mode,
+/// so callers that already know the device is in Custom mode avoid extra round trips.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn get_boosts(
+    device: &impl CommandTarget,
+) -> Result<(MaybeKnown<CpuBoost>, MaybeKnown<GpuBoost>)> {
+    operation("get_boosts", || {
+        Ok((get_cpu_boost(device)?, get_gpu_boost(device)?))
+    })
 }
 
-/// Gets the current GPU boost level.
-pub fn get_gpu_boost(device: &Device) -> Result<GpuBoost> {
-    GpuBoost::try_from(get_boost_internal(device, Cluster::Gpu)?)
+/// Reads only `fields`, for polling a few settings cheaply.
+///
+/// Sends just the packets those fields need: the performance mode is read once for
+/// every field that depends on it, boosts are only read in Custom mode and the fan
+/// speed only in Manual fan mode. Fields whose read fails, or whose feature the
+/// descriptor lacks, are `None`.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn read_fields(device: &impl CommandTarget, fields: &[Field]) -> PartialState {
+    let state = DeviceState::read_fields_from(device, device.info().features, fields);
+    PartialState::new(fields, state)
 }
 
-/// Sets the fan speed in RPM. Valid range is 2000-5000.
+/// Sets the fan speed in RPM, within the descriptor's `fan_rpm_range`.
 ///
 /// Requires Balanced performance mode with Manual fan mode.
-pub fn set_fan_rpm(device: &Device, rpm: u16) -> Result<()> {
-    if !(2000..=5000).contains(&rpm) {
-        return Err(RazerError::PreconditionFailed(format!(
-            "RPM must be between 2000 and 5000, got {}",
-            rpm
-        )));
-    }
-    if get_perf_mode(device)? != (PerfMode::Balanced, FanMode::Manual) {
-        return Err(RazerError::PreconditionFailed(format!(
-            "Performance mode must be {:?} and fan mode must be {:?}",
-            PerfMode::Balanced,
-            FanMode::Manual
-        )));
-    }
-    debug!("Setting fan RPM to {}", rpm);
-    FanZone::ALL.into_iter().try_for_each(|zone| {
-        send_command(
-            device,
-            cmd::SET_FAN_RPM,
-            &[0, zone as u8, (rpm / 100) as u8],
-        )
-        .map(|_| ())
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn set_fan_rpm(device: &impl CommandTarget, rpm: FanRpm) -> Result<()> {
+    operation("set_fan_rpm", || {
+        device.info().check_fan_rpm(rpm)?;
+        require_modes(device, PerfMode::Balanced, Some(FanMode::Manual))?;
+        debug!("Setting fan RPM to {}", rpm);
+        let reports = FanZone::ALL
+            .into_iter()
+            .map(|zone| {
+                Packet::builder(cmd::SET_FAN_RPM)
+                    .arg_u8(0)
+                    .arg_u8(zone as u8)
+                    .arg_u8(rpm.as_raw_byte())
+                    .build()
+            })
+            .collect::<Result<Vec<_>>>()?;
+        send_batch(device, &reports)
     })
 }
 
 /// Gets the current fan RPM for the specified zone.
-pub fn get_fan_rpm(device: &Device, fan_zone: FanZone) -> Result<u16> {
-    let response = device.send(Packet::new(cmd::GET_FAN_RPM, &[0, fan_zone as u8, 0]))?;
-    if response.get_args()[1] != fan_zone as u8 {
-        return Err(RazerError::ResponseMismatch);
-    }
-    Ok(response.get_args()[2] as u16 * 100)
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn get_fan_rpm(device: &impl CommandTarget, fan_zone: FanZone) -> Result<FanRpm> {
+    operation("get_fan_rpm", || {
+        let report = Packet::builder(cmd::GET_FAN_RPM)
+            .arg_u8(0)
+            .arg_u8(fan_zone as u8)
+            .arg_u8(0)
+            .build()?;
+        let response = device.send(report)?;
+        if FanZone::try_from(response.arg(1)?)? != fan_zone {
+            return Err(RazerError::ResponseMismatch);
+        }
+        Ok(FanRpm::from_raw_byte(response.arg(2)?))
+    })
 }
 
 /// Enables or disables max fan speed mode. Requires Custom performance mode.
-pub fn set_max_fan_speed_mode(device: &Device, mode: MaxFanSpeedMode) -> Result<()> {
-    if get_perf_mode(device)?.0 != PerfMode::Custom {
-        return Err(RazerError::PreconditionFailed(format!(
-            "Performance mode must be {:?}",
-            PerfMode::Custom
-        )));
-    }
-    send_command(device, cmd::SET_MAX_FAN_SPEED, &[mode as u8]).map(|_| ())
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn set_max_fan_speed_mode(device: &impl CommandTarget, mode: MaxFanSpeedMode) -> Result<()> {
+    operation("set_max_fan_speed_mode", || {
+        require_modes(device, PerfMode::Custom, None)?;
+        send_command(device, cmd::SET_MAX_FAN_SPEED, &[mode as u8]).map(|_| ())
+    })
 }
 
 /// Gets the current max fan speed mode setting.
-pub fn get_max_fan_speed_mode(device: &Device) -> Result<MaxFanSpeedMode> {
-    device
-        .send(Packet::new(cmd::GET_MAX_FAN_SPEED, &[0]))?
-        .get_args()[0]
-        .try_into()
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn get_max_fan_speed_mode(device: &impl CommandTarget) -> Result<MaxFanSpeedMode> {
+    operation("get_max_fan_speed_mode", || {
+        device
+            .send(Packet::builder(cmd::GET_MAX_FAN_SPEED).arg_u8(0).build()?)?
+            .arg(0)?
+            .try_into()
+    })
 }
 
 /// Sets the fan mode to Auto or Manual. Requires Balanced performance mode.
-pub fn set_fan_mode(device: &Device, mode: FanMode) -> Result<()> {
-    if get_perf_mode(device)?.0 != PerfMode::Balanced {
-        return Err(RazerError::PreconditionFailed(format!(
-            "Performance mode must be {:?}",
-            PerfMode::Balanced
-        )));
-    }
-    set_perf_mode_internal(device, PerfMode::Balanced, mode)
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn set_fan_mode(device: &impl CommandTarget, mode: FanMode) -> Result<()> {
+    operation("set_fan_mode", || {
+        require_modes(device, PerfMode::Balanced, None)?;
+        set_perf_mode_internal(device, PerfMode::Balanced, mode)
+    })
+}
+
+/// Gets the firmware version as (major, minor).
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn get_firmware_version(device: &impl CommandTarget) -> Result<(u8, u8)> {
+    operation("get_firmware_version", || {
+        let response = device.send(Packet::new(cmd::GET_FIRMWARE_VERSION, &[0, 0]))?;
+        Ok((response.arg(0)?, response.arg(1)?))
+    })
+}
+
+/// Gets the serial number stored in the firmware.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn get_serial(device: &impl CommandTarget) -> Result<String> {
+    operation("get_serial", || {
+        let response = device.send(Packet::new(cmd::GET_SERIAL, &[0; 22]))?;
+        let serial = response.args_exact(22)?;
+        let end = serial.iter().position(|&b| b == 0).unwrap_or(serial.len());
+        Ok(String::from_utf8_lossy(&serial[..end]).trim().to_string())
+    })
 }
 
 /// Sends a custom USB HID command to the device.
 ///
 /// # Warning
 /// Use at your own risk. Incorrect commands may cause unexpected behavior.
-pub fn custom_command(device: &Device, command: u16, args: &[u8]) -> Result<()> {
-    let report = Packet::new(command, args);
-    debug!("Report   {:?}", report);
-    let response = device.send(report)?;
-    debug!("Response {:?}", response);
-    Ok(())
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn custom_command(device: &impl CommandTarget, command: u16, args: &[u8]) -> Result<()> {
+    operation("custom_command", || {
+        let report = Packet::try_new(command, args)?;
+        debug!("Report   {}", report.hexdump());
+        let response = device.send(report)?;
+        debug!("Response {}", response.hexdump());
+        Ok(())
+    })
 }
 
-fn set_logo_power(device: &Device, mode: LogoMode) -> Result<Packet> {
+fn set_logo_power(device: &impl CommandTarget, mode: LogoMode) -> Result<Packet> {
     match mode {
         LogoMode::Off => send_command(device, cmd::SET_LOGO_POWER, &[1, 4, 0]),
         LogoMode::Static | LogoMode::Breathing => {
@@ -234,7 +516,7 @@ fn set_logo_power(device: &Device, mode: LogoMode) -> Result<Packet> {
     }
 }
 
-fn set_logo_mode_internal(device: &Device, mode: LogoMode) -> Result<Packet> {
+fn set_logo_mode_internal(device: &impl CommandTarget, mode: LogoMode) -> Result<Packet> {
     match mode {
         LogoMode::Static => send_command(device, cmd::SET_LOGO_MODE, &[1, 4, 0]),
         LogoMode::Breathing => send_command(device, cmd::SET_LOGO_MODE, &[1, 4, 2]),
@@ -242,10 +524,10 @@ fn set_logo_mode_internal(device: &Device, mode: LogoMode) -> Result<Packet> {
     }
 }
 
-fn get_logo_power(device: &Device) -> Result<bool> {
+fn get_logo_power(device: &impl CommandTarget) -> Result<bool> {
     match device
         .send(Packet::new(cmd::GET_LOGO_POWER, &[1, 4, 0]))?
-        .get_args()[2]
+        .arg(2)?
     {
         0 => Ok(false),
         1 => Ok(true),
@@ -256,10 +538,10 @@ fn get_logo_power(device: &Device) -> Result<bool> {
     }
 }
 
-fn get_logo_mode_internal(device: &Device) -> Result<LogoMode> {
+fn get_logo_mode_internal(device: &impl CommandTarget) -> Result<LogoMode> {
     match device
         .send(Packet::new(cmd::GET_LOGO_MODE, &[1, 4, 0]))?
-        .get_args()[2]
+        .arg(2)?
     {
         0 => Ok(LogoMode::Static),
         2 => Ok(LogoMode::Breathing),
@@ -271,77 +553,843 @@ fn get_logo_mode_internal(device: &Device) -> Result<LogoMode> {
 }
 
 /// Gets the current lid logo mode (Off, Static, or Breathing).
-pub fn get_logo_mode(device: &Device) -> Result<LogoMode> {
-    let power = get_logo_power(device)?;
-    match power {
-        true => get_logo_mode_internal(device),
-        false => Ok(LogoMode::Off),
-    }
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn get_logo_mode(device: &impl CommandTarget) -> Result<LogoMode> {
+    operation("get_logo_mode", || {
+        let power = get_logo_power(device)?;
+        match power {
+            true => get_logo_mode_internal(device),
+            false => Ok(LogoMode::Off),
+        }
+    })
 }
 
 /// Sets the lid logo mode (Off, Static, or Breathing).
-pub fn set_logo_mode(device: &Device, mode: LogoMode) -> Result<()> {
-    debug!("Setting logo mode to {:?}", mode);
-    if mode != LogoMode::Off {
-        set_logo_mode_internal(device, mode)?;
-    }
-    set_logo_power(device, mode)?;
-    Ok(())
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn set_logo_mode(device: &impl CommandTarget, mode: LogoMode) -> Result<()> {
+    operation("set_logo_mode", || {
+        debug!("Setting logo mode to {:?}", mode);
+        if mode != LogoMode::Off {
+            set_logo_mode_internal(device, mode)?;
+        }
+        set_logo_power(device, mode)?;
+        Ok(())
+    })
 }
 
-/// Gets the current keyboard backlight brightness (0-255).
-pub fn get_keyboard_brightness(device: &Device) -> Result<u8> {
-    let response = device.send(Packet::new(cmd::GET_KBD_BRIGHTNESS, &[1, 5, 0]))?;
-    if response.get_args()[1] != 5 {
-        return Err(RazerError::ResponseMismatch);
-    }
-    Ok(response.get_args()[2])
+/// Gets the current keyboard backlight brightness.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn get_keyboard_brightness(device: &impl CommandTarget) -> Result<Brightness> {
+    operation("get_keyboard_brightness", || {
+        let response = device.send(Packet::new(cmd::GET_KBD_BRIGHTNESS, &[1, 5, 0]))?;
+        if response.arg(1)? != 5 {
+            return Err(RazerError::ResponseMismatch);
+        }
+        response.arg(2).map(Brightness::new)
+    })
 }
 
-/// Sets the keyboard backlight brightness (0-255).
-pub fn set_keyboard_brightness(device: &Device, brightness: u8) -> Result<()> {
-    debug!("Setting keyboard brightness to {}", brightness);
-    let args = &[1, 5, brightness];
-    let response = device.send(Packet::new(cmd::SET_KBD_BRIGHTNESS, args))?;
-    if !response.get_args().starts_with(args) {
-        return Err(RazerError::ResponseMismatch);
-    }
-    Ok(())
+/// Sets the keyboard backlight brightness, from a [`Brightness`] or a raw 0-255 value.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device, brightness))
+)]
+pub fn set_keyboard_brightness(
+    device: &impl CommandTarget,
+    brightness: impl Into<Brightness>,
+) -> Result<()> {
+    operation("set_keyboard_brightness", || {
+        let brightness = brightness.into();
+        debug!("Setting keyboard brightness to {}", brightness);
+        let args = &[1, 5, brightness.get()];
+        let response = device.send(Packet::new(cmd::SET_KBD_BRIGHTNESS, args))?;
+        if !response.get_args().starts_with(args) {
+            return Err(RazerError::ResponseMismatch);
+        }
+        Ok(())
+    })
+}
+
+/// Colors keys `0..colors.len()` of keyboard matrix row `row` on a per-key keyboard,
+/// one RGB triple per key.
+///
+/// Fails with [`RazerError::FeatureNotSupported`] before anything is sent unless the
+/// descriptor declares [`PerKey`](crate::descriptor::LightingCapability::PerKey), and with
+/// [`RazerError::PreconditionFailed`] if the row doesn't fit its matrix.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn set_key_row(device: &impl CommandTarget, row: u8, colors: &[[u8; 3]]) -> Result<()> {
+    operation("set_key_row", || {
+        let info = device.info();
+        let Some((rows, cols)) = info.lighting.per_key() else {
+            return Err(RazerError::FeatureNotSupported {
+                feature: feature::PER_KEY,
+                device: info.name.to_string(),
+            });
+        };
+        if row >= rows || colors.is_empty() || colors.len() > cols as usize {
+            return Err(RazerError::PreconditionFailed(format!(
+                "Row {} with {} keys does not fit the {}x{} keyboard matrix",
+                row,
+                colors.len(),
+                rows,
+                cols
+            )));
+        }
+        debug!("Setting {} key colors in row {}", colors.len(), row);
+        let report = Packet::builder(cmd::SET_CUSTOM_FRAME)
+            .args(&[0, 0, row, 0, colors.len() as u8 - 1])
+            .args(colors.as_flattened())
+            .build()?;
+        device.send(report).map(|_| ())
+    })
 }
 
 /// Gets whether lights stay on when the laptop is closed/sleeping.
-pub fn get_lights_always_on(device: &Device) -> Result<LightsAlwaysOn> {
-    device
-        .send(Packet::new(cmd::GET_LIGHTS_ALWAYS_ON, &[0, 0]))?
-        .get_args()[0]
-        .try_into()
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn get_lights_always_on(device: &impl CommandTarget) -> Result<LightsAlwaysOn> {
+    operation("get_lights_always_on", || {
+        device
+            .send(Packet::new(cmd::GET_LIGHTS_ALWAYS_ON, &[0, 0]))?
+            .arg(0)?
+            .try_into()
+    })
 }
 
 /// Sets whether lights stay on when the laptop is closed/sleeping.
-pub fn set_lights_always_on(device: &Device, lights_always_on: LightsAlwaysOn) -> Result<()> {
-    let args = &[lights_always_on as u8, 0];
-    let response = device.send(Packet::new(cmd::SET_LIGHTS_ALWAYS_ON, args))?;
-    if !response.get_args().starts_with(args) {
-        return Err(RazerError::ResponseMismatch);
-    }
-    Ok(())
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn set_lights_always_on(
+    device: &impl CommandTarget,
+    lights_always_on: LightsAlwaysOn,
+) -> Result<()> {
+    operation("set_lights_always_on", || {
+        let args = &[lights_always_on as u8, 0];
+        let response = device.send(Packet::new(cmd::SET_LIGHTS_ALWAYS_ON, args))?;
+        if !response.get_args().starts_with(args) {
+            return Err(RazerError::ResponseMismatch);
+        }
+        Ok(())
+    })
 }
 
 /// Gets the battery care mode (limits charging to 80% to extend battery life).
-pub fn get_battery_care(device: &Device) -> Result<BatteryCare> {
-    device
-        .send(Packet::new(cmd::GET_BATTERY_CARE, &[0]))?
-        .get_args()[0]
-        .try_into()
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn get_battery_care(device: &impl CommandTarget) -> Result<BatteryCare> {
+    operation("get_battery_care", || {
+        device
+            .send(Packet::new(cmd::GET_BATTERY_CARE, &[0]))?
+            .arg(0)?
+            .try_into()
+    })
 }
 
 /// Sets the battery care mode (limits charging to 80% to extend battery life).
-pub fn set_battery_care(device: &Device, mode: BatteryCare) -> Result<()> {
-    debug!("Setting battery care to {:?}", mode);
-    let args = &[mode as u8];
-    let response = device.send(Packet::new(cmd::SET_BATTERY_CARE, args))?;
-    if !response.get_args().starts_with(args) {
-        return Err(RazerError::ResponseMismatch);
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn set_battery_care(device: &impl CommandTarget, mode: BatteryCare) -> Result<()> {
+    operation("set_battery_care", || {
+        debug!("Setting battery care to {:?}", mode);
+        let args = &[mode as u8];
+        let response = device.send(Packet::new(cmd::SET_BATTERY_CARE, args))?;
+        if !response.get_args().starts_with(args) {
+            return Err(RazerError::ResponseMismatch);
+        }
+        Ok(())
+    })
+}
+
+/// Gets the charge limit battery care applies, [`ChargeLimit::MAX`] while it's off.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn get_charge_limit(device: &impl CommandTarget) -> Result<ChargeLimit> {
+    operation("get_charge_limit", || {
+        let value = device
+            .send(Packet::new(cmd::GET_BATTERY_CARE, &[0]))?
+            .arg(0)?;
+        ChargeLimit::from_wire(value, device.info().charge_limit_encoding)
+    })
+}
+
+/// Sets the charge limit, turning battery care off for [`ChargeLimit::MAX`].
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(device))
+)]
+pub fn set_charge_limit(device: &impl CommandTarget, limit: ChargeLimit) -> Result<()> {
+    operation("set_charge_limit", || {
+        debug!("Setting charge limit to {}", limit);
+        let args = &[limit.to_wire(device.info().charge_limit_encoding)];
+        let response = device.send(Packet::new(cmd::SET_BATTERY_CARE, args))?;
+        if !response.get_args().starts_with(args) {
+            return Err(RazerError::ResponseMismatch);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::{Descriptor, LightingCapability, SUPPORTED};
+    use crate::device::Device;
+    use crate::transport::MockTransport;
+
+    fn mock_device() -> (Device, MockTransport) {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        (device, mock)
+    }
+
+    fn script_perf_mode(mock: &MockTransport, perf_mode: PerfMode, fan_mode: FanMode) {
+        for zone in ThermalZone::ALL {
+            mock.push_response(&[0, zone as u8, perf_mode.raw(), fan_mode as u8]);
+        }
+    }
+
+    #[test]
+    fn test_set_perf_mode_sends_both_zones() {
+        let (device, mock) = mock_device();
+        set_perf_mode(&device, PerfMode::Silent).unwrap();
+        assert_eq!(
+            mock.sent_commands(),
+            vec![
+                (cmd::SET_PERF_MODE, vec![0x01, 0x01, 0x05, 0x00]),
+                (cmd::SET_PERF_MODE, vec![0x01, 0x02, 0x05, 0x00]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_perf_mode_parses_response() {
+        let (device, mock) = mock_device();
+        script_perf_mode(&mock, PerfMode::Custom, FanMode::Auto);
+        assert_eq!(
+            get_perf_mode(&device).unwrap(),
+            (PerfMode::Custom, FanMode::Auto)
+        );
+        assert_eq!(
+            mock.sent_commands(),
+            vec![
+                (cmd::GET_PERF_MODE, vec![0, 0x01, 0, 0]),
+                (cmd::GET_PERF_MODE, vec![0, 0x02, 0, 0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_perf_mode_rejects_undeclared_mode() {
+        let mock = MockTransport::new();
+        let descriptor = Descriptor {
+            perf_modes: &[PerfMode::Balanced, PerfMode::Custom],
+            ..SUPPORTED[0].clone()
+        };
+        let device = Device::with_transport(mock.clone(), descriptor);
+        assert!(matches!(
+            set_perf_mode(&device, PerfMode::Silent),
+            Err(RazerError::PreconditionFailed(_))
+        ));
+        assert!(matches!(
+            set_perf_mode(&device, PerfMode::Unknown(6)),
+            Err(RazerError::PreconditionFailed(_))
+        ));
+        assert!(mock.sent_commands().is_empty());
+    }
+
+    #[test]
+    fn test_get_perf_mode_keeps_unknown_modes() {
+        let (device, mock) = mock_device();
+        script_perf_mode(&mock, PerfMode::Unknown(6), FanMode::Auto);
+        assert_eq!(
+            get_perf_mode(&device).unwrap(),
+            (PerfMode::Unknown(6), FanMode::Auto)
+        );
+    }
+
+    #[test]
+    fn test_get_perf_mode_zone_mismatch() {
+        let (device, mock) = mock_device();
+        mock.push_response(&[0, 0x01, PerfMode::Custom.raw(), 0]);
+        mock.push_response(&[0, 0x02, PerfMode::Silent.raw(), 0]);
+        let error = get_perf_mode(&device).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Modes do not match between zones: Custom (Auto) vs Silent (Auto)"
+        );
+        assert!(matches!(
+            error,
+            RazerError::ZoneMismatch {
+                zone1: (PerfMode::Custom, FanMode::Auto),
+                zone2: (PerfMode::Silent, FanMode::Auto),
+            }
+        ));
+    }
+
+    #[test]
+    fn test_set_cpu_boost_requires_custom_mode() {
+        let (device, mock) = mock_device();
+        script_perf_mode(&mock, PerfMode::Balanced, FanMode::Auto);
+        let error = set_cpu_boost(&device, CpuBoost::High).unwrap_err();
+        assert!(matches!(
+            error,
+            RazerError::ModeRequired {
+                remedy: Remedy::PerfMode(PerfMode::Custom),
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Performance mode must be Custom and fan mode must be Auto, but is Balanced (Auto)"
+        );
+        assert_eq!(mock.sent().len(), 2);
+
+        script_perf_mode(&mock, PerfMode::Custom, FanMode::Manual);
+        assert!(matches!(
+            set_cpu_boost(&device, CpuBoost::High),
+            Err(RazerError::ModeRequired {
+                remedy: Remedy::FanMode(FanMode::Auto),
+                ..
+            })
+        ));
+        assert_eq!(mock.sent().len(), 4);
+    }
+
+    #[test]
+    fn test_set_cpu_boost() {
+        let (device, mock) = mock_device();
+        script_perf_mode(&mock, PerfMode::Custom, FanMode::Auto);
+        set_cpu_boost(&device, CpuBoost::Overclock).unwrap();
+        assert_eq!(
+            mock.sent_commands().last().unwrap(),
+            &(cmd::SET_BOOST, vec![0, 0x01, 0x04])
+        );
+    }
+
+    #[test]
+    fn test_set_boost_checks_descriptor_levels() {
+        let mock = MockTransport::new();
+        let mut descriptor = SUPPORTED[0].clone();
+        descriptor.cpu_boosts = &[CpuBoost::Low, CpuBoost::Medium];
+        descriptor.gpu_boosts = &[GpuBoost::Low];
+        let device = Device::with_transport(mock.clone(), descriptor);
+        for result in [
+            set_cpu_boost(&device, CpuBoost::Overclock),
+            set_gpu_boost(&device, GpuBoost::High),
+        ] {
+            assert!(matches!(result, Err(RazerError::UnsupportedLevel { .. })));
+        }
+        assert!(mock.sent().is_empty());
+
+        let err = SUPPORTED[1]
+            .check_cpu_boost(CpuBoost::Overclock)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Overclock is not supported on Razer Blade 14\" (2023) Mercury"
+        );
+    }
+
+    #[test]
+    fn test_set_key_row() {
+        let (device, mock) = mock_device();
+        set_key_row(&device, 2, &[[0xff, 0x00, 0x00], [0x00, 0xff, 0x00]]).unwrap();
+        assert_eq!(
+            mock.sent_commands(),
+            vec![(
+                cmd::SET_CUSTOM_FRAME,
+                vec![0, 0, 2, 0, 1, 0xff, 0x00, 0x00, 0x00, 0xff, 0x00]
+            )]
+        );
+
+        assert!(matches!(
+            set_key_row(&device, 6, &[[0; 3]]),
+            Err(RazerError::PreconditionFailed(_))
+        ));
+        assert!(set_key_row(&device, 0, &[[0; 3]; 17]).is_err());
+
+        let mock = MockTransport::new();
+        let mut descriptor = SUPPORTED[0].clone();
+        descriptor.lighting = LightingCapability::BrightnessOnly;
+        let device = Device::with_transport(mock.clone(), descriptor);
+        assert!(matches!(
+            set_key_row(&device, 0, &[[0; 3]]),
+            Err(RazerError::FeatureNotSupported {
+                feature: feature::PER_KEY,
+                ..
+            })
+        ));
+        assert!(mock.sent().is_empty());
+    }
+
+    #[test]
+    fn test_set_gpu_boost() {
+        let (device, mock) = mock_device();
+        script_perf_mode(&mock, PerfMode::Custom, FanMode::Auto);
+        set_gpu_boost(&device, GpuBoost::Medium).unwrap();
+        assert_eq!(
+            mock.sent_commands().last().unwrap(),
+            &(cmd::SET_BOOST, vec![0, 0x02, 0x01])
+        );
+    }
+
+    #[test]
+    fn test_get_cpu_and_gpu_boost() {
+        let (device, mock) = mock_device();
+        mock.push_response(&[0, 0x01, 0x03]);
+        mock.push_response(&[0, 0x02, 0x02]);
+        assert_eq!(get_cpu_boost(&device).unwrap(), CpuBoost::Boost);
+        assert_eq!(get_gpu_boost(&device).unwrap(), GpuBoost::High);
+        assert_eq!(
+            mock.sent_commands(),
+            vec![
+                (cmd::GET_BOOST, vec![0, 0x01, 0]),
+                (cmd::GET_BOOST, vec![0, 0x02, 0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_boosts_keeps_unknown_levels() {
+        let (device, mock) = mock_device();
+        mock.push_response(&[0, 0x01, 0x06]);
+        mock.push_response(&[0, 0x02, 0x01]);
+        assert_eq!(
+            get_boosts(&device).unwrap(),
+            (MaybeKnown::Unknown(6), MaybeKnown::Known(GpuBoost::Medium))
+        );
+    }
+
+    #[test]
+    fn test_get_boost_cluster_mismatch() {
+        let (device, mock) = mock_device();
+        mock.push_response(&[0, 0x02, 0x01]);
+        let error = get_cpu_boost(&device).unwrap_err();
+        assert!(matches!(error.root(), RazerError::ResponseMismatch));
+        assert_eq!(error.context().unwrap().operation, Some("get_cpu_boost"));
+    }
+
+    #[test]
+    fn test_get_boosts_sends_exactly_two_packets() {
+        let (device, mock) = mock_device();
+        mock.push_response(&[0, 0x01, 0x04]);
+        mock.push_response(&[0, 0x02, 0x00]);
+        assert_eq!(
+            get_boosts(&device).unwrap(),
+            (CpuBoost::Overclock.into(), GpuBoost::Low.into())
+        );
+        assert_eq!(mock.sent().len(), 2);
+    }
+
+    fn read_commands(mock: &MockTransport, fields: &[Field]) -> (PartialState, Vec<u16>) {
+        let info = SUPPORTED[0].with_features(crate::feature::ALL_FEATURES);
+        let device = Device::with_transport(mock.clone(), info);
+        let state = read_fields(&device, fields);
+        let commands = mock.sent_commands().into_iter().map(|(c, _)| c).collect();
+        (state, commands)
+    }
+
+    #[test]
+    fn test_read_fields_sends_only_what_is_needed() {
+        let mock = MockTransport::new();
+        let (state, commands) =
+            read_commands(&mock, &[Field::KeyboardBrightness, Field::BatteryCare]);
+        assert_eq!(commands, [cmd::GET_KBD_BRIGHTNESS, cmd::GET_BATTERY_CARE]);
+        assert_eq!(
+            state.fields(),
+            [Field::KeyboardBrightness, Field::BatteryCare]
+        );
+        assert_eq!(state.get(Field::PerfMode), None);
+
+        let mock = MockTransport::new();
+        let (_, commands) = read_commands(&mock, &[]);
+        assert!(commands.is_empty());
+
+        // every field, as DeviceState::read_from reads them
+        let mock = MockTransport::new();
+        let (_, commands) = read_commands(&mock, Field::ALL);
+        assert_eq!(
+            commands,
+            [
+                cmd::GET_PERF_MODE,
+                cmd::GET_PERF_MODE,
+                cmd::GET_MAX_FAN_SPEED,
+                cmd::GET_KBD_BRIGHTNESS,
+                cmd::GET_BATTERY_CARE,
+                cmd::GET_LOGO_POWER,
+                cmd::GET_LIGHTS_ALWAYS_ON,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_fields_shares_the_mode_read() {
+        let mock = MockTransport::new();
+        script_perf_mode(&mock, PerfMode::Custom, FanMode::Auto);
+        mock.push_response(&[0, 0x01, 0x04]);
+        mock.push_response(&[0, 0x02, 0x00]);
+        let fields = [
+            Field::CpuBoost,
+            Field::GpuBoost,
+            Field::FanRpm,
+            Field::CpuBoost,
+        ];
+        let (state, commands) = read_commands(&mock, &fields);
+        // no fan speed read with the fans in Auto
+        assert_eq!(
+            commands,
+            [
+                cmd::GET_PERF_MODE,
+                cmd::GET_PERF_MODE,
+                cmd::GET_BOOST,
+                cmd::GET_BOOST
+            ]
+        );
+        assert_eq!(
+            state.fields(),
+            [Field::CpuBoost, Field::GpuBoost, Field::FanRpm]
+        );
+        assert_eq!(state.get(Field::PerfMode), None);
+        assert_eq!(
+            serde_json::to_value(&state).unwrap(),
+            serde_json::json!({ "cpu_boost": "Overclock", "gpu_boost": "Low", "fan_rpm": null })
+        );
+
+        let mock = MockTransport::new();
+        script_perf_mode(&mock, PerfMode::Balanced, FanMode::Manual);
+        let (_, commands) = read_commands(&mock, &[Field::FanRpm, Field::FanMode]);
+        assert_eq!(
+            commands,
+            [cmd::GET_PERF_MODE, cmd::GET_PERF_MODE, cmd::GET_FAN_RPM]
+        );
+
+        let mock = MockTransport::new();
+        script_perf_mode(&mock, PerfMode::Custom, FanMode::Auto);
+        let (_, commands) = read_commands(&mock, &[Field::GpuBoost]);
+        assert_eq!(
+            commands,
+            [cmd::GET_PERF_MODE, cmd::GET_PERF_MODE, cmd::GET_BOOST]
+        );
+    }
+
+    #[test]
+    fn test_set_fan_rpm_remedies() {
+        let (device, mock) = mock_device();
+        let rpm = FanRpm::new(3500).unwrap();
+        let remedy = |error| match error {
+            RazerError::ModeRequired { remedy, .. } => remedy,
+            error => panic!("{:?}", error),
+        };
+
+        script_perf_mode(&mock, PerfMode::Silent, FanMode::Auto);
+        let error = set_fan_rpm(&device, rpm).unwrap_err();
+        assert_eq!(remedy(error), Remedy::PerfMode(PerfMode::Balanced));
+
+        script_perf_mode(&mock, PerfMode::Balanced, FanMode::Auto);
+        let error = set_fan_rpm(&device, rpm).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Performance mode must be Balanced and fan mode must be Manual, but is Balanced (Auto)"
+        );
+        assert_eq!(remedy(error), Remedy::FanMode(FanMode::Manual));
+        assert!(mock
+            .sent_commands()
+            .iter()
+            .all(|(command, _)| *command == cmd::GET_PERF_MODE));
+    }
+
+    #[test]
+    fn test_set_fan_rpm_validates_range() {
+        let (device, mock) = mock_device();
+        assert!(set_fan_rpm(&device, FanRpm::new(1900).unwrap()).is_err());
+        assert!(set_fan_rpm(&device, FanRpm::new(5100).unwrap()).is_err());
+        assert!(mock.sent().is_empty());
+    }
+
+    #[test]
+    fn test_set_fan_rpm_uses_descriptor_range() {
+        let mock = MockTransport::new();
+        let mut descriptor = SUPPORTED[0].clone();
+        descriptor.fan_rpm_range = 1500..=5300;
+        let device = Device::with_transport(mock.clone(), descriptor);
+        assert!(set_fan_rpm(&device, FanRpm::new(1400).unwrap()).is_err());
+        assert!(set_fan_rpm(&device, FanRpm::new(5400).unwrap()).is_err());
+        assert!(mock.sent().is_empty());
+
+        script_perf_mode(&mock, PerfMode::Balanced, FanMode::Manual);
+        set_fan_rpm(&device, FanRpm::new(5200).unwrap()).unwrap();
+        assert_eq!(
+            mock.sent_commands().last().unwrap(),
+            &(cmd::SET_FAN_RPM, vec![0, 0x02, 52])
+        );
+    }
+
+    #[test]
+    fn test_set_fan_rpm() {
+        let (device, mock) = mock_device();
+        script_perf_mode(&mock, PerfMode::Balanced, FanMode::Manual);
+        set_fan_rpm(&device, FanRpm::new(3500).unwrap()).unwrap();
+        assert_eq!(
+            mock.sent_commands()[2..],
+            [
+                (cmd::SET_FAN_RPM, vec![0, 0x01, 35]),
+                (cmd::SET_FAN_RPM, vec![0, 0x02, 35]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_fan_rpm() {
+        let (device, mock) = mock_device();
+        mock.push_response(&[0, 0x01, 42]);
+        assert_eq!(
+            get_fan_rpm(&device, FanZone::Zone1).unwrap(),
+            FanRpm::new(4200).unwrap()
+        );
+        assert_eq!(
+            mock.sent_commands(),
+            vec![(cmd::GET_FAN_RPM, vec![0, 0x01, 0])]
+        );
+    }
+
+    #[test]
+    fn test_set_fan_mode_requires_balanced() {
+        let (device, mock) = mock_device();
+        script_perf_mode(&mock, PerfMode::Silent, FanMode::Auto);
+        assert!(set_fan_mode(&device, FanMode::Manual).is_err());
+    }
+
+    #[test]
+    fn test_set_fan_mode() {
+        let (device, mock) = mock_device();
+        script_perf_mode(&mock, PerfMode::Balanced, FanMode::Auto);
+        set_fan_mode(&device, FanMode::Manual).unwrap();
+        assert_eq!(
+            mock.sent_commands()[2..],
+            [
+                (cmd::SET_PERF_MODE, vec![0x01, 0x01, 0x00, 0x01]),
+                (cmd::SET_PERF_MODE, vec![0x01, 0x02, 0x00, 0x01]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_max_fan_speed_mode() {
+        let (device, mock) = mock_device();
+        script_perf_mode(&mock, PerfMode::Custom, FanMode::Auto);
+        set_max_fan_speed_mode(&device, MaxFanSpeedMode::Enable).unwrap();
+        assert_eq!(
+            mock.sent_commands().last().unwrap(),
+            &(cmd::SET_MAX_FAN_SPEED, vec![0x02])
+        );
+
+        mock.push_response(&[0x02]);
+        assert_eq!(
+            get_max_fan_speed_mode(&device).unwrap(),
+            MaxFanSpeedMode::Enable
+        );
+    }
+
+    #[test]
+    fn test_set_logo_mode() {
+        let (device, mock) = mock_device();
+        set_logo_mode(&device, LogoMode::Breathing).unwrap();
+        assert_eq!(
+            mock.sent_commands(),
+            vec![
+                (cmd::SET_LOGO_MODE, vec![1, 4, 2]),
+                (cmd::SET_LOGO_POWER, vec![1, 4, 1]),
+            ]
+        );
+
+        mock.clear();
+        set_logo_mode(&device, LogoMode::Off).unwrap();
+        assert_eq!(
+            mock.sent_commands(),
+            vec![(cmd::SET_LOGO_POWER, vec![1, 4, 0])]
+        );
+    }
+
+    #[test]
+    fn test_get_logo_mode() {
+        let (device, mock) = mock_device();
+        mock.push_response(&[1, 4, 0]);
+        assert_eq!(get_logo_mode(&device).unwrap(), LogoMode::Off);
+        assert_eq!(mock.sent().len(), 1);
+
+        mock.push_response(&[1, 4, 1]);
+        mock.push_response(&[1, 4, 0]);
+        assert_eq!(get_logo_mode(&device).unwrap(), LogoMode::Static);
+    }
+
+    #[test]
+    fn test_keyboard_brightness() {
+        let (device, mock) = mock_device();
+        set_keyboard_brightness(&device, 128).unwrap();
+        assert_eq!(
+            mock.sent_commands(),
+            vec![(cmd::SET_KBD_BRIGHTNESS, vec![1, 5, 128])]
+        );
+
+        mock.push_response(&[1, 5, 77]);
+        assert_eq!(
+            get_keyboard_brightness(&device).unwrap(),
+            Brightness::new(77)
+        );
+    }
+
+    #[test]
+    fn test_lights_always_on() {
+        let (device, mock) = mock_device();
+        set_lights_always_on(&device, LightsAlwaysOn::Enable).unwrap();
+        assert_eq!(
+            mock.sent_commands(),
+            vec![(cmd::SET_LIGHTS_ALWAYS_ON, vec![0x03, 0])]
+        );
+
+        mock.push_response(&[0x00]);
+        assert_eq!(
+            get_lights_always_on(&device).unwrap(),
+            LightsAlwaysOn::Disable
+        );
+    }
+
+    #[test]
+    fn test_battery_care() {
+        let (device, mock) = mock_device();
+        set_battery_care(&device, BatteryCare::Enable).unwrap();
+        assert_eq!(
+            mock.sent_commands(),
+            vec![(cmd::SET_BATTERY_CARE, vec![0xd0])]
+        );
+
+        mock.push_response(&[0x50]);
+        assert_eq!(get_battery_care(&device).unwrap(), BatteryCare::Disable);
+    }
+
+    #[test]
+    fn test_charge_limit() {
+        let (device, mock) = mock_device();
+        set_charge_limit(&device, ChargeLimit::new(60)).unwrap();
+        set_charge_limit(&device, ChargeLimit::MAX).unwrap();
+        assert_eq!(
+            mock.sent_commands(),
+            vec![
+                (cmd::SET_BATTERY_CARE, vec![0x80 | 60]),
+                (cmd::SET_BATTERY_CARE, vec![0x50]),
+            ]
+        );
+
+        mock.push_response(&[0xd0]);
+        assert_eq!(get_charge_limit(&device).unwrap(), ChargeLimit::new(80));
+        mock.push_response(&[0x50]);
+        assert_eq!(get_charge_limit(&device).unwrap(), ChargeLimit::MAX);
+    }
+
+    #[test]
+    fn test_identity_commands() {
+        let (device, mock) = mock_device();
+        mock.push_response(&[2, 1]);
+        assert_eq!(get_firmware_version(&device).unwrap(), (2, 1));
+
+        mock.push_response(b"IJ2148B12345678\0");
+        assert_eq!(get_serial(&device).unwrap(), "IJ2148B12345678");
+        assert_eq!(mock.sent_commands()[1], (0x0082, vec![0; 22]));
+    }
+
+    fn truncated<T>(result: Result<T>) -> bool {
+        matches!(
+            result.map_err(RazerError::into_root),
+            Err(RazerError::InvalidDataSize { .. })
+        )
+    }
+
+    #[test]
+    fn test_truncated_responses() {
+        let (device, mock) = mock_device();
+        mock.push_truncated(3);
+        assert!(truncated(get_perf_mode(&device)));
+        mock.push_truncated(2);
+        assert!(truncated(get_cpu_boost(&device)));
+        mock.push_truncated(1);
+        assert!(truncated(get_fan_rpm(&device, FanZone::Zone1)));
+        // logo power, then the mode itself
+        mock.push_truncated(0);
+        assert!(truncated(get_logo_mode(&device)));
+        mock.push_response(&[1, 4, 1]);
+        mock.push_truncated(2);
+        assert!(truncated(get_logo_mode(&device)));
+    }
+
+    #[test]
+    fn test_command_ids() {
+        let command = CommandId::SetPerfMode;
+        assert_eq!(command.raw(), cmd::SET_PERF_MODE);
+        assert_eq!((command.class(), command.id()), (0x0d, 0x02));
+        assert_eq!(command.to_string(), "0x0d02 SET_PERF_MODE");
+        assert_eq!(CommandId::describe(0x0792), "0x0792 GET_BATTERY_CARE");
+        assert_eq!(CommandId::describe(0x1234), "0x1234");
+        for &command in CommandId::ALL {
+            assert_eq!(CommandId::from_raw(command.raw()), Some(command));
+        }
+        assert_eq!(
+            CommandId::SetBoost.required_perf_mode(),
+            Some(PerfMode::Custom)
+        );
+    }
+
+    #[test]
+    fn test_custom_command() {
+        let (device, mock) = mock_device();
+        custom_command(&device, 0x0d82, &[0, 1, 0, 0]).unwrap();
+        assert_eq!(mock.sent_commands(), vec![(0x0d82, vec![0, 1, 0, 0])]);
+
+        let error = custom_command(&device, 0x0d82, &[0; 81]).unwrap_err();
+        assert!(
+            matches!(error.root(), RazerError::ArgumentsTooLong(81)),
+            "{}",
+            error
+        );
+        assert_eq!(mock.sent().len(), 1);
+    }
+
+    #[test]
+    fn test_status_codes_map_to_errors() {
+        let (device, mock) = mock_device();
+        // busy for the first read and every re-read within the budget
+        for _ in 0..6 {
+            mock.push_status(0x01);
+        }
+        assert!(matches!(
+            get_battery_care(&device),
+            Err(RazerError::DeviceBusy)
+        ));
+        mock.push_status(0x05);
+        assert!(matches!(
+            get_battery_care(&device),
+            Err(RazerError::CommandNotSupported)
+        ));
     }
-    Ok(())
 }
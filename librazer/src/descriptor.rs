@@ -1,44 +1,1227 @@
+use crate::command::{cmd, CommandId};
+use crate::error::{RazerError, Result};
 use crate::feature;
+use crate::limits;
+use crate::state::FieldValue;
+use crate::types::{ChargeLimitEncoding, CpuBoost, FanCurve, FanRpm, GpuBoost, PerfMode};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock, RwLock};
 
-// model_number_prefix shall conform to https://mysupport.razer.com/app/answers/detail/a_id/5481
-#[derive(Debug, Clone)]
+/// Delays around each feature report exchange, in microseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Timing {
+    /// Delay before sending a report, so the device is ready for a new command.
+    pub pre_send_us: u64,
+    /// Delay between sending a report and reading the response.
+    pub pre_read_us: u64,
+}
+
+impl Timing {
+    /// 1ms before send and 2ms before read, which works for most models.
+    pub const DEFAULT: Timing = Timing {
+        pre_send_us: 1000,
+        pre_read_us: 2000,
+    };
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Timing::DEFAULT
+    }
+}
+
+/// Read-only command used to check that an interface answers before it is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct HealthCheck {
+    pub command: u16,
+    pub args: &'static [u8],
+}
+
+impl HealthCheck {
+    /// Firmware version query, answered by every Razer device.
+    pub const DEFAULT: HealthCheck = HealthCheck {
+        command: crate::command::cmd::GET_FIRMWARE_VERSION,
+        args: &[0, 0],
+    };
+}
+
+/// Model-specific protocol deviations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Quirks {
+    /// Keep the full pre-send delay between every report of a
+    /// [`send_many`](crate::device::Device::send_many) batch.
+    pub serial_batches: bool,
+    /// Commands whose responses carry a remaining-packets count of their own
+    /// instead of echoing the report's.
+    pub loose_remaining_packets: &'static [u16],
+    /// Accept feature-report reads shorter than a full packet as long as they
+    /// cover the response's arguments, padding the rest with zeros. Some hubs on
+    /// Windows drop the trailing padding. Needs `skip_response_crc`, since the
+    /// CRC byte is part of what gets dropped.
+    pub lenient_short_reads: bool,
+    /// Send every report with this transaction id instead of a random one, for
+    /// firmware that only answers a specific id.
+    pub fixed_transaction_id: Option<u8>,
+    /// Accept responses whose CRC byte doesn't match their contents.
+    pub skip_response_crc: bool,
+}
+
+impl Quirks {
+    /// No deviations from the default protocol handling.
+    pub const NONE: Quirks = Quirks {
+        serial_batches: false,
+        loose_remaining_packets: &[],
+        lenient_short_reads: false,
+        fixed_transaction_id: None,
+        skip_response_crc: false,
+    };
+
+    /// Deviations of the 2023 Blades, also assumed for models not in [`SUPPORTED`].
+    pub const BLADE_2023: Quirks = Quirks {
+        serial_batches: false,
+        loose_remaining_packets: &[cmd::GET_MAX_FAN_SPEED, cmd::GET_BATTERY_CARE],
+        lenient_short_reads: false,
+        fixed_transaction_id: None,
+        // the firmware's CRC has not been checked against captures yet
+        skip_response_crc: true,
+    };
+
+    /// Tolerant handling for models nothing is known about: every deviation seen
+    /// so far is allowed and batches keep the full delay between reports.
+    pub const GENERIC: Quirks = Quirks {
+        serial_batches: true,
+        lenient_short_reads: true,
+        ..Quirks::BLADE_2023
+    };
+
+    /// Whether a response to `command` may announce a different number of
+    /// remaining packets than its report.
+    pub fn allows_loose_remaining_packets(&self, command: u16) -> bool {
+        self.loose_remaining_packets.contains(&command)
+    }
+}
+
+/// How the keyboard backlight can be controlled, beyond the `kbd-backlight` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LightingCapability {
+    /// One brightness for the whole keyboard.
+    BrightnessOnly,
+    /// Four independently colored zones.
+    FourZone,
+    /// A color per key, addressed as a `rows` by `cols` matrix.
+    PerKey { rows: u8, cols: u8 },
+}
+
+impl LightingCapability {
+    /// Most columns a custom frame row can carry: 5 header bytes and 3 per key
+    /// in the 80 argument bytes of a packet.
+    pub const MAX_COLS: u8 = 25;
+
+    /// Returns the matrix size if keys can be colored one by one.
+    pub fn per_key(&self) -> Option<(u8, u8)> {
+        match *self {
+            LightingCapability::PerKey { rows, cols } => Some((rows, cols)),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for LightingCapability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LightingCapability::BrightnessOnly => write!(f, "brightness only"),
+            LightingCapability::FourZone => write!(f, "4-zone"),
+            LightingCapability::PerKey { rows, cols } => write!(f, "per-key ({}x{})", rows, cols),
+        }
+    }
+}
+
+/// Feature a command belongs to, `None` for those every device answers.
+pub(crate) const fn command_feature(command: u16) -> Option<&'static str> {
+    match command {
+        cmd::SET_PERF_MODE | cmd::GET_PERF_MODE | cmd::SET_BOOST | cmd::GET_BOOST => {
+            Some(feature::PERF)
+        }
+        cmd::SET_FAN_RPM | cmd::GET_FAN_RPM | cmd::SET_MAX_FAN_SPEED | cmd::GET_MAX_FAN_SPEED => {
+            Some(feature::FAN)
+        }
+        cmd::SET_LOGO_POWER | cmd::GET_LOGO_POWER | cmd::SET_LOGO_MODE | cmd::GET_LOGO_MODE => {
+            Some(feature::LIDLOGO)
+        }
+        cmd::SET_KBD_BRIGHTNESS | cmd::GET_KBD_BRIGHTNESS | cmd::SET_CUSTOM_FRAME => {
+            Some(feature::KBDBACKLIGHT)
+        }
+        cmd::SET_LIGHTS_ALWAYS_ON | cmd::GET_LIGHTS_ALWAYS_ON => Some(feature::LIGHTSALWAYSON),
+        cmd::SET_BATTERY_CARE | cmd::GET_BATTERY_CARE => Some(feature::BATTERYCARE),
+        _ => None,
+    }
+}
+
+/// Returns why `quirks` don't fit a device with `features`, if they don't.
+///
+/// Checked at compile time for [`SUPPORTED`] and when loading descriptors.
+const fn quirks_problem(quirks: &Quirks, features: &[&str]) -> Option<&'static str> {
+    if quirks.lenient_short_reads && !quirks.skip_response_crc {
+        return Some("lenient_short_reads drops the CRC byte, it needs skip_response_crc");
+    }
+    crate::const_for! { command in quirks.loose_remaining_packets => {
+        if let Some(feature) = command_feature(*command) {
+            if !feature::contains(features, feature) {
+                return Some("loose_remaining_packets lists a command of a feature the device lacks");
+            }
+        }
+    }}
+    None
+}
+
+/// Builds a validated [`Descriptor`] for a built-in model.
+///
+/// `name`, `prefix`, `pid` and `features` come first, in that order; features
+/// are constant names from [`feature`](crate::feature). Models sold under
+/// several numbers with the same firmware take `prefixes: [..]` instead of
+/// `prefix`. Any other descriptor
+/// field may follow and the rest are taken from [`DEFAULTS`]. The result is
+/// checked by [`validate_descriptor`] at compile time, so unknown features,
+/// inconsistent quirks or oversized lighting fail the build.
+///
+/// ```
+/// use librazer::descriptor::{Descriptor, Quirks};
+///
+/// const BLADE: Descriptor = librazer::descriptor! {
+///     name: "Razer Blade 15\" (2022)",
+///     prefix: "RZ09-0421",
+///     pid: 0x028a,
+///     features: [BATTERYCARE, FAN, KBDBACKLIGHT, PERF],
+///     quirks: Quirks::BLADE_2023,
+///     fan_rpm_range: 2200..=5000,
+/// };
+/// assert_eq!(BLADE.features, ["battery-care", "fan", "kbd-backlight", "perf"]);
+/// ```
+#[macro_export]
+macro_rules! descriptor {
+    (name: $name:expr, prefix: $prefix:expr, $($rest:tt)*) => {
+        $crate::descriptor! { name: $name, prefixes: [$prefix], $($rest)* }
+    };
+    (
+        name: $name:expr,
+        prefixes: [$($prefix:expr),+ $(,)?],
+        pid: $pid:expr,
+        features: [$($feature:ident),* $(,)?]
+        $(, $field:ident: $value:expr)* $(,)?
+    ) => {
+        const {
+            let descriptor = $crate::descriptor::Descriptor {
+                model_number_prefixes: &[$($prefix),+],
+                name: $name,
+                pid: $pid,
+                features: &[$($crate::feature::$feature),*],
+                $($field: $value,)*
+                ..$crate::descriptor::DEFAULTS
+            };
+            $crate::descriptor::validate_descriptor(&descriptor);
+            descriptor
+        }
+    };
+}
+
+// model number prefixes shall conform to https://mysupport.razer.com/app/answers/detail/a_id/5481
+#[derive(Debug, Clone, Serialize)]
 pub struct Descriptor {
-    pub model_number_prefix: &'static str,
+    /// Model numbers this descriptor applies to. Detection narrows the list to
+    /// the prefix that matched.
+    pub model_number_prefixes: &'static [&'static str],
     pub name: &'static str,
+    /// USB product id, serialized as hex like `0x029f`.
+    #[serde(serialize_with = "serialize_pid")]
     pub pid: u16,
     pub features: &'static [&'static str],
+    pub timing: Timing,
+    /// Command sent when an interface has to be probed before use.
+    pub health_check: HealthCheck,
+    pub quirks: Quirks,
+    /// HID report id of the control interface's feature reports.
+    pub report_id: u8,
+    /// Fan speeds accepted in manual fan mode, in RPM. Serialized as `[min, max]`.
+    #[serde(serialize_with = "serialize_range")]
+    pub fan_rpm_range: RangeInclusive<u16>,
+    /// Performance modes the EC accepts. Writing another one can make it ignore
+    /// performance mode writes until reboot.
+    pub perf_modes: &'static [PerfMode],
+    /// CPU boost levels the EC handles. Others are accepted but misbehave.
+    pub cpu_boosts: &'static [CpuBoost],
+    /// GPU boost levels the EC handles.
+    pub gpu_boosts: &'static [GpuBoost],
+    /// Keyboard lighting controls. Only meaningful with the `kbd-backlight` feature.
+    pub lighting: LightingCapability,
+    /// How battery care commands carry a charge limit. Only meaningful with the
+    /// `battery-care` feature.
+    pub charge_limit_encoding: ChargeLimitEncoding,
+    /// Where the PID and command behavior came from, such as a USB capture or a
+    /// user report. Empty when not recorded.
+    pub notes: &'static str,
+    /// Link to the capture or report behind [`notes`](Self::notes), empty if none.
+    pub source_url: &'static str,
+}
+
+/// Manual fan speed range of the 2023 Blades, used when a model doesn't list its own.
+pub const DEFAULT_FAN_RPM_RANGE: RangeInclusive<u16> = limits::FAN_RPM_RANGE;
+
+/// Values [`descriptor!`](crate::descriptor!) uses for fields it isn't given.
+pub const DEFAULTS: Descriptor = Descriptor {
+    model_number_prefixes: &[],
+    name: "",
+    pid: 0x0000,
+    features: &[],
+    timing: Timing::DEFAULT,
+    health_check: HealthCheck::DEFAULT,
+    quirks: Quirks::NONE,
+    report_id: 0x00,
+    fan_rpm_range: DEFAULT_FAN_RPM_RANGE,
+    perf_modes: &PerfMode::ALL,
+    cpu_boosts: &CpuBoost::ALL,
+    gpu_boosts: &GpuBoost::ALL,
+    lighting: LightingCapability::BrightnessOnly,
+    charge_limit_encoding: ChargeLimitEncoding::FlaggedPercent,
+    notes: "",
+    source_url: "",
+};
+
+impl Descriptor {
+    /// Returns the first of the [`model_number_prefixes`](Self::model_number_prefixes),
+    /// which is the matched one on detected devices. Empty if there are none.
+    pub fn model_number_prefix(&self) -> &'static str {
+        self.model_number_prefixes.first().copied().unwrap_or("")
+    }
+
+    /// Returns whether `model` starts with one of this descriptor's prefixes.
+    pub fn matches_model(&self, model: &str) -> bool {
+        self.model_number_prefixes
+            .iter()
+            .any(|prefix| model.starts_with(prefix))
+    }
+
+    /// Returns a copy listing only the prefix `model` starts with, so that
+    /// [`model_number_prefix`](Self::model_number_prefix) reports it. Unchanged if
+    /// no prefix matches.
+    pub fn for_model(&self, model: &str) -> Descriptor {
+        let prefixes: &'static [&'static str] = self.model_number_prefixes;
+        match prefixes.iter().find(|prefix| model.starts_with(**prefix)) {
+            Some(prefix) => Descriptor {
+                model_number_prefixes: std::slice::from_ref(prefix),
+                ..self.clone()
+            },
+            None => self.clone(),
+        }
+    }
+
+    /// Returns a copy of this descriptor whose features are the known feature names in `names`.
+    ///
+    /// Used for models missing from [`SUPPORTED`] whose features were probed at runtime.
+    /// Unknown names are dropped. The feature list is interned to satisfy the
+    /// `'static` bound, so calling this again for the same features leaks nothing.
+    pub fn with_features<S: AsRef<str>>(&self, names: &[S]) -> Descriptor {
+        let features: Vec<&'static str> = feature::ALL_FEATURES
+            .iter()
+            .copied()
+            .filter(|feature| names.iter().any(|name| name.as_ref() == *feature))
+            .collect();
+        Descriptor {
+            features: intern_list(&features),
+            ..self.clone()
+        }
+    }
+
+    /// Lists the protocol deviations in effect for this model as name and value,
+    /// including non-default timing and report id.
+    pub fn active_quirks(&self) -> Vec<(&'static str, String)> {
+        let quirks = &self.quirks;
+        let mut active = Vec::new();
+        if self.timing != Timing::DEFAULT {
+            active.push((
+                "timing",
+                format!(
+                    "{}us before send, {}us before read",
+                    self.timing.pre_send_us, self.timing.pre_read_us
+                ),
+            ));
+        }
+        if self.report_id != 0 {
+            active.push(("report_id", format!("0x{:02x}", self.report_id)));
+        }
+        if quirks.serial_batches {
+            active.push(("serial_batches", "true".to_string()));
+        }
+        if !quirks.loose_remaining_packets.is_empty() {
+            let commands: Vec<String> = quirks
+                .loose_remaining_packets
+                .iter()
+                .map(|&raw| CommandId::describe(raw))
+                .collect();
+            active.push(("loose_remaining_packets", commands.join(", ")));
+        }
+        if quirks.lenient_short_reads {
+            active.push(("lenient_short_reads", "true".to_string()));
+        }
+        if let Some(id) = quirks.fixed_transaction_id {
+            active.push(("fixed_transaction_id", format!("0x{:02x}", id)));
+        }
+        if quirks.skip_response_crc {
+            active.push(("skip_response_crc", "true".to_string()));
+        }
+        active
+    }
+
+    /// Fails with [`RazerError::FeatureNotSupported`] unless `feature` is in
+    /// `features`.
+    pub fn require(&self, feature: &'static str) -> Result<()> {
+        if self.features.contains(&feature) {
+            return Ok(());
+        }
+        Err(RazerError::FeatureNotSupported {
+            feature,
+            device: self.name.to_string(),
+        })
+    }
+
+    /// Fails with [`RazerError::PreconditionFailed`] unless `mode` is in `perf_modes`.
+    pub fn check_perf_mode(&self, mode: PerfMode) -> Result<()> {
+        if self.perf_modes.contains(&mode) {
+            return Ok(());
+        }
+        let supported: Vec<String> = self.perf_modes.iter().map(|m| m.to_string()).collect();
+        Err(RazerError::PreconditionFailed(format!(
+            "{} performance mode is not supported on {} (supported: {})",
+            mode,
+            self.name,
+            supported.join(", ")
+        )))
+    }
+
+    /// Fails with [`RazerError::FanRpmOutOfRange`] unless `rpm` is in `fan_rpm_range`.
+    pub fn check_fan_rpm(&self, rpm: FanRpm) -> Result<()> {
+        FanRpm::new_in(rpm.get(), &self.fan_rpm_range).map(drop)
+    }
+
+    /// Fails with [`RazerError::FanRpmOutOfRange`] unless every speed of `curve`
+    /// is in `fan_rpm_range`.
+    pub fn check_fan_curve(&self, curve: &FanCurve) -> Result<()> {
+        curve.check_range(&self.fan_rpm_range)
+    }
+
+    /// Fails with [`RazerError::UnsupportedLevel`] unless `boost` is in `cpu_boosts`.
+    pub fn check_cpu_boost(&self, boost: CpuBoost) -> Result<()> {
+        self.check_level(self.cpu_boosts.contains(&boost), boost)
+    }
+
+    /// Fails with [`RazerError::UnsupportedLevel`] unless `boost` is in `gpu_boosts`.
+    pub fn check_gpu_boost(&self, boost: GpuBoost) -> Result<()> {
+        self.check_level(self.gpu_boosts.contains(&boost), boost)
+    }
+
+    /// Checks that this model takes `value`: its feature is declared and a
+    /// performance mode, boost level or fan speed is one the EC handles. Fails with
+    /// [`RazerError::FeatureNotSupported`] or [`RazerError::UnsupportedLevel`], or
+    /// the error of the matching `check_` method.
+    pub fn check_value(&self, value: FieldValue) -> Result<()> {
+        self.require(value.field().feature())?;
+        match value {
+            FieldValue::PerfMode(mode) => self.check_level(self.perf_modes.contains(&mode), mode),
+            FieldValue::CpuBoost(boost) => self.check_cpu_boost(boost.into_known()?),
+            FieldValue::GpuBoost(boost) => self.check_gpu_boost(boost.into_known()?),
+            FieldValue::FanRpm(rpm) => self.check_fan_rpm(rpm),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_level(&self, supported: bool, level: impl std::fmt::Display) -> Result<()> {
+        if supported {
+            return Ok(());
+        }
+        Err(RazerError::UnsupportedLevel {
+            level: level.to_string(),
+            device: self.name.to_string(),
+        })
+    }
 }
 
 pub const SUPPORTED: &[Descriptor] = &[
-    Descriptor {
-        model_number_prefix: "RZ09-0483T",
+    crate::descriptor! {
         name: "Razer Blade 16\" (2023) Black",
+        prefix: "RZ09-0483T",
         pid: 0x029f,
-        features: &[
-            feature::BATTERYCARE,
-            feature::FAN,
-            feature::KBDBACKLIGHT,
-            feature::LIDLOGO,
-            feature::LIGHTSALWAYSON,
-            feature::PERF,
-        ],
+        features: [BATTERYCARE, FAN, KBDBACKLIGHT, LIDLOGO, LIGHTSALWAYSON, PERF],
+        quirks: Quirks::BLADE_2023,
+        lighting: LightingCapability::PerKey { rows: 6, cols: 16 },
+        notes: "USB capture of Razer Synapse, annotated in data/",
+        source_url: "https://github.com/stvnksslr/razer-ctl/blob/main/data/README.md",
     },
-    Descriptor {
-        model_number_prefix: "RZ09-0482X",
+    crate::descriptor! {
         name: "Razer Blade 14\" (2023) Mercury",
+        prefix: "RZ09-0482X",
         pid: 0x029d,
-        features: &[
-            feature::BATTERYCARE,
-            feature::FAN,
-            feature::KBDBACKLIGHT,
-            feature::LIGHTSALWAYSON,
-            feature::PERF,
+        features: [BATTERYCARE, FAN, KBDBACKLIGHT, LIGHTSALWAYSON, PERF],
+        quirks: Quirks::BLADE_2023,
+        fan_rpm_range: 2000..=5300,
+        cpu_boosts: &[
+            CpuBoost::Low,
+            CpuBoost::Medium,
+            CpuBoost::High,
+            CpuBoost::Boost,
         ],
+        lighting: LightingCapability::PerKey { rows: 6, cols: 16 },
+        notes: "Shares the Blade 16 protocol; no capture of its own on file",
     },
 ];
 
-const _VALIDATE_FEATURES: () = {
-    crate::const_for! { device in SUPPORTED => {
-        feature::validate_features(device.features);
-    }}
+/// Descriptor for Razer laptops missing from [`SUPPORTED`], with only the features
+/// that work on essentially every Blade. Opened through [`generic`].
+pub const GENERIC: Descriptor = Descriptor {
+    model_number_prefixes: &[],
+    name: "Generic Razer Blade (unsupported model)",
+    pid: 0x0000,
+    features: &[feature::KBDBACKLIGHT, feature::PERF],
+    timing: Timing {
+        pre_send_us: 2000,
+        pre_read_us: 4000,
+    },
+    health_check: HealthCheck::DEFAULT,
+    quirks: Quirks::GENERIC,
+    report_id: 0x00,
+    fan_rpm_range: DEFAULT_FAN_RPM_RANGE,
+    perf_modes: &PerfMode::ALL,
+    cpu_boosts: &[CpuBoost::Low, CpuBoost::Medium, CpuBoost::High],
+    gpu_boosts: &GpuBoost::ALL,
+    lighting: LightingCapability::BrightnessOnly,
+    charge_limit_encoding: ChargeLimitEncoding::FlaggedPercent,
+    notes: "Fallback for unlisted models, not verified on any device",
+    source_url: "",
 };
+
+/// Returns [`GENERIC`] for the device with `pid`.
+///
+/// Probing with [`Device::probe_features`](crate::device::Device::probe_features)
+/// can extend the feature set once the device is open.
+pub fn generic(pid: u16) -> Descriptor {
+    Descriptor { pid, ..GENERIC }
+}
+
+/// Returns whether `descriptor` is a [`generic`] one rather than a known model.
+pub fn is_generic(descriptor: &Descriptor) -> bool {
+    descriptor.name == GENERIC.name
+}
+
+/// Asserts that every model number prefix in `descriptors` is reachable.
+///
+/// Detection takes the first descriptor with a matching prefix, so no prefix
+/// may appear twice, and a prefix may only extend one of another descriptor when
+/// it is listed before it. Called at compile time for [`SUPPORTED`].
+pub const fn validate_model_prefixes(descriptors: &[Descriptor]) {
+    let mut rest = descriptors;
+    while let [first, tail @ ..] = rest {
+        rest = tail;
+        let mut prefixes = first.model_number_prefixes;
+        while let [prefix, others @ ..] = prefixes {
+            prefixes = others;
+            assert!(
+                !feature::contains(others, prefix),
+                "A descriptor lists a model number prefix twice"
+            );
+            crate::const_for! { later in tail => {
+                crate::const_for! { later_prefix in later.model_number_prefixes => {
+                    assert!(
+                        !const_str::equal!(*prefix, *later_prefix),
+                        "Two descriptors share a model number prefix"
+                    );
+                    assert!(
+                        !const_str::starts_with!(*later_prefix, *prefix),
+                        "Model number prefix is shadowed by a shorter one listed before it"
+                    );
+                }}
+            }}
+        }
+    }
+}
+
+/// Asserts that `descriptor` names a model and that its features, quirks and
+/// lighting are consistent. Called at compile time by [`descriptor!`](crate::descriptor!).
+pub const fn validate_descriptor(descriptor: &Descriptor) {
+    assert!(!descriptor.name.is_empty(), "Descriptor name is empty");
+    assert!(
+        !descriptor.model_number_prefixes.is_empty(),
+        "Descriptor has no model number prefix"
+    );
+    crate::const_for! { prefix in descriptor.model_number_prefixes => {
+        assert!(!prefix.is_empty(), "Descriptor model number prefix is empty");
+    }}
+    feature::validate_features(descriptor.features);
+    if let Some(problem) = quirks_problem(&descriptor.quirks, descriptor.features) {
+        panic!("{}", problem);
+    }
+    if let LightingCapability::PerKey { cols, .. } = descriptor.lighting {
+        assert!(
+            cols <= LightingCapability::MAX_COLS,
+            "Per-key row does not fit a packet"
+        );
+    }
+}
+
+const _VALIDATE_FEATURES: () = validate_model_prefixes(SUPPORTED);
+
+/// Returns the models this crate supports out of the box.
+pub fn supported() -> &'static [Descriptor] {
+    SUPPORTED
+}
+
+/// Summary of a supported model, for listings and generated documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SupportedModel {
+    pub name: &'static str,
+    pub model_prefixes: &'static [&'static str],
+    /// USB product id, serialized as hex like `0x029f`.
+    #[serde(serialize_with = "serialize_pid")]
+    pub pid: u16,
+    pub features: &'static [&'static str],
+    #[serde(skip_serializing_if = "str::is_empty")]
+    pub notes: &'static str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    pub source_url: &'static str,
+}
+
+fn serialize_pid<S: serde::Serializer>(
+    pid: &u16,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:#06x}", pid))
+}
+
+fn serialize_range<S: serde::Serializer>(
+    range: &RangeInclusive<u16>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    [*range.start(), *range.end()].serialize(serializer)
+}
+
+/// Returns the [`supported`] table as pretty-printed JSON, with every descriptor
+/// field, for support matrices generated outside Rust.
+pub fn to_json() -> String {
+    serde_json::to_string_pretty(supported()).expect("descriptors serialize to JSON")
+}
+
+impl From<&Descriptor> for SupportedModel {
+    fn from(descriptor: &Descriptor) -> Self {
+        SupportedModel {
+            name: descriptor.name,
+            model_prefixes: descriptor.model_number_prefixes,
+            pid: descriptor.pid,
+            features: descriptor.features,
+            notes: descriptor.notes,
+            source_url: descriptor.source_url,
+        }
+    }
+}
+
+/// Returns a [`SupportedModel`] for each of the [`supported`] descriptors.
+pub fn supported_models() -> Vec<SupportedModel> {
+    supported().iter().map(SupportedModel::from).collect()
+}
+
+/// Descriptors added at runtime with [`register`].
+static EXTRA: RwLock<Vec<Descriptor>> = RwLock::new(Vec::new());
+
+/// Returns the [`SUPPORTED`] descriptors followed by those added with [`register`].
+pub fn known() -> Vec<Descriptor> {
+    let extra = EXTRA.read().unwrap_or_else(|e| e.into_inner());
+    SUPPORTED.iter().chain(extra.iter()).cloned().collect()
+}
+
+/// Returns the known descriptor for `pid`, built-in ones first.
+pub fn find_by_pid(pid: u16) -> Option<Descriptor> {
+    known().into_iter().find(|d| d.pid == pid)
+}
+
+/// Returns the first known descriptor for `model`, which may be a full model
+/// number or part of a prefix, like `RZ09-0482` for `RZ09-0482X`.
+pub fn find_by_model(model: &str) -> Option<Descriptor> {
+    if model.is_empty() {
+        return None;
+    }
+    known()
+        .into_iter()
+        .find(|d| {
+            d.model_number_prefixes
+                .iter()
+                .any(|prefix| model.starts_with(prefix) || prefix.starts_with(model))
+        })
+        .map(|d| d.for_model(model))
+}
+
+/// Adds `descriptors` after the built-in ones, so detection and PID lookups
+/// consider them. Descriptors for a PID that is already known are skipped.
+///
+/// Returns the descriptors that were added.
+pub fn register(descriptors: impl IntoIterator<Item = Descriptor>) -> Vec<Descriptor> {
+    let mut extra = EXTRA.write().unwrap_or_else(|e| e.into_inner());
+    let mut added = Vec::new();
+    for descriptor in descriptors {
+        let known = SUPPORTED
+            .iter()
+            .chain(extra.iter())
+            .any(|d| d.pid == descriptor.pid);
+        if known {
+            debug!(
+                "Skipping descriptor {} for already known PID 0x{:04X}",
+                descriptor.name, descriptor.pid
+            );
+            continue;
+        }
+        extra.push(descriptor.clone());
+        added.push(descriptor);
+    }
+    added
+}
+
+/// A device as written in a descriptors file or a configuration, turned into a
+/// [`Descriptor`] with [`build`](Self::build).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DescriptorSpec {
+    /// Model numbers of the device. A single `model_number_prefix` string is
+    /// accepted too.
+    #[serde(alias = "model_number_prefix", deserialize_with = "one_or_many")]
+    pub model_number_prefixes: Vec<String>,
+    pub name: String,
+    pub pid: u16,
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub report_id: u8,
+    /// Lowest and highest manual fan speed, [`DEFAULT_FAN_RPM_RANGE`] if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fan_rpm_range: Option<(u16, u16)>,
+    /// Supported performance modes, all known ones if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perf_modes: Option<Vec<PerfMode>>,
+    /// Supported CPU boost levels, all of them if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_boosts: Option<Vec<CpuBoost>>,
+    /// Supported GPU boost levels, all of them if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu_boosts: Option<Vec<GpuBoost>>,
+    /// Keyboard lighting controls, brightness only if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lighting: Option<LightingCapability>,
+    /// Charge limit encoding of battery care commands, `flagged-percent` if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub charge_limit_encoding: Option<ChargeLimitEncoding>,
+    /// Where the PID and command behavior came from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timing: Option<TimingSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quirks: Option<QuirksSpec>,
+}
+
+/// [`Timing`] of a [`DescriptorSpec`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimingSpec {
+    pub pre_send_us: u64,
+    pub pre_read_us: u64,
+}
+
+/// [`Quirks`] of a [`DescriptorSpec`], all off unless given.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct QuirksSpec {
+    pub serial_batches: bool,
+    pub loose_remaining_packets: Vec<u16>,
+    pub lenient_short_reads: bool,
+    pub fixed_transaction_id: Option<u8>,
+    pub skip_response_crc: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DescriptorFile {
+    #[serde(default)]
+    device: Vec<DescriptorSpec>,
+}
+
+impl DescriptorSpec {
+    /// Checks the spec and leaks its strings to fit the `'static` fields of a
+    /// [`Descriptor`], so build each spec once.
+    pub fn build(self) -> Result<Descriptor> {
+        self.into_descriptor()
+            .map_err(RazerError::InvalidDescriptor)
+    }
+
+    /// Checks the entry like `validate_features` does for [`SUPPORTED`].
+    fn into_descriptor(self) -> std::result::Result<Descriptor, String> {
+        if self.model_number_prefixes.is_empty() {
+            return Err(format!("{}: model_number_prefixes is empty", self.name));
+        }
+        if self.model_number_prefixes.iter().any(String::is_empty) {
+            return Err(format!("{}: a model number prefix is empty", self.name));
+        }
+        let features = self
+            .features
+            .iter()
+            .map(|name| {
+                feature::ALL_FEATURES
+                    .iter()
+                    .copied()
+                    .find(|feature| feature == name)
+                    .ok_or_else(|| {
+                        format!(
+                            "{}: unknown feature {:?}, expected one of {:?}",
+                            self.name,
+                            name,
+                            feature::ALL_FEATURES
+                        )
+                    })
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let fan_rpm_range = match self.fan_rpm_range {
+            None => DEFAULT_FAN_RPM_RANGE,
+            // speeds are sent in hundreds of RPM in a single byte
+            Some((min, max)) if min <= max && max <= u16::from(u8::MAX) * 100 => min..=max,
+            Some((min, max)) => {
+                return Err(format!(
+                    "{}: invalid fan_rpm_range {}-{}",
+                    self.name, min, max
+                ))
+            }
+        };
+        let lighting = self.lighting.unwrap_or(LightingCapability::BrightnessOnly);
+        if let Some((rows, cols)) = lighting.per_key() {
+            if rows == 0 || cols == 0 || cols > LightingCapability::MAX_COLS {
+                return Err(format!(
+                    "{}: invalid per-key matrix {}x{}, at most {} columns",
+                    self.name,
+                    rows,
+                    cols,
+                    LightingCapability::MAX_COLS
+                ));
+            }
+        }
+        let given = self.quirks.is_some();
+        let quirks = self.quirks.map_or(Quirks::BLADE_2023, |quirks| Quirks {
+            serial_batches: quirks.serial_batches,
+            loose_remaining_packets: quirks.loose_remaining_packets.leak(),
+            lenient_short_reads: quirks.lenient_short_reads,
+            fixed_transaction_id: quirks.fixed_transaction_id,
+            skip_response_crc: quirks.skip_response_crc,
+        });
+        // the defaults may mention features the device lacks, which is harmless
+        if let Some(problem) = quirks_problem(&quirks, &features).filter(|_| given) {
+            return Err(format!("{}: {}", self.name, problem));
+        }
+        Ok(Descriptor {
+            model_number_prefixes: self
+                .model_number_prefixes
+                .into_iter()
+                .map(|prefix| &*prefix.leak())
+                .collect::<Vec<_>>()
+                .leak(),
+            name: self.name.leak(),
+            pid: self.pid,
+            features: features.leak(),
+            timing: self.timing.map_or(Timing::DEFAULT, |timing| Timing {
+                pre_send_us: timing.pre_send_us,
+                pre_read_us: timing.pre_read_us,
+            }),
+            health_check: HealthCheck::DEFAULT,
+            quirks,
+            report_id: self.report_id,
+            fan_rpm_range,
+            perf_modes: self.perf_modes.map_or(&PerfMode::ALL, |modes| modes.leak()),
+            cpu_boosts: self
+                .cpu_boosts
+                .map_or(&CpuBoost::ALL, |boosts| boosts.leak()),
+            gpu_boosts: self
+                .gpu_boosts
+                .map_or(&GpuBoost::ALL, |boosts| boosts.leak()),
+            lighting,
+            charge_limit_encoding: self
+                .charge_limit_encoding
+                .unwrap_or(ChargeLimitEncoding::FlaggedPercent),
+            notes: self.notes.map_or("", |notes| notes.leak()),
+            source_url: self.source_url.map_or("", |url| url.leak()),
+        })
+    }
+}
+
+/// Deserializes a list of strings, or a single string as a list of one.
+fn one_or_many<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+/// Reads device descriptors from a TOML file, or JSON if the name ends in `.json`.
+///
+/// Each `[[device]]` table takes `model_number_prefixes` (or a single
+/// `model_number_prefix`), `name`, `pid` and `features` like a [`SUPPORTED`] entry, plus optional `timing`, `quirks` and
+/// `report_id`. Unknown features are rejected. Without `quirks`, those of the
+/// 2023 Blades apply. Pass the result to [`register`] to use it.
+pub fn load_extra(path: impl AsRef<Path>) -> Result<Vec<Descriptor>> {
+    let path = path.as_ref();
+    let invalid = |reason: String| RazerError::InvalidDescriptorFile {
+        path: path.display().to_string(),
+        reason,
+    };
+    let text = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+    let file: DescriptorFile = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&text).map_err(|e| invalid(e.to_string()))?
+    } else {
+        toml::from_str(&text).map_err(|e| invalid(e.to_string()))?
+    };
+    file.device
+        .into_iter()
+        .map(|entry| entry.into_descriptor().map_err(&invalid))
+        .collect()
+}
+
+/// Returns a `'static` copy of `value`, leaking it only the first time it is
+/// seen, so descriptors built again at runtime, e.g. by a daemon re-detecting
+/// after every resume, don't grow memory.
+pub(crate) fn intern_str(value: &str) -> &'static str {
+    static STRINGS: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    let mut strings = STRINGS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(interned) = strings.get(value) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(value.into());
+    strings.insert(interned);
+    interned
+}
+
+/// Like [`intern_str`], for a list of `'static` strings.
+pub(crate) fn intern_list(values: &[&'static str]) -> &'static [&'static str] {
+    static LISTS: OnceLock<Mutex<HashSet<&'static [&'static str]>>> = OnceLock::new();
+    let mut lists = LISTS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(interned) = lists.get(values) {
+        return interned;
+    }
+    let interned: &'static [&'static str] = Vec::leak(values.to_vec());
+    lists.insert(interned);
+    interned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("librazer-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_load_extra_toml() {
+        let path = write(
+            "descriptors.toml",
+            r#"
+[[device]]
+model_number_prefix = "RZ09-0421"
+name = "Razer Blade 15 (2022)"
+pid = 0x028a
+features = ["perf", "fan"]
+
+[device.timing]
+pre_send_us = 1500
+pre_read_us = 3000
+
+[device.quirks]
+lenient_short_reads = true
+skip_response_crc = true
+"#,
+        );
+        let descriptors = load_extra(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(descriptors.len(), 1);
+        let descriptor = &descriptors[0];
+        assert_eq!(descriptor.pid, 0x028a);
+        assert_eq!(descriptor.features, [feature::PERF, feature::FAN]);
+        assert_eq!(descriptor.timing.pre_send_us, 1500);
+        assert!(descriptor.quirks.lenient_short_reads);
+        assert!(descriptor.quirks.loose_remaining_packets.is_empty());
+        assert_eq!(descriptor.report_id, 0);
+    }
+
+    #[test]
+    fn test_load_extra_json() {
+        let path = write(
+            "descriptors.json",
+            r#"{"device": [{"model_number_prefix": "RZ09-0421", "name": "Blade", "pid": 650, "features": []}]}"#,
+        );
+        let descriptors = load_extra(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(descriptors[0].pid, 650);
+        assert_eq!(descriptors[0].quirks, Quirks::BLADE_2023);
+        assert_eq!(descriptors[0].timing, Timing::DEFAULT);
+        assert_eq!(descriptors[0].fan_rpm_range, DEFAULT_FAN_RPM_RANGE);
+        assert_eq!(descriptors[0].lighting, LightingCapability::BrightnessOnly);
+    }
+
+    #[test]
+    fn test_load_extra_rejects_unknown_features() {
+        let path = write(
+            "bad-feature.toml",
+            "[[device]]\nmodel_number_prefix = \"RZ09\"\nname = \"Blade\"\npid = 1\nfeatures = [\"rgb\"]\n",
+        );
+        let result = load_extra(&path);
+        std::fs::remove_file(path).unwrap();
+        let Err(RazerError::InvalidDescriptorFile { reason, .. }) = result else {
+            panic!("expected an invalid file");
+        };
+        assert!(reason.contains("rgb"), "{}", reason);
+
+        assert!(load_extra("/nonexistent/descriptors.toml").is_err());
+    }
+
+    #[test]
+    fn test_quirks_problem() {
+        for descriptor in SUPPORTED {
+            assert_eq!(
+                quirks_problem(&descriptor.quirks, descriptor.features),
+                None
+            );
+        }
+        let lenient = Quirks {
+            lenient_short_reads: true,
+            ..Quirks::NONE
+        };
+        assert!(quirks_problem(&lenient, feature::ALL_FEATURES).is_some());
+        // GET_BATTERY_CARE belongs to a feature this device doesn't declare
+        assert!(quirks_problem(&Quirks::BLADE_2023, &[feature::FAN]).is_some());
+        assert_eq!(
+            quirks_problem(&Quirks::BLADE_2023, &[feature::FAN, feature::BATTERYCARE]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_active_quirks() {
+        let names: Vec<_> = SUPPORTED[0]
+            .active_quirks()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["loose_remaining_packets", "skip_response_crc"]);
+        assert_eq!(
+            SUPPORTED[0].active_quirks()[0].1,
+            "0x078f GET_MAX_FAN_SPEED, 0x0792 GET_BATTERY_CARE"
+        );
+    }
+
+    #[test]
+    fn test_supported_models() {
+        let models = supported_models();
+        assert_eq!(models.len(), SUPPORTED.len());
+        assert_eq!(models[0].model_prefixes, SUPPORTED[0].model_number_prefixes);
+
+        let json = serde_json::to_value(&models[0]).unwrap();
+        assert_eq!(json["pid"], format!("{:#06x}", SUPPORTED[0].pid));
+        assert_eq!(
+            json["features"].as_array().unwrap().len(),
+            SUPPORTED[0].features.len()
+        );
+        assert_eq!(json["notes"], SUPPORTED[0].notes);
+        assert_eq!(json["source_url"], SUPPORTED[0].source_url);
+        let without_url = serde_json::to_value(&models[1]).unwrap();
+        assert!(without_url.get("source_url").is_none());
+    }
+
+    #[test]
+    fn test_generic() {
+        let generic = generic(0x0299);
+        assert_eq!(generic.pid, 0x0299);
+        assert!(generic.name.contains("Generic"));
+        assert_eq!(generic.features, [feature::KBDBACKLIGHT, feature::PERF]);
+        assert!(is_generic(&generic));
+        assert!(is_generic(&generic.with_features(feature::ALL_FEATURES)));
+        assert!(!is_generic(&SUPPORTED[0]));
+    }
+
+    #[test]
+    fn test_with_features_reuses_the_feature_list() {
+        let generic = generic(0x0299);
+        let first = generic.with_features(&[feature::PERF, "unknown"]);
+        let second = generic.with_features(&[feature::PERF]);
+        assert_eq!(first.features, [feature::PERF]);
+        assert!(std::ptr::eq(first.features, second.features));
+        assert!(std::ptr::eq(intern_str("Model"), intern_str("Model")));
+    }
+
+    /// Pins the [`to_json`] schema. Run with `UPDATE_SNAPSHOTS=1` to accept a change.
+    #[test]
+    fn test_json_snapshot() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/snapshots/supported.json");
+        let json = to_json() + "\n";
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(path, &json).unwrap();
+        }
+        assert_eq!(
+            json,
+            include_str!("snapshots/supported.json"),
+            "the supported table JSON changed, rerun with UPDATE_SNAPSHOTS=1 if intended"
+        );
+    }
+
+    #[test]
+    fn test_several_prefixes() {
+        let blade = Descriptor {
+            model_number_prefixes: &["RZ09-0409", "RZ09-0421"],
+            ..SUPPORTED[0].clone()
+        };
+        assert_eq!(blade.model_number_prefix(), "RZ09-0409");
+        assert!(blade.matches_model("RZ09-0421EB"));
+        assert!(!blade.matches_model("RZ09-0482X"));
+
+        let detected = blade.for_model("RZ09-0421EB");
+        assert_eq!(detected.model_number_prefixes, ["RZ09-0421"]);
+        assert_eq!(detected.model_number_prefix(), "RZ09-0421");
+        assert_eq!(
+            blade.for_model("RZ09-0482X").model_number_prefixes,
+            blade.model_number_prefixes
+        );
+
+        let spec: DescriptorSpec = toml::from_str(
+            "model_number_prefixes = [\"RZ09-0409\", \"RZ09-0421\"]\nname = \"Blade\"\npid = 1\nfeatures = []\n",
+        )
+        .unwrap();
+        let built = spec.build().unwrap();
+        assert_eq!(built.model_number_prefixes, ["RZ09-0409", "RZ09-0421"]);
+    }
+
+    #[test]
+    fn test_find_by_model() {
+        let blade = &SUPPORTED[0];
+        let prefix = blade.model_number_prefix();
+        let full = format!("{}ABCD", prefix);
+        assert_eq!(find_by_model(prefix).unwrap().pid, blade.pid);
+        assert_eq!(find_by_model(&full).unwrap().pid, blade.pid);
+        let partial = &prefix[..prefix.len() - 1];
+        assert_eq!(find_by_model(partial).unwrap().pid, blade.pid);
+        assert!(find_by_model("").is_none());
+        assert!(find_by_model("RZ00-0000").is_none());
+    }
+
+    #[test]
+    fn test_spec_roundtrip() {
+        let spec = DescriptorSpec {
+            model_number_prefixes: vec!["RZ09-0482".to_string()],
+            name: "Blade".to_string(),
+            pid: 0x029f,
+            features: vec![feature::PERF.to_string()],
+            report_id: 0,
+            fan_rpm_range: Some((1800, 5300)),
+            perf_modes: None,
+            cpu_boosts: Some(vec![CpuBoost::Low, CpuBoost::Boost]),
+            gpu_boosts: None,
+            lighting: Some(LightingCapability::PerKey { rows: 6, cols: 16 }),
+            charge_limit_encoding: None,
+            notes: Some("User report".to_string()),
+            source_url: None,
+            timing: None,
+            quirks: Some(QuirksSpec {
+                serial_batches: true,
+                ..Default::default()
+            }),
+        };
+        let text = toml::to_string(&spec).unwrap();
+        assert_eq!(toml::from_str::<DescriptorSpec>(&text).unwrap(), spec);
+
+        let descriptor = spec.clone().build().unwrap();
+        assert_eq!(descriptor.features, [feature::PERF]);
+        assert!(descriptor.quirks.serial_batches);
+        assert_eq!(descriptor.fan_rpm_range, 1800..=5300);
+        assert_eq!(descriptor.cpu_boosts, [CpuBoost::Low, CpuBoost::Boost]);
+        assert_eq!(descriptor.gpu_boosts, GpuBoost::ALL);
+        assert_eq!(descriptor.lighting.per_key(), Some((6, 16)));
+        assert_eq!(descriptor.notes, "User report");
+        assert_eq!(descriptor.source_url, "");
+        assert!(matches!(
+            descriptor.check_cpu_boost(CpuBoost::High),
+            Err(RazerError::UnsupportedLevel { .. })
+        ));
+        assert!(descriptor.require(feature::PERF).is_ok());
+        let error = descriptor.require(feature::LIDLOGO).unwrap_err();
+        assert!(matches!(
+            error,
+            RazerError::FeatureNotSupported {
+                feature: feature::LIDLOGO,
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            format!("lid-logo is not supported on {}", descriptor.name)
+        );
+
+        let wide = DescriptorSpec {
+            lighting: Some(LightingCapability::PerKey { rows: 6, cols: 30 }),
+            ..spec.clone()
+        };
+        assert!(wide.build().is_err());
+
+        let reversed = DescriptorSpec {
+            fan_rpm_range: Some((5000, 2000)),
+            ..spec.clone()
+        };
+        assert!(reversed.build().is_err());
+
+        let unknown = DescriptorSpec {
+            features: vec!["rgb".to_string()],
+            ..spec
+        };
+        assert!(matches!(
+            unknown.build(),
+            Err(RazerError::InvalidDescriptor(_))
+        ));
+    }
+
+    #[test]
+    fn test_register_skips_known_pids() {
+        let extra = Descriptor {
+            model_number_prefixes: &["RZ09-TEST"],
+            name: "Test Blade",
+            pid: 0xfff0,
+            ..SUPPORTED[0].clone()
+        };
+        let duplicate = SUPPORTED[0].clone();
+        let added = register([extra.clone(), duplicate, extra]);
+        assert_eq!(added.len(), 1);
+        assert_eq!(find_by_pid(0xfff0).unwrap().name, "Test Blade");
+        assert_eq!(known().len(), SUPPORTED.len() + 1);
+        assert_eq!(
+            find_by_pid(SUPPORTED[0].pid).unwrap().name,
+            SUPPORTED[0].name
+        );
+    }
+}
@@ -1,11 +1,100 @@
-use crate::descriptor::{Descriptor, SUPPORTED};
-use crate::error::{RazerError, Result};
+use crate::descriptor::{self, Descriptor, HealthCheck, LightingCapability, Quirks, Timing};
+use crate::error::{ErrorCode, RazerError, Result};
+use crate::limits::REPORT_SIZE;
+use crate::lock::{DeviceLock, LockGuard};
 use crate::packet::Packet;
+use crate::packet_log::{self, Direction};
+use crate::permission::{hidraw_access_denied, inaccessible_razer_hidraw};
+use crate::rate::RateLimiter;
+use crate::replay::Recorder;
+use crate::transport::{self, HidTransport, MockTransport, RecordingTransport, Transport};
+use crate::types::{ChargeLimitEncoding, CpuBoost, GpuBoost, PerfMode};
+use crate::{command, feature};
 
-use log::{debug, trace, warn};
+use log::{debug, info, trace, warn};
+use std::borrow::Cow;
+use std::ffi::{CStr, CString};
 #[cfg(target_os = "linux")]
 use std::fs;
-use std::{thread, time};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time;
+
+const RAZER_VID: u16 = 0x1532;
+
+/// Lazily created context shared by every caller.
+///
+/// `stale` is set once the initial device list has been consumed, after which
+/// lookups that need fresh data refresh it.
+struct SharedContext<A> {
+    cell: OnceLock<Mutex<CachedContext<A>>>,
+}
+
+struct CachedContext<A> {
+    api: A,
+    stale: bool,
+}
+
+impl<A> SharedContext<A> {
+    const fn new() -> Self {
+        SharedContext {
+            cell: OnceLock::new(),
+        }
+    }
+
+    /// Runs `f` with the context, calling `create` on first use. A failed
+    /// `create` is not cached, so the next call tries again.
+    ///
+    /// With `rescan`, `refresh` updates the context unless it was just created.
+    fn with<T>(
+        &self,
+        rescan: bool,
+        create: impl FnOnce() -> Result<A>,
+        refresh: impl FnOnce(&mut A) -> Result<()>,
+        f: impl FnOnce(&A) -> Result<T>,
+    ) -> Result<T> {
+        let cache = match self.cell.get() {
+            Some(cache) => cache,
+            None => {
+                let api = create()?;
+                self.cell
+                    .get_or_init(|| Mutex::new(CachedContext { api, stale: false }))
+            }
+        };
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        if rescan && cache.stale {
+            refresh(&mut cache.api)?;
+        }
+        cache.stale = true;
+        f(&cache.api)
+    }
+}
+
+/// Process-wide hidapi context.
+///
+/// `HidApi::new` scans the whole USB bus, so it is created once and shared.
+static HID_API: SharedContext<hidapi::HidApi> = SharedContext::new();
+
+/// Runs `f` with the shared hidapi context, creating it on first use.
+///
+/// With `rescan`, the device list is refreshed unless it was just created,
+/// rescanning Razer devices only.
+pub(crate) fn with_hid_api<T>(
+    rescan: bool,
+    f: impl FnOnce(&hidapi::HidApi) -> Result<T>,
+) -> Result<T> {
+    HID_API.with(
+        rescan,
+        || Ok(hidapi::HidApi::new()?),
+        |api| {
+            trace!("Rescanning Razer HID devices");
+            api.reset_devices()?;
+            api.add_devices(RAZER_VID, 0)?;
+            Ok(())
+        },
+        f,
+    )
+}
 
 /// Result of enumerating connected Razer devices.
 ///
@@ -16,14 +105,510 @@ pub struct EnumerationResult {
     pub pids: Vec<u16>,
     /// Model number prefix (e.g., "RZ09-0483T").
     pub model: String,
+    /// USB product string reported for each PID, in the same order as `pids`.
+    pub products: Vec<Option<String>>,
+}
+
+impl EnumerationResult {
+    fn into_unsupported(self) -> RazerError {
+        RazerError::UnsupportedModel {
+            model: self.model,
+            pids: self.pids,
+            products: self
+                .products
+                .into_iter()
+                .map(|product| product.unwrap_or_else(|| "unknown".to_string()))
+                .collect(),
+        }
+    }
+}
+
+/// A step of [`Device::detect_with`], passed to the progress callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectStep {
+    /// Listing Razer HID devices.
+    Enumerating,
+    /// Reading the laptop model number from the system.
+    ReadingModel,
+    /// Opening the device with this PID.
+    Opening(u16),
+}
+
+impl std::fmt::Display for DetectStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DetectStep::Enumerating => write!(f, "Enumerating Razer HID devices"),
+            DetectStep::ReadingModel => write!(f, "Reading laptop model"),
+            DetectStep::Opening(pid) => write!(f, "Opening PID {:#06x}", pid),
+        }
+    }
+}
+
+type DetectProgress = Arc<dyn Fn(DetectStep) + Send + Sync>;
+type DetectSelect = Arc<dyn Fn(&[Descriptor]) -> usize + Send + Sync>;
+
+/// How [`Device::detect_with`] reports progress and when it gives up.
+#[derive(Clone, Default)]
+pub struct DetectOptions {
+    /// Fail with [`RazerError::DetectTimeout`] after this long. `None` waits
+    /// for detection to finish.
+    pub timeout: Option<time::Duration>,
+    /// Called as detection moves to each [`DetectStep`], from a worker thread.
+    pub progress: Option<DetectProgress>,
+    /// Setting this flag fails detection with [`RazerError::Cancelled`].
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Picks a device by index when several supported ones are connected. By
+    /// default the one matching the laptop model, listed first, is used.
+    pub select: Option<DetectSelect>,
+    /// Open Razer PIDs of an unknown model with the [generic](descriptor::generic)
+    /// descriptor instead of failing with [`RazerError::UnsupportedModel`].
+    pub allow_generic: bool,
+}
+
+impl DetectOptions {
+    /// Sets the progress callback.
+    pub fn on_progress(mut self, progress: impl Fn(DetectStep) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// Sets the callback choosing among several supported devices.
+    pub fn on_select(
+        mut self,
+        select: impl Fn(&[Descriptor]) -> usize + Send + Sync + 'static,
+    ) -> Self {
+        self.select = Some(Arc::new(select));
+        self
+    }
+
+    /// Picks one of the non-empty `candidates`.
+    fn choose(&self, mut candidates: Vec<Descriptor>) -> Result<Descriptor> {
+        let index = match &self.select {
+            Some(select) if candidates.len() > 1 => select(&candidates),
+            _ => 0,
+        };
+        if index >= candidates.len() {
+            return Err(RazerError::Other(format!(
+                "Selected device {} but only {} are connected",
+                index,
+                candidates.len()
+            )));
+        }
+        Ok(candidates.swap_remove(index))
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    /// Reports `step`, or fails if detection was cancelled.
+    fn step(&self, step: DetectStep) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(RazerError::Cancelled);
+        }
+        trace!("Detection step: {}", step);
+        if let Some(progress) = &self.progress {
+            progress(step);
+        }
+        Ok(())
+    }
+}
+
+/// How often [`Device::detect_with`] checks the cancel flag while waiting.
+const DETECT_POLL: time::Duration = time::Duration::from_millis(20);
+
+/// Location of a single HID interface belonging to a supported Razer device.
+///
+/// Returned by [`Device::enumerate_supported`] and accepted by [`Device::open`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DevicePathInfo {
+    /// Platform-specific HID path used to open the interface.
+    pub path: CString,
+    /// USB product ID.
+    pub pid: u16,
+    /// USB interface number, or -1 if the platform does not report it.
+    pub interface_number: i32,
+    /// HID usage page of the collection, or 0 if unknown.
+    pub usage_page: u16,
+    /// HID usage within the usage page, or 0 if unknown.
+    pub usage: u16,
+}
+
+impl DevicePathInfo {
+    /// Usage page of the vendor-defined collection that accepts control reports.
+    pub const CONTROL_USAGE_PAGE: u16 = 0xff00;
+
+    fn from_hid(info: &hidapi::DeviceInfo) -> Self {
+        DevicePathInfo {
+            path: info.path().to_owned(),
+            pid: info.product_id(),
+            interface_number: info.interface_number(),
+            usage_page: info.usage_page(),
+            usage: info.usage(),
+        }
+    }
+
+    /// Whether the metadata identifies this as the control interface
+    /// (vendor usage page, or interface 0 per openrazer).
+    pub fn is_control_interface(&self) -> bool {
+        self.usage_page == Self::CONTROL_USAGE_PAGE || self.interface_number == 0
+    }
+}
+
+/// Read-only command that succeeds if the device supports a feature.
+type FeatureProbe = fn(&Device) -> Result<()>;
+
+/// Result of [`Device::probe_features`].
+#[derive(Debug, Clone)]
+pub struct ProbedFeatures {
+    /// Features whose GET command completed successfully.
+    pub supported: Vec<&'static str>,
+    /// Features the device rejected or answered with unexpected data.
+    pub unsupported: Vec<&'static str>,
+    /// The device's descriptor with its feature list replaced by `supported`.
+    pub descriptor: Descriptor,
+}
+
+/// Strings the USB device reports about itself, as seen by hidapi.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsbInfo {
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+    /// USB interface number, or -1 if the platform does not report it.
+    pub interface_number: i32,
+}
+
+impl UsbInfo {
+    fn from_hid(info: &hidapi::DeviceInfo) -> Self {
+        UsbInfo {
+            manufacturer: info.manufacturer_string().map(str::to_string),
+            product: info.product_string().map(str::to_string),
+            serial_number: info.serial_number().map(str::to_string),
+            interface_number: info.interface_number(),
+        }
+    }
+}
+
+/// What a device reports about itself, read once by [`Device::identity`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Identity {
+    /// Firmware version as (major, minor), if the device answered.
+    pub firmware: Option<(u8, u8)>,
+    /// Serial number stored in the firmware, if the device answered.
+    pub serial: Option<String>,
+    /// USB strings, for devices backed by hardware.
+    pub usb_strings: Option<UsbInfo>,
+}
+
+/// Retry policy for [`Device::send`].
+///
+/// The default makes a single attempt, matching the behavior before retries existed,
+/// but waits out a briefly Busy device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendOptions {
+    /// Extra attempts after a [retryable](RazerError::is_retryable) error.
+    pub retries: u8,
+    /// Upper bound on the total time spent, checked between attempts and Busy
+    /// re-reads, with or without retries.
+    ///
+    /// A single blocking HID call cannot be interrupted, so this bounds how long
+    /// retries continue rather than the duration of each call.
+    pub timeout: time::Duration,
+    /// Total time spent re-reading the response while the device reports Busy.
+    ///
+    /// The firmware usually finishes the command, so the response is read again
+    /// with growing delays before the report is re-sent or DeviceBusy is returned.
+    pub busy_budget: time::Duration,
+}
+
+impl SendOptions {
+    /// Single attempt, 1s timeout and a 20ms Busy budget.
+    pub const DEFAULT: SendOptions = SendOptions {
+        retries: 0,
+        timeout: time::Duration::from_secs(1),
+        busy_budget: time::Duration::from_millis(20),
+    };
+
+    /// Delays between re-reads of a Busy response: doubling from 1ms, clipped so
+    /// that they add up to at most `busy_budget`.
+    fn busy_delays(&self) -> impl Iterator<Item = time::Duration> {
+        let budget = self.busy_budget;
+        let mut spent = time::Duration::ZERO;
+        let mut next = time::Duration::from_millis(1);
+        std::iter::from_fn(move || {
+            let delay = next.min(budget.saturating_sub(spent));
+            if delay.is_zero() {
+                return None;
+            }
+            spent += delay;
+            next *= 2;
+            Some(delay)
+        })
+    }
+
+    /// Decides whether a failed attempt may be retried.
+    ///
+    /// Returns `Ok` and bumps `attempt` when another attempt is allowed, otherwise
+    /// the error to report.
+    fn check_retry(&self, error: RazerError, attempt: &mut u8, start: time::Instant) -> Result<()> {
+        if !error.is_retryable() {
+            return Err(error);
+        }
+        if start.elapsed() >= self.timeout {
+            debug!("Giving up after {:?}: {}", start.elapsed(), error);
+            return Err(RazerError::CommandTimeout);
+        }
+        if *attempt >= self.retries {
+            return Err(error);
+        }
+        *attempt += 1;
+        debug!("Retrying ({}/{}) after: {}", attempt, self.retries, error);
+        Ok(())
+    }
+}
+
+impl Default for SendOptions {
+    fn default() -> Self {
+        SendOptions::DEFAULT
+    }
+}
+
+/// Counters describing how a [`Device`] has behaved since it was opened or
+/// [`reset_stats`](Device::reset_stats) was called.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceStats {
+    /// Times the device was re-opened after a send found it disconnected,
+    /// e.g. after suspend/resume.
+    pub reopens: u64,
+    /// Sends that returned a response.
+    pub succeeded: u64,
+    /// Sends that failed, after any retries.
+    pub failed: u64,
+    /// Sends per command class, the high byte of the command (e.g. 0x0d for
+    /// performance commands).
+    pub by_class: std::collections::BTreeMap<u8, u64>,
+    /// Time per send, including retries and Busy re-reads.
+    pub latency: LatencyStats,
+}
+
+/// Send latency percentiles.
+///
+/// Latencies are counted in power-of-two buckets starting at 64µs, so the
+/// percentiles are upper bounds, capped at `max`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    pub p50: time::Duration,
+    pub p90: time::Duration,
+    pub p99: time::Duration,
+    pub max: time::Duration,
+}
+
+/// Recent failures of a [`Device`], for long-running processes that need to
+/// tell a flaky connection from a dead one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceHealth {
+    /// Failed sends per error category.
+    pub failures: std::collections::BTreeMap<ErrorCode, u64>,
+    /// When the last send failed.
+    pub last_failure: Option<time::SystemTime>,
+    /// Sends that failed in a row; a successful send resets it.
+    pub streak: u64,
+    /// Failed sends among the last `recent_sends`.
+    pub recent_failures: u32,
+    /// Sends counted in `recent_failures`, up to [`HEALTH_WINDOW`].
+    pub recent_sends: u32,
+}
+
+/// Number of most recent sends [`DeviceHealth::recent_failures`] looks at.
+pub const HEALTH_WINDOW: u32 = 20;
+
+/// Upper bound of the first latency bucket; each further bucket doubles it.
+const LATENCY_BASE_US: u64 = 64;
+/// The last bucket, from about 4s, also takes anything slower.
+const LATENCY_BUCKETS: usize = 18;
+
+/// Live counters behind [`DeviceStats`]. Recording a send costs a few relaxed
+/// atomic increments; the summary is only computed when read.
+struct Metrics {
+    reopens: AtomicU64,
+    succeeded: AtomicU64,
+    failed: AtomicU64,
+    by_class: [AtomicU64; 256],
+    latency: [AtomicU64; LATENCY_BUCKETS],
+    max_latency_us: AtomicU64,
+    failures: [AtomicU64; ErrorCode::ALL.len()],
+    streak: AtomicU64,
+    /// Milliseconds since the Unix epoch, 0 if nothing failed yet.
+    last_failure_ms: AtomicU64,
+    /// One bit per recent send, set if it failed, newest in bit 0.
+    recent: AtomicU64,
+    recent_sends: AtomicU64,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Metrics {
+            reopens: AtomicU64::new(0),
+            succeeded: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            by_class: std::array::from_fn(|_| AtomicU64::new(0)),
+            latency: std::array::from_fn(|_| AtomicU64::new(0)),
+            max_latency_us: AtomicU64::new(0),
+            failures: std::array::from_fn(|_| AtomicU64::new(0)),
+            streak: AtomicU64::new(0),
+            last_failure_ms: AtomicU64::new(0),
+            recent: AtomicU64::new(0),
+            recent_sends: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, class: u8, failure: Option<ErrorCode>, elapsed: time::Duration) {
+        match failure {
+            None => {
+                self.succeeded.fetch_add(1, Ordering::Relaxed);
+                self.streak.store(0, Ordering::Relaxed);
+            }
+            Some(code) => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                self.streak.fetch_add(1, Ordering::Relaxed);
+                let index = ErrorCode::ALL.iter().position(|&c| c == code).unwrap_or(0);
+                self.failures[index].fetch_add(1, Ordering::Relaxed);
+                let now = time::SystemTime::now()
+                    .duration_since(time::UNIX_EPOCH)
+                    .map_or(1, |since| since.as_millis().max(1) as u64);
+                self.last_failure_ms.store(now, Ordering::Relaxed);
+            }
+        }
+        let window = (1u64 << HEALTH_WINDOW) - 1;
+        let _ = self
+            .recent
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((bits << 1 | failure.is_some() as u64) & window)
+            });
+        let _ = self
+            .recent_sends
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sends| {
+                Some((sends + 1).min(HEALTH_WINDOW.into()))
+            });
+        self.by_class[class as usize].fetch_add(1, Ordering::Relaxed);
+        let micros = elapsed.as_micros() as u64;
+        self.latency[latency_bucket(micros)].fetch_add(1, Ordering::Relaxed);
+        self.max_latency_us.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> DeviceStats {
+        let buckets: Vec<u64> = self
+            .latency
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect();
+        let max = time::Duration::from_micros(self.max_latency_us.load(Ordering::Relaxed));
+        let total: u64 = buckets.iter().sum();
+        let percentile = |p: u64| {
+            // smallest bucket holding at least p% of the samples
+            let rank = (total * p).div_ceil(100).max(1);
+            let mut seen = 0;
+            let bucket = buckets
+                .iter()
+                .position(|&count| {
+                    seen += count;
+                    seen >= rank
+                })
+                .unwrap_or(0);
+            time::Duration::from_micros(LATENCY_BASE_US << bucket).min(max)
+        };
+
+        DeviceStats {
+            reopens: self.reopens.load(Ordering::Relaxed),
+            succeeded: self.succeeded.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            by_class: self
+                .by_class
+                .iter()
+                .enumerate()
+                .map(|(class, count)| (class as u8, count.load(Ordering::Relaxed)))
+                .filter(|&(_, count)| count > 0)
+                .collect(),
+            latency: LatencyStats {
+                p50: percentile(50),
+                p90: percentile(90),
+                p99: percentile(99),
+                max,
+            },
+        }
+    }
+
+    fn health(&self) -> DeviceHealth {
+        let last_failure_ms = self.last_failure_ms.load(Ordering::Relaxed);
+        DeviceHealth {
+            failures: ErrorCode::ALL
+                .into_iter()
+                .zip(&self.failures)
+                .map(|(code, count)| (code, count.load(Ordering::Relaxed)))
+                .filter(|&(_, count)| count > 0)
+                .collect(),
+            last_failure: (last_failure_ms > 0)
+                .then(|| time::UNIX_EPOCH + time::Duration::from_millis(last_failure_ms)),
+            streak: self.streak.load(Ordering::Relaxed),
+            recent_failures: self.recent.load(Ordering::Relaxed).count_ones(),
+            recent_sends: self.recent_sends.load(Ordering::Relaxed) as u32,
+        }
+    }
+
+    fn reset(&self) {
+        for counter in [
+            &self.reopens,
+            &self.succeeded,
+            &self.failed,
+            &self.max_latency_us,
+            &self.streak,
+            &self.last_failure_ms,
+            &self.recent,
+            &self.recent_sends,
+        ]
+        .into_iter()
+        .chain(&self.by_class)
+        .chain(&self.latency)
+        .chain(&self.failures)
+        {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Whether the first `bytes_read` bytes of a response buffer (report id first)
+/// hold the packet header and all `data_size` argument bytes.
+fn covers_args(response_buf: &[u8], bytes_read: usize) -> bool {
+    // report id, then 8 header bytes; data_size is the sixth header byte
+    const HEADER: usize = 1 + 8;
+    bytes_read >= HEADER && bytes_read >= HEADER + (response_buf[1 + 5] as usize).min(80)
+}
+
+/// Index of the smallest latency bucket whose upper bound is at least `micros`.
+fn latency_bucket(micros: u64) -> usize {
+    (0..LATENCY_BUCKETS - 1)
+        .find(|&bucket| micros <= LATENCY_BASE_US << bucket)
+        .unwrap_or(LATENCY_BUCKETS - 1)
 }
 
 /// Represents a connected Razer laptop device.
 ///
 /// Wraps hidapi for USB HID communication. Use [`Device::detect`] for automatic
 /// detection or [`Device::new`] with a specific [`Descriptor`] for manual setup.
+/// [`Device::with_transport`] accepts any [`Transport`], e.g. a mock in tests.
 pub struct Device {
-    device: hidapi::HidDevice,
+    transport: Box<dyn Transport>,
+    interface: Option<DevicePathInfo>,
+    usb_info: Option<UsbInfo>,
+    timing: Timing,
+    send_options: SendOptions,
+    metrics: Metrics,
+    identity: OnceLock<Identity>,
+    lock: Option<DeviceLock>,
+    rate_limit: Option<RateLimiter>,
     /// Device descriptor containing model info and supported features.
     pub info: Descriptor,
 }
@@ -41,20 +626,52 @@ fn read_device_model() -> Result<String> {
     Ok(system_sku.chars().take(10).collect())
 }
 
+/// DMI attributes that may hold the model number, in order of preference.
 #[cfg(target_os = "linux")]
-fn read_device_model() -> Result<String> {
-    let sku = fs::read_to_string("/sys/devices/virtual/dmi/id/product_sku")
+const DMI_MODEL_SOURCES: &[&str] = &["product_sku", "product_name", "board_name"];
+
+#[cfg(target_os = "linux")]
+fn read_dmi(attribute: &str) -> std::io::Result<String> {
+    fs::read_to_string(format!("/sys/devices/virtual/dmi/id/{}", attribute))
         .map(|s| s.trim().to_string())
-        .map_err(|e| {
-            RazerError::ModelDetectionFailed(format!("Failed to read product SKU: {}", e))
-        })?;
+}
+
+#[cfg(target_os = "linux")]
+fn read_device_model() -> Result<String> {
+    parse_dmi_model(DMI_MODEL_SOURCES.iter().map(|source| {
+        let value = read_dmi(source);
+        if let Err(e) = &value {
+            debug!("Failed to read DMI {}: {}", source, e);
+        }
+        (*source, value.ok())
+    }))
+}
 
-    debug!("Linux product SKU: {}", sku);
+/// Picks the first DMI value that starts with "RZ" and clips it to the model prefix.
+///
+/// `values` pairs each attribute name with its contents, `None` if unreadable.
+#[cfg(any(target_os = "linux", test))]
+fn parse_dmi_model<'a>(
+    values: impl IntoIterator<Item = (&'a str, Option<String>)>,
+) -> Result<String> {
+    let mut first_value: Option<String> = None;
+    for (source, value) in values {
+        let Some(value) = value.filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        debug!("Linux DMI {}: {}", source, value);
+        if value.starts_with("RZ") {
+            debug!("Using model from DMI {}", source);
+            return Ok(value.chars().take(10).collect());
+        }
+        first_value.get_or_insert(value);
+    }
 
-    if sku.starts_with("RZ") {
-        Ok(sku.chars().take(10).collect())
-    } else {
-        Err(RazerError::InvalidModel(sku))
+    match first_value {
+        Some(value) => Err(RazerError::InvalidModel(value)),
+        None => Err(RazerError::ModelDetectionFailed(
+            "No DMI product SKU, product name or board name available".to_string(),
+        )),
     }
 }
 
@@ -64,158 +681,2008 @@ fn read_device_model() -> Result<String> {
     Err(RazerError::UnsupportedPlatform)
 }
 
-impl Device {
-    const RAZER_VID: u16 = 0x1532;
+/// Applies `LIBRAZER_PRE_SEND_US` / `LIBRAZER_PRE_READ_US` overrides, if set.
+fn timing_from_env(mut timing: Timing) -> Timing {
+    let read_env = |name: &str| {
+        let value = std::env::var(name).ok()?;
+        match value.trim().parse::<u64>() {
+            Ok(us) => Some(us),
+            Err(_) => {
+                warn!("Ignoring invalid {}={:?}", name, value);
+                None
+            }
+        }
+    };
+    if let Some(us) = read_env("LIBRAZER_PRE_SEND_US") {
+        timing.pre_send_us = us;
+    }
+    if let Some(us) = read_env("LIBRAZER_PRE_READ_US") {
+        timing.pre_read_us = us;
+    }
+    timing
+}
 
+impl Device {
     /// Returns a reference to the device descriptor.
     pub fn info(&self) -> &Descriptor {
         &self.info
     }
 
+    /// Fails with [`RazerError::FeatureNotSupported`] unless the descriptor lists
+    /// `feature`, see [`Descriptor::require`].
+    pub fn require(&self, feature: &'static str) -> Result<()> {
+        self.info.require(feature)
+    }
+
     /// Creates a new Device with the specified descriptor.
     ///
     /// Opens the USB HID device matching the descriptor's PID.
     pub fn new(descriptor: Descriptor) -> Result<Device> {
-        let api = hidapi::HidApi::new()?;
-
-        // there are multiple devices with the same pid, pick first that support feature report
-        let mut last_error: Option<String> = None;
-        for info in api.device_list().filter(|info| {
-            (info.vendor_id(), info.product_id()) == (Device::RAZER_VID, descriptor.pid)
-        }) {
-            let path = info.path();
-            trace!("Trying to open device at path: {:?}", path);
-            match api.open_path(path) {
-                Ok(device) => {
-                    trace!("Opened device, testing feature report...");
-                    // Report ID (1 byte) + Packet (90 bytes) = 91 bytes total
-                    match device.send_feature_report(&[0u8; 91]) {
-                        Ok(_) => {
-                            debug!(
-                                "Connected to {} (PID: 0x{:04X})",
-                                descriptor.name, descriptor.pid
-                            );
-                            return Ok(Device {
-                                device,
-                                info: descriptor.clone(),
-                            });
-                        }
-                        Err(e) => {
-                            debug!("Feature report failed on path {:?}: {}", path, e);
-                            last_error = Some(e.to_string());
-                        }
-                    }
-                }
-                Err(e) => {
-                    debug!("Failed to open path {:?}: {}", path, e);
-                    last_error = Some(e.to_string());
+        let find_interfaces = |api: &hidapi::HidApi| {
+            Ok(api
+                .device_list()
+                .filter(|info| (info.vendor_id(), info.product_id()) == (RAZER_VID, descriptor.pid))
+                .map(DevicePathInfo::from_hid)
+                .collect::<Vec<_>>())
+        };
+        // reuse the current device list, rescanning only if the device isn't in it
+        let mut interfaces = with_hid_api(false, find_interfaces)?;
+        if interfaces.is_empty() {
+            interfaces = with_hid_api(true, find_interfaces)?;
+        }
+
+        // Prefer the control interface identified by metadata, vendor usage page first.
+        // Probing other interfaces can glitch keyboard input, so it is only done when
+        // the metadata doesn't single one out.
+        let mut control: Vec<_> = interfaces
+            .iter()
+            .filter(|info| info.is_control_interface())
+            .cloned()
+            .collect();
+        control.sort_by_key(|info| info.usage_page != DevicePathInfo::CONTROL_USAGE_PAGE);
+        let probe = control.is_empty();
+        if probe {
+            debug!("No control interface in HID metadata, probing all interfaces");
+        } else {
+            interfaces = control;
+        }
+
+        // there are multiple devices with the same pid, pick first that opens
+        let mut last_error: Option<RazerError> = None;
+        for info in interfaces {
+            match with_hid_api(false, |api| {
+                Device::open_hid(api, &info, &descriptor, probe)
+            }) {
+                Ok(device) => return Ok(device),
+                // a permission problem explains the other failures too, keep it
+                Err(e) if matches!(last_error, Some(RazerError::PermissionDenied { .. })) => {
+                    debug!("Ignoring {} after a permission error", e)
                 }
+                Err(e) => last_error = Some(e),
             }
         }
-        Err(RazerError::DeviceOpenFailed {
-            name: descriptor.name.to_string(),
-            reason: last_error.unwrap_or_else(|| "no matching device found".to_string()),
-        })
+        Err(
+            last_error.unwrap_or_else(|| match inaccessible_razer_hidraw() {
+                Some(path) => RazerError::permission_denied(Some(path)),
+                None => RazerError::NoDeviceWithPid(descriptor.pid),
+            }),
+        )
     }
 
-    /// Sends a USB HID feature report and returns the response.
+    /// Opens the device with the given PID, skipping model detection.
     ///
-    /// Handles the low-level protocol including timing delays and response validation.
-    pub fn send(&self, report: Packet) -> Result<Packet> {
-        // extra byte for report id
-        let mut response_buf: Vec<u8> = vec![0x00; 1 + std::mem::size_of::<Packet>()];
-
-        // Delay before sending to ensure device is ready for new command.
-        // Per openrazer protocol, USB HID polling rate requires minimum inter-command spacing.
-        thread::sleep(time::Duration::from_micros(1000));
-        self.device.send_feature_report(
-            [0_u8; 1] // report id
-                .iter()
-                .copied()
-                .chain(Into::<Vec<u8>>::into(&report).into_iter())
-                .collect::<Vec<_>>()
-                .as_slice(),
-        )?;
+    /// Uses the [known](descriptor::known) descriptor for the PID when there is
+    /// one, otherwise a placeholder from [`descriptor_for_pid`](Self::descriptor_for_pid).
+    pub fn new_with_pid(pid: u16) -> Result<Device> {
+        Device::new(Device::descriptor_for_pid(pid))
+    }
 
-        // Delay before reading response to allow device to process command.
-        // 2ms provides margin for device firmware to prepare response buffer.
-        thread::sleep(time::Duration::from_micros(2000));
-        let bytes_read = self.device.get_feature_report(&mut response_buf)?;
-        if response_buf.len() != bytes_read {
-            return Err(RazerError::InvalidDataSize {
-                expected: response_buf.len(),
-                actual: bytes_read,
-            });
-        }
+    /// Opens the HID interface at `path` directly, bypassing enumeration.
+    ///
+    /// The health check is skipped if enumeration identifies the path as
+    /// the control interface.
+    pub fn new_at_path(path: &CStr, descriptor: Descriptor) -> Result<Device> {
+        with_hid_api(false, |api| {
+            let info = api
+                .device_list()
+                .find(|info| info.path() == path)
+                .map(DevicePathInfo::from_hid)
+                .unwrap_or_else(|| DevicePathInfo {
+                    path: path.to_owned(),
+                    pid: descriptor.pid,
+                    interface_number: -1,
+                    usage_page: 0,
+                    usage: 0,
+                });
+            let probe = !info.is_control_interface();
+            Device::open_hid(api, &info, &descriptor, probe)
+        })
+    }
 
-        // skip report id byte
-        let response = <&[u8] as TryInto<Packet>>::try_into(&response_buf[1..])?;
-        response.ensure_matches_report(&report)
+    /// Returns the [known](descriptor::known) descriptor for `pid`, or a
+    /// placeholder without any features if the PID is unknown.
+    pub fn descriptor_for_pid(pid: u16) -> Descriptor {
+        descriptor::find_by_pid(pid).unwrap_or_else(|| {
+            debug!(
+                "PID 0x{:04X} is not in the supported list, using placeholder",
+                pid
+            );
+            Descriptor {
+                model_number_prefixes: &[],
+                name: "Unknown Razer device",
+                pid,
+                features: &[],
+                timing: Timing::DEFAULT,
+                health_check: HealthCheck::DEFAULT,
+                quirks: Quirks::BLADE_2023,
+                report_id: 0x00,
+                fan_rpm_range: descriptor::DEFAULT_FAN_RPM_RANGE,
+                perf_modes: &PerfMode::ALL,
+                cpu_boosts: &CpuBoost::ALL,
+                gpu_boosts: &GpuBoost::ALL,
+                lighting: LightingCapability::BrightnessOnly,
+                charge_limit_encoding: ChargeLimitEncoding::FlaggedPercent,
+                notes: "",
+                source_url: "",
+            }
+        })
     }
 
-    /// Enumerates connected Razer devices and detects the laptop model.
-    ///
-    /// Returns an [`EnumerationResult`] containing the list of PIDs found and
-    /// the model number prefix (e.g., "RZ09-0483T").
-    pub fn enumerate() -> Result<EnumerationResult> {
-        let pids: Vec<_> = hidapi::HidApi::new()?
+    /// Opens the interface described by `info`, optionally running the descriptor's
+    /// health check on it.
+    fn open_hid(
+        api: &hidapi::HidApi,
+        info: &DevicePathInfo,
+        descriptor: &Descriptor,
+        probe: bool,
+    ) -> Result<Device> {
+        let path = info.path.as_c_str();
+        trace!("Trying to open device at path: {:?}", path);
+        let device = api.open_path(path).map_err(|e| {
+            debug!("Failed to open path {:?}: {}", path, e);
+            let path = path.to_string_lossy();
+            if transport::is_permission_error(&e) || hidraw_access_denied(&path) {
+                return RazerError::permission_denied(Some(path.into_owned()));
+            }
+            RazerError::DeviceOpenFailed {
+                name: descriptor.name.to_string(),
+                reason: e.to_string(),
+            }
+        })?;
+
+        let usb_info = api
             .device_list()
-            .filter(|info| info.vendor_id() == Device::RAZER_VID)
-            .map(|info| info.product_id())
-            .collect::<std::collections::HashSet<_>>()
-            .into_iter()
-            .collect();
+            .find(|hid_info| hid_info.path() == path)
+            .map(UsbInfo::from_hid)
+            .or_else(|| device.get_device_info().ok().map(|i| UsbInfo::from_hid(&i)));
 
-        if pids.is_empty() {
-            debug!("No Razer devices found in USB enumeration");
-            return Err(RazerError::NoDevicesFound);
+        let mut device =
+            Device::with_transport(HidTransport::new(device, path), descriptor.clone());
+        device.interface = Some(info.clone());
+        device.usb_info = usb_info;
+        device.lock = DeviceLock::open_default()
+            .map_err(|e| warn!("Sending without the device lock: {}", e))
+            .ok();
+
+        if probe {
+            trace!("Opened device, running health check...");
+            device.check_health().map_err(|e| {
+                debug!("Health check failed on path {:?}: {}", path, e);
+                RazerError::FeatureReportRejected {
+                    name: descriptor.name.to_string(),
+                    reason: e.to_string(),
+                }
+            })?;
         }
 
-        debug!("Found Razer devices with PIDs: {:?}", pids);
+        debug!(
+            "Connected to {} (PID: 0x{:04X}) on interface {} (usage page 0x{:04X}, usage 0x{:04X}){}",
+            descriptor.name,
+            descriptor.pid,
+            info.interface_number,
+            info.usage_page,
+            info.usage,
+            if probe { ", selected by probing" } else { ", selected by metadata" }
+        );
+        Ok(device)
+    }
 
-        match read_device_model() {
-            Ok(model) => {
-                debug!("Detected model: {}", model);
-                if model.starts_with("RZ09-") {
-                    Ok(EnumerationResult { pids, model })
-                } else {
-                    warn!("Model {} is not a Razer laptop (expected RZ09-*)", model);
-                    Err(RazerError::InvalidModel(model))
-                }
-            }
-            Err(e) => {
-                warn!("Failed to detect model: {}", e);
-                Err(e)
-            }
+    /// Sends the descriptor's read-only health check command and expects a
+    /// successful reply with a matching transaction id.
+    fn check_health(&self) -> Result<()> {
+        let _lock = self.acquire_lock()?;
+        let check = self.info.health_check;
+        let probe = Packet::try_new(check.command, check.args)?;
+        let start = time::Instant::now();
+        self.exchange(&self.with_quirks(&probe), &self.send_options, true, start)
+            .map(drop)
+    }
+
+    /// Returns `report` as the model's quirks want it sent, e.g. with a fixed
+    /// transaction id.
+    fn with_quirks<'a>(&self, report: &'a Packet) -> Cow<'a, Packet> {
+        match self.info.quirks.fixed_transaction_id {
+            Some(id) => Cow::Owned(report.clone().with_transaction_id(id)),
+            None => Cow::Borrowed(report),
         }
     }
 
-    /// Auto-detects and connects to a supported Razer laptop.
-    ///
-    /// Combines [`enumerate`](Self::enumerate) with the [`SUPPORTED`] device list
-    /// to find and open a compatible device.
-    pub fn detect() -> Result<Device> {
-        let enumeration = Device::enumerate()?;
-        trace!("Looking for support for model: {}", enumeration.model);
+    /// Returns the USB strings reported by the opened device, if it is backed by hardware.
+    pub fn usb_info(&self) -> Option<&UsbInfo> {
+        self.usb_info.as_ref()
+    }
 
-        match SUPPORTED
-            .iter()
-            .find(|supported| enumeration.model.starts_with(supported.model_number_prefix))
-        {
-            Some(supported) => {
-                debug!("Found supported device: {}", supported.name);
-                Device::new(supported.clone())
+    /// Returns the device's identity, querying it on first use.
+    ///
+    /// Later calls return the cached value without talking to the device. Queries
+    /// that fail leave the corresponding field empty.
+    pub fn identity(&self) -> &Identity {
+        self.identity.get_or_init(|| {
+            let firmware = command::get_firmware_version(self)
+                .map_err(|e| debug!("Failed to read firmware version: {}", e))
+                .ok();
+            let serial = command::get_serial(self)
+                .map_err(|e| debug!("Failed to read serial: {}", e))
+                .ok()
+                .filter(|serial| !serial.is_empty());
+            Identity {
+                firmware,
+                serial,
+                usb_strings: self.usb_info.clone(),
             }
-            None => {
-                warn!(
-                    "Model {} with PIDs {:0>4x?} is not supported",
-                    enumeration.model, enumeration.pids
-                );
-                Err(RazerError::UnsupportedModel {
-                    model: enumeration.model,
-                    pids: enumeration.pids,
+        })
+    }
+
+    /// Forgets the cached identity, e.g. after a firmware update.
+    pub fn invalidate_identity(&mut self) {
+        self.identity.take();
+    }
+
+    /// Returns the HID interface the device was opened on, if it is backed by hardware.
+    pub fn interface(&self) -> Option<&DevicePathInfo> {
+        self.interface.as_ref()
+    }
+
+    /// Lists every connected HID interface whose PID matches a
+    /// [known](descriptor::known) descriptor.
+    ///
+    /// Unlike [`detect`](Self::detect) this does not consult the laptop model, so
+    /// it also reports devices when several Razer products are plugged in.
+    pub fn enumerate_supported() -> Result<Vec<(Descriptor, DevicePathInfo)>> {
+        let known = descriptor::known();
+        let devices: Vec<_> = with_hid_api(true, |api| {
+            Ok(api
+                .device_list()
+                .filter(|info| info.vendor_id() == RAZER_VID)
+                .filter_map(|info| {
+                    let descriptor = known.iter().find(|d| d.pid == info.product_id())?;
+                    Some((descriptor.clone(), DevicePathInfo::from_hid(info)))
                 })
-            }
+                .collect())
+        })?;
+
+        debug!("Found {} supported HID interfaces", devices.len());
+        Ok(devices)
+    }
+
+    /// Opens the specific interface described by `info`.
+    ///
+    /// The descriptor is looked up among the [known](descriptor::known) ones by
+    /// the interface's PID.
+    pub fn open(info: &DevicePathInfo) -> Result<Device> {
+        let descriptor =
+            descriptor::find_by_pid(info.pid).ok_or_else(|| RazerError::UnsupportedModel {
+                model: "unknown".to_string(),
+                pids: vec![info.pid],
+                products: vec![],
+            })?;
+        Device::new_at_path(&info.path, descriptor)
+    }
+
+    /// Creates a Device that talks to the given transport instead of opening hardware.
+    pub fn with_transport(transport: impl Transport + 'static, descriptor: Descriptor) -> Device {
+        Device {
+            transport: Box::new(transport),
+            interface: None,
+            usb_info: None,
+            timing: timing_from_env(descriptor.timing),
+            send_options: SendOptions::default(),
+            metrics: Metrics::new(),
+            identity: OnceLock::new(),
+            lock: None,
+            rate_limit: None,
+            info: descriptor,
         }
     }
+
+    /// Returns counters for monitoring a long-running connection.
+    pub fn stats(&self) -> DeviceStats {
+        self.metrics.snapshot()
+    }
+
+    /// Returns recent failures, e.g. to decide whether to [`reconnect`](Self::reconnect)
+    /// after a run of failed sends or just back off.
+    pub fn health(&self) -> DeviceHealth {
+        self.metrics.health()
+    }
+
+    /// Zeroes the counters returned by [`stats`](Self::stats) and [`health`](Self::health).
+    pub fn reset_stats(&self) {
+        self.metrics.reset();
+    }
+
+    /// Returns the HID path the device was opened from, if it is backed by hardware.
+    pub fn hid_path(&self) -> Option<&CStr> {
+        self.transport.path()
+    }
+
+    /// Logs every following report/response pair, returning a handle to the log.
+    pub fn start_recording(&mut self) -> Recorder {
+        let recorder = Recorder::new(self.info.name, self.info.pid);
+        // placeholder while the real transport is moved into the wrapper
+        let inner = std::mem::replace(&mut self.transport, Box::new(MockTransport::new()));
+        self.transport = Box::new(RecordingTransport::new(inner, recorder.clone()));
+        recorder
+    }
+
+    /// Returns the delays currently used around each report.
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    /// Overrides the descriptor's delays for this device, e.g. for experimentation.
+    pub fn set_timing(&mut self, timing: Timing) {
+        debug!("Using timing {:?}", timing);
+        self.timing = timing;
+    }
+
+    /// Re-opens the device after it re-enumerated (re-plug, suspend/resume).
+    ///
+    /// Tries the transport's own reopen first (the same HID path for hardware
+    /// devices) and falls back to searching by the descriptor's PID, since the
+    /// path may change when the device comes back.
+    pub fn reconnect(&mut self) -> Result<()> {
+        self.reconnect_with(Device::new)
+    }
+
+    /// [`reconnect`](Self::reconnect), opening the device found by PID with `open`.
+    ///
+    /// The found device may be at another path, so its handle and what describes
+    /// it replace the old ones; the settings, counters and rate limiter stay.
+    fn reconnect_with(&mut self, open: impl FnOnce(Descriptor) -> Result<Device>) -> Result<()> {
+        let Err(e) = self.transport.reopen() else {
+            return Ok(());
+        };
+        debug!("Reopen failed ({}), searching by PID", e);
+        let device = open(self.info.clone()).map_err(|e| match e {
+            RazerError::NoDeviceWithPid(_) => RazerError::Disconnected,
+            e => e,
+        })?;
+        self.transport = device.transport;
+        self.interface = device.interface;
+        self.usb_info = device.usb_info;
+        self.lock = device.lock;
+        self.identity = OnceLock::new();
+        Ok(())
+    }
+
+    /// Sends a USB HID feature report and returns the response.
+    ///
+    /// Handles the low-level protocol including timing delays and response validation.
+    /// If the device went away (e.g. a stale handle after suspend/resume) it is
+    /// re-opened at the same path and the report is sent once more. Fails with
+    /// [`RazerError::Disconnected`] if that doesn't help; see
+    /// [`reconnect`](Self::reconnect).
+    pub fn send(&self, report: Packet) -> Result<Packet> {
+        self.send_with_options(report, &self.send_options)
+    }
+
+    /// Like [`send`](Self::send), with an explicit retry policy.
+    ///
+    /// HID errors and Busy statuses re-send the report until `options.retries`
+    /// is used up. Once `options.timeout` has elapsed no further attempt is made
+    /// and [`RazerError::CommandTimeout`] is returned.
+    pub fn send_with_options(&self, report: Packet, options: &SendOptions) -> Result<Packet> {
+        self.pace(std::slice::from_ref(&report))?;
+        let _lock = self.acquire_lock()?;
+        self.send_attempts(&report, options, true)
+    }
+
+    /// Sends several reports in order and returns their responses.
+    ///
+    /// A report whose command class differs from the previous one skips the
+    /// pre-send delay: the previous response has been read, so the firmware is idle,
+    /// and commands of different classes don't interfere. Reports of the same class
+    /// keep the full delay. Descriptors with [`Quirks::serial_batches`] always use
+    /// the full delay. Stops at the first error.
+    pub fn send_many(&self, reports: &[Packet]) -> Result<Vec<Packet>> {
+        self.pace(reports)?;
+        let _lock = self.acquire_lock()?;
+        let serial = self.info.quirks.serial_batches;
+        let mut previous_class = None;
+        reports
+            .iter()
+            .map(|report| {
+                let class = (report.command() >> 8) as u8;
+                let pre_send_delay = serial || previous_class.is_none_or(|c| c == class);
+                previous_class = Some(class);
+                self.send_attempts(report, &self.send_options, pre_send_delay)
+            })
+            .collect()
+    }
+
+    /// Sends `frames` as one multi-packet transfer and returns the response to each.
+    ///
+    /// Each frame's remaining-packets count is set to the number of frames after
+    /// it, whatever it was before (see [`Packet::sequence`]). The device must
+    /// acknowledge every frame but the last with the same count, otherwise the
+    /// transfer fails with [`RazerError::UnexpectedResponse`]. Stops at the first error.
+    pub fn send_sequence(&self, frames: &[Packet]) -> Result<Vec<Packet>> {
+        let count = u16::try_from(frames.len())
+            .map_err(|_| RazerError::Other(format!("Sequence of {} frames", frames.len())))?;
+        self.pace(frames)?;
+        let _lock = self.acquire_lock()?;
+        frames
+            .iter()
+            .zip((0..count).rev())
+            .map(|(frame, remaining)| {
+                let frame = frame.clone().with_remaining_packets(remaining);
+                self.send_attempts(&frame, &self.send_options, true)
+            })
+            .collect()
+    }
+
+    /// Runs [`exchange`](Self::exchange) under the retry policy. Retries always wait
+    /// the pre-send delay. A disconnect re-opens the transport and sends again once,
+    /// without counting against `options.retries`.
+    fn send_attempts(
+        &self,
+        report: &Packet,
+        options: &SendOptions,
+        pre_send_delay: bool,
+    ) -> Result<Packet> {
+        let report = self.with_quirks(report);
+        let report = report.as_ref();
+        #[cfg(feature = "tracing")]
+        let span = crate::instrument::send_span(report);
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+
+        let start = time::Instant::now();
+        let mut attempt = 0;
+        let mut reopened = false;
+        let result = loop {
+            let delay = pre_send_delay || attempt > 0 || reopened;
+            match self.exchange(report, options, delay, start) {
+                Err(RazerError::Disconnected) if !reopened => {
+                    reopened = true;
+                    if let Err(e) = self.reopen() {
+                        break Err(e);
+                    }
+                }
+                Err(e) => {
+                    if let Err(e) = options.check_retry(e, &mut attempt, start) {
+                        break Err(e);
+                    }
+                }
+                result => break result,
+            }
+        };
+        self.metrics.record(
+            (report.command() >> 8) as u8,
+            result.as_ref().err().map(RazerError::code),
+            start.elapsed(),
+        );
+        #[cfg(feature = "tracing")]
+        crate::instrument::record_outcome(&span, &result, start.elapsed());
+        result.map_err(|e| e.with_command_context(report.command(), report.get_args()))
+    }
+
+    /// Re-opens the transport in place after a send found the device gone.
+    fn reopen(&self) -> Result<()> {
+        self.transport.reopen().map_err(|e| {
+            debug!("Reopen failed: {}", e);
+            RazerError::Disconnected
+        })?;
+        let reopens = self.metrics.reopens.fetch_add(1, Ordering::Relaxed) + 1;
+        info!(
+            "Re-opened {} after it disconnected (reopen #{})",
+            self.info.name, reopens
+        );
+        Ok(())
+    }
+
+    /// Returns the retry policy used by [`send`](Self::send).
+    pub fn send_options(&self) -> SendOptions {
+        self.send_options
+    }
+
+    /// Sets the retry policy used by [`send`](Self::send).
+    pub fn set_send_options(&mut self, options: SendOptions) {
+        self.send_options = options;
+    }
+
+    /// Returns the cross-process lock held for each send, if any.
+    pub fn lock(&self) -> Option<&DeviceLock> {
+        self.lock.as_ref()
+    }
+
+    /// Sets the cross-process lock held for each send and batch; `None` sends
+    /// without one. Devices opened on hardware use
+    /// [`DeviceLock::open_default`], others none.
+    pub fn set_lock(&mut self, lock: Option<DeviceLock>) {
+        self.lock = lock;
+    }
+
+    fn acquire_lock(&self) -> Result<Option<LockGuard<'_>>> {
+        self.lock.as_ref().map(DeviceLock::acquire).transpose()
+    }
+
+    /// Returns the rate limiter paced sends wait for, if any.
+    pub fn rate_limit(&self) -> Option<&RateLimiter> {
+        self.rate_limit.as_ref()
+    }
+
+    /// Sets the rate limiter; `None`, the default, sends as fast as the device
+    /// answers. A send, batch or sequence takes one token, from the read budget
+    /// if all of its reports read.
+    pub fn set_rate_limit(&mut self, limiter: Option<RateLimiter>) {
+        self.rate_limit = limiter;
+    }
+
+    /// Returns the device with [`set_rate_limit`](Self::set_rate_limit) applied.
+    pub fn with_rate_limit(mut self, limiter: RateLimiter) -> Device {
+        self.rate_limit = Some(limiter);
+        self
+    }
+
+    /// Waits for the rate limiter to let `reports` through.
+    fn pace(&self, reports: &[Packet]) -> Result<()> {
+        match &self.rate_limit {
+            Some(limiter) => limiter.acquire(!reports.iter().all(Packet::is_read)),
+            None => Ok(()),
+        }
+    }
+
+    /// Performs a single report/response exchange, re-reading a Busy response
+    /// until the busy budget is spent or the timeout since `start` has passed.
+    fn exchange(
+        &self,
+        report: &Packet,
+        options: &SendOptions,
+        pre_send_delay: bool,
+        start: time::Instant,
+    ) -> Result<Packet> {
+        // Delay before sending to ensure device is ready for new command.
+        // Per openrazer protocol, USB HID polling rate requires minimum inter-command spacing.
+        if pre_send_delay {
+            self.transport
+                .sleep(time::Duration::from_micros(self.timing.pre_send_us));
+        }
+        self.write_report(report)?;
+
+        // Delay before reading response to allow device to process command.
+        // The default 2ms provides margin for device firmware to prepare response buffer.
+        self.transport
+            .sleep(time::Duration::from_micros(self.timing.pre_read_us));
+
+        // Busy means the firmware hasn't finished yet, so read again instead of re-sending
+        let mut delays = options.busy_delays();
+        loop {
+            match self.read_response(report) {
+                Err(RazerError::DeviceBusy) => {
+                    match delays.next().filter(|_| start.elapsed() < options.timeout) {
+                        Some(delay) => {
+                            trace!("Device busy, reading again in {:?}", delay);
+                            self.transport.sleep(delay);
+                        }
+                        None => {
+                            debug!("Device still busy after {:?}", options.busy_budget);
+                            return Err(RazerError::DeviceBusy);
+                        }
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Sends `report` without any delay.
+    fn write_report(&self, report: &Packet) -> Result<()> {
+        packet_log::log(Direction::Sent, report);
+        let mut buf = [0x00; REPORT_SIZE];
+        report.write_report(self.info.report_id, &mut buf);
+        self.transport.send_feature_report(&buf)
+    }
+
+    /// Reads the response to `report` and validates it, without any delay.
+    fn read_response(&self, report: &Packet) -> Result<Packet> {
+        // extra byte for report id, which hidapi expects to be set
+        let mut response_buf = [0x00; REPORT_SIZE];
+        response_buf[0] = self.info.report_id;
+        let read = self.transport.get_feature_report(&mut response_buf);
+        #[cfg(feature = "tracing")]
+        crate::instrument::exchange(
+            report,
+            match &read {
+                Ok(n) => Ok(response_buf.get(1..*n).unwrap_or_default()),
+                Err(e) => Err(e.to_string()),
+            },
+        );
+        let bytes_read = read?;
+        if response_buf.len() != bytes_read {
+            if !self.info.quirks.lenient_short_reads || !covers_args(&response_buf, bytes_read) {
+                return Err(RazerError::InvalidDataSize {
+                    expected: response_buf.len(),
+                    actual: bytes_read,
+                });
+            }
+            warn!(
+                "Short feature report read ({} of {} bytes), padding with zeros",
+                bytes_read,
+                response_buf.len()
+            );
+            response_buf[bytes_read..].fill(0x00);
+        }
+
+        // skip report id byte
+        let response = <&[u8] as TryInto<Packet>>::try_into(&response_buf[1..])?;
+        packet_log::log(Direction::Received, &response);
+        response.ensure_matches_report(report, &self.info.quirks)
+    }
+
+    /// Enumerates connected Razer devices and detects the laptop model.
+    ///
+    /// Returns an [`EnumerationResult`] containing the list of PIDs found and
+    /// the model number prefix (e.g., "RZ09-0483T").
+    pub fn enumerate() -> Result<EnumerationResult> {
+        Device::enumerate_steps(&DetectOptions::default())
+    }
+
+    fn enumerate_steps(options: &DetectOptions) -> Result<EnumerationResult> {
+        options.step(DetectStep::Enumerating)?;
+        let products = with_hid_api(true, |api| {
+            let mut products = std::collections::BTreeMap::new();
+            for info in api
+                .device_list()
+                .filter(|info| info.vendor_id() == RAZER_VID)
+            {
+                let product = products.entry(info.product_id()).or_insert(None);
+                if product.is_none() {
+                    *product = info.product_string().map(str::to_string);
+                }
+            }
+            Ok(products)
+        })?;
+        let (pids, products): (Vec<u16>, Vec<Option<String>>) = products.into_iter().unzip();
+
+        if pids.is_empty() {
+            debug!("No Razer devices found in USB enumeration");
+            if let Some(path) = inaccessible_razer_hidraw() {
+                return Err(RazerError::permission_denied(Some(path)));
+            }
+            return Err(RazerError::NoDevicesFound);
+        }
+
+        debug!(
+            "Found Razer devices with PIDs: {:?}, products: {:?}",
+            pids, products
+        );
+
+        options.step(DetectStep::ReadingModel)?;
+        match read_device_model() {
+            Ok(model) => {
+                debug!("Detected model: {}", model);
+                if model.starts_with("RZ09-") {
+                    Ok(EnumerationResult {
+                        pids,
+                        model,
+                        products,
+                    })
+                } else {
+                    warn!("Model {} is not a Razer laptop (expected RZ09-*)", model);
+                    Err(RazerError::InvalidModel(model))
+                }
+            }
+            Err(e) => {
+                warn!("Failed to detect model: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Describes everything detection looks at, for attaching to bug reports.
+    ///
+    /// Never fails: problems are written into the report instead.
+    pub fn enumeration_report() -> String {
+        use std::fmt::Write;
+
+        let mut report = String::new();
+        let _ = writeln!(
+            report,
+            "librazer {} on {} {}",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        );
+
+        let _ = match read_device_model() {
+            Ok(model) => {
+                let supported = descriptor::known()
+                    .into_iter()
+                    .find(|supported| supported.matches_model(&model));
+                let _ = writeln!(
+                    report,
+                    "Model: {} ({})",
+                    model,
+                    supported.as_ref().map_or("not supported", |d| d.name)
+                );
+                match supported {
+                    Some(d) if !d.source_url.is_empty() => {
+                        writeln!(report, "Descriptor source: {} ({})", d.source_url, d.notes)
+                    }
+                    Some(d) if !d.notes.is_empty() => {
+                        writeln!(report, "Descriptor source: {}", d.notes)
+                    }
+                    _ => Ok(()),
+                }
+            }
+            Err(e) => writeln!(report, "Model: detection failed: {}", e),
+        };
+        #[cfg(target_os = "linux")]
+        for source in DMI_MODEL_SOURCES {
+            let value = read_dmi(source).unwrap_or_else(|e| format!("unreadable ({})", e));
+            let _ = writeln!(report, "DMI {}: {}", source, value);
+        }
+
+        let _ = writeln!(report, "Razer HID interfaces:");
+        let interfaces = with_hid_api(true, |api| {
+            Ok(api
+                .device_list()
+                .filter(|info| info.vendor_id() == RAZER_VID)
+                .map(|info| {
+                    format!(
+                        "  PID 0x{:04X} interface {} usage page 0x{:04X} usage 0x{:04X}, \
+                         manufacturer {:?}, product {:?}, path {}",
+                        info.product_id(),
+                        info.interface_number(),
+                        info.usage_page(),
+                        info.usage(),
+                        info.manufacturer_string().unwrap_or("unknown"),
+                        info.product_string().unwrap_or("unknown"),
+                        info.path().to_string_lossy()
+                    )
+                })
+                .collect::<Vec<_>>())
+        });
+        let _ = match interfaces {
+            Ok(lines) if lines.is_empty() => writeln!(report, "  none"),
+            Ok(lines) => writeln!(report, "{}", lines.join("\n")),
+            Err(e) => writeln!(report, "  enumeration failed: {}", e),
+        };
+        report
+    }
+
+    /// Issues the GET command of every known feature and records which ones the
+    /// device answers.
+    ///
+    /// Only read commands are sent, so probing does not change device state.
+    pub fn probe_features(&self) -> ProbedFeatures {
+        let probes: [(&'static str, FeatureProbe); 6] = [
+            (feature::PERF, |d| command::get_perf_mode(d).map(drop)),
+            (feature::KBDBACKLIGHT, |d| {
+                command::get_keyboard_brightness(d).map(drop)
+            }),
+            (feature::LIDLOGO, |d| command::get_logo_mode(d).map(drop)),
+            (feature::BATTERYCARE, |d| {
+                command::get_battery_care(d).map(drop)
+            }),
+            (feature::LIGHTSALWAYSON, |d| {
+                command::get_lights_always_on(d).map(drop)
+            }),
+            (feature::FAN, |d| {
+                command::get_max_fan_speed_mode(d).map(drop)
+            }),
+        ];
+
+        let mut supported = Vec::new();
+        let mut unsupported = Vec::new();
+        for (feature, probe) in probes {
+            match probe(self) {
+                Ok(()) => supported.push(feature),
+                Err(e) => {
+                    debug!("Probing {} failed: {}", feature, e);
+                    unsupported.push(feature);
+                }
+            }
+        }
+        debug!("Probed features: {:?}", supported);
+
+        ProbedFeatures {
+            descriptor: self.info.with_features(&supported),
+            supported,
+            unsupported,
+        }
+    }
+
+    /// Like [`detect`](Self::detect), but opens models without a
+    /// [known](descriptor::known) descriptor by probing their features.
+    ///
+    /// Each Razer PID is opened in turn with the [generic](descriptor::generic)
+    /// descriptor, and the first one that answers any probe is returned with the
+    /// probed features. Commands for unlisted models are untested, so this is opt-in.
+    pub fn detect_unsupported() -> Result<Device> {
+        let enumeration = Device::enumerate()?;
+        if let Some(supported) = descriptor::known()
+            .into_iter()
+            .find(|supported| supported.matches_model(&enumeration.model))
+        {
+            debug!("Found supported device: {}", supported.name);
+            return Device::new(supported.for_model(&enumeration.model));
+        }
+
+        warn!(
+            "Model {} is not supported, probing features of PIDs {:0>4x?}",
+            enumeration.model, enumeration.pids
+        );
+        // interned so the synthesized descriptor can show the detected model
+        let model = descriptor::intern_str(&enumeration.model);
+        for &pid in &enumeration.pids {
+            let mut device = match Device::new(descriptor::generic(pid)) {
+                Ok(device) => device,
+                Err(e) => {
+                    debug!("Could not open PID 0x{:04X}: {}", pid, e);
+                    continue;
+                }
+            };
+            device.info.model_number_prefixes = descriptor::intern_list(&[model]);
+
+            let probed = device.probe_features();
+            if probed.supported.is_empty() {
+                debug!("PID 0x{:04X} answered no probes", pid);
+                continue;
+            }
+            warn!(
+                "Using probed features {:?} for unsupported model {} (PID 0x{:04X})",
+                probed.supported, model, pid
+            );
+            device.info = probed.descriptor;
+            return Ok(device);
+        }
+
+        Err(enumeration.into_unsupported())
+    }
+
+    /// Auto-detects and connects to a supported Razer laptop.
+    ///
+    /// Combines [`enumerate`](Self::enumerate) with the [`SUPPORTED`](descriptor::SUPPORTED)
+    /// device list, followed by any [registered](descriptor::register) descriptors,
+    /// to find and open a compatible device. When several supported devices are
+    /// connected, the one matching the laptop model is preferred; see
+    /// [`detect_with_selector`](Self::detect_with_selector) to choose instead.
+    pub fn detect() -> Result<Device> {
+        Device::detect_steps(&DetectOptions::default())
+    }
+
+    /// Like [`detect`](Self::detect), letting `select` pick among the supported
+    /// devices by index when more than one is connected.
+    ///
+    /// The candidates are ordered with the one matching the laptop model first.
+    pub fn detect_with_selector(
+        select: impl Fn(&[Descriptor]) -> usize + Send + Sync + 'static,
+    ) -> Result<Device> {
+        Device::detect_steps(&DetectOptions::default().on_select(select))
+    }
+
+    /// Opens every connected supported device, the one matching the laptop model
+    /// first. Devices that fail to open are skipped.
+    pub fn detect_all() -> Result<Vec<Device>> {
+        if let Some(device) = simulated_device() {
+            return Ok(vec![device]);
+        }
+        // every candidate is opened, so several PID matches aren't ambiguous here
+        let options = DetectOptions::default().on_select(|_| 0);
+        Ok(Device::detect_candidates(&options)?
+            .into_iter()
+            .filter_map(|descriptor| {
+                Device::open_candidate(&options, descriptor)
+                    .map_err(|e| debug!("Skipping device: {}", e))
+                    .ok()
+            })
+            .collect())
+    }
+
+    /// Like [`detect`](Self::detect), reporting progress and bounded in time.
+    ///
+    /// Detection runs on a worker thread, since hidapi calls can stall on a flaky
+    /// hub and cannot be interrupted. On timeout or cancellation the worker is
+    /// abandoned and closes whatever it opens once it finishes; until then it holds
+    /// the shared hidapi context, so other calls into this module may block. Time
+    /// spent in the [`select`](DetectOptions::select) callback doesn't count
+    /// towards the timeout.
+    pub fn detect_with(options: DetectOptions) -> Result<Device> {
+        if let Some(device) = simulated_device() {
+            return Ok(device);
+        }
+        let start = time::Instant::now();
+        let worker_options = options.clone();
+        let candidates = run_bounded(&options, options.timeout, move || {
+            Device::detect_candidates(&worker_options)
+        })?;
+        let elapsed = start.elapsed();
+
+        let descriptor = options.choose(candidates)?;
+        let worker_options = options.clone();
+        run_bounded(
+            &options,
+            options
+                .timeout
+                .map(|timeout| timeout.saturating_sub(elapsed)),
+            move || Device::open_candidate(&worker_options, descriptor),
+        )
+        .map_err(|e| match (e, options.timeout) {
+            // report the configured timeout rather than what was left of it
+            (RazerError::DetectTimeout(_), Some(timeout)) => RazerError::DetectTimeout(timeout),
+            (e, _) => e,
+        })
+    }
+
+    /// Opens `descriptor` without checking that it matches the laptop model.
+    ///
+    /// For models missing from the supported list that behave like a listed one.
+    /// Only the descriptor's PID has to be connected; the model mismatch is
+    /// logged as a warning since its commands are untested on this machine.
+    pub fn detect_as(descriptor: Descriptor) -> Result<Device> {
+        if let Some(device) = simulated_device() {
+            return Ok(device);
+        }
+        let model = read_device_model().unwrap_or_else(|e| format!("unknown ({})", e));
+        if !descriptor.matches_model(&model) {
+            warn!(
+                "Treating model {} as {} ({}, PID 0x{:04X}). It is not supported, commands may misbehave",
+                model,
+                descriptor.name,
+                descriptor.model_number_prefixes.join("/"),
+                descriptor.pid
+            );
+        }
+        Device::new(descriptor)
+    }
+
+    fn detect_steps(options: &DetectOptions) -> Result<Device> {
+        if let Some(device) = simulated_device() {
+            return Ok(device);
+        }
+        let descriptor = options.choose(Device::detect_candidates(options)?)?;
+        Device::open_candidate(options, descriptor)
+    }
+
+    /// Returns the supported descriptors worth trying, the one matching the laptop
+    /// model first. Never empty.
+    fn detect_candidates(options: &DetectOptions) -> Result<Vec<Descriptor>> {
+        let enumeration = Device::enumerate_steps(options)?;
+        // built-in descriptors come first, so they win over user-provided ones
+        match_candidates(enumeration, &descriptor::known(), options)
+    }
+
+    fn open_candidate(options: &DetectOptions, descriptor: Descriptor) -> Result<Device> {
+        options.step(DetectStep::Opening(descriptor.pid))?;
+        Device::new(descriptor)
+    }
+}
+
+/// Picks the descriptors in `known` worth trying for `enumeration`.
+///
+/// The descriptor whose prefix matches the model comes first. Without one, the
+/// model number is assumed to be wrong (as in VMs or with corrupted DMI data) and
+/// devices are matched by PID alone, which has to be unambiguous unless a
+/// [`select`](DetectOptions::select) callback picks among them.
+fn match_candidates(
+    enumeration: EnumerationResult,
+    known: &[Descriptor],
+    options: &DetectOptions,
+) -> Result<Vec<Descriptor>> {
+    trace!("Looking for support for model: {}", enumeration.model);
+    let matching = known
+        .iter()
+        .find(|supported| supported.matches_model(&enumeration.model));
+    // the model match is only a preference, another supported device may be plugged in
+    let mut candidates: Vec<Descriptor> = matching
+        .map(|supported| supported.for_model(&enumeration.model))
+        .into_iter()
+        .chain(
+            known
+                .iter()
+                .filter(|supported| {
+                    enumeration.pids.contains(&supported.pid)
+                        && matching.is_none_or(|m| m.pid != supported.pid)
+                })
+                .cloned(),
+        )
+        .collect();
+    candidates.dedup_by_key(|descriptor| descriptor.pid);
+
+    match (matching, candidates.len()) {
+        (Some(supported), 1) => debug!("Found supported device: {}", supported.name),
+        (Some(supported), _) => debug!(
+            "Found supported devices {:?}, preferring {} by model",
+            candidates.iter().map(|d| d.name).collect::<Vec<_>>(),
+            supported.name
+        ),
+        (None, 0) if options.allow_generic && !enumeration.pids.is_empty() => {
+            warn!(
+                "Model {} with PIDs {:0>4x?} is not supported, using the generic descriptor",
+                enumeration.model, enumeration.pids
+            );
+            candidates = enumeration
+                .pids
+                .iter()
+                .map(|&pid| descriptor::generic(pid))
+                .collect();
+        }
+        (None, 0) => {
+            warn!(
+                "Model {} with PIDs {:0>4x?} is not supported",
+                enumeration.model, enumeration.pids
+            );
+            return Err(enumeration.into_unsupported());
+        }
+        (None, 1) => warn!(
+            "Model {} is not supported, matched {} by PID {:#06x}",
+            enumeration.model, candidates[0].name, candidates[0].pid
+        ),
+        (None, _) if options.select.is_some() => warn!(
+            "Model {} is not supported, but supported devices {:?} are connected",
+            enumeration.model,
+            candidates.iter().map(|d| d.name).collect::<Vec<_>>()
+        ),
+        (None, _) => {
+            return Err(RazerError::AmbiguousDevice {
+                model: enumeration.model,
+                candidates: candidates
+                    .iter()
+                    .map(|d| format!("{} ({:#06x})", d.name, d.pid))
+                    .collect(),
+            });
+        }
+    }
+    Ok(candidates)
+}
+
+/// Returns the simulated device when simulation is enabled.
+#[cfg(feature = "simulation")]
+fn simulated_device() -> Option<Device> {
+    if !crate::simulation::enabled() {
+        return None;
+    }
+    warn!(
+        "{} is set, using the simulated device",
+        crate::simulation::SIMULATE_ENV
+    );
+    Some(crate::simulation::device())
+}
+
+#[cfg(not(feature = "simulation"))]
+fn simulated_device() -> Option<Device> {
+    None
+}
+
+/// Runs `f` on a worker thread, waiting at most `timeout` and watching the
+/// cancel flag in `options`.
+fn run_bounded<T: Send + 'static>(
+    options: &DetectOptions,
+    timeout: Option<time::Duration>,
+    f: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::Builder::new()
+        .name("razer-detect".to_string())
+        .spawn(move || {
+            let _ = tx.send(f());
+        })
+        .map_err(|e| RazerError::Other(format!("Failed to start detection: {}", e)))?;
+
+    let start = time::Instant::now();
+    loop {
+        let wait = timeout.map_or(DETECT_POLL, |timeout| {
+            timeout.saturating_sub(start.elapsed()).min(DETECT_POLL)
+        });
+        match rx.recv_timeout(wait) {
+            Ok(result) => return result,
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(RazerError::Other("Detection thread panicked".to_string()))
+            }
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+        }
+        if options.is_cancelled() {
+            debug!("Detection cancelled after {:?}", start.elapsed());
+            return Err(RazerError::Cancelled);
+        }
+        if let Some(timeout) = timeout.filter(|t| start.elapsed() >= *t) {
+            warn!("Device detection timed out after {:?}", timeout);
+            return Err(RazerError::DetectTimeout(timeout));
+        }
+    }
+}
+
+/// Something [`command`](crate::command) functions can send packets through.
+///
+/// Implemented by [`Device`] and [`SharedDevice`], so every command works with either.
+pub trait CommandTarget {
+    /// Returns the descriptor of the device commands are sent to.
+    fn info(&self) -> &Descriptor;
+
+    /// Sends a USB HID feature report and returns the response.
+    fn send(&self, report: Packet) -> Result<Packet>;
+
+    /// Sends several reports in order, stopping at the first error.
+    ///
+    /// The default sends them one by one.
+    fn send_many(&self, reports: &[Packet]) -> Result<Vec<Packet>> {
+        reports
+            .iter()
+            .map(|report| self.send(report.clone()))
+            .collect()
+    }
+}
+
+impl CommandTarget for Device {
+    fn info(&self) -> &Descriptor {
+        Device::info(self)
+    }
+
+    fn send(&self, report: Packet) -> Result<Packet> {
+        Device::send(self, report)
+    }
+
+    fn send_many(&self, reports: &[Packet]) -> Result<Vec<Packet>> {
+        Device::send_many(self, reports)
+    }
+}
+
+/// Cloneable, thread-safe handle to a [`Device`].
+///
+/// This is the intended way to use one device from several threads, e.g. a UI
+/// thread and a background refresh. Each `send` is serialized by an internal
+/// lock; commands that send several packets may interleave with other threads,
+/// so hold [`lock`](Self::lock) when a sequence must not be interrupted.
+#[derive(Clone)]
+pub struct SharedDevice {
+    device: Arc<Mutex<Device>>,
+    info: Descriptor,
+}
+
+impl SharedDevice {
+    /// Wraps an opened device.
+    pub fn new(device: Device) -> Self {
+        SharedDevice {
+            info: device.info.clone(),
+            device: Arc::new(Mutex::new(device)),
+        }
+    }
+
+    /// Returns the device descriptor.
+    pub fn info(&self) -> &Descriptor {
+        &self.info
+    }
+
+    /// Locks the device for exclusive use.
+    ///
+    /// A panic in another holder does not poison the handle; the device has no
+    /// state that could be left inconsistent.
+    pub fn lock(&self) -> MutexGuard<'_, Device> {
+        self.device.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sends a USB HID feature report and returns the response.
+    pub fn send(&self, report: Packet) -> Result<Packet> {
+        self.lock().send(report)
+    }
+}
+
+impl From<Device> for SharedDevice {
+    fn from(device: Device) -> Self {
+        SharedDevice::new(device)
+    }
+}
+
+impl CommandTarget for SharedDevice {
+    fn info(&self) -> &Descriptor {
+        SharedDevice::info(self)
+    }
+
+    fn send(&self, report: Packet) -> Result<Packet> {
+        SharedDevice::send(self, report)
+    }
+
+    fn send_many(&self, reports: &[Packet]) -> Result<Vec<Packet>> {
+        self.lock().send_many(reports)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::cmd;
+    use crate::descriptor::SUPPORTED;
+    use crate::transport::MockTransport;
+
+    fn path_info(interface_number: i32, usage_page: u16) -> DevicePathInfo {
+        DevicePathInfo {
+            path: CString::new("test").unwrap(),
+            pid: 0x029f,
+            interface_number,
+            usage_page,
+            usage: 0,
+        }
+    }
+
+    #[test]
+    fn test_control_interface_detection() {
+        assert!(path_info(2, DevicePathInfo::CONTROL_USAGE_PAGE).is_control_interface());
+        assert!(path_info(0, 0x0001).is_control_interface());
+        assert!(!path_info(1, 0x000c).is_control_interface());
+        assert!(!path_info(-1, 0).is_control_interface());
+    }
+
+    #[test]
+    fn test_probe_features() {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), Device::descriptor_for_pid(0x0bad));
+        mock.push_response(&[0, 1, 0, 0]); // perf, both zones
+        mock.push_response(&[0, 2, 0, 0]);
+        mock.push_status(0x05); // kbd brightness not supported
+        mock.push_status(0x05); // logo not supported
+        mock.push_response(&[0xd0]); // battery care
+        mock.push_response(&[0x03]); // lights always on
+        mock.push_status(0x05); // max fan speed not supported
+
+        let probed = device.probe_features();
+        assert_eq!(
+            probed.supported,
+            vec![feature::PERF, feature::BATTERYCARE, feature::LIGHTSALWAYSON]
+        );
+        assert_eq!(
+            probed.unsupported,
+            vec![feature::KBDBACKLIGHT, feature::LIDLOGO, feature::FAN]
+        );
+        // ALL_FEATURES order
+        assert_eq!(
+            probed.descriptor.features,
+            &[feature::BATTERYCARE, feature::LIGHTSALWAYSON, feature::PERF]
+        );
+        assert_eq!(probed.descriptor.pid, 0x0bad);
+    }
+
+    #[test]
+    fn test_report_id() {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        command::get_keyboard_brightness(&device).unwrap();
+        assert_eq!(mock.sent_report_ids(), [0x00]);
+
+        mock.clear();
+        let mut descriptor = SUPPORTED[0].clone();
+        descriptor.report_id = 0x02;
+        let device = Device::with_transport(mock.clone(), descriptor);
+        command::get_keyboard_brightness(&device).unwrap();
+        device.check_health().unwrap();
+        assert_eq!(mock.sent_report_ids(), [0x02, 0x02]);
+    }
+
+    #[test]
+    fn test_health_check() {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        device.check_health().unwrap();
+        assert_eq!(mock.sent_commands(), vec![(0x0081, vec![0, 0])]);
+
+        mock.push_status(0x05);
+        assert!(matches!(
+            device.check_health(),
+            Err(RazerError::CommandNotSupported)
+        ));
+    }
+
+    #[test]
+    fn test_parse_dmi_model_prefers_sku() {
+        let model = parse_dmi_model([
+            ("product_sku", Some("RZ09-0483TEB3".to_string())),
+            ("product_name", Some("RZ09-0482X".to_string())),
+        ]);
+        assert_eq!(model.unwrap(), "RZ09-0483T");
+    }
+
+    #[test]
+    fn test_parse_dmi_model_falls_back_when_sku_missing() {
+        let model = parse_dmi_model([
+            ("product_sku", None),
+            ("product_name", Some("RZ09-0482XE".to_string())),
+            ("board_name", Some("Blade".to_string())),
+        ]);
+        assert_eq!(model.unwrap(), "RZ09-0482X");
+
+        let model = parse_dmi_model([
+            ("product_sku", Some(String::new())),
+            ("product_name", Some("Blade 14".to_string())),
+            ("board_name", Some("RZ09-0482X".to_string())),
+        ]);
+        assert_eq!(model.unwrap(), "RZ09-0482X");
+    }
+
+    #[test]
+    fn test_parse_dmi_model_errors() {
+        assert!(matches!(
+            parse_dmi_model([("product_sku", Some("XPS 15".to_string()))]),
+            Err(RazerError::InvalidModel(value)) if value == "XPS 15"
+        ));
+        assert!(matches!(
+            parse_dmi_model([("product_sku", None), ("product_name", None)]),
+            Err(RazerError::ModelDetectionFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_detect_with_cancelled() {
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        let recorded = steps.clone();
+        let options = DetectOptions {
+            timeout: Some(time::Duration::from_secs(5)),
+            cancel: Some(Arc::new(AtomicBool::new(true))),
+            ..DetectOptions::default()
+        }
+        .on_progress(move |step| recorded.lock().unwrap().push(step));
+
+        assert!(matches!(
+            Device::detect_with(options),
+            Err(RazerError::Cancelled)
+        ));
+        assert!(steps.lock().unwrap().is_empty());
+        assert_eq!(
+            DetectStep::Opening(0x029f).to_string(),
+            "Opening PID 0x029f"
+        );
+    }
+
+    #[test]
+    fn test_detect_options_choose() {
+        let candidates = SUPPORTED.to_vec();
+        let options = DetectOptions::default().on_select(|candidates| candidates.len() - 1);
+        assert_eq!(
+            options.choose(candidates.clone()).unwrap().pid,
+            SUPPORTED[SUPPORTED.len() - 1].pid
+        );
+        // the selector isn't asked about a single candidate
+        assert_eq!(
+            options.choose(candidates[..1].to_vec()).unwrap().pid,
+            SUPPORTED[0].pid
+        );
+        // without a selector the model match, listed first, wins
+        assert_eq!(
+            DetectOptions::default()
+                .choose(candidates.clone())
+                .unwrap()
+                .pid,
+            SUPPORTED[0].pid
+        );
+
+        let out_of_range = DetectOptions::default().on_select(|candidates| candidates.len());
+        assert!(out_of_range.choose(candidates).is_err());
+    }
+
+    fn enumeration(model: &str, pids: &[u16]) -> EnumerationResult {
+        EnumerationResult {
+            pids: pids.to_vec(),
+            model: model.to_string(),
+            products: vec![None; pids.len()],
+        }
+    }
+
+    #[test]
+    fn test_match_candidates() {
+        let options = DetectOptions::default();
+        let pids: Vec<u16> = SUPPORTED.iter().map(|d| d.pid).collect();
+
+        // the model match comes first, other connected supported devices follow
+        let model = format!("{}ABCD", SUPPORTED[1].model_number_prefix());
+        let candidates = match_candidates(enumeration(&model, &pids), SUPPORTED, &options).unwrap();
+        assert_eq!(candidates[0].pid, SUPPORTED[1].pid);
+        assert_eq!(candidates.len(), SUPPORTED.len());
+
+        // a wrong model number falls back to the single connected supported PID
+        let candidates = match_candidates(
+            enumeration("RZ00-0000", &[SUPPORTED[1].pid, 0x0001]),
+            SUPPORTED,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].pid, SUPPORTED[1].pid);
+
+        // several PID matches are ambiguous unless a selector picks one
+        match match_candidates(enumeration("RZ00-0000", &pids), SUPPORTED, &options) {
+            Err(RazerError::AmbiguousDevice { model, candidates }) => {
+                assert_eq!(model, "RZ00-0000");
+                assert_eq!(candidates.len(), SUPPORTED.len());
+                assert!(candidates[0].contains(SUPPORTED[0].name));
+            }
+            other => panic!("expected AmbiguousDevice, got {:?}", other.map(|c| c.len())),
+        }
+        let selecting = DetectOptions::default().on_select(|_| 0);
+        assert_eq!(
+            match_candidates(enumeration("RZ00-0000", &pids), SUPPORTED, &selecting)
+                .unwrap()
+                .len(),
+            SUPPORTED.len()
+        );
+
+        assert!(matches!(
+            match_candidates(enumeration("RZ00-0000", &[0x0001]), SUPPORTED, &options),
+            Err(RazerError::UnsupportedModel { .. })
+        ));
+    }
+
+    #[test]
+    fn test_identity_is_cached() {
+        let mock = MockTransport::new();
+        let mut device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        mock.push_response(&[1, 2]);
+        mock.push_response(b"SERIAL01");
+
+        let identity = device.identity().clone();
+        assert_eq!(identity.firmware, Some((1, 2)));
+        assert_eq!(identity.serial.as_deref(), Some("SERIAL01"));
+        assert_eq!(device.identity(), &identity);
+        assert_eq!(mock.sent().len(), 2);
+
+        device.invalidate_identity();
+        mock.push_status(0x05); // not supported
+        mock.push_status(0x05);
+        assert_eq!(device.identity().firmware, None);
+        assert_eq!(mock.sent().len(), 4);
+    }
+
+    #[test]
+    fn test_send_stats() {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        command::get_perf_mode(&device).unwrap();
+        command::get_keyboard_brightness(&device).unwrap();
+        mock.push_status(0x05); // not supported
+        assert!(command::get_battery_care(&device).is_err());
+
+        let stats = device.stats();
+        assert_eq!((stats.succeeded, stats.failed), (3, 1));
+        assert_eq!(
+            stats.by_class.into_iter().collect::<Vec<_>>(),
+            vec![(0x03, 1), (0x07, 1), (0x0d, 2)]
+        );
+        assert!(stats.latency.p50 <= stats.latency.p99);
+        assert!(stats.latency.p99 <= stats.latency.max);
+
+        device.reset_stats();
+        assert_eq!(device.stats(), DeviceStats::default());
+    }
+
+    #[test]
+    fn test_device_health() {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        assert_eq!(device.health(), DeviceHealth::default());
+
+        for _ in 0..2 {
+            mock.push_status(0x05); // not supported
+            assert!(command::get_battery_care(&device).is_err());
+        }
+        let health = device.health();
+        assert_eq!(health.streak, 2);
+        assert_eq!((health.recent_failures, health.recent_sends), (2, 2));
+        assert_eq!(
+            health.failures.into_iter().collect::<Vec<_>>(),
+            vec![(ErrorCode::Unsupported, 2)]
+        );
+        assert!(health.last_failure.is_some());
+
+        command::get_keyboard_brightness(&device).unwrap();
+        assert_eq!(device.health().streak, 0);
+
+        // only the last HEALTH_WINDOW sends count as recent
+        for _ in 0..HEALTH_WINDOW - 1 {
+            command::get_keyboard_brightness(&device).unwrap();
+        }
+        let health = device.health();
+        assert_eq!(
+            (health.recent_failures, health.recent_sends),
+            (0, HEALTH_WINDOW)
+        );
+        assert_eq!(health.failures[&ErrorCode::Unsupported], 2);
+
+        device.reset_stats();
+        assert_eq!(device.health(), DeviceHealth::default());
+    }
+
+    #[test]
+    fn test_latency_percentiles() {
+        let metrics = Metrics::new();
+        for _ in 0..90 {
+            metrics.record(0x0d, None, time::Duration::from_micros(3000));
+        }
+        for _ in 0..10 {
+            metrics.record(0x0d, None, time::Duration::from_millis(40));
+        }
+        let latency = metrics.snapshot().latency;
+        assert_eq!(latency.p50, time::Duration::from_micros(4096));
+        assert_eq!(latency.p90, time::Duration::from_micros(4096));
+        assert_eq!(latency.p99, time::Duration::from_millis(40));
+        assert_eq!(latency.max, time::Duration::from_millis(40));
+        assert_eq!(latency_bucket(0), 0);
+        assert_eq!(latency_bucket(u64::MAX), LATENCY_BUCKETS - 1);
+    }
+
+    #[test]
+    fn test_descriptor_for_supported_pid() {
+        let desc = Device::descriptor_for_pid(0x029f);
+        assert_eq!(desc.model_number_prefix(), "RZ09-0483T");
+        assert!(!desc.features.is_empty());
+    }
+
+    #[test]
+    fn test_reconnect_after_disconnect() {
+        let mock = MockTransport::new();
+        let mut device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+
+        mock.disconnect();
+        mock.fail_reopen(true);
+        assert!(matches!(
+            command::get_battery_care(&device),
+            Err(RazerError::Disconnected)
+        ));
+        assert_eq!(device.stats().reopens, 0);
+
+        mock.fail_reopen(false);
+        device.reconnect().unwrap();
+        assert_eq!(mock.reopen_count(), 1);
+        mock.push_response(&[0xd0]);
+        assert!(command::get_battery_care(&device).is_ok());
+    }
+
+    #[test]
+    fn test_reconnect_falls_back_to_the_device_found_by_pid() {
+        let old = MockTransport::new();
+        let mut device = Device::with_transport(old.clone(), SUPPORTED[0].clone());
+        device.interface = Some(path_info(0, 0x0001));
+        old.disconnect();
+        old.fail_reopen(true);
+
+        let new = MockTransport::new();
+        device
+            .reconnect_with(|descriptor| {
+                let mut found = Device::with_transport(new.clone(), descriptor);
+                found.interface = Some(path_info(2, 0x000c));
+                Ok(found)
+            })
+            .unwrap();
+        assert_eq!(device.interface().unwrap().interface_number, 2);
+
+        new.push_response(&[0xd0]);
+        assert!(command::get_battery_care(&device).is_ok());
+        assert_eq!(new.sent().len(), 1);
+        assert!(old.sent().is_empty());
+    }
+
+    #[test]
+    fn test_send_reopens_after_disconnect() {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+
+        mock.disconnect();
+        mock.push_response(&[0xd0]);
+        assert_eq!(
+            command::get_battery_care(&device).unwrap(),
+            crate::types::BatteryCare::Enable
+        );
+        assert_eq!(mock.reopen_count(), 1);
+        assert_eq!(device.stats().reopens, 1);
+        // the report is only sent once the transport is back
+        assert_eq!(mock.sent_commands().len(), 1);
+
+        // only one reopen per send
+        mock.disconnect();
+        mock.fail_reopen(true);
+        assert!(matches!(
+            command::get_battery_care(&device),
+            Err(RazerError::Disconnected)
+        ));
+        assert_eq!(device.stats().reopens, 1);
+    }
+
+    #[test]
+    fn test_send_uses_configured_timing() {
+        let mock = MockTransport::new();
+        let mut device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        device.set_timing(Timing {
+            pre_send_us: 5000,
+            pre_read_us: 250,
+        });
+
+        command::set_battery_care(&device, crate::types::BatteryCare::Enable).unwrap();
+        assert_eq!(
+            mock.sleeps(),
+            vec![
+                time::Duration::from_micros(5000),
+                time::Duration::from_micros(250)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_send_retries_hid_errors() {
+        let mock = MockTransport::new();
+        let mut device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        device.set_send_options(SendOptions {
+            retries: 2,
+            timeout: time::Duration::from_secs(60),
+            ..SendOptions::DEFAULT
+        });
+
+        mock.push_hid_error();
+        mock.push_hid_error();
+        mock.push_response(&[0xd0]);
+        assert_eq!(
+            command::get_battery_care(&device).unwrap(),
+            crate::types::BatteryCare::Enable
+        );
+        assert_eq!(mock.sent().len(), 3);
+    }
+
+    #[test]
+    fn test_send_without_retries_fails_fast() {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+
+        mock.push_hid_error();
+        assert!(matches!(
+            command::get_battery_care(&device).map_err(RazerError::into_root),
+            Err(RazerError::Hid(_))
+        ));
+        assert_eq!(mock.sent().len(), 1);
+    }
+
+    #[test]
+    fn test_send_timeout() {
+        let mock = MockTransport::new();
+        let mut device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        device.set_send_options(SendOptions {
+            retries: 5,
+            timeout: time::Duration::ZERO,
+            busy_budget: time::Duration::ZERO,
+        });
+
+        mock.push_status(0x01);
+        assert!(matches!(
+            command::get_battery_care(&device),
+            Err(RazerError::CommandTimeout)
+        ));
+    }
+
+    #[test]
+    fn test_send_timeout_without_retries() {
+        /// A mock whose reads take 20ms and whose delays are slept.
+        struct Slow(MockTransport);
+        impl Transport for Slow {
+            fn send_feature_report(&self, data: &[u8]) -> Result<()> {
+                self.0.send_feature_report(data)
+            }
+            fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize> {
+                std::thread::sleep(time::Duration::from_millis(20));
+                self.0.get_feature_report(buf)
+            }
+        }
+
+        let mock = MockTransport::new();
+        let mut device = Device::with_transport(Slow(mock.clone()), SUPPORTED[0].clone());
+        device.set_send_options(SendOptions {
+            retries: 0,
+            timeout: time::Duration::from_millis(10),
+            busy_budget: time::Duration::from_secs(1),
+        });
+
+        for _ in 0..10 {
+            mock.push_status(0x01);
+        }
+        let start = time::Instant::now();
+        assert!(matches!(
+            command::get_battery_care(&device).map_err(RazerError::into_root),
+            Err(RazerError::CommandTimeout)
+        ));
+        // one read, not a second of Busy re-reads
+        assert!(start.elapsed() < time::Duration::from_millis(500));
+        assert_eq!(mock.sent().len(), 1);
+    }
+
+    /// Reports a full profile would send, grouped by command class.
+    fn profile_reports() -> Vec<Packet> {
+        vec![
+            Packet::new(0x0d02, &[1, 1, 4, 0]),
+            Packet::new(0x0d02, &[1, 2, 4, 0]),
+            Packet::new(0x0d07, &[0, 1, 3]),
+            Packet::new(0x0d07, &[0, 2, 2]),
+            Packet::new(0x0303, &[1, 5, 128]),
+            Packet::new(0x0300, &[1, 4, 1]),
+            Packet::new(0x0712, &[0xd0]),
+            Packet::new(0x0004, &[0x03, 0]),
+        ]
+    }
+
+    #[test]
+    fn test_send_many_skips_delay_between_classes() {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        let reports = profile_reports();
+
+        let responses = device.send_many(&reports).unwrap();
+        assert_eq!(responses.len(), reports.len());
+        let pre_send = time::Duration::from_micros(Timing::DEFAULT.pre_send_us);
+        // every report except the first of the 0x03, 0x07 and 0x00 classes
+        let full_delays = mock.sleeps().iter().filter(|d| **d == pre_send).count();
+        assert_eq!(full_delays, 5);
+
+        mock.clear();
+        let mut serial = SUPPORTED[0].clone();
+        serial.quirks.serial_batches = true;
+        let device = Device::with_transport(mock.clone(), serial);
+        device.send_many(&reports).unwrap();
+        let full_delays = mock.sleeps().iter().filter(|d| **d == pre_send).count();
+        assert_eq!(full_delays, reports.len());
+    }
+
+    #[test]
+    fn test_send_sequence() {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        let data: Vec<u8> = (0..=200).map(|i| i as u8).collect();
+        // counts are overwritten, even if they were set
+        let mut frames = Packet::sequence(0x0f03, &data);
+        frames[0] = frames[0].clone().with_remaining_packets(7);
+
+        let responses = device.send_sequence(&frames).unwrap();
+        assert_eq!(responses.len(), 3);
+        let sent: Vec<Packet> = mock
+            .sent()
+            .iter()
+            .map(|bytes| Packet::try_from(bytes.as_slice()).unwrap())
+            .collect();
+        let remaining: Vec<u16> = sent.iter().map(Packet::remaining_packets).collect();
+        assert_eq!(remaining, [2, 1, 0]);
+        for ((response, sent), chunk) in responses.iter().zip(&sent).zip(data.chunks(80)) {
+            assert_eq!(response.remaining_packets(), sent.remaining_packets());
+            assert_eq!(response.get_args(), chunk);
+        }
+        assert!(device.send_sequence(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_send_sequence_stops_on_error() {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        mock.push_response(&[]);
+        mock.push_status(0x05); // second frame not supported
+        let frames = Packet::sequence(0x0f03, &[0; 240]);
+
+        assert!(matches!(
+            device.send_sequence(&frames),
+            Err(RazerError::CommandNotSupported)
+        ));
+        assert_eq!(mock.sent().len(), 2);
+    }
+
+    #[test]
+    fn test_short_reads() {
+        let mock = MockTransport::new();
+        // 4 argument bytes, so reads need 1 + 8 + 4 bytes
+        let report = || Packet::new(cmd::GET_PERF_MODE, &[0x00, 0x01, 0x02, 0x03]);
+        let mut lenient = SUPPORTED[0].clone();
+        lenient.quirks.lenient_short_reads = true;
+        let device = Device::with_transport(mock.clone(), lenient);
+
+        mock.push_short_read(13);
+        let response = device.send(report()).unwrap();
+        assert_eq!(response.get_args(), &[0x00, 0x01, 0x02, 0x03]);
+
+        mock.push_short_read(12);
+        assert!(matches!(
+            device.send(report()).map_err(RazerError::into_root),
+            Err(RazerError::InvalidDataSize {
+                expected: 91,
+                actual: 12
+            })
+        ));
+        mock.push_short_read(5);
+        assert!(device.send(report()).is_err());
+
+        // strict by default
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        mock.push_short_read(90);
+        assert!(matches!(
+            device.send(report()).map_err(RazerError::into_root),
+            Err(RazerError::InvalidDataSize {
+                expected: 91,
+                actual: 90
+            })
+        ));
+    }
+
+    #[test]
+    fn test_loose_remaining_packets_quirk() {
+        let mock = MockTransport::new();
+        let report = || Packet::new(cmd::GET_BATTERY_CARE, &[0x00]);
+        // declared by the 2023 Blades
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        mock.push_remaining_packets(1);
+        assert!(device.send(report()).is_ok());
+
+        let mut strict = SUPPORTED[0].clone();
+        strict.quirks = Quirks::NONE;
+        let device = Device::with_transport(mock.clone(), strict);
+        mock.push_remaining_packets(1);
+        assert!(matches!(
+            device.send(report()).map_err(RazerError::into_root),
+            Err(RazerError::UnexpectedResponse(_))
+        ));
+    }
+
+    #[test]
+    fn test_response_crc_quirk() {
+        let mock = MockTransport::new();
+        let report = || Packet::new(cmd::GET_PERF_MODE, &[0x00, 0x01]);
+        let mut strict = SUPPORTED[0].clone();
+        strict.quirks = Quirks::NONE;
+        let device = Device::with_transport(mock.clone(), strict);
+        assert!(device.send(report()).is_ok());
+        mock.push_bad_crc();
+        assert!(matches!(
+            device.send(report()).map_err(RazerError::into_root),
+            Err(RazerError::ResponseCrc { .. })
+        ));
+
+        // the 2023 Blades skip the check
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        mock.push_bad_crc();
+        assert!(device.send(report()).is_ok());
+    }
+
+    #[test]
+    fn test_fixed_transaction_id_quirk() {
+        let mock = MockTransport::new();
+        let mut descriptor = SUPPORTED[0].clone();
+        descriptor.quirks.fixed_transaction_id = Some(0x1f);
+        let device = Device::with_transport(mock.clone(), descriptor);
+        let response = device
+            .send(Packet::new(cmd::GET_PERF_MODE, &[0x00, 0x01]).with_transaction_id(0x42))
+            .unwrap();
+        assert_eq!(response.transaction_id(), 0x1f);
+        assert_eq!(mock.sent()[0][1], 0x1f);
+    }
+
+    #[test]
+    fn test_health_check_uses_fixed_transaction_id() {
+        let mock = MockTransport::new();
+        let mut descriptor = SUPPORTED[0].clone();
+        descriptor.quirks.fixed_transaction_id = Some(0x3f);
+        let device = Device::with_transport(mock.clone(), descriptor);
+        device.check_health().unwrap();
+        assert_eq!(mock.sent()[0][1], 0x3f);
+    }
+
+    #[test]
+    fn test_busy_once_then_success() {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+
+        mock.push_status(0x01);
+        mock.push_response(&[0xd0]);
+        assert_eq!(
+            command::get_battery_care(&device).unwrap(),
+            crate::types::BatteryCare::Enable
+        );
+        // read again, not re-sent
+        assert_eq!(mock.sent().len(), 1);
+        assert_eq!(mock.sleeps().last(), Some(&time::Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn test_busy_forever() {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+
+        for _ in 0..10 {
+            mock.push_status(0x01);
+        }
+        assert!(matches!(
+            command::get_battery_care(&device),
+            Err(RazerError::DeviceBusy)
+        ));
+        assert_eq!(mock.sent().len(), 1);
+        // pre-send and pre-read delays, then 1, 2, 4, 8 and the remaining 5ms
+        let busy_waits: Vec<_> = mock.sleeps()[2..].to_vec();
+        assert_eq!(
+            busy_waits,
+            [1, 2, 4, 8, 5].map(time::Duration::from_millis).to_vec()
+        );
+        assert_eq!(
+            busy_waits.iter().sum::<time::Duration>(),
+            SendOptions::DEFAULT.busy_budget
+        );
+    }
+
+    #[test]
+    fn test_enumerate_then_open_creates_hid_api_once() {
+        // the same calls `enumerate_supported`, `new_with_pid` and `enumerate`
+        // make through `with_hid_api`, against a context that counts its setup
+        let context = SharedContext::new();
+        let creates = std::cell::Cell::new(0);
+        let refreshes = std::cell::Cell::new(0);
+        let run = |rescan| {
+            context.with(
+                rescan,
+                || {
+                    creates.set(creates.get() + 1);
+                    Ok(())
+                },
+                |_| {
+                    refreshes.set(refreshes.get() + 1);
+                    Ok(())
+                },
+                |_| Ok(()),
+            )
+        };
+        run(true).unwrap();
+        run(false).unwrap();
+        run(true).unwrap();
+        assert_eq!(creates.get(), 1);
+        // only the second enumeration needs a fresh device list
+        assert_eq!(refreshes.get(), 1);
+    }
+
+    #[test]
+    fn test_failed_hid_api_creation_is_retried() {
+        let context = SharedContext::<()>::new();
+        let failed = context.with(
+            false,
+            || Err(RazerError::NoDevicesFound),
+            |_| Ok(()),
+            |_| Ok(()),
+        );
+        assert!(failed.is_err());
+        assert!(context
+            .with(false, || Ok(()), |_| Ok(()), |_| Ok(()))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_shared_device_across_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SharedDevice>();
+
+        let mock = MockTransport::new();
+        let shared = SharedDevice::new(Device::with_transport(mock.clone(), SUPPORTED[0].clone()));
+
+        let handles: Vec<_> = (0..4u8)
+            .map(|i| {
+                let shared = shared.clone();
+                std::thread::spawn(move || command::set_keyboard_brightness(&shared, i))
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+        assert_eq!(mock.sent().len(), 4);
+    }
+
+    #[test]
+    fn test_descriptor_for_unknown_pid_is_placeholder() {
+        let desc = Device::descriptor_for_pid(0xffff);
+        assert_eq!(desc.pid, 0xffff);
+        assert!(desc.features.is_empty());
+    }
 }
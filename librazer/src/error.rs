@@ -1,7 +1,187 @@
+use crate::permission::PermissionDetails;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::sync::Arc;
 use thiserror::Error;
 
+/// Environment variable that adds the start of the received packet to
+/// [`Mismatch`] messages.
+pub const DUMP_RESPONSES_ENV: &str = "RAZER_DUMP_RESPONSES";
+
+/// How a response packet differed from the report it should answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub sent_command: u16,
+    pub sent_id: u8,
+    pub sent_remaining: u16,
+    pub received_command: u16,
+    pub received_id: u8,
+    pub received_remaining: u16,
+    pub received_status: u8,
+    /// Leading bytes of the received packet, capped at [`Mismatch::DUMP_LEN`].
+    pub response: Vec<u8>,
+}
+
+impl Mismatch {
+    /// Number of response bytes kept for diagnostics.
+    pub const DUMP_LEN: usize = 32;
+
+    /// Returns the kept response bytes as space-separated hex.
+    pub fn response_hex(&self) -> String {
+        let bytes: Vec<String> = self.response.iter().map(|b| format!("{:02x}", b)).collect();
+        bytes.join(" ")
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sent 0x{:04x} (id 0x{:02x}, remaining {}), received 0x{:04x} (id 0x{:02x}, remaining {}, status 0x{:02x})",
+            self.sent_command,
+            self.sent_id,
+            self.sent_remaining,
+            self.received_command,
+            self.received_id,
+            self.received_remaining,
+            self.received_status
+        )?;
+        if std::env::var_os(DUMP_RESPONSES_ENV).is_some_and(|v| !v.is_empty() && v != "0") {
+            write!(f, "; response {}", self.response_hex())?;
+        }
+        Ok(())
+    }
+}
+
+/// Where a protocol error happened, see [`RazerError::context`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandContext {
+    /// Raw code of the command whose response failed, when one was sent.
+    pub command: Option<u16>,
+    /// Arguments the command was sent with.
+    pub args: Vec<u8>,
+    /// Library function that was running, like `get_cpu_boost`.
+    pub operation: Option<&'static str>,
+}
+
+impl fmt::Display for CommandContext {
+    /// Formats like `0x0d87 GET_BOOST [00 01 00] in get_cpu_boost`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(command) = self.command {
+            let args: Vec<String> = self.args.iter().map(|b| format!("{:02x}", b)).collect();
+            parts.push(format!(
+                "{} [{}]",
+                crate::command::CommandId::describe(command),
+                args.join(" ")
+            ));
+        }
+        if let Some(operation) = self.operation {
+            parts.push(format!("in {}", operation));
+        }
+        f.write_str(&parts.join(" "))
+    }
+}
+
+/// A setting change that satisfies a [`RazerError::ModeRequired`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Remedy {
+    PerfMode(crate::types::PerfMode),
+    FanMode(crate::types::FanMode),
+}
+
+impl fmt::Display for Remedy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Remedy::PerfMode(mode) => write!(f, "set the performance mode to {}", mode),
+            Remedy::FanMode(mode) => write!(f, "set the fan mode to {}", mode),
+        }
+    }
+}
+
+fn mode_required_message(
+    required: &(crate::types::PerfMode, Option<crate::types::FanMode>),
+    actual: &(crate::types::PerfMode, crate::types::FanMode),
+) -> String {
+    let mut message = format!("Performance mode must be {}", required.0);
+    if let Some(fan_mode) = required.1 {
+        message += &format!(" and fan mode must be {}", fan_mode);
+    }
+    message + &format!(", but is {} ({})", actual.0, actual.1)
+}
+
+/// Serializable summary of an error, for JSON output. [`RazerError`] serializes
+/// as this.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorReport {
+    /// [`ErrorCode`] number.
+    pub code: u8,
+    /// [`ErrorCode`] name, like `unsupported`.
+    pub kind: &'static str,
+    pub message: String,
+    /// Failed command, like `0x0d87 GET_BOOST`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Arguments the failed command was sent with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<u8>>,
+    /// Library function that was running, like `get_cpu_boost`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation: Option<&'static str>,
+    /// Feature that isn't supported, like `lid-logo`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feature: Option<String>,
+    /// Setting change that would let the command succeed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remedy: Option<Remedy>,
+    /// Why the device may not be opened.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permission: Option<PermissionDetails>,
+}
+
+impl ErrorReport {
+    /// Report with just the code and message.
+    pub fn new(code: ErrorCode, message: String) -> ErrorReport {
+        ErrorReport {
+            code: code.get(),
+            kind: code.kind(),
+            message,
+            command: None,
+            args: None,
+            operation: None,
+            feature: None,
+            remedy: None,
+            permission: None,
+        }
+    }
+}
+
+impl From<&RazerError> for ErrorReport {
+    fn from(error: &RazerError) -> ErrorReport {
+        let mut report = ErrorReport::new(error.code(), error.to_string());
+        if let Some(context) = error.context() {
+            report.command = context.command.map(crate::command::CommandId::describe);
+            report.args = context.command.map(|_| context.args.clone());
+            report.operation = context.operation;
+        }
+        match error.root() {
+            RazerError::FeatureNotSupported { feature, .. } => {
+                report.feature = Some(feature.to_string())
+            }
+            RazerError::ModeRequired { remedy, .. } => report.remedy = Some(*remedy),
+            RazerError::PermissionDenied(details) => report.permission = Some((**details).clone()),
+            _ => {}
+        }
+        report
+    }
+}
+
 /// Errors that can occur when communicating with Razer devices.
-#[derive(Error, Debug)]
+///
+/// Cloneable, so requests that share one command, e.g. writes coalesced by the
+/// [`DeviceWorker`](crate::worker::DeviceWorker), all get the same error.
+#[derive(Error, Debug, Clone)]
 pub enum RazerError {
     /// No Razer devices were found on the USB bus.
     #[error("No Razer devices found")]
@@ -15,6 +195,17 @@ pub enum RazerError {
     #[error("Device busy, try again")]
     DeviceBusy,
 
+    /// Another process kept the [device lock](crate::lock) longer than the wait.
+    #[error("Another process is using the device ({path} stayed locked for {}ms)", waited.as_millis())]
+    DeviceLocked {
+        path: String,
+        waited: std::time::Duration,
+    },
+
+    /// Sending now would exceed the device's [rate limit](crate::rate).
+    #[error("Too many commands, the rate limit allows another in {}ms", retry_after.as_millis())]
+    RateLimited { retry_after: std::time::Duration },
+
     /// The command failed to execute.
     #[error("Command failed")]
     CommandFailed,
@@ -27,10 +218,19 @@ pub enum RazerError {
     #[error("Command failed with unknown status: 0x{0:02X}")]
     UnknownStatus(u8),
 
-    /// The response packet does not match the sent command.
+    /// The response's CRC does not match its bytes.
+    #[error("Response CRC is 0x{actual:02x}, expected 0x{expected:02x}")]
+    ResponseCrc { expected: u8, actual: u8 },
+
+    /// The response's arguments do not echo the report.
     #[error("Response does not match the report")]
     ResponseMismatch,
 
+    /// The response packet's header does not match the sent report: another
+    /// command, transaction id or sequence position.
+    #[error("Response does not match the report: {0}")]
+    UnexpectedResponse(Box<Mismatch>),
+
     /// Failed to read the device model from the system.
     #[error("Failed to detect model: {0}")]
     ModelDetectionFailed(String),
@@ -40,8 +240,31 @@ pub enum RazerError {
     InvalidModel(String),
 
     /// The device model is not in the supported device list.
-    #[error("Model {model} with PIDs {pids:0>4x?} is not supported")]
-    UnsupportedModel { model: String, pids: Vec<u16> },
+    ///
+    /// `products` holds the USB product string reported for each PID, so a new
+    /// model can be told apart from a misread model number.
+    #[error(
+        "Model {model} with PIDs {pids:0>4x?} ({products:?}) is not supported. \
+         Please open an issue at https://github.com/stvnksslr/razer-ctl/issues \
+         and attach the enumeration report (`blade_helper doctor`)"
+    )]
+    UnsupportedModel {
+        model: String,
+        pids: Vec<u16>,
+        products: Vec<String>,
+    },
+
+    /// The model is not supported and the PIDs of several supported devices are
+    /// connected, so none of them can be picked by PID alone.
+    #[error(
+        "Model {model} is not supported and several supported devices are connected: \
+         {}. Select one explicitly",
+        candidates.join(", ")
+    )]
+    AmbiguousDevice {
+        model: String,
+        candidates: Vec<String>,
+    },
 
     /// Automatic model detection is not available on this platform.
     #[error("Automatic model detection is not implemented for this platform")]
@@ -51,26 +274,754 @@ pub enum RazerError {
     #[error("Failed to open device {name:?}: {reason}")]
     DeviceOpenFailed { name: String, reason: String },
 
+    /// No HID interface with the requested PID is connected.
+    #[error("No Razer device with PID {0:#06x} found")]
+    NoDeviceWithPid(u16),
+
+    /// The HID device exists but this user may not open it.
+    ///
+    /// The details say which node was refused and why, as far as could be told.
+    #[error(
+        "Permission denied accessing {}. {}",
+        .0.path.as_deref().unwrap_or("the Razer HID device"),
+        .0.advice()
+    )]
+    PermissionDenied(Box<PermissionDetails>),
+
+    /// The device was opened but rejected the probe feature report.
+    #[error("Device {name:?} refused feature report: {reason}")]
+    FeatureReportRejected { name: String, reason: String },
+
+    /// A device description given at runtime is not valid.
+    #[error("Invalid descriptor: {0}")]
+    InvalidDescriptor(String),
+
+    /// A descriptors file could not be read or describes an invalid device.
+    #[error("Invalid descriptors file {path}: {reason}")]
+    InvalidDescriptorFile { path: String, reason: String },
+
     /// Invalid value when converting from raw bytes.
     #[error("Failed to convert {value} to {type_name}")]
     InvalidValue { value: u8, type_name: &'static str },
 
+    /// A name that doesn't match any variant or alias, when parsing a string.
+    #[error("{name:?} is not a valid {type_name}")]
+    InvalidName {
+        name: String,
+        type_name: &'static str,
+    },
+
+    /// A color that isn't hex like `#44d62c` or a known name.
+    #[error("Invalid color {color:?}: {reason}")]
+    InvalidColor { color: String, reason: String },
+
+    /// A timeout that isn't whole seconds like `30s`, `5m` or `never`, or is too long.
+    #[error("Invalid timeout {timeout:?}: {reason}")]
+    InvalidTimeout { timeout: String, reason: String },
+
+    /// Command arguments don't fit in the 80 bytes of a packet.
+    #[error("Command arguments are {0} bytes, at most 80 fit in a packet")]
+    ArgumentsTooLong(usize),
+
     /// Invalid data size in packet or response.
     #[error("Invalid data size: expected {expected}, got {actual}")]
     InvalidDataSize { expected: usize, actual: usize },
 
-    /// USB HID communication error.
+    /// The device went away, e.g. after a re-plug or suspend/resume.
+    ///
+    /// Usually transient: [`Device::reconnect`](crate::device::Device::reconnect)
+    /// can re-open it once it re-enumerates.
+    #[error("Device disconnected")]
+    Disconnected,
+
+    /// [`Device::detect_with`](crate::device::Device::detect_with) ran past its timeout.
+    #[error("Device detection timed out after {0:?}")]
+    DetectTimeout(std::time::Duration),
+
+    /// The caller cancelled the operation.
+    #[error("Cancelled")]
+    Cancelled,
+
+    /// USB HID communication error, shared since `HidError` can't be cloned.
     #[error("HID error: {0}")]
-    Hid(#[from] hidapi::HidError),
+    Hid(#[source] Arc<hidapi::HidError>),
+
+    /// The device's descriptor does not declare this capability.
+    #[error("{feature} is not supported on {device}")]
+    FeatureNotSupported {
+        feature: &'static str,
+        device: String,
+    },
+
+    /// The device's descriptor does not list this boost level or performance mode.
+    #[error("{level} is not supported on {device}")]
+    UnsupportedLevel { level: String, device: String },
+
+    /// A fan speed outside the range the device accepts.
+    #[error("Fan speed {rpm} RPM is outside the range of {min}-{max} RPM")]
+    FanRpmOutOfRange { rpm: u16, min: u16, max: u16 },
+
+    /// Fan curve points that don't describe a curve.
+    #[error("Invalid fan curve: {0}")]
+    InvalidFanCurve(String),
+
+    /// The two thermal zones report different modes, usually after another tool
+    /// set only one of them. Setting the performance mode again resyncs them.
+    #[error(
+        "Modes do not match between zones: {} ({}) vs {} ({})",
+        zone1.0, zone1.1, zone2.0, zone2.1
+    )]
+    ZoneMismatch {
+        zone1: (crate::types::PerfMode, crate::types::FanMode),
+        zone2: (crate::types::PerfMode, crate::types::FanMode),
+    },
+
+    /// The device isn't in the modes the command needs. `required` has no fan mode
+    /// when any fits.
+    #[error("{}", mode_required_message(required, actual))]
+    ModeRequired {
+        required: (crate::types::PerfMode, Option<crate::types::FanMode>),
+        actual: (crate::types::PerfMode, crate::types::FanMode),
+        remedy: Remedy,
+    },
 
     /// Precondition not met for the command.
     #[error("{0}")]
     PreconditionFailed(String),
 
+    /// A service holding the device on the client's behalf, see
+    /// [`ipc`](crate::ipc), failed the request.
+    #[error("{message}")]
+    Remote { code: ErrorCode, message: String },
+
+    /// The connection to a service holding the device failed.
+    #[error("Service connection failed: {0}")]
+    ServiceFailed(String),
+
     /// Generic error for other cases.
     #[error("{0}")]
     Other(String),
+
+    /// A protocol error with the command it came from. Displays as the error
+    /// alone to keep messages short; see [`context`](RazerError::context) and
+    /// [`root`](RazerError::root).
+    #[error("{error}")]
+    WithContext {
+        error: Box<RazerError>,
+        context: Box<CommandContext>,
+    },
+}
+
+impl From<hidapi::HidError> for RazerError {
+    fn from(e: hidapi::HidError) -> Self {
+        RazerError::Hid(Arc::new(e))
+    }
+}
+
+/// Stable category of an error, for scripts that need more than success or
+/// failure. The numbers are `blade_helper`'s exit codes and never change meaning.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ErrorCode {
+    /// Anything not covered below, including invalid arguments.
+    Other = 1,
+    /// No device, or not the one asked for, is connected.
+    DeviceNotFound = 2,
+    /// The device exists but may not be opened by this user.
+    Permission = 3,
+    /// The model, feature or level isn't supported.
+    Unsupported = 4,
+    /// The device isn't in a state the command needs, like Custom mode.
+    Precondition = 5,
+    /// The device was busy or didn't answer in time.
+    Busy = 6,
+    /// The device answered with something that doesn't fit the protocol.
+    Protocol = 7,
+}
+
+impl ErrorCode {
+    /// Every code, in numeric order.
+    pub const ALL: [ErrorCode; 7] = [
+        ErrorCode::Other,
+        ErrorCode::DeviceNotFound,
+        ErrorCode::Permission,
+        ErrorCode::Unsupported,
+        ErrorCode::Precondition,
+        ErrorCode::Busy,
+        ErrorCode::Protocol,
+    ];
+
+    /// The code numbered `code`, if there is one.
+    pub fn from_u8(code: u8) -> Option<ErrorCode> {
+        ErrorCode::ALL.into_iter().find(|c| c.get() == code)
+    }
+
+    /// The numeric code, also used as the process exit code.
+    pub const fn get(self) -> u8 {
+        self as u8
+    }
+
+    /// Name of the category, like `device-not-found`.
+    pub const fn kind(self) -> &'static str {
+        match self {
+            ErrorCode::Other => "other",
+            ErrorCode::DeviceNotFound => "device-not-found",
+            ErrorCode::Permission => "permission",
+            ErrorCode::Unsupported => "unsupported",
+            ErrorCode::Precondition => "precondition",
+            ErrorCode::Busy => "busy",
+            ErrorCode::Protocol => "protocol",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.kind())
+    }
+}
+
+impl RazerError {
+    /// A [`PermissionDenied`](RazerError::PermissionDenied) for `path`, with
+    /// [diagnosed](PermissionDetails::diagnose) details.
+    pub fn permission_denied(path: Option<String>) -> RazerError {
+        RazerError::PermissionDenied(Box::new(PermissionDetails::diagnose(path)))
+    }
+
+    /// Category of the error, see [`ErrorCode`].
+    pub fn code(&self) -> ErrorCode {
+        use RazerError::*;
+        match self {
+            WithContext { error, .. } => error.code(),
+            Remote { code, .. } => *code,
+            NoDevicesFound
+            | NoDeviceWithPid(_)
+            | Disconnected
+            | DetectTimeout(_)
+            | DeviceOpenFailed { .. }
+            | AmbiguousDevice { .. } => ErrorCode::DeviceNotFound,
+            PermissionDenied { .. } => ErrorCode::Permission,
+            CommandNotSupported
+            | UnsupportedModel { .. }
+            | InvalidModel(_)
+            | UnsupportedPlatform
+            | FeatureNotSupported { .. }
+            | UnsupportedLevel { .. } => ErrorCode::Unsupported,
+            PreconditionFailed(_) | ModeRequired { .. } | ZoneMismatch { .. } => {
+                ErrorCode::Precondition
+            }
+            DeviceBusy | CommandTimeout | DeviceLocked { .. } | RateLimited { .. } => {
+                ErrorCode::Busy
+            }
+            CommandFailed
+            | UnknownStatus(_)
+            | ResponseCrc { .. }
+            | ResponseMismatch
+            | UnexpectedResponse(_)
+            | FeatureReportRejected { .. }
+            | InvalidValue { .. }
+            | InvalidDataSize { .. }
+            | Hid(_) => ErrorCode::Protocol,
+            ModelDetectionFailed(_)
+            | InvalidDescriptor(_)
+            | InvalidDescriptorFile { .. }
+            | InvalidName { .. }
+            | InvalidColor { .. }
+            | InvalidTimeout { .. }
+            | ArgumentsTooLong(_)
+            | Cancelled
+            | FanRpmOutOfRange { .. }
+            | InvalidFanCurve(_)
+            | ServiceFailed(_)
+            | Other(_) => ErrorCode::Other,
+        }
+    }
+
+    /// Whether sending the same command again may succeed: the device was busy,
+    /// timed out or dropped off the bus, or a HID transfer failed for a reason other
+    /// than permissions.
+    pub fn is_retryable(&self) -> bool {
+        use hidapi::HidError;
+        use RazerError::*;
+        match self {
+            WithContext { error, .. } => error.is_retryable(),
+            Remote { code, .. } => *code == ErrorCode::Busy,
+            DeviceBusy
+            | CommandTimeout
+            | DeviceLocked { .. }
+            | RateLimited { .. }
+            | Disconnected => true,
+            Hid(e) => {
+                matches!(
+                    **e,
+                    HidError::HidApiError { .. }
+                        | HidError::HidApiErrorEmpty
+                        | HidError::IncompleteSendError { .. }
+                        | HidError::IoError { .. }
+                ) && !crate::transport::is_permission_error(e)
+            }
+            NoDevicesFound
+            | CommandNotSupported
+            | CommandFailed
+            | UnknownStatus(_)
+            | ResponseCrc { .. }
+            | ResponseMismatch
+            | UnexpectedResponse(_)
+            | ModelDetectionFailed(_)
+            | InvalidModel(_)
+            | UnsupportedModel { .. }
+            | AmbiguousDevice { .. }
+            | UnsupportedPlatform
+            | DeviceOpenFailed { .. }
+            | NoDeviceWithPid(_)
+            | PermissionDenied { .. }
+            | FeatureReportRejected { .. }
+            | InvalidDescriptor(_)
+            | InvalidDescriptorFile { .. }
+            | InvalidValue { .. }
+            | InvalidName { .. }
+            | InvalidColor { .. }
+            | InvalidTimeout { .. }
+            | ArgumentsTooLong(_)
+            | InvalidDataSize { .. }
+            | DetectTimeout(_)
+            | Cancelled
+            | FeatureNotSupported { .. }
+            | UnsupportedLevel { .. }
+            | FanRpmOutOfRange { .. }
+            | InvalidFanCurve(_)
+            | ZoneMismatch { .. }
+            | ModeRequired { .. }
+            | PreconditionFailed(_)
+            | ServiceFailed(_)
+            | Other(_) => false,
+        }
+    }
+
+    /// Whether the device was refused to this user, so installing the udev rules
+    /// or running elevated would help.
+    pub fn is_permission(&self) -> bool {
+        match self {
+            RazerError::WithContext { error, .. } => error.is_permission(),
+            RazerError::PermissionDenied { .. } => true,
+            RazerError::Hid(e) => crate::transport::is_permission_error(e),
+            _ => false,
+        }
+    }
+
+    /// The command and operation a protocol error came from, if known.
+    pub fn context(&self) -> Option<&CommandContext> {
+        match self {
+            RazerError::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The error without its [`context`](RazerError::context), for matching.
+    pub fn root(&self) -> &RazerError {
+        match self {
+            RazerError::WithContext { error, .. } => error,
+            error => error,
+        }
+    }
+
+    /// Owned [`root`](RazerError::root).
+    pub fn into_root(self) -> RazerError {
+        match self {
+            RazerError::WithContext { error, .. } => *error,
+            error => error,
+        }
+    }
+
+    /// Records the command a protocol error answered. Other errors, and errors
+    /// that already name a command, are returned as they are.
+    pub fn with_command_context(self, command: u16, args: &[u8]) -> RazerError {
+        self.map_context(|context| {
+            if context.command.is_none() {
+                context.command = Some(command);
+                context.args = args.to_vec();
+            }
+        })
+    }
+
+    /// Records the library function a protocol error happened in, keeping the
+    /// innermost one when operations nest.
+    pub fn in_operation(self, operation: &'static str) -> RazerError {
+        self.map_context(|context| {
+            context.operation.get_or_insert(operation);
+        })
+    }
+
+    fn map_context(self, update: impl FnOnce(&mut CommandContext)) -> RazerError {
+        match self {
+            RazerError::WithContext { error, mut context } => {
+                update(&mut context);
+                RazerError::WithContext { error, context }
+            }
+            error if error.code() == ErrorCode::Protocol => {
+                let mut context = Box::default();
+                update(&mut context);
+                RazerError::WithContext {
+                    error: Box::new(error),
+                    context,
+                }
+            }
+            error => error,
+        }
+    }
+}
+
+impl Serialize for RazerError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        ErrorReport::from(self).serialize(serializer)
+    }
 }
 
 /// Result type alias using [`RazerError`].
 pub type Result<T> = std::result::Result<T, RazerError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes_are_stable() {
+        // blade_helper exits with these, so scripts depend on the numbers
+        let codes: Vec<(u8, &str)> = ErrorCode::ALL.iter().map(|c| (c.get(), c.kind())).collect();
+        assert_eq!(
+            codes,
+            [
+                (1, "other"),
+                (2, "device-not-found"),
+                (3, "permission"),
+                (4, "unsupported"),
+                (5, "precondition"),
+                (6, "busy"),
+                (7, "protocol"),
+            ]
+        );
+        assert_eq!(ErrorCode::from_u8(5), Some(ErrorCode::Precondition));
+        assert_eq!(ErrorCode::from_u8(0), None);
+        assert_eq!(RazerError::NoDevicesFound.code(), ErrorCode::DeviceNotFound);
+        assert_eq!(
+            RazerError::PermissionDenied(Default::default()).code(),
+            ErrorCode::Permission
+        );
+        assert_eq!(
+            RazerError::CommandNotSupported.code(),
+            ErrorCode::Unsupported
+        );
+        assert_eq!(
+            RazerError::PreconditionFailed(String::new()).code(),
+            ErrorCode::Precondition
+        );
+        assert_eq!(RazerError::CommandTimeout.code(), ErrorCode::Busy);
+        assert_eq!(RazerError::ResponseMismatch.code(), ErrorCode::Protocol);
+    }
+
+    /// One of every variant, with its code and whether it's retryable. A new
+    /// variant fails to compile in `code` and `is_retryable` until it's classified;
+    /// add it here too.
+    fn every_variant() -> Vec<(RazerError, ErrorCode, bool)> {
+        use crate::types::{FanMode, PerfMode};
+        use ErrorCode::*;
+        use RazerError as E;
+        let text = String::new;
+        let mismatch = Mismatch {
+            sent_command: 0,
+            sent_id: 0,
+            sent_remaining: 0,
+            received_command: 0,
+            received_id: 0,
+            received_remaining: 0,
+            received_status: 0,
+            response: Vec::new(),
+        };
+        let modes = (PerfMode::Balanced, FanMode::Auto);
+        let io = |kind| hidapi::HidError::IoError {
+            error: std::io::Error::from(kind),
+        };
+        vec![
+            (E::NoDevicesFound, DeviceNotFound, false),
+            (E::CommandNotSupported, Unsupported, false),
+            (E::DeviceBusy, Busy, true),
+            (E::CommandFailed, Protocol, false),
+            (E::CommandTimeout, Busy, true),
+            (
+                E::DeviceLocked {
+                    path: "/run/user/1000/razer-ctl.lock".into(),
+                    waited: std::time::Duration::from_secs(2),
+                },
+                Busy,
+                true,
+            ),
+            (
+                E::RateLimited {
+                    retry_after: std::time::Duration::from_millis(100),
+                },
+                Busy,
+                true,
+            ),
+            (E::UnknownStatus(0x42), Protocol, false),
+            (
+                E::ResponseCrc {
+                    expected: 0,
+                    actual: 1,
+                },
+                Protocol,
+                false,
+            ),
+            (E::ResponseMismatch, Protocol, false),
+            (E::UnexpectedResponse(Box::new(mismatch)), Protocol, false),
+            (E::ModelDetectionFailed(text()), Other, false),
+            (E::InvalidModel(text()), Unsupported, false),
+            (
+                E::UnsupportedModel {
+                    model: text(),
+                    pids: Vec::new(),
+                    products: Vec::new(),
+                },
+                Unsupported,
+                false,
+            ),
+            (
+                E::AmbiguousDevice {
+                    model: text(),
+                    candidates: Vec::new(),
+                },
+                DeviceNotFound,
+                false,
+            ),
+            (E::UnsupportedPlatform, Unsupported, false),
+            (
+                E::DeviceOpenFailed {
+                    name: text(),
+                    reason: text(),
+                },
+                DeviceNotFound,
+                false,
+            ),
+            (E::NoDeviceWithPid(0x029f), DeviceNotFound, false),
+            (E::PermissionDenied(Default::default()), Permission, false),
+            (
+                E::FeatureReportRejected {
+                    name: text(),
+                    reason: text(),
+                },
+                Protocol,
+                false,
+            ),
+            (E::InvalidDescriptor(text()), Other, false),
+            (
+                E::InvalidDescriptorFile {
+                    path: text(),
+                    reason: text(),
+                },
+                Other,
+                false,
+            ),
+            (
+                E::InvalidValue {
+                    value: 0,
+                    type_name: "",
+                },
+                Protocol,
+                false,
+            ),
+            (
+                E::InvalidName {
+                    name: text(),
+                    type_name: "",
+                },
+                Other,
+                false,
+            ),
+            (
+                E::InvalidColor {
+                    color: text(),
+                    reason: text(),
+                },
+                Other,
+                false,
+            ),
+            (
+                E::InvalidTimeout {
+                    timeout: text(),
+                    reason: text(),
+                },
+                Other,
+                false,
+            ),
+            (E::ArgumentsTooLong(81), Other, false),
+            (
+                E::InvalidDataSize {
+                    expected: 0,
+                    actual: 1,
+                },
+                Protocol,
+                false,
+            ),
+            (E::Disconnected, DeviceNotFound, true),
+            (
+                E::DetectTimeout(std::time::Duration::ZERO),
+                DeviceNotFound,
+                false,
+            ),
+            (E::Cancelled, Other, false),
+            (
+                hidapi::HidError::HidApiError { message: text() }.into(),
+                Protocol,
+                true,
+            ),
+            (io(std::io::ErrorKind::BrokenPipe).into(), Protocol, true),
+            (
+                io(std::io::ErrorKind::PermissionDenied).into(),
+                Protocol,
+                false,
+            ),
+            (
+                hidapi::HidError::InitializationError.into(),
+                Protocol,
+                false,
+            ),
+            (
+                E::FeatureNotSupported {
+                    feature: "",
+                    device: text(),
+                },
+                Unsupported,
+                false,
+            ),
+            (
+                E::UnsupportedLevel {
+                    level: text(),
+                    device: text(),
+                },
+                Unsupported,
+                false,
+            ),
+            (
+                E::FanRpmOutOfRange {
+                    rpm: 0,
+                    min: 0,
+                    max: 0,
+                },
+                Other,
+                false,
+            ),
+            (E::InvalidFanCurve(text()), Other, false),
+            (
+                E::ZoneMismatch {
+                    zone1: modes,
+                    zone2: modes,
+                },
+                Precondition,
+                false,
+            ),
+            (
+                E::ModeRequired {
+                    required: (PerfMode::Custom, None),
+                    actual: modes,
+                    remedy: Remedy::PerfMode(PerfMode::Custom),
+                },
+                Precondition,
+                false,
+            ),
+            (E::PreconditionFailed(text()), Precondition, false),
+            (
+                E::Remote {
+                    code: Busy,
+                    message: text(),
+                },
+                Busy,
+                true,
+            ),
+            (E::ServiceFailed(text()), Other, false),
+            (E::Other(text()), Other, false),
+            (
+                E::DeviceBusy.with_command_context(0x0d87, &[0, 1, 0]),
+                Busy,
+                true,
+            ),
+            (
+                E::CommandFailed.with_command_context(0x0d87, &[0, 1, 0]),
+                Protocol,
+                false,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_command_context() {
+        let error = RazerError::CommandFailed
+            .with_command_context(0x0d87, &[0x00, 0x01, 0x00])
+            .in_operation("get_cpu_boost")
+            .in_operation("get_boosts");
+        assert_eq!(error.to_string(), "Command failed");
+        assert!(matches!(error.root(), RazerError::CommandFailed));
+        assert_eq!(
+            error.context().unwrap().to_string(),
+            "0x0d87 GET_BOOST [00 01 00] in get_cpu_boost"
+        );
+
+        // only protocol errors carry context
+        let busy = RazerError::DeviceBusy.with_command_context(0x0d87, &[]);
+        assert!(busy.context().is_none());
+        let mismatch = RazerError::ResponseMismatch.in_operation("get_fan_rpm");
+        assert_eq!(mismatch.context().unwrap().to_string(), "in get_fan_rpm");
+    }
+
+    #[test]
+    fn test_error_json() {
+        let error = RazerError::CommandFailed
+            .with_command_context(0x0d87, &[0x00, 0x01, 0x00])
+            .in_operation("get_cpu_boost");
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "code": 7,
+                "kind": "protocol",
+                "message": "Command failed",
+                "command": "0x0d87 GET_BOOST",
+                "args": [0, 1, 0],
+                "operation": "get_cpu_boost",
+            })
+        );
+        let unsupported = RazerError::FeatureNotSupported {
+            feature: "lid-logo",
+            device: "Blade".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&unsupported).unwrap(),
+            serde_json::json!({
+                "code": 4,
+                "kind": "unsupported",
+                "message": "lid-logo is not supported on Blade",
+                "feature": "lid-logo",
+            })
+        );
+        let denied = RazerError::PermissionDenied(Box::new(PermissionDetails {
+            mode: Some(0o600),
+            ..PermissionDetails::new(Some("/dev/hidraw3".to_string()))
+        }));
+        assert_eq!(
+            serde_json::to_value(&denied).unwrap()["permission"],
+            serde_json::json!({ "path": "/dev/hidraw3", "mode": 0o600 })
+        );
+    }
+
+    #[test]
+    fn test_every_variant_is_classified() {
+        for (error, code, retryable) in every_variant() {
+            assert_eq!(error.code(), code, "{:?}", error);
+            assert_eq!(error.is_retryable(), retryable, "{:?}", error);
+            assert_eq!(
+                error.is_permission(),
+                code == ErrorCode::Permission
+                    || matches!(&error, RazerError::Hid(e) if matches!(&**e, hidapi::HidError::IoError { error }
+                        if error.kind() == std::io::ErrorKind::PermissionDenied)),
+                "{:?}",
+                error
+            );
+        }
+    }
+}
@@ -15,6 +15,9 @@ pub const KBDBACKLIGHT: &str = "kbd-backlight";
 pub const FAN: &str = "fan";
 /// Feature name for performance mode control
 pub const PERF: &str = "perf";
+/// Feature name for per-key keyboard lighting. Declared by the descriptor's
+/// `lighting` capability rather than its features, so not in [`ALL_FEATURES`].
+pub const PER_KEY: &str = "per-key";
 
 /// All valid feature names for compile-time validation
 pub const ALL_FEATURES: &[&str] = &[
@@ -38,7 +41,7 @@ macro_rules! const_for {
     };
 }
 
-const fn contains(array: &[&str], value: &str) -> bool {
+pub(crate) const fn contains(array: &[&str], value: &str) -> bool {
     const_for! { it in array => {
         if const_str::equal!(*it, value) {
             return true;
@@ -0,0 +1,59 @@
+//! `tracing` spans and events, enabled by the `tracing` feature.
+//!
+//! Every send runs in a DEBUG `send` span with the fields `command`, `data_size`,
+//! `status` (or `error`) and `latency_us`, nested in a span named after the
+//! [`command`](crate::command) function that sent it. Each response read also
+//! emits a TRACE event on [`EXCHANGE_TARGET`] with the raw report and response,
+//! which [`Recorder`](crate::replay::Recorder) turns into a recording when used
+//! as a layer. The `log` macros are unaffected.
+
+use crate::command::CommandId;
+use crate::error::Result;
+use crate::packet::Packet;
+use crate::replay::to_hex;
+use std::time::Duration;
+use tracing::field::Empty;
+use tracing::Span;
+
+/// Target of the events carrying each exchange with the firmware.
+pub const EXCHANGE_TARGET: &str = "librazer::exchange";
+
+/// Opens the span of one send, completed by [`record_outcome`].
+pub(crate) fn send_span(report: &Packet) -> Span {
+    tracing::debug_span!(
+        "send",
+        command = %CommandId::describe(report.command()),
+        data_size = report.data_size(),
+        status = Empty,
+        error = Empty,
+        latency_us = Empty,
+    )
+}
+
+/// Fills in the response status, or the error, and how long the send took.
+pub(crate) fn record_outcome(span: &Span, result: &Result<Packet>, elapsed: Duration) {
+    match result {
+        Ok(response) => span.record("status", response.status()),
+        Err(e) => span.record("error", tracing::field::display(e)),
+    };
+    span.record("latency_us", elapsed.as_micros() as u64);
+}
+
+/// Emits the exchange event for `report` and the raw `response`, both without
+/// the report id byte.
+pub(crate) fn exchange(report: &Packet, response: std::result::Result<&[u8], String>) {
+    let mut bytes = [0; Packet::SIZE];
+    report.to_bytes(&mut bytes);
+    match response {
+        Ok(response) => tracing::trace!(
+            target: EXCHANGE_TARGET,
+            report = %to_hex(&bytes),
+            response = %to_hex(response),
+        ),
+        Err(error) => tracing::trace!(
+            target: EXCHANGE_TARGET,
+            report = %to_hex(&bytes),
+            error = %error,
+        ),
+    }
+}
@@ -0,0 +1,216 @@
+//! Length-prefixed JSON protocol between a service that holds the device and
+//! unprivileged clients, like the Windows named-pipe service.
+//!
+//! Every message is a little-endian `u32` byte count followed by that many bytes
+//! of JSON. The client writes a [`Request`] and reads one [`Response`], as often
+//! as it likes on one connection.
+
+use crate::device::{CommandTarget, SharedDevice};
+use crate::error::{ErrorCode, RazerError, Result};
+use crate::state::DeviceState;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+
+/// Named pipe the Windows service listens on.
+pub const PIPE_NAME: &str = r"\\.\pipe\razer-ctl";
+
+/// Longest message accepted, far above any real one.
+pub const MAX_MESSAGE_LEN: u32 = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Request {
+    /// Reads every setting.
+    Status,
+    /// Writes the settings that are set, see [`DeviceState::apply_to`].
+    Apply { profile: DeviceState },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Response {
+    /// Answers [`Request::Status`].
+    Status(Status),
+    /// Answers a [`Request::Apply`] that succeeded.
+    Applied,
+    /// The request failed; `code` is an [`ErrorCode`] number.
+    Error { code: u8, message: String },
+}
+
+/// The device and its settings, as the service sees them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    pub name: String,
+    /// Model number prefix, like `RZ09-0483`.
+    pub model: String,
+    pub state: DeviceState,
+}
+
+/// Answers `request` using `device`.
+pub fn handle(device: &impl CommandTarget, request: Request) -> Response {
+    let result = match request {
+        Request::Status => {
+            let info = device.info();
+            Ok(Response::Status(Status {
+                name: info.name.to_string(),
+                model: info.model_number_prefix().to_string(),
+                state: DeviceState::read_from(device, info.features),
+            }))
+        }
+        Request::Apply { profile } => profile.apply_to(device).map(|()| Response::Applied),
+    };
+    result.unwrap_or_else(|e| Response::Error {
+        code: e.code().get(),
+        message: e.to_string(),
+    })
+}
+
+/// Answers requests from `stream` until the client hangs up. A malformed
+/// message ends the connection with an error.
+///
+/// The device is locked for each request, so requests from several clients
+/// don't interleave their commands.
+pub fn serve(device: &SharedDevice, mut stream: impl Read + Write) -> io::Result<()> {
+    loop {
+        let request = match read_message(&mut stream) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            request => request?,
+        };
+        let response = handle(&*device.lock(), request);
+        write_message(&mut stream, &response)?;
+    }
+}
+
+/// Frames `message` as it is sent: the length, then the JSON.
+pub fn encode(message: &impl Serialize) -> io::Result<Vec<u8>> {
+    let json = serde_json::to_vec(message)?;
+    let len = u32::try_from(json.len())
+        .ok()
+        .filter(|&len| len <= MAX_MESSAGE_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "message too long"))?;
+    let mut frame = len.to_le_bytes().to_vec();
+    frame.extend(json);
+    Ok(frame)
+}
+
+/// Parses the JSON of a message whose length prefix has been read.
+pub fn decode<T: DeserializeOwned>(json: &[u8]) -> io::Result<T> {
+    Ok(serde_json::from_slice(json)?)
+}
+
+/// Checks a length prefix against [`MAX_MESSAGE_LEN`].
+pub fn check_len(len: u32) -> io::Result<usize> {
+    if len > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message of {} bytes is too long", len),
+        ));
+    }
+    Ok(len as usize)
+}
+
+pub fn write_message(stream: &mut impl Write, message: &impl Serialize) -> io::Result<()> {
+    stream.write_all(&encode(message)?)?;
+    stream.flush()
+}
+
+pub fn read_message<T: DeserializeOwned>(stream: &mut impl Read) -> io::Result<T> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len)?;
+    let mut json = vec![0; check_len(u32::from_le_bytes(len))?];
+    stream.read_exact(&mut json)?;
+    decode(&json)
+}
+
+/// Client side of a connection to the service.
+pub struct Client<S> {
+    stream: S,
+}
+
+impl<S: Read + Write> Client<S> {
+    pub fn new(stream: S) -> Self {
+        Client { stream }
+    }
+
+    /// Reads every setting through the service.
+    pub fn status(&mut self) -> Result<Status> {
+        match self.request(&Request::Status)? {
+            Response::Status(status) => Ok(status),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Writes the settings in `profile` that are set, through the service.
+    pub fn apply(&mut self, profile: &DeviceState) -> Result<()> {
+        let request = Request::Apply {
+            profile: profile.clone(),
+        };
+        match self.request(&request)? {
+            Response::Applied => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn request(&mut self, request: &Request) -> Result<Response> {
+        let service_failed = |e: io::Error| RazerError::ServiceFailed(e.to_string());
+        write_message(&mut self.stream, request).map_err(service_failed)?;
+        match read_message(&mut self.stream).map_err(service_failed)? {
+            Response::Error { code, message } => Err(RazerError::Remote {
+                code: ErrorCode::from_u8(code).unwrap_or(ErrorCode::Other),
+                message,
+            }),
+            response => Ok(response),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Client<std::fs::File> {
+    /// Connects to the service at [`PIPE_NAME`]. Fails with
+    /// [`RazerError::ServiceFailed`] when it isn't running.
+    pub fn connect() -> Result<Self> {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(PIPE_NAME)
+            .map(Client::new)
+            .map_err(|e| RazerError::ServiceFailed(e.to_string()))
+    }
+}
+
+fn unexpected(response: Response) -> RazerError {
+    RazerError::ServiceFailed(format!("Unexpected response {:?}", response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PerfMode;
+
+    #[test]
+    fn test_message_format() {
+        let frame = encode(&Request::Status).unwrap();
+        assert_eq!(&frame[..4], &[17, 0, 0, 0]);
+        assert_eq!(&frame[4..], br#"{"type":"status"}"#);
+
+        let profile = DeviceState {
+            perf_mode: Some(PerfMode::Silent),
+            ..Default::default()
+        };
+        let request = Request::Apply { profile };
+        let frame = encode(&request).unwrap();
+        assert_eq!(
+            read_message::<Request>(&mut frame.as_slice()).unwrap(),
+            request
+        );
+    }
+
+    #[test]
+    fn test_rejects_long_messages() {
+        let mut frame = (MAX_MESSAGE_LEN + 1).to_le_bytes().to_vec();
+        frame.extend([b' '; 16]);
+        let error = read_message::<Request>(&mut frame.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,309 @@
+//! One laptop's settings, gated on what its model supports.
+//!
+//! The functions in [`command`] send whatever they are given, so calling a
+//! setter the model lacks costs an exchange and an EC error at best. A
+//! [`RazerLaptop`] wraps a [`Device`] and checks the descriptor's features, and
+//! the levels and fan speeds it lists, before sending anything, failing with
+//! [`RazerError::FeatureNotSupported`] and the like locally.
+//!
+//! [`RazerError::FeatureNotSupported`]: crate::error::RazerError::FeatureNotSupported
+
+use crate::command;
+use crate::descriptor::Descriptor;
+use crate::device::{CommandTarget, Device};
+use crate::error::Result;
+use crate::feature;
+use crate::packet::Packet;
+use crate::persist::FieldOutcome;
+use crate::profile::Profile;
+use crate::state::{DeviceState, Field, PartialState};
+use crate::types::{
+    BatteryCare, Brightness, ChargeLimit, CpuBoost, FanMode, FanRpm, FanZone, GpuBoost,
+    LightsAlwaysOn, LogoMode, MaxFanSpeedMode, MaybeKnown, PerfMode,
+};
+
+/// A [`Device`] whose settings are checked against its descriptor.
+pub struct RazerLaptop {
+    device: Device,
+}
+
+impl RazerLaptop {
+    pub fn new(device: Device) -> RazerLaptop {
+        RazerLaptop { device }
+    }
+
+    /// Detects the connected laptop, see [`Device::detect`].
+    pub fn detect() -> Result<RazerLaptop> {
+        Device::detect().map(RazerLaptop::new)
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    /// Returns the device, e.g. to change its send options or lock.
+    pub fn device_mut(&mut self) -> &mut Device {
+        &mut self.device
+    }
+
+    pub fn into_device(self) -> Device {
+        self.device
+    }
+
+    pub fn info(&self) -> &Descriptor {
+        self.device.info()
+    }
+
+    pub fn features(&self) -> &[&'static str] {
+        self.info().features
+    }
+
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features().contains(&feature)
+    }
+
+    /// Runs `f` if the model has `feature`.
+    fn gated<T>(&self, feature: &'static str, f: impl FnOnce(&Device) -> Result<T>) -> Result<T> {
+        self.device.require(feature)?;
+        f(&self.device)
+    }
+
+    /// Reads the performance mode and the fan mode that goes with it.
+    pub fn perf_mode(&self) -> Result<(PerfMode, FanMode)> {
+        self.gated(feature::PERF, command::get_perf_mode)
+    }
+
+    pub fn set_perf_mode(&self, mode: PerfMode) -> Result<()> {
+        self.gated(feature::PERF, |device| {
+            device.info().check_perf_mode(mode)?;
+            command::set_perf_mode(device, mode)
+        })
+    }
+
+    pub fn cpu_boost(&self) -> Result<MaybeKnown<CpuBoost>> {
+        self.gated(feature::PERF, command::get_cpu_boost)
+    }
+
+    pub fn set_cpu_boost(&self, boost: CpuBoost) -> Result<()> {
+        self.gated(feature::PERF, |device| {
+            device.info().check_cpu_boost(boost)?;
+            command::set_cpu_boost(device, boost)
+        })
+    }
+
+    pub fn gpu_boost(&self) -> Result<MaybeKnown<GpuBoost>> {
+        self.gated(feature::PERF, command::get_gpu_boost)
+    }
+
+    pub fn set_gpu_boost(&self, boost: GpuBoost) -> Result<()> {
+        self.gated(feature::PERF, |device| {
+            device.info().check_gpu_boost(boost)?;
+            command::set_gpu_boost(device, boost)
+        })
+    }
+
+    pub fn fan_mode(&self) -> Result<FanMode> {
+        self.gated(feature::FAN, |device| {
+            command::get_perf_mode(device).map(|(_, mode)| mode)
+        })
+    }
+
+    pub fn set_fan_mode(&self, mode: FanMode) -> Result<()> {
+        self.gated(feature::FAN, |device| command::set_fan_mode(device, mode))
+    }
+
+    /// Reads the speed of the first fan, what it runs at in any fan mode.
+    pub fn fan_rpm(&self) -> Result<FanRpm> {
+        self.gated(feature::FAN, |device| {
+            command::get_fan_rpm(device, FanZone::Zone1)
+        })
+    }
+
+    /// Sets the manual fan speed, failing unless it is in the model's range.
+    pub fn set_fan_rpm(&self, rpm: FanRpm) -> Result<()> {
+        self.gated(feature::FAN, |device| {
+            device.info().check_fan_rpm(rpm)?;
+            command::set_fan_rpm(device, rpm)
+        })
+    }
+
+    pub fn max_fan_speed(&self) -> Result<MaxFanSpeedMode> {
+        self.gated(feature::FAN, command::get_max_fan_speed_mode)
+    }
+
+    pub fn set_max_fan_speed(&self, mode: MaxFanSpeedMode) -> Result<()> {
+        self.gated(feature::FAN, |device| {
+            command::set_max_fan_speed_mode(device, mode)
+        })
+    }
+
+    pub fn keyboard_brightness(&self) -> Result<Brightness> {
+        self.gated(feature::KBDBACKLIGHT, command::get_keyboard_brightness)
+    }
+
+    pub fn set_keyboard_brightness(&self, brightness: impl Into<Brightness>) -> Result<()> {
+        self.gated(feature::KBDBACKLIGHT, |device| {
+            command::set_keyboard_brightness(device, brightness)
+        })
+    }
+
+    pub fn logo_mode(&self) -> Result<LogoMode> {
+        self.gated(feature::LIDLOGO, command::get_logo_mode)
+    }
+
+    pub fn set_logo_mode(&self, mode: LogoMode) -> Result<()> {
+        self.gated(feature::LIDLOGO, |device| {
+            command::set_logo_mode(device, mode)
+        })
+    }
+
+    pub fn battery_care(&self) -> Result<BatteryCare> {
+        self.gated(feature::BATTERYCARE, command::get_battery_care)
+    }
+
+    pub fn set_battery_care(&self, care: BatteryCare) -> Result<()> {
+        self.gated(feature::BATTERYCARE, |device| {
+            command::set_battery_care(device, care)
+        })
+    }
+
+    pub fn charge_limit(&self) -> Result<ChargeLimit> {
+        self.gated(feature::BATTERYCARE, command::get_charge_limit)
+    }
+
+    pub fn set_charge_limit(&self, limit: ChargeLimit) -> Result<()> {
+        self.gated(feature::BATTERYCARE, |device| {
+            command::set_charge_limit(device, limit)
+        })
+    }
+
+    pub fn lights_always_on(&self) -> Result<LightsAlwaysOn> {
+        self.gated(feature::LIGHTSALWAYSON, command::get_lights_always_on)
+    }
+
+    pub fn set_lights_always_on(&self, lights: LightsAlwaysOn) -> Result<()> {
+        self.gated(feature::LIGHTSALWAYSON, |device| {
+            command::set_lights_always_on(device, lights)
+        })
+    }
+
+    /// Reads every setting the model supports.
+    pub fn snapshot(&self) -> DeviceState {
+        DeviceState::read_from(&self.device, self.features())
+    }
+
+    /// Reads only `fields`, see [`command::read_fields`].
+    pub fn read_fields(&self, fields: &[Field]) -> PartialState {
+        command::read_fields(&self.device, fields)
+    }
+
+    /// Applies `profile`, skipping the settings this model can't take; see
+    /// [`Profile::apply`].
+    pub fn apply(&self, profile: &Profile) -> Vec<FieldOutcome> {
+        profile.apply(&self.device)
+    }
+}
+
+impl From<Device> for RazerLaptop {
+    fn from(device: Device) -> Self {
+        RazerLaptop::new(device)
+    }
+}
+
+impl CommandTarget for RazerLaptop {
+    fn info(&self) -> &Descriptor {
+        self.device.info()
+    }
+
+    fn send(&self, report: Packet) -> Result<Packet> {
+        self.device.send(report)
+    }
+
+    fn send_many(&self, reports: &[Packet]) -> Result<Vec<Packet>> {
+        self.device.send_many(reports)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::cmd;
+    use crate::descriptor::SUPPORTED;
+    use crate::error::RazerError;
+    use crate::persist::Restored;
+    use crate::transport::MockTransport;
+
+    fn laptop(features: &[&str]) -> (RazerLaptop, MockTransport) {
+        let mock = MockTransport::new();
+        let desc = SUPPORTED[0].with_features(features);
+        let laptop = RazerLaptop::new(Device::with_transport(mock.clone(), desc));
+        (laptop, mock)
+    }
+
+    #[test]
+    fn test_unsupported_settings_fail_before_sending() {
+        let (laptop, mock) = laptop(&[feature::PERF, feature::KBDBACKLIGHT]);
+        let unsupported: Vec<Result<()>> = vec![
+            laptop.set_logo_mode(LogoMode::Static),
+            laptop.logo_mode().map(drop),
+            laptop.set_battery_care(BatteryCare::Enable),
+            laptop.set_lights_always_on(LightsAlwaysOn::Enable),
+            laptop.set_fan_mode(FanMode::Manual),
+            laptop.fan_rpm().map(drop),
+        ];
+        for result in unsupported {
+            assert!(
+                matches!(result, Err(RazerError::FeatureNotSupported { .. })),
+                "{:?}",
+                result
+            );
+        }
+        assert!(mock.sent().is_empty());
+
+        laptop.set_keyboard_brightness(80).unwrap();
+        assert_eq!(
+            mock.sent_commands(),
+            [(cmd::SET_KBD_BRIGHTNESS, vec![1, 5, 80])]
+        );
+    }
+
+    #[test]
+    fn test_levels_are_checked_before_sending() {
+        let (mut laptop, mock) = laptop(feature::ALL_FEATURES);
+        laptop.device_mut().info.fan_rpm_range = 2000..=4500;
+        let error = laptop.set_fan_rpm(FanRpm::new(5000).unwrap()).unwrap_err();
+        assert!(
+            matches!(error, RazerError::FanRpmOutOfRange { .. }),
+            "{}",
+            error
+        );
+        assert!(mock.sent().is_empty());
+    }
+
+    #[test]
+    fn test_apply_skips_what_the_model_lacks() {
+        let (laptop, mock) = laptop(&[feature::KBDBACKLIGHT]);
+        let profile = Profile::new(
+            "night",
+            &DeviceState {
+                keyboard_brightness: Some(Brightness::new(10)),
+                logo_mode: Some(LogoMode::Off),
+                ..Default::default()
+            },
+        );
+        mock.push_response(&[1, 5, 200]);
+        let report = laptop.apply(&profile);
+        let outcomes: Vec<(&str, bool)> = report
+            .iter()
+            .map(|e| (e.field.as_str(), matches!(e.outcome, Restored::Skipped(_))))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [("keyboard_brightness", false), ("logo_mode", true)]
+        );
+        assert_eq!(
+            mock.sent_commands().last().unwrap(),
+            &(cmd::SET_KBD_BRIGHTNESS, vec![1, 5, 10])
+        );
+    }
+}
@@ -1,8 +1,32 @@
+#[cfg(feature = "async")]
+pub mod async_device;
 pub mod command;
 pub mod device;
 pub mod error;
 pub mod feature;
+#[cfg(feature = "tracing")]
+pub mod instrument;
+pub mod ipc;
+pub mod laptop;
+pub mod limits;
+pub mod lock;
+pub mod monitor;
+pub mod packet;
+pub mod packet_log;
+pub mod permission;
+pub mod persist;
+pub mod plan;
+pub mod profile;
+pub mod rate;
+pub mod replay;
+#[cfg(feature = "simulation")]
+pub mod simulation;
+pub mod state;
+pub mod transport;
 pub mod types;
+pub mod watch;
+pub mod worker;
 
 pub mod descriptor;
-mod packet;
+
+pub use laptop::RazerLaptop;
@@ -0,0 +1,47 @@
+//! Ranges and sizes of the protocol, for clients that check input before it
+//! reaches a device. The library validates against the same constants.
+
+use crate::packet::Packet;
+use std::ops::RangeInclusive;
+
+/// Slowest manual fan speed of the 2023 Blades, in RPM.
+pub const FAN_RPM_MIN: u16 = 2000;
+/// Fastest manual fan speed of the 2023 Blades, in RPM. Some models go higher,
+/// see [`Descriptor::fan_rpm_range`](crate::descriptor::Descriptor::fan_rpm_range).
+pub const FAN_RPM_MAX: u16 = 5000;
+/// Manual fan speeds of the 2023 Blades, in RPM.
+pub const FAN_RPM_RANGE: RangeInclusive<u16> = FAN_RPM_MIN..=FAN_RPM_MAX;
+
+/// Brightest keyboard backlight value.
+pub const BRIGHTNESS_MAX: u8 = u8::MAX;
+
+/// Longest idle timeout, in seconds: the most a two-byte argument holds.
+pub const TIMEOUT_MAX_SECS: u16 = u16::MAX;
+
+/// Size of a HID report: the report id followed by a [`Packet`].
+pub const REPORT_SIZE: usize = 1 + Packet::SIZE;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::DEFAULT_FAN_RPM_RANGE;
+    use crate::types::{Brightness, FanRpm, Timeout};
+
+    #[test]
+    fn test_limits_reject_values_just_outside() {
+        assert_eq!(DEFAULT_FAN_RPM_RANGE, FAN_RPM_RANGE);
+        assert!(FanRpm::new_in(FAN_RPM_MIN, &FAN_RPM_RANGE).is_ok());
+        assert!(FanRpm::new_in(FAN_RPM_MAX, &FAN_RPM_RANGE).is_ok());
+        assert!(FanRpm::new_in(FAN_RPM_MIN - 1, &FAN_RPM_RANGE).is_err());
+        assert!(FanRpm::new_in(FAN_RPM_MAX + 1, &FAN_RPM_RANGE).is_err());
+
+        assert_eq!(Brightness::MAX.get(), BRIGHTNESS_MAX);
+        assert!(BRIGHTNESS_MAX.to_string().parse::<Brightness>().is_ok());
+        let above = (BRIGHTNESS_MAX as u16 + 1).to_string();
+        assert!(above.parse::<Brightness>().is_err());
+        assert!(Timeout::from_secs(TIMEOUT_MAX_SECS).is_ok());
+        let above = format!("{}s", TIMEOUT_MAX_SECS as u32 + 1);
+        assert!(above.parse::<Timeout>().is_err());
+        assert_eq!(REPORT_SIZE, 91);
+    }
+}
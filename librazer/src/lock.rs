@@ -0,0 +1,275 @@
+//! Advisory lock that keeps processes from interleaving reports to the device.
+//!
+//! The EC answers whatever report came last, so a GUI and a cron job sending at
+//! the same time read each other's responses, see Busy storms or leave the
+//! thermal zones in different modes. A [`Device`](crate::device::Device) with a
+//! [`DeviceLock`] holds it for every send, and for the whole of a batch. It is
+//! an exclusive lock on a file, [`default_path`] unless `RAZER_LOCK_FILE` names
+//! another, taken with `flock` on Linux and `LockFileEx` on Windows, so it is
+//! released when a process dies. Only processes that use the same file are
+//! kept apart.
+
+use crate::error::{RazerError, Result};
+use log::{debug, warn};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Environment variable naming the lock file instead of [`default_path`].
+pub const LOCK_FILE_ENV: &str = "RAZER_LOCK_FILE";
+
+/// Name of the lock file in [`default_path`]'s directory.
+pub const LOCK_FILE: &str = "razer-ctl.lock";
+
+/// Longest pause between attempts while another process holds the lock.
+const MAX_POLL: Duration = Duration::from_millis(20);
+
+/// Returns `$RAZER_LOCK_FILE`, or `razer-ctl.lock` in `$XDG_RUNTIME_DIR` on Linux,
+/// in `%ProgramData%` on Windows, where the service's temporary directory isn't
+/// the user's, and in the temporary directory otherwise.
+pub fn default_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(LOCK_FILE_ENV).filter(|path| !path.is_empty()) {
+        return PathBuf::from(path);
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir).join(LOCK_FILE);
+    }
+    #[cfg(windows)]
+    if let Some(dir) = std::env::var_os("ProgramData").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir).join(LOCK_FILE);
+    }
+    std::env::temp_dir().join(LOCK_FILE)
+}
+
+/// An open lock file, taken with [`acquire`](Self::acquire).
+#[derive(Debug)]
+pub struct DeviceLock {
+    file: File,
+    path: PathBuf,
+    wait: Duration,
+    /// Threads sharing this lock take turns here; the file lock only keeps out
+    /// other open files.
+    local: Mutex<()>,
+}
+
+impl DeviceLock {
+    /// How long [`acquire`](Self::acquire) waits for another process by default.
+    pub const DEFAULT_WAIT: Duration = Duration::from_secs(2);
+
+    /// Opens the lock file at [`default_path`].
+    pub fn open_default() -> Result<DeviceLock> {
+        DeviceLock::open(default_path())
+    }
+
+    /// Opens or creates the lock file at `path`. A file another user created is
+    /// opened read-only, which is enough to lock it.
+    pub fn open(path: impl Into<PathBuf>) -> Result<DeviceLock> {
+        let path = path.into();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .or_else(|_| File::open(&path))
+            .map_err(|e| {
+                RazerError::Other(format!(
+                    "Failed to open lock file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        Ok(DeviceLock {
+            file,
+            path,
+            wait: DeviceLock::DEFAULT_WAIT,
+            local: Mutex::new(()),
+        })
+    }
+
+    /// Sets how long [`acquire`](Self::acquire) waits before failing.
+    pub fn with_wait(mut self, wait: Duration) -> DeviceLock {
+        self.wait = wait;
+        self
+    }
+
+    /// Returns the lock file's path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns how long [`acquire`](Self::acquire) waits before failing.
+    pub fn wait(&self) -> Duration {
+        self.wait
+    }
+
+    /// Takes the lock until the guard is dropped, failing with
+    /// [`RazerError::DeviceLocked`] if another process keeps it longer than the
+    /// wait. If the file system can't lock files at all, goes on unlocked.
+    pub fn acquire(&self) -> Result<LockGuard<'_>> {
+        let start = Instant::now();
+        let local = self.local.lock().unwrap_or_else(|e| e.into_inner());
+        let mut delay = Duration::from_millis(1);
+        loop {
+            let locked = match self.file.try_lock() {
+                Ok(()) => true,
+                Err(TryLockError::WouldBlock) => false,
+                Err(TryLockError::Error(e)) => {
+                    warn!(
+                        "Failed to lock {}, going on without: {}",
+                        self.path.display(),
+                        e
+                    );
+                    return Ok(LockGuard {
+                        lock: self,
+                        locked: false,
+                        _local: local,
+                    });
+                }
+            };
+            let waited = start.elapsed();
+            if locked {
+                if waited >= MAX_POLL {
+                    debug!("Waited {:?} for {}", waited, self.path.display());
+                }
+                return Ok(LockGuard {
+                    lock: self,
+                    locked: true,
+                    _local: local,
+                });
+            }
+            if waited >= self.wait {
+                return Err(RazerError::DeviceLocked {
+                    path: self.path.display().to_string(),
+                    waited,
+                });
+            }
+            thread::sleep(delay.min(self.wait - waited));
+            delay = (delay * 2).min(MAX_POLL);
+        }
+    }
+}
+
+/// Holds a [`DeviceLock`] until dropped.
+#[must_use = "the lock is released when the guard is dropped"]
+pub struct LockGuard<'a> {
+    lock: &'a DeviceLock,
+    locked: bool,
+    _local: MutexGuard<'a, ()>,
+}
+
+impl Drop for LockGuard<'_> {
+    fn drop(&mut self) {
+        if self.locked {
+            if let Err(e) = self.lock.file.unlock() {
+                warn!("Failed to unlock {}: {}", self.lock.path.display(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command;
+    use crate::descriptor::SUPPORTED;
+    use crate::device::Device;
+    use crate::packet::Packet;
+    use crate::transport::Transport;
+    use crate::types::PerfMode;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Echoes every report and counts the ones sent while another exchange was
+    /// still waiting for its response.
+    #[derive(Clone, Default)]
+    struct Shared {
+        last: Arc<Mutex<Vec<u8>>>,
+        busy: Arc<AtomicBool>,
+        overlaps: Arc<AtomicUsize>,
+        exchanges: Arc<AtomicUsize>,
+    }
+
+    impl Transport for Shared {
+        fn send_feature_report(&self, data: &[u8]) -> Result<()> {
+            if self.busy.swap(true, Ordering::SeqCst) {
+                self.overlaps.fetch_add(1, Ordering::SeqCst);
+            }
+            *self.last.lock().unwrap() = data[1..].to_vec();
+            Ok(())
+        }
+
+        fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize> {
+            let request = Packet::try_from(self.last.lock().unwrap().as_slice())?;
+            let bytes: Vec<u8> = (&request.into_response(0x02, &[])).into();
+            buf[1..=bytes.len()].copy_from_slice(&bytes);
+            self.exchanges.fetch_add(1, Ordering::SeqCst);
+            self.busy.store(false, Ordering::SeqCst);
+            Ok(1 + bytes.len())
+        }
+
+        fn sleep(&self, duration: Duration) {
+            // long enough for the other thread to get in between
+            thread::sleep(duration.max(Duration::from_micros(200)));
+        }
+    }
+
+    fn lock_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "razer-ctl-test-{}-{}.lock",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_lock_serializes_two_devices() {
+        let path = lock_path("serialize");
+        let ec = Shared::default();
+        // two devices with their own lock files, like two processes
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let mut device = Device::with_transport(ec.clone(), SUPPORTED[0].clone());
+                device.set_lock(Some(DeviceLock::open(&path).unwrap()));
+                thread::spawn(move || {
+                    for _ in 0..25 {
+                        command::set_perf_mode(&device, PerfMode::Silent).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(ec.exchanges.load(Ordering::SeqCst), 100);
+        assert_eq!(ec.overlaps.load(Ordering::SeqCst), 0);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_held_lock_times_out() {
+        let path = lock_path("timeout");
+        let holder = DeviceLock::open(&path).unwrap();
+        let waiter = DeviceLock::open(&path)
+            .unwrap()
+            .with_wait(Duration::from_millis(30));
+
+        let guard = holder.acquire().unwrap();
+        let start = Instant::now();
+        let error = waiter.acquire().err().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(30));
+        assert!(
+            matches!(error, RazerError::DeviceLocked { .. }),
+            "{}",
+            error
+        );
+        assert!(error.is_retryable());
+
+        drop(guard);
+        drop(waiter.acquire().unwrap());
+        let _ = std::fs::remove_file(path);
+    }
+}
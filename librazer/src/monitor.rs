@@ -0,0 +1,410 @@
+//! Background sampling that keeps a rolling history of settings, for graphs.
+//!
+//! [`Sampler`] reads the configured fields of a device on its own thread every
+//! interval and keeps the last `capacity` values of each in a ring buffer, with
+//! [`Sampler::summary`] giving the minimum, maximum and average of numeric
+//! fields over a window. Unlike [`DeviceState`], the fan speed is sampled in
+//! every fan mode, as what the fans are doing. The protocol has no temperature
+//! command, so there is no temperature to sample.
+//!
+//! While the device answers Busy the interval doubles, up to
+//! [`SampleOptions::max_backoff`], and drops back once a read goes through.
+
+use crate::command;
+use crate::descriptor::Descriptor;
+use crate::device::CommandTarget;
+use crate::error::{ErrorCode, RazerError, Result};
+use crate::feature;
+use crate::packet::Packet;
+use crate::state::{DeviceState, Field, FieldValue, PartialState};
+use crate::types::FanZone;
+use log::debug;
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What a [`Sampler`] reads, how often and how much of it is kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleOptions {
+    /// Time between reads while the device keeps up.
+    pub interval: Duration,
+    /// Fields to read and keep a history of.
+    pub fields: Vec<Field>,
+    /// Values kept per field; the oldest are dropped first.
+    pub capacity: usize,
+    /// Longest time between reads while the device answers Busy.
+    pub max_backoff: Duration,
+}
+
+impl Default for SampleOptions {
+    fn default() -> Self {
+        SampleOptions {
+            interval: Duration::from_secs(1),
+            fields: vec![Field::PerfMode, Field::FanMode, Field::FanRpm],
+            capacity: 600,
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Minimum, maximum and average of a numeric field's samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    /// Number of samples summarized.
+    pub samples: usize,
+}
+
+/// Returns `value` as a number if it is one: a fan speed in RPM or a brightness.
+pub fn numeric(value: FieldValue) -> Option<f64> {
+    match value {
+        FieldValue::FanRpm(rpm) => Some(f64::from(rpm.get())),
+        FieldValue::KeyboardBrightness(brightness) => Some(f64::from(brightness.get())),
+        _ => None,
+    }
+}
+
+struct History {
+    capacity: usize,
+    latest: Option<(Instant, PartialState)>,
+    series: HashMap<Field, VecDeque<(Instant, FieldValue)>>,
+    interval: Duration,
+    stopped: bool,
+}
+
+impl History {
+    fn record(&mut self, at: Instant, sample: PartialState) {
+        for (field, value) in sample.iter() {
+            let Some(value) = value else {
+                continue;
+            };
+            let series = self.series.entry(field).or_default();
+            if series.len() == self.capacity {
+                series.pop_front();
+            }
+            series.push_back((at, value));
+        }
+        self.latest = Some((at, sample));
+    }
+}
+
+/// Shared between the sampler and its thread.
+struct Shared {
+    history: Mutex<History>,
+    wake: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, History> {
+        self.history.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Thread sampling a device into per-field ring buffers.
+///
+/// Dropping the sampler stops the thread.
+pub struct Sampler {
+    shared: Arc<Shared>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Sampler {
+    /// Moves `device`, usually a [`SharedDevice`](crate::device::SharedDevice)
+    /// the rest of the program also uses, to a new sampling thread.
+    pub fn spawn<D>(device: D, options: SampleOptions) -> Result<Sampler>
+    where
+        D: CommandTarget + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            history: Mutex::new(History {
+                capacity: options.capacity.max(1),
+                latest: None,
+                series: HashMap::new(),
+                interval: options.interval,
+                stopped: false,
+            }),
+            wake: Condvar::new(),
+        });
+        let thread = thread::Builder::new()
+            .name("razer-monitor".to_string())
+            .spawn({
+                let shared = shared.clone();
+                move || sample(&device, &options, &shared)
+            })
+            .map_err(|e| RazerError::Other(format!("Failed to start sampler: {}", e)))?;
+        Ok(Sampler {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the last sample and when it was taken, `None` before the first.
+    pub fn latest(&self) -> Option<(Instant, PartialState)> {
+        self.shared.lock().latest.clone()
+    }
+
+    /// Returns the kept values of `field`, oldest first. Reads that failed
+    /// leave gaps rather than entries.
+    pub fn history(&self, field: Field) -> Vec<(Instant, FieldValue)> {
+        let history = self.shared.lock();
+        history
+            .series
+            .get(&field)
+            .map_or_else(Vec::new, |series| series.iter().copied().collect())
+    }
+
+    /// Summarizes the values of `field` sampled in the last `window`, `None` if
+    /// there are none or the field isn't [`numeric`].
+    pub fn summary(&self, field: Field, window: Duration) -> Option<Summary> {
+        let history = self.shared.lock();
+        let since = Instant::now().checked_sub(window);
+        let values: Vec<f64> = history
+            .series
+            .get(&field)?
+            .iter()
+            .filter(|(at, _)| since.is_none_or(|since| *at >= since))
+            .map(|&(_, value)| numeric(value))
+            .collect::<Option<_>>()?;
+        if values.is_empty() {
+            return None;
+        }
+        Some(Summary {
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            avg: values.iter().sum::<f64>() / values.len() as f64,
+            samples: values.len(),
+        })
+    }
+
+    /// Returns the time until the next read, longer than the configured
+    /// interval while backing off.
+    pub fn interval(&self) -> Duration {
+        self.shared.lock().interval
+    }
+
+    /// Stops sampling and waits for the thread to finish.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn sample(device: &impl CommandTarget, options: &SampleOptions, shared: &Shared) {
+    let mut interval = options.interval;
+    loop {
+        let observed = Observed {
+            device,
+            busy: Cell::new(false),
+        };
+        let values = read(&observed, &options.fields);
+        let at = Instant::now();
+
+        interval = if observed.busy.get() {
+            let backoff = (interval * 2).min(options.max_backoff.max(options.interval));
+            debug!("Device busy, sampling every {:?}", backoff);
+            backoff
+        } else {
+            options.interval
+        };
+
+        let mut history = shared.lock();
+        history.record(at, values);
+        history.interval = interval;
+        let deadline = at + interval;
+        loop {
+            if history.stopped {
+                return;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            history = shared
+                .wake
+                .wait_timeout(history, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+}
+
+/// Reads `fields`, the fan speed in every fan mode.
+fn read(device: &impl CommandTarget, fields: &[Field]) -> PartialState {
+    let mut state: DeviceState = command::read_fields(device, fields).into_state();
+    if fields.contains(&Field::FanRpm)
+        && state.fan_rpm.is_none()
+        && device.info().features.contains(&feature::FAN)
+    {
+        state.fan_rpm = command::get_fan_rpm(device, FanZone::Zone1).ok();
+    }
+    PartialState::new(fields, state)
+}
+
+/// Passes sends through, noting whether the device answered Busy.
+struct Observed<'a, D> {
+    device: &'a D,
+    busy: Cell<bool>,
+}
+
+impl<D: CommandTarget> Observed<'_, D> {
+    fn observe<T>(&self, result: Result<T>) -> Result<T> {
+        if result.as_ref().is_err_and(|e| e.code() == ErrorCode::Busy) {
+            self.busy.set(true);
+        }
+        result
+    }
+}
+
+impl<D: CommandTarget> CommandTarget for Observed<'_, D> {
+    fn info(&self) -> &Descriptor {
+        self.device.info()
+    }
+
+    fn send(&self, report: Packet) -> Result<Packet> {
+        self.observe(self.device.send(report))
+    }
+
+    fn send_many(&self, reports: &[Packet]) -> Result<Vec<Packet>> {
+        self.observe(self.device.send_many(reports))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::cmd;
+    use crate::descriptor::SUPPORTED;
+    use crate::types::{FanMode, FanRpm, PerfMode};
+    use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+    /// Answers the fan speed from `rpm`, Busy while `busy` is set, and Balanced
+    /// with the fan in Auto otherwise.
+    struct Target {
+        info: Descriptor,
+        rpm: Arc<AtomicU8>,
+        busy: Arc<AtomicBool>,
+    }
+
+    impl CommandTarget for Target {
+        fn info(&self) -> &Descriptor {
+            &self.info
+        }
+
+        fn send(&self, report: Packet) -> Result<Packet> {
+            if self.busy.load(Ordering::SeqCst) {
+                return Err(RazerError::DeviceBusy);
+            }
+            match report.command() {
+                cmd::GET_PERF_MODE => Ok(Packet::new(
+                    cmd::GET_PERF_MODE,
+                    &[
+                        0,
+                        report.arg(1)?,
+                        PerfMode::Balanced.raw(),
+                        FanMode::Auto as u8,
+                    ],
+                )),
+                cmd::GET_FAN_RPM => Ok(Packet::new(
+                    cmd::GET_FAN_RPM,
+                    &[0, report.arg(1)?, self.rpm.load(Ordering::SeqCst)],
+                )),
+                _ => Ok(report),
+            }
+        }
+    }
+
+    fn wait_until(mut done: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done() {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_history_and_summary() {
+        let rpm = Arc::new(AtomicU8::new(30));
+        let target = Target {
+            info: SUPPORTED[0].clone(),
+            rpm: rpm.clone(),
+            busy: Arc::default(),
+        };
+        let options = SampleOptions {
+            interval: Duration::from_millis(2),
+            capacity: 4,
+            ..Default::default()
+        };
+        let sampler = Sampler::spawn(target, options).unwrap();
+        wait_until(|| sampler.history(Field::FanRpm).len() == 4);
+        rpm.store(50, Ordering::SeqCst);
+        wait_until(|| {
+            sampler
+                .history(Field::FanRpm)
+                .iter()
+                .all(|&(_, value)| value == FieldValue::FanRpm(FanRpm::from_raw_byte(50)))
+        });
+        assert_eq!(sampler.history(Field::FanRpm).len(), 4);
+
+        let (_, latest) = sampler.latest().unwrap();
+        assert_eq!(
+            latest.get(Field::PerfMode),
+            Some(FieldValue::PerfMode(PerfMode::Balanced))
+        );
+        assert_eq!(
+            latest.get(Field::FanRpm),
+            Some(FieldValue::FanRpm(FanRpm::from_raw_byte(50)))
+        );
+        let summary = sampler
+            .summary(Field::FanRpm, Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(
+            (summary.min, summary.max, summary.avg),
+            (5000.0, 5000.0, 5000.0)
+        );
+        assert_eq!(summary.samples, 4);
+        assert_eq!(
+            sampler.summary(Field::PerfMode, Duration::from_secs(60)),
+            None
+        );
+        sampler.stop();
+    }
+
+    #[test]
+    fn test_backs_off_while_busy() {
+        let busy = Arc::new(AtomicBool::new(true));
+        let target = Target {
+            info: SUPPORTED[0].clone(),
+            rpm: Arc::default(),
+            busy: busy.clone(),
+        };
+        let options = SampleOptions {
+            interval: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(8),
+            ..Default::default()
+        };
+        let sampler = Sampler::spawn(target, options).unwrap();
+        wait_until(|| sampler.interval() == Duration::from_millis(8));
+        assert!(sampler.history(Field::PerfMode).is_empty());
+
+        busy.store(false, Ordering::SeqCst);
+        wait_until(|| sampler.interval() == Duration::from_millis(1));
+        assert!(!sampler.history(Field::PerfMode).is_empty());
+    }
+}
@@ -1,7 +1,9 @@
-use crate::error::{RazerError, Result};
+use crate::command::CommandId;
+use crate::descriptor::Quirks;
+use crate::error::{Mismatch, RazerError, Result};
+use crate::limits::REPORT_SIZE;
 use rand::Rng;
-use serde::{Deserialize, Serialize};
-use serde_big_array::BigArray;
+use std::fmt;
 
 /// USB HID feature report packet for Razer device communication.
 ///
@@ -9,15 +11,19 @@ use serde_big_array::BigArray;
 /// reports and responses are read back in the same format.
 ///
 /// # Protocol
-/// - Bytes 0-1: Status and transaction ID
-/// - Bytes 2-7: Protocol metadata (remaining packets, type, size, command)
+/// - Byte 0: Status
+/// - Byte 1: Transaction ID
+/// - Bytes 2-3: Remaining packets (little-endian)
+/// - Byte 4: Protocol type
+/// - Byte 5: Data size
+/// - Byte 6: Command class
+/// - Byte 7: Command ID
 /// - Bytes 8-87: Command arguments (80 bytes)
 /// - Byte 88: CRC (XOR of bytes 2-87)
 /// - Byte 89: Reserved
 ///
 /// See `data/README.md` for reverse engineering details.
-#[repr(C)]
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct Packet {
     status: u8,
     id: u8,
@@ -26,14 +32,13 @@ pub struct Packet {
     data_size: u8,
     command_class: u8,
     command_id: u8,
-    #[serde(with = "BigArray")]
     args: [u8; 80],
     crc: u8,
     reserved: u8,
 }
 
 /// Status codes for USB HID command packets (per openrazer protocol).
-enum CommandStatus {
+pub(crate) enum CommandStatus {
     /// Initial status for outgoing packets (not yet processed)
     New = 0x00,
     /// Device is busy processing the command
@@ -49,13 +54,31 @@ enum CommandStatus {
 }
 
 impl Packet {
+    /// Size of a packet on the wire, without the HID report id.
+    pub const SIZE: usize = 90;
+
     /// Creates a new packet with the given command and arguments.
     ///
     /// The command is a 16-bit value where the high byte is the command class
     /// and the low byte is the command ID (e.g., 0x0d02 for SET_PERF_MODE).
+    ///
+    /// Meant for arguments of a known size, like the fixed ones of each command;
+    /// use [`Packet::try_new`] for arguments that come from a caller or a file.
+    ///
+    /// # Panics
+    /// If `args` is longer than 80 bytes; [`Packet::try_new`] returns an error instead.
     pub fn new(command: u16, args: &[u8]) -> Packet {
+        Packet::try_new(command, args).expect("command arguments exceed 80 bytes")
+    }
+
+    /// Like [`Packet::new`], failing with [`RazerError::ArgumentsTooLong`] if
+    /// `args` is longer than 80 bytes.
+    pub fn try_new(command: u16, args: &[u8]) -> Result<Packet> {
         let mut args_buffer = [0x00; 80];
-        args_buffer[..args.len()].copy_from_slice(args);
+        args_buffer
+            .get_mut(..args.len())
+            .ok_or(RazerError::ArgumentsTooLong(args.len()))?
+            .copy_from_slice(args);
 
         let mut packet = Packet {
             status: CommandStatus::New as u8,
@@ -70,7 +93,138 @@ impl Packet {
             reserved: 0x00,
         };
         packet.crc = packet.calculate_crc();
-        packet
+        Ok(packet)
+    }
+
+    /// Starts a [`PacketBuilder`] for `command`.
+    pub fn builder(command: u16) -> PacketBuilder {
+        PacketBuilder::new(command)
+    }
+
+    /// Splits `data` into frames of a multi-packet transfer for `command`.
+    ///
+    /// Each frame carries up to 80 bytes and counts the frames that follow it in
+    /// `remaining_packets`. Empty `data` gives a single empty frame.
+    pub fn sequence(command: u16, data: &[u8]) -> Vec<Packet> {
+        if data.is_empty() {
+            return vec![Packet::new(command, &[])];
+        }
+        let chunks = data.chunks(80);
+        let count = chunks.len();
+        chunks
+            .enumerate()
+            .map(|(index, chunk)| {
+                Packet::new(command, chunk).with_remaining_packets((count - 1 - index) as u16)
+            })
+            .collect()
+    }
+
+    /// Returns this packet with `remaining_packets` set and the CRC updated.
+    pub fn with_remaining_packets(mut self, remaining: u16) -> Packet {
+        self.remaining_packets = remaining;
+        self.crc = self.calculate_crc();
+        self
+    }
+
+    /// Returns this packet with its transaction id replaced. The id is not
+    /// covered by the CRC.
+    pub fn with_transaction_id(mut self, id: u8) -> Packet {
+        self.id = id;
+        self
+    }
+
+    /// Returns the transaction id the response has to echo.
+    pub fn transaction_id(&self) -> u8 {
+        self.id
+    }
+
+    /// Returns whether the CRC byte matches the rest of the packet.
+    pub fn crc_matches(&self) -> bool {
+        self.crc == self.calculate_crc()
+    }
+
+    /// Returns how many frames of a multi-packet transfer follow this one.
+    pub fn remaining_packets(&self) -> u16 {
+        self.remaining_packets
+    }
+
+    /// Formats the header and meaningful arguments on one line, for logs.
+    ///
+    /// ```text
+    /// status=0x02 id=0x1f cmd=0x0d02 name=SET_PERF_MODE size=10 args=00 01 02 03 04 05 06 07 | 08 09
+    /// ```
+    ///
+    /// `name` is only present for known commands ([`CommandId`]). Arguments are
+    /// grouped by 8 bytes; `remaining=N` follows `size` for frames of a
+    /// multi-packet sequence. The format is stable so logs can be parsed.
+    pub fn hexdump(&self) -> String {
+        let mut dump = format!(
+            "status=0x{:02x} id=0x{:02x} cmd=0x{:04x}",
+            self.status,
+            self.id,
+            self.command()
+        );
+        if let Some(command) = CommandId::from_raw(self.command()) {
+            dump.push_str(&format!(" name={}", command.name()));
+        }
+        dump.push_str(&format!(" size={}", self.data_size));
+        if self.remaining_packets != 0 {
+            dump.push_str(&format!(" remaining={}", self.remaining_packets));
+        }
+        dump.push_str(" args=");
+        let args = self.get_args();
+        if args.is_empty() {
+            dump.push('-');
+        }
+        for (index, group) in args.chunks(8).enumerate() {
+            if index > 0 {
+                dump.push_str(" | ");
+            }
+            let bytes: Vec<String> = group.iter().map(|b| format!("{:02x}", b)).collect();
+            dump.push_str(&bytes.join(" "));
+        }
+        dump
+    }
+
+    /// Writes the HID feature report for this packet, `report_id` first, to `buf`.
+    ///
+    /// Unlike the `Vec<u8>` conversion this doesn't allocate.
+    pub fn write_report(&self, report_id: u8, buf: &mut [u8; REPORT_SIZE]) {
+        let (id, bytes) = buf.split_at_mut(1);
+        id[0] = report_id;
+        self.to_bytes(bytes.try_into().expect("slice is one packet long"));
+    }
+
+    /// Writes the wire format of this packet to `bytes`.
+    pub fn to_bytes(&self, bytes: &mut [u8; Packet::SIZE]) {
+        bytes[0] = self.status;
+        bytes[1] = self.id;
+        bytes[2..4].copy_from_slice(&self.remaining_packets.to_le_bytes());
+        bytes[4] = self.protocol_type;
+        bytes[5] = self.data_size;
+        bytes[6] = self.command_class;
+        bytes[7] = self.command_id;
+        bytes[8..88].copy_from_slice(&self.args);
+        bytes[88] = self.crc;
+        bytes[89] = self.reserved;
+    }
+
+    /// Reads a packet from its wire format. The CRC is taken as is, not checked.
+    pub fn from_bytes(bytes: &[u8; Packet::SIZE]) -> Packet {
+        let mut args = [0x00; 80];
+        args.copy_from_slice(&bytes[8..88]);
+        Packet {
+            status: bytes[0],
+            id: bytes[1],
+            remaining_packets: u16::from_le_bytes([bytes[2], bytes[3]]),
+            protocol_type: bytes[4],
+            data_size: bytes[5],
+            command_class: bytes[6],
+            command_id: bytes[7],
+            args,
+            crc: bytes[88],
+            reserved: bytes[89],
+        }
     }
 
     /// Calculate CRC by XORing bytes 2-87 of the packet (per openrazer protocol).
@@ -91,27 +245,107 @@ impl Packet {
         crc
     }
 
+    /// Returns the 16-bit command code (command class in the high byte).
+    pub fn command(&self) -> u16 {
+        (self.command_class as u16) << 8 | self.command_id as u16
+    }
+
+    /// Whether the command reads rather than writes: getters have the high bit
+    /// of the command ID set.
+    pub fn is_read(&self) -> bool {
+        self.command_id & 0x80 != 0
+    }
+
+    /// Returns the status byte, 0x02 in a successful response.
+    pub fn status(&self) -> u8 {
+        self.status
+    }
+
+    /// Returns the declared argument length, which a misbehaving device may set
+    /// past the 80 argument bytes.
+    pub fn data_size(&self) -> u8 {
+        self.data_size
+    }
+
     /// Returns the valid argument bytes (up to data_size).
+    ///
+    /// A data_size past the 80 argument bytes, which only a misbehaving device
+    /// sends, is capped.
     pub fn get_args(&self) -> &[u8] {
-        &self.args[..self.data_size as usize]
+        &self.args[..(self.data_size as usize).min(self.args.len())]
+    }
+
+    /// Returns argument byte `index`, or [`RazerError::InvalidDataSize`] if the
+    /// packet's data_size doesn't cover it.
+    pub fn arg(&self, index: usize) -> Result<u8> {
+        self.get_args()
+            .get(index)
+            .copied()
+            .ok_or(RazerError::InvalidDataSize {
+                expected: index + 1,
+                actual: self.get_args().len(),
+            })
+    }
+
+    /// Returns the first `len` argument bytes, or [`RazerError::InvalidDataSize`]
+    /// if the packet has fewer.
+    pub fn args_exact(&self, len: usize) -> Result<&[u8]> {
+        self.get_args()
+            .get(..len)
+            .ok_or(RazerError::InvalidDataSize {
+                expected: len,
+                actual: self.get_args().len(),
+            })
+    }
+
+    /// Returns this response with its data_size cut to `data_size`, as sent by
+    /// a device that answers with fewer arguments than asked for.
+    pub(crate) fn truncated(mut self, data_size: u8) -> Packet {
+        self.data_size = data_size;
+        self.crc = self.calculate_crc();
+        self
+    }
+
+    fn mismatch(&self, report: &Packet) -> RazerError {
+        let mut bytes = [0x00; Packet::SIZE];
+        self.to_bytes(&mut bytes);
+        RazerError::UnexpectedResponse(Box::new(Mismatch {
+            sent_command: report.command(),
+            sent_id: report.id,
+            sent_remaining: report.remaining_packets,
+            received_command: self.command(),
+            received_id: self.id,
+            received_remaining: self.remaining_packets,
+            received_status: self.status,
+            response: bytes[..Mismatch::DUMP_LEN].to_vec(),
+        }))
     }
 
     /// Validates that this response packet matches the original report.
     ///
-    /// Checks command class, command ID, transaction ID, and status code.
-    pub fn ensure_matches_report(self, report: &Packet) -> Result<Self> {
+    /// Checks command class, command ID, transaction ID, sequence position, CRC
+    /// and status code. `quirks` lists the device's known deviations from the protocol.
+    pub fn ensure_matches_report(self, report: &Packet, quirks: &Quirks) -> Result<Self> {
         if (report.command_class, report.command_id, report.id)
             != (self.command_class, self.command_id, self.id)
         {
-            return Err(RazerError::ResponseMismatch);
+            return Err(self.mismatch(report));
         }
 
+        // Every frame is acknowledged with its own count. Some firmware answers
+        // the last (or only) frame of certain commands with a count of its own.
         if self.remaining_packets != report.remaining_packets
-            && (self.command_class, self.command_id) != (0x07, 0x92) /* 0x0792 (bho) has special handling */
-            && (self.command_class, self.command_id) != (0x07, 0x8f)
-        /* 0x078f max fan speed mode has special handling */
+            && !(report.remaining_packets == 0
+                && quirks.allows_loose_remaining_packets(report.command()))
         {
-            return Err(RazerError::ResponseMismatch);
+            return Err(self.mismatch(report));
+        }
+
+        if !quirks.skip_response_crc && !self.crc_matches() {
+            return Err(RazerError::ResponseCrc {
+                expected: self.calculate_crc(),
+                actual: self.crc,
+            });
         }
 
         match self.status {
@@ -135,11 +369,90 @@ impl Packet {
 
         Ok(self)
     }
+
+    /// Turns this report into a response with the given status.
+    ///
+    /// `args` overwrite the leading argument bytes; the remaining arguments are echoed.
+    /// Bytes past the 80 a packet holds are dropped. Used by transports that answer
+    /// on behalf of a device.
+    pub(crate) fn into_response(mut self, status: u8, args: &[u8]) -> Packet {
+        let args = &args[..args.len().min(self.args.len())];
+        self.status = status;
+        self.args[..args.len()].copy_from_slice(args);
+        self.data_size = self.data_size.max(args.len() as u8);
+        self.crc = self.calculate_crc();
+        self
+    }
+}
+
+/// Builds a [`Packet`] argument by argument.
+///
+/// Arguments past the 80 bytes of a packet are counted but not stored, and make
+/// [`build`](Self::build) fail.
+#[derive(Debug, Clone)]
+pub struct PacketBuilder {
+    command: u16,
+    args: [u8; 80],
+    len: usize,
+}
+
+impl PacketBuilder {
+    /// Starts a packet for `command` without arguments.
+    pub fn new(command: u16) -> Self {
+        PacketBuilder {
+            command,
+            args: [0x00; 80],
+            len: 0,
+        }
+    }
+
+    /// Appends one byte.
+    pub fn arg_u8(self, value: u8) -> Self {
+        self.args(&[value])
+    }
+
+    /// Appends a 16-bit value, low byte first.
+    pub fn arg_u16_le(self, value: u16) -> Self {
+        self.args(&value.to_le_bytes())
+    }
+
+    /// Appends `bytes`.
+    pub fn args(mut self, bytes: &[u8]) -> Self {
+        let end = self.len + bytes.len();
+        if end <= self.args.len() {
+            self.args[self.len..end].copy_from_slice(bytes);
+        }
+        self.len = end;
+        self
+    }
+
+    /// Returns the packet, or [`RazerError::ArgumentsTooLong`] if the arguments
+    /// don't fit.
+    pub fn build(self) -> Result<Packet> {
+        if self.len > self.args.len() {
+            return Err(RazerError::ArgumentsTooLong(self.len));
+        }
+        Packet::try_new(self.command, &self.args[..self.len])
+    }
+}
+
+impl fmt::Debug for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.hexdump())
+    }
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.hexdump())
+    }
 }
 
 impl From<&Packet> for Vec<u8> {
     fn from(packet: &Packet) -> Vec<u8> {
-        bincode::serialize(packet).expect("Packet serialization failed - this is a bug")
+        let mut bytes = [0x00; Packet::SIZE];
+        packet.to_bytes(&mut bytes);
+        bytes.to_vec()
     }
 }
 
@@ -147,25 +460,345 @@ impl TryFrom<&[u8]> for Packet {
     type Error = RazerError;
 
     fn try_from(data: &[u8]) -> std::result::Result<Self, Self::Error> {
-        if data.len() != std::mem::size_of::<Packet>() {
-            return Err(RazerError::InvalidDataSize {
-                expected: std::mem::size_of::<Packet>(),
-                actual: data.len(),
-            });
-        }
-
-        bincode::deserialize::<Packet>(data)
-            .map_err(|e| RazerError::Other(format!("Deserialization failed: {}", e)))
+        let bytes = data.try_into().map_err(|_| RazerError::InvalidDataSize {
+            expected: Packet::SIZE,
+            actual: data.len(),
+        })?;
+        Ok(Packet::from_bytes(bytes))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_packet_size() {
-        assert_eq!(std::mem::size_of::<Packet>(), 90);
+        assert_eq!(Packet::SIZE, 90);
+        let bytes: Vec<u8> = (&Packet::new(0x0d02, &[0x01])).into();
+        assert_eq!(bytes.len(), Packet::SIZE);
+    }
+
+    fn args_pattern() -> [u8; 80] {
+        let mut args = [0x00; 80];
+        for (i, byte) in args.iter_mut().enumerate() {
+            *byte = (i as u8).wrapping_mul(37).wrapping_add(11);
+        }
+        args
+    }
+
+    /// Packets and their encoding as produced by the previous bincode-based
+    /// implementation.
+    fn captured() -> Vec<(Packet, Vec<u8>)> {
+        let mut small_args = [0x00; 80];
+        small_args[..2].copy_from_slice(&[0x01, 0x02]);
+        let small = Packet {
+            status: 0x00,
+            id: 0x1f,
+            remaining_packets: 0,
+            protocol_type: 0x00,
+            data_size: 2,
+            command_class: 0x0d,
+            command_id: 0x02,
+            args: small_args,
+            crc: 0x0e,
+            reserved: 0x00,
+        };
+        let small_bytes = [
+            &[0x00, 0x1f, 0x00, 0x00, 0x00, 0x02, 0x0d, 0x02, 0x01, 0x02][..],
+            &[0x00; 78],
+            &[0x0e, 0x00],
+        ]
+        .concat();
+
+        let full = Packet {
+            status: 0x02,
+            id: 0xa5,
+            remaining_packets: 0x0102,
+            protocol_type: 0x03,
+            data_size: 80,
+            command_class: 0x07,
+            command_id: 0x92,
+            args: args_pattern(),
+            crc: 0x15,
+            reserved: 0x5a,
+        };
+        let full_bytes = [
+            &[0x02, 0xa5, 0x02, 0x01, 0x03, 0x50, 0x07, 0x92][..],
+            &[
+                0x0b, 0x30, 0x55, 0x7a, 0x9f, 0xc4, 0xe9, 0x0e, 0x33, 0x58, 0x7d, 0xa2, 0xc7, 0xec,
+                0x11, 0x36, 0x5b, 0x80, 0xa5, 0xca, 0xef, 0x14, 0x39, 0x5e, 0x83, 0xa8, 0xcd, 0xf2,
+                0x17, 0x3c, 0x61, 0x86, 0xab, 0xd0, 0xf5, 0x1a, 0x3f, 0x64, 0x89, 0xae, 0xd3, 0xf8,
+                0x1d, 0x42, 0x67, 0x8c, 0xb1, 0xd6, 0xfb, 0x20, 0x45, 0x6a, 0x8f, 0xb4, 0xd9, 0xfe,
+                0x23, 0x48, 0x6d, 0x92, 0xb7, 0xdc, 0x01, 0x26, 0x4b, 0x70, 0x95, 0xba, 0xdf, 0x04,
+                0x29, 0x4e, 0x73, 0x98, 0xbd, 0xe2, 0x07, 0x2c, 0x51, 0x76,
+            ],
+            &[0x15, 0x5a],
+        ]
+        .concat();
+
+        let saturated = Packet {
+            status: 0x05,
+            id: 0xff,
+            remaining_packets: 0xfffe,
+            protocol_type: 0xff,
+            data_size: 0,
+            command_class: 0xff,
+            command_id: 0xff,
+            args: [0xff; 80],
+            crc: 0xfe,
+            reserved: 0xff,
+        };
+        let saturated_bytes = [
+            &[0x05, 0xff, 0xfe, 0xff, 0xff, 0x00, 0xff, 0xff][..],
+            &[0xff; 80],
+            &[0xfe, 0xff],
+        ]
+        .concat();
+
+        vec![
+            (small, small_bytes),
+            (full, full_bytes),
+            (saturated, saturated_bytes),
+        ]
+    }
+
+    #[test]
+    fn test_wire_format_matches_capture() {
+        for (packet, expected) in captured() {
+            assert_eq!(packet.crc, packet.calculate_crc());
+            let bytes: Vec<u8> = (&packet).into();
+            assert_eq!(bytes, expected);
+
+            let restored = Packet::try_from(expected.as_slice()).unwrap();
+            let mut roundtrip = [0x00; Packet::SIZE];
+            restored.to_bytes(&mut roundtrip);
+            assert_eq!(roundtrip.as_slice(), expected.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_hexdump_format() {
+        let mut packet = Packet::new(0x0d02, &(0..10).collect::<Vec<u8>>());
+        packet.id = 0x1f;
+        assert_eq!(
+            packet.hexdump(),
+            "status=0x00 id=0x1f cmd=0x0d02 name=SET_PERF_MODE size=10 args=00 01 02 03 04 05 06 07 | 08 09"
+        );
+        assert_eq!(format!("{:?}", packet), packet.hexdump());
+
+        let mut response = Packet::new(0x0086, &[])
+            .with_remaining_packets(2)
+            .into_response(CommandStatus::Successful as u8, &[]);
+        response.id = 0xa0;
+        assert_eq!(
+            response.to_string(),
+            "status=0x02 id=0xa0 cmd=0x0086 size=0 remaining=2 args=-"
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        let packet = Packet::builder(0x0d02)
+            .arg_u8(0x01)
+            .arg_u16_le(0x1234)
+            .args(&[0xaa, 0xbb])
+            .build()
+            .unwrap();
+        assert_eq!(packet.command(), 0x0d02);
+        assert_eq!(packet.get_args(), &[0x01, 0x34, 0x12, 0xaa, 0xbb]);
+        assert_eq!(packet.crc, packet.calculate_crc());
+
+        let full = Packet::builder(0x0f03).args(&[0x07; 80]).build().unwrap();
+        assert_eq!(full.get_args(), &[0x07; 80]);
+        assert!(Packet::builder(0x0f03)
+            .build()
+            .unwrap()
+            .get_args()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_builder_rejects_overlong_args() {
+        assert!(matches!(
+            Packet::builder(0x0f03).args(&[0; 81]).build(),
+            Err(RazerError::ArgumentsTooLong(81))
+        ));
+        assert!(matches!(
+            Packet::builder(0x0f03).args(&[0; 79]).arg_u16_le(1).build(),
+            Err(RazerError::ArgumentsTooLong(81))
+        ));
+        // further arguments after an overflow are still counted
+        assert!(matches!(
+            Packet::builder(0x0f03).args(&[0; 90]).arg_u8(1).build(),
+            Err(RazerError::ArgumentsTooLong(91))
+        ));
+    }
+
+    #[test]
+    fn test_write_report_matches_vec() {
+        for (packet, _) in captured() {
+            let mut buf = [0xee; REPORT_SIZE];
+            packet.write_report(0x00, &mut buf);
+            let expected: Vec<u8> = std::iter::once(0x00)
+                .chain(Vec::<u8>::from(&packet))
+                .collect();
+            assert_eq!(buf.as_slice(), expected.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_try_new_rejects_overlong_args() {
+        assert_eq!(
+            Packet::try_new(0x0d02, &[0x01; 80])
+                .unwrap()
+                .get_args()
+                .len(),
+            80
+        );
+        assert!(matches!(
+            Packet::try_new(0x0d02, &[0x01; 81]),
+            Err(RazerError::ArgumentsTooLong(81))
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_try_new(command: u16, args in proptest::collection::vec(any::<u8>(), 0..200)) {
+            match Packet::try_new(command, &args) {
+                Ok(packet) => {
+                    prop_assert!(args.len() <= 80);
+                    prop_assert_eq!(packet.command(), command);
+                    prop_assert_eq!(packet.get_args(), args.as_slice());
+                    prop_assert_eq!(packet.crc, packet.calculate_crc());
+                }
+                Err(e) => {
+                    prop_assert!(args.len() > 80);
+                    prop_assert!(matches!(e, RazerError::ArgumentsTooLong(len) if len == args.len()));
+                }
+            }
+        }
+
+        #[test]
+        fn prop_any_buffer_parses(bytes in proptest::collection::vec(any::<u8>(), Packet::SIZE)) {
+            let packet = Packet::try_from(bytes.as_slice()).unwrap();
+            prop_assert!(packet.get_args().len() <= 80);
+            for index in 0..=Packet::SIZE {
+                prop_assert_eq!(packet.arg(index).ok(), packet.get_args().get(index).copied());
+                prop_assert_eq!(packet.args_exact(index).is_ok(), index <= packet.get_args().len());
+            }
+            let _ = packet.hexdump();
+            let _ = packet.clone().ensure_matches_report(&packet, &Quirks::BLADE_2023);
+            let mut roundtrip = [0x00; Packet::SIZE];
+            packet.to_bytes(&mut roundtrip);
+            prop_assert_eq!(roundtrip.as_slice(), bytes.as_slice());
+        }
+
+        #[test]
+        fn prop_packet_roundtrips(
+            command: u16,
+            args in proptest::collection::vec(any::<u8>(), 0..=80),
+            remaining: u16,
+            id: u8,
+        ) {
+            let packet = Packet::new(command, &args)
+                .with_remaining_packets(remaining)
+                .with_transaction_id(id);
+            let mut bytes = [0x00; Packet::SIZE];
+            packet.to_bytes(&mut bytes);
+            let parsed = Packet::from_bytes(&bytes);
+            prop_assert!(parsed.crc_matches());
+            prop_assert_eq!(parsed, packet);
+        }
+
+        #[test]
+        fn prop_crc_catches_any_bit_flip(
+            bytes in proptest::collection::vec(any::<u8>(), Packet::SIZE),
+            offset in 2..88usize,
+            bit in 0..8u8,
+        ) {
+            let mut valid: [u8; Packet::SIZE] = bytes.as_slice().try_into().unwrap();
+            valid[88] = Packet::from_bytes(&valid).calculate_crc();
+            prop_assert!(Packet::from_bytes(&valid).crc_matches());
+
+            let mut corrupted = valid;
+            corrupted[offset] ^= 1 << bit;
+            let corrupted = Packet::from_bytes(&corrupted);
+            prop_assert_ne!(corrupted.calculate_crc(), valid[88]);
+            prop_assert!(!corrupted.crc_matches());
+        }
+
+        #[test]
+        fn prop_response_keeps_at_most_80_args(
+            request in proptest::collection::vec(any::<u8>(), 0..=80),
+            args in proptest::collection::vec(any::<u8>(), 0..200),
+        ) {
+            let response = Packet::new(0x0d82, &request).into_response(0x02, &args);
+            let kept = args.len().min(80);
+            prop_assert_eq!(&response.get_args()[..kept], &args[..kept]);
+            prop_assert_eq!(response.get_args().len(), kept.max(request.len()));
+            prop_assert!(response.crc_matches());
+        }
+
+        #[test]
+        fn prop_any_length_is_checked(bytes in proptest::collection::vec(any::<u8>(), 0..200)) {
+            let parsed = Packet::try_from(bytes.as_slice());
+            prop_assert_eq!(parsed.is_ok(), bytes.len() == Packet::SIZE);
+        }
+    }
+
+    #[test]
+    fn test_bounds_checked_args() {
+        let packet = Packet::new(0x0d82, &[0x00, 0x01, 0x02]);
+        assert_eq!(packet.arg(2).unwrap(), 0x02);
+        assert!(matches!(
+            packet.arg(3),
+            Err(RazerError::InvalidDataSize {
+                expected: 4,
+                actual: 3
+            })
+        ));
+        assert_eq!(packet.args_exact(2).unwrap(), &[0x00, 0x01]);
+        assert_eq!(packet.args_exact(3).unwrap(), &[0x00, 0x01, 0x02]);
+        assert!(matches!(
+            packet.args_exact(4),
+            Err(RazerError::InvalidDataSize {
+                expected: 4,
+                actual: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_every_byte_roundtrips() {
+        for offset in 0..Packet::SIZE {
+            for value in [0x01, 0x80, 0xff] {
+                let mut bytes = [0x00; Packet::SIZE];
+                bytes[offset] = value;
+                let mut roundtrip = [0x00; Packet::SIZE];
+                Packet::from_bytes(&bytes).to_bytes(&mut roundtrip);
+                assert_eq!(roundtrip, bytes, "offset {}", offset);
+            }
+        }
+    }
+
+    #[test]
+    fn test_field_offsets() {
+        let packet = Packet::new(0x0792, &[0xaa, 0xbb]).with_remaining_packets(0x0304);
+        let mut bytes = [0x00; Packet::SIZE];
+        packet.to_bytes(&mut bytes);
+        assert_eq!(bytes[0], CommandStatus::New as u8);
+        assert_eq!(bytes[1], packet.id);
+        assert_eq!(bytes[2..4], [0x04, 0x03]);
+        assert_eq!(bytes[4], 0x00);
+        assert_eq!(bytes[5], 2);
+        assert_eq!(bytes[6..8], [0x07, 0x92]);
+        assert_eq!(bytes[8..10], [0xaa, 0xbb]);
+        assert!(bytes[10..88].iter().all(|&b| b == 0));
+        assert_eq!(bytes[88], packet.crc);
+        assert_eq!(bytes[88], bytes[2..88].iter().fold(0, |crc, b| crc ^ b));
+        assert_eq!(bytes[89], 0x00);
     }
 
     #[test]
@@ -204,6 +837,139 @@ mod tests {
         assert_ne!(packet.crc, 0);
     }
 
+    #[test]
+    fn test_packet_sequence() {
+        let data: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        let frames = Packet::sequence(0x0f03, &data);
+        assert_eq!(frames.len(), 3);
+        let remaining: Vec<u16> = frames.iter().map(Packet::remaining_packets).collect();
+        assert_eq!(remaining, [2, 1, 0]);
+        assert_eq!(frames[0].get_args(), &data[..80]);
+        assert_eq!(frames[2].get_args(), &data[160..]);
+        assert!(frames.iter().all(|f| f.crc == f.calculate_crc()));
+
+        let frames = Packet::sequence(0x0f03, &[]);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].remaining_packets(), 0);
+    }
+
+    #[test]
+    fn test_ensure_matches_report_status() {
+        let report = Packet::new(0x0d82, &[0x00, 0x01]);
+        let check = |status: u8| {
+            report
+                .clone()
+                .into_response(status, &[])
+                .ensure_matches_report(&report, &Quirks::NONE)
+        };
+        assert!(check(0x02).is_ok());
+        assert!(matches!(check(0x00), Err(RazerError::UnknownStatus(0x00))));
+        assert!(matches!(check(0x01), Err(RazerError::DeviceBusy)));
+        assert!(matches!(check(0x03), Err(RazerError::CommandFailed)));
+        assert!(matches!(check(0x04), Err(RazerError::CommandTimeout)));
+        assert!(matches!(check(0x05), Err(RazerError::CommandNotSupported)));
+        assert!(matches!(check(0x42), Err(RazerError::UnknownStatus(0x42))));
+    }
+
+    #[test]
+    fn test_ensure_matches_report_mismatch() {
+        let report = Packet::new(0x0d82, &[0x00, 0x01]);
+        let ok = |mut response: Packet| {
+            response.status = CommandStatus::Successful as u8;
+            response.ensure_matches_report(&report, &Quirks::NONE)
+        };
+
+        let mut other_id = report.clone();
+        other_id.id = report.id.wrapping_add(1);
+        assert!(matches!(
+            ok(other_id),
+            Err(RazerError::UnexpectedResponse(_))
+        ));
+
+        let mut other_class = report.clone();
+        other_class.command_class = 0x0e;
+        assert!(matches!(
+            ok(other_class),
+            Err(RazerError::UnexpectedResponse(_))
+        ));
+
+        let mut other_id_byte = report.clone();
+        other_id_byte.command_id = 0x02;
+        assert!(matches!(
+            ok(other_id_byte),
+            Err(RazerError::UnexpectedResponse(_))
+        ));
+    }
+
+    #[test]
+    fn test_mismatch_details() {
+        let mut report = Packet::new(0x0d82, &[0x00, 0x01]);
+        report.id = 0x1f;
+        let mut response = report
+            .clone()
+            .into_response(CommandStatus::Successful as u8, &[]);
+        response.id = 0x20;
+
+        let Err(RazerError::UnexpectedResponse(mismatch)) =
+            response.ensure_matches_report(&report, &Quirks::NONE)
+        else {
+            panic!("expected a mismatch");
+        };
+        assert_eq!(mismatch.sent_id, 0x1f);
+        assert_eq!(mismatch.received_id, 0x20);
+        assert_eq!(mismatch.received_status, 0x02);
+        assert_eq!(mismatch.response.len(), Mismatch::DUMP_LEN);
+        assert!(mismatch
+            .response_hex()
+            .starts_with("02 20 00 00 00 02 0d 82 00 01"));
+        assert!(mismatch.to_string().starts_with(
+            "sent 0x0d82 (id 0x1f, remaining 0), received 0x0d82 (id 0x20, remaining 0, status 0x02)"
+        ));
+    }
+
+    #[test]
+    fn test_ensure_matches_report_sequencing() {
+        let report = Packet::new(0x0f03, &[0x01]).with_remaining_packets(1);
+        let ack = report
+            .clone()
+            .into_response(CommandStatus::Successful as u8, &[]);
+        assert!(ack.ensure_matches_report(&report, &Quirks::NONE).is_ok());
+
+        // a frame with more to follow must be acknowledged with its own count
+        let wrong = ack_with_remaining(&report, 0);
+        assert!(matches!(
+            wrong.ensure_matches_report(&report, &Quirks::NONE),
+            Err(RazerError::UnexpectedResponse(_))
+        ));
+
+        // the last frame's response may announce more data if the device has
+        // the quirk for that command
+        let last = Packet::new(0x0792, &[0x01]);
+        let more = ack_with_remaining(&last, 3);
+        assert!(more
+            .clone()
+            .ensure_matches_report(&last, &Quirks::BLADE_2023)
+            .is_ok());
+        assert!(matches!(
+            more.ensure_matches_report(&last, &Quirks::NONE),
+            Err(RazerError::UnexpectedResponse(_))
+        ));
+        let other = Packet::new(0x0d82, &[0x01]);
+        assert!(ack_with_remaining(&other, 3)
+            .ensure_matches_report(&other, &Quirks::BLADE_2023)
+            .is_err());
+        assert!(ack_with_remaining(&report, 0)
+            .ensure_matches_report(&report, &Quirks::BLADE_2023)
+            .is_err());
+    }
+
+    fn ack_with_remaining(report: &Packet, remaining: u16) -> Packet {
+        report
+            .clone()
+            .into_response(CommandStatus::Successful as u8, &[])
+            .with_remaining_packets(remaining)
+    }
+
     #[test]
     fn test_packet_try_from_invalid_size() {
         let short_data = vec![0u8; 50];
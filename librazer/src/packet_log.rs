@@ -0,0 +1,224 @@
+//! Structured logging of every packet [`Device`](crate::device::Device) sends and
+//! receives.
+//!
+//! Each packet is logged at DEBUG on [`TARGET`] with its direction, command
+//! name, status, data_size and arguments. [`PacketLogMode::Redacted`], the
+//! default, keeps the first [`REDACTED_ARGS`] argument bytes and masks all of
+//! them for commands that return identifying data (see
+//! [`CommandId::identifies_device`]), so logs can be shared in bug reports.
+//! `RAZER_LOG_PACKETS=full|redacted|off` picks the mode, unless
+//! [`set_mode`] already did.
+
+use crate::command::CommandId;
+use crate::packet::Packet;
+use log::{debug, log_enabled, warn, Level};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Environment variable that selects the [`PacketLogMode`].
+pub const LOG_PACKETS_ENV: &str = "RAZER_LOG_PACKETS";
+
+/// Log target of the packet lines, for filters like `RUST_LOG=librazer::packets=debug`.
+pub const TARGET: &str = "librazer::packets";
+
+/// Number of argument bytes kept in [`PacketLogMode::Redacted`].
+pub const REDACTED_ARGS: usize = 8;
+
+/// How much of each packet is logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PacketLogMode {
+    /// Nothing is logged.
+    Off,
+    /// The first [`REDACTED_ARGS`] argument bytes, none for identity commands.
+    #[default]
+    Redacted,
+    /// Every argument byte, serial numbers included.
+    Full,
+}
+
+impl PacketLogMode {
+    const ALL: [PacketLogMode; 3] = [
+        PacketLogMode::Off,
+        PacketLogMode::Redacted,
+        PacketLogMode::Full,
+    ];
+
+    /// Returns the name used by `RAZER_LOG_PACKETS`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            PacketLogMode::Off => "off",
+            PacketLogMode::Redacted => "redacted",
+            PacketLogMode::Full => "full",
+        }
+    }
+}
+
+impl fmt::Display for PacketLogMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PacketLogMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PacketLogMode::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("Invalid packet log mode: {} (full, redacted or off)", s))
+    }
+}
+
+/// Which way a packet went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Direction::Sent => "sent",
+            Direction::Received => "received",
+        })
+    }
+}
+
+/// [`PacketLogMode`] as a `u8`, `UNSET` until read from the environment or set.
+static MODE: AtomicU8 = AtomicU8::new(UNSET);
+const UNSET: u8 = u8::MAX;
+
+/// Returns the mode in effect, reading `RAZER_LOG_PACKETS` the first time.
+pub fn mode() -> PacketLogMode {
+    match MODE.load(Ordering::Relaxed) {
+        UNSET => {
+            let mode = mode_from_env();
+            // a concurrent set_mode wins over the environment
+            let _ = MODE.compare_exchange(UNSET, mode as u8, Ordering::Relaxed, Ordering::Relaxed);
+            PacketLogMode::ALL[MODE.load(Ordering::Relaxed) as usize]
+        }
+        mode => PacketLogMode::ALL[mode as usize],
+    }
+}
+
+/// Overrides `RAZER_LOG_PACKETS` for the rest of the process.
+pub fn set_mode(mode: PacketLogMode) {
+    MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+fn mode_from_env() -> PacketLogMode {
+    match std::env::var(LOG_PACKETS_ENV) {
+        Ok(value) if !value.is_empty() => value.parse().unwrap_or_else(|e| {
+            warn!("{}, using {}", e, PacketLogMode::default());
+            PacketLogMode::default()
+        }),
+        _ => PacketLogMode::default(),
+    }
+}
+
+/// Formats `packet` for `mode`, or returns `None` for [`PacketLogMode::Off`].
+///
+/// ```text
+/// sent GET_SERIAL status=0x00 size=22 args=<redacted>
+/// received GET_BATTERY_CARE status=0x02 size=1 args=d0
+/// ```
+///
+/// Unknown commands show their raw code, and redacted dumps that drop bytes
+/// end with `…`.
+pub fn format(mode: PacketLogMode, direction: Direction, packet: &Packet) -> Option<String> {
+    let command = CommandId::from_raw(packet.command());
+    let name = match command {
+        Some(command) => command.name().to_string(),
+        None => format!("0x{:04x}", packet.command()),
+    };
+    let args = packet.get_args();
+    let shown = match mode {
+        PacketLogMode::Off => return None,
+        PacketLogMode::Full => args,
+        PacketLogMode::Redacted if command.is_some_and(CommandId::identifies_device) => &[],
+        PacketLogMode::Redacted => &args[..args.len().min(REDACTED_ARGS)],
+    };
+
+    let mut line = format!(
+        "{} {} status=0x{:02x} size={} args=",
+        direction,
+        name,
+        packet.status(),
+        packet.data_size()
+    );
+    if shown.is_empty() && !args.is_empty() {
+        line.push_str("<redacted>");
+        return Some(line);
+    }
+    if args.is_empty() {
+        line.push('-');
+    }
+    let bytes: Vec<String> = shown.iter().map(|b| format!("{:02x}", b)).collect();
+    line.push_str(&bytes.join(" "));
+    if shown.len() < args.len() {
+        line.push_str(" …");
+    }
+    Some(line)
+}
+
+/// Logs `packet` under the current [`mode`].
+pub(crate) fn log(direction: Direction, packet: &Packet) {
+    if !log_enabled!(target: TARGET, Level::Debug) {
+        return;
+    }
+    if let Some(line) = format(mode(), direction, packet) {
+        debug!(target: TARGET, "{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::cmd;
+
+    #[test]
+    fn test_identity_args_are_masked() {
+        let serial = Packet::new(cmd::GET_SERIAL, b"BY2150M00000000000042");
+        let line = format(PacketLogMode::Redacted, Direction::Received, &serial).unwrap();
+        assert_eq!(
+            line,
+            "received GET_SERIAL status=0x00 size=21 args=<redacted>"
+        );
+        assert!(!line.contains("42"));
+
+        let full = format(PacketLogMode::Full, Direction::Received, &serial).unwrap();
+        assert!(full.ends_with("30 30 34 32"));
+        assert_eq!(
+            format(PacketLogMode::Off, Direction::Received, &serial),
+            None
+        );
+    }
+
+    #[test]
+    fn test_redacted_truncates_args() {
+        let args: Vec<u8> = (0..12).collect();
+        let packet = Packet::new(0x1234, &args);
+        assert_eq!(
+            format(PacketLogMode::Redacted, Direction::Sent, &packet).unwrap(),
+            "sent 0x1234 status=0x00 size=12 args=00 01 02 03 04 05 06 07 …"
+        );
+        let empty = Packet::new(cmd::GET_PERF_MODE, &[]);
+        assert_eq!(
+            format(PacketLogMode::Redacted, Direction::Sent, &empty).unwrap(),
+            "sent GET_PERF_MODE status=0x00 size=0 args=-"
+        );
+    }
+
+    #[test]
+    fn test_mode_parse() {
+        assert_eq!("FULL".parse(), Ok(PacketLogMode::Full));
+        assert_eq!(" off".parse(), Ok(PacketLogMode::Off));
+        assert!("verbose".parse::<PacketLogMode>().is_err());
+        for mode in PacketLogMode::ALL {
+            assert_eq!(mode.to_string().parse(), Ok(mode));
+        }
+    }
+}
@@ -0,0 +1,394 @@
+//! Why the user may not open a Razer HID device, gathered when
+//! [`RazerError::PermissionDenied`](crate::error::RazerError::PermissionDenied)
+//! is raised so the message can say what to fix on this system.
+
+use serde::Serialize;
+#[cfg(target_os = "linux")]
+use std::fs;
+
+/// USB vendor ID udev rules match on, as it appears in a rule.
+const RAZER_VID_HEX: &str = "1532";
+
+/// Directories udev reads rules from, in the order it applies them.
+#[cfg(target_os = "linux")]
+const UDEV_RULE_DIRS: &[&str] = &[
+    "/etc/udev/rules.d",
+    "/run/udev/rules.d",
+    "/usr/lib/udev/rules.d",
+    "/lib/udev/rules.d",
+];
+
+/// What is known about a refused HID node and the rules that should have
+/// opened it up. Fields that don't apply on this platform are left empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PermissionDetails {
+    /// The HID node that was refused, when known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Permission bits of the node, like `0o600`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    /// Owning user of the node, by name when it resolves.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Owning group of the node, by name when it resolves.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Whether this process is a member of `group`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_group: Option<bool>,
+    /// Whether `/etc/group` lists the user in `group`, which a session started
+    /// before the user was added doesn't pick up.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listed_in_group: Option<bool>,
+    /// udev rule files that mention the Razer vendor ID.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub udev_rules: Vec<String>,
+    /// How those rules grant access, like `uaccess` or `GROUP="plugdev"`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rule_grants: Vec<String>,
+    /// Windows only: whether the process runs as administrator.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elevated: Option<bool>,
+}
+
+impl PermissionDetails {
+    /// Details with only the refused node, without looking at the system.
+    pub fn new(path: Option<String>) -> PermissionDetails {
+        PermissionDetails {
+            path,
+            ..Default::default()
+        }
+    }
+
+    /// Inspects the node at `path`, the user's groups and the installed udev
+    /// rules on Linux, or the process elevation on Windows.
+    pub fn diagnose(path: Option<String>) -> PermissionDetails {
+        let mut details = PermissionDetails::new(path);
+        details.inspect();
+        details
+    }
+
+    #[cfg(target_os = "linux")]
+    fn inspect(&mut self) {
+        use std::os::unix::fs::MetadataExt;
+
+        let groups = fs::read_to_string("/etc/group").unwrap_or_default();
+        if let Some(metadata) = self.path.as_ref().and_then(|p| fs::metadata(p).ok()) {
+            let passwd = fs::read_to_string("/etc/passwd").unwrap_or_default();
+            let status = fs::read_to_string("/proc/self/status").unwrap_or_default();
+            let gid = metadata.gid();
+            self.mode = Some(metadata.mode() & 0o777);
+            self.owner = Some(id_name(&passwd, metadata.uid()));
+            self.group = Some(id_name(&groups, gid));
+            self.in_group = Some(process_gids(&status).contains(&gid));
+            let user = process_uid(&status).and_then(|uid| lookup_name(&passwd, uid));
+            self.listed_in_group =
+                user.map(|user| group_members(&groups, gid).any(|member| member == user));
+        }
+        for dir in UDEV_RULE_DIRS {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            let mut files: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+            files.sort();
+            for file in files {
+                let Ok(contents) = fs::read_to_string(&file) else {
+                    continue;
+                };
+                if let Some(grants) = rule_grants(&contents) {
+                    self.udev_rules.push(file.display().to_string());
+                    for grant in grants {
+                        if !self.rule_grants.contains(&grant) {
+                            self.rule_grants.push(grant);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    fn inspect(&mut self) {
+        use winreg::enums::{HKEY_LOCAL_MACHINE, KEY_WRITE};
+
+        // only administrators may write under HKLM\SOFTWARE
+        let hklm = winreg::RegKey::predef(HKEY_LOCAL_MACHINE);
+        self.elevated = Some(hklm.open_subkey_with_flags("SOFTWARE", KEY_WRITE).is_ok());
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    fn inspect(&mut self) {}
+
+    /// What to do about it, based on what [`diagnose`](Self::diagnose) found.
+    pub fn advice(&self) -> String {
+        if let Some(elevated) = self.elevated {
+            return if elevated {
+                "Another program, like Razer Synapse, may hold the device, or it isn't using \
+                 the standard HID driver"
+                    .to_string()
+            } else {
+                "Run the command from an administrator prompt".to_string()
+            };
+        }
+        if !cfg!(target_os = "linux") {
+            return "Check that no other program holds the device".to_string();
+        }
+        if self.udev_rules.is_empty() {
+            return "No udev rule for Razer devices is installed: copy 99-razer.rules to \
+                    /etc/udev/rules.d, see the README (on NixOS, add it to \
+                    services.udev.extraRules)"
+                .to_string();
+        }
+        let group_rw = self.mode.is_some_and(|mode| mode & 0o060 == 0o060);
+        match (&self.group, self.in_group, self.listed_in_group) {
+            (Some(group), Some(false), Some(true)) if group_rw => format!(
+                "You were added to the {} group after logging in: log out and back in",
+                group
+            ),
+            (Some(group), Some(false), _) if group_rw => format!(
+                "Add yourself to the {0} group (`sudo usermod -aG {0} $USER`), then log in again",
+                group
+            ),
+            _ => "A udev rule is installed but didn't apply: run `sudo udevadm control \
+                  --reload-rules && sudo udevadm trigger` and replug the device"
+                .to_string(),
+        }
+    }
+
+    /// The facts found, one per line, for printing under the error.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let (Some(path), Some(mode)) = (&self.path, self.mode) {
+            lines.push(format!(
+                "Node: {} (mode {:03o}, owner {}:{})",
+                path,
+                mode,
+                self.owner.as_deref().unwrap_or("?"),
+                self.group.as_deref().unwrap_or("?")
+            ));
+        }
+        if let (Some(group), Some(in_group)) = (&self.group, self.in_group) {
+            let membership = match (in_group, self.listed_in_group) {
+                (true, _) => "member",
+                (false, Some(true)) => "listed in /etc/group, but not in this session",
+                (false, _) => "not a member",
+            };
+            lines.push(format!("Group {}: {}", group, membership));
+        }
+        if cfg!(target_os = "linux") {
+            if self.udev_rules.is_empty() {
+                lines.push("udev rules: none mention Razer's vendor ID".to_string());
+            } else {
+                let grants = if self.rule_grants.is_empty() {
+                    "no access grant".to_string()
+                } else {
+                    self.rule_grants.join(", ")
+                };
+                lines.push(format!(
+                    "udev rules: {} ({})",
+                    self.udev_rules.join(", "),
+                    grants
+                ));
+            }
+        }
+        if let Some(elevated) = self.elevated {
+            lines.push(format!(
+                "Administrator: {}",
+                if elevated { "yes" } else { "no" }
+            ));
+        }
+        lines
+    }
+}
+
+/// How the rules in `contents` that match the Razer vendor ID grant access,
+/// or `None` if none match it.
+fn rule_grants(contents: &str) -> Option<Vec<String>> {
+    let mut matched = false;
+    let mut grants = Vec::new();
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('#') || !line.to_lowercase().contains(RAZER_VID_HEX) {
+            continue;
+        }
+        matched = true;
+        for part in line.split(',').map(str::trim) {
+            let grant = if part.starts_with("TAG") && part.contains("uaccess") {
+                "uaccess".to_string()
+            } else if part.starts_with("GROUP=") || part.starts_with("MODE=") {
+                part.replace(' ', "")
+            } else {
+                continue;
+            };
+            if !grants.contains(&grant) {
+                grants.push(grant);
+            }
+        }
+    }
+    matched.then_some(grants)
+}
+
+/// Name of `id` in a passwd or group file, falling back to the number.
+#[cfg(target_os = "linux")]
+fn id_name(file: &str, id: u32) -> String {
+    lookup_name(file, id).map_or_else(|| id.to_string(), str::to_string)
+}
+
+#[cfg(target_os = "linux")]
+fn lookup_name(file: &str, id: u32) -> Option<&str> {
+    file.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        let name = fields.next()?;
+        (fields.nth(1)?.parse() == Ok(id)).then_some(name)
+    })
+}
+
+/// Users listed as members of group `gid` in a group file.
+#[cfg(target_os = "linux")]
+fn group_members(groups: &str, gid: u32) -> impl Iterator<Item = &str> {
+    groups
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() == 4 && fields[2].parse() == Ok(gid))
+        .map(|fields| fields[3])
+        .unwrap_or_default()
+        .split(',')
+        .filter(|member| !member.is_empty())
+}
+
+/// Real uid from `/proc/self/status`.
+#[cfg(target_os = "linux")]
+fn process_uid(status: &str) -> Option<u32> {
+    status_field(status, "Uid:").first().copied()
+}
+
+/// Primary and supplementary gids from `/proc/self/status`.
+#[cfg(target_os = "linux")]
+fn process_gids(status: &str) -> Vec<u32> {
+    let mut gids = status_field(status, "Gid:");
+    gids.truncate(1);
+    gids.extend(status_field(status, "Groups:"));
+    gids
+}
+
+#[cfg(target_os = "linux")]
+fn status_field(status: &str, name: &str) -> Vec<u32> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(name))
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|id| id.parse().ok())
+        .collect()
+}
+
+/// Returns whether `path` is a hidraw node this user may not open read-write.
+#[cfg(target_os = "linux")]
+pub(crate) fn hidraw_access_denied(path: &str) -> bool {
+    path.starts_with("/dev/hidraw")
+        && fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .is_err_and(|e| e.kind() == std::io::ErrorKind::PermissionDenied)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn hidraw_access_denied(_path: &str) -> bool {
+    false
+}
+
+/// Finds a Razer hidraw node that exists but can't be opened, which means the
+/// udev rules are missing rather than the device.
+#[cfg(target_os = "linux")]
+pub(crate) fn inaccessible_razer_hidraw() -> Option<String> {
+    let vendor = format!("HID_ID=0003:0000{}:", RAZER_VID_HEX);
+    fs::read_dir("/sys/class/hidraw")
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            fs::read_to_string(entry.path().join("device/uevent"))
+                .is_ok_and(|uevent| uevent.lines().any(|line| line.starts_with(&vendor)))
+        })
+        .map(|entry| format!("/dev/{}", entry.file_name().to_string_lossy()))
+        .find(|path| hidraw_access_denied(path))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn inaccessible_razer_hidraw() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_grants() {
+        let rules = r#"
+# SUBSYSTEM=="usb", ATTR{idVendor}=="1532", MODE="0600"
+SUBSYSTEM=="usb", ATTR{idVendor}=="1532", MODE="0666"
+SUBSYSTEM=="hidraw", ATTRS{idVendor}=="1532", TAG+="uaccess", GROUP="plugdev"
+SUBSYSTEM=="usb", ATTR{idVendor}=="046d", MODE="0666"
+"#;
+        assert_eq!(
+            rule_grants(rules).unwrap(),
+            vec![r#"MODE="0666""#, "uaccess", r#"GROUP="plugdev""#]
+        );
+        assert_eq!(rule_grants(r#"ATTR{idVendor}=="046d""#), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_account_files() {
+        let groups = "root:x:0:\nplugdev:x:46:alice,bob\n";
+        assert_eq!(id_name(groups, 46), "plugdev");
+        assert_eq!(id_name(groups, 1000), "1000");
+        assert_eq!(
+            group_members(groups, 46).collect::<Vec<_>>(),
+            ["alice", "bob"]
+        );
+        assert_eq!(group_members(groups, 0).count(), 0);
+
+        let status = "Name:\tbhelper\nUid:\t1000\t1000\t1000\t1000\nGid:\t1000\t1000\t1000\t1000\nGroups:\t27 46 \n";
+        assert_eq!(process_uid(status), Some(1000));
+        assert_eq!(process_gids(status), [1000, 27, 46]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_advice() {
+        let details = PermissionDetails::new(Some("/dev/hidraw3".to_string()));
+        assert!(details.advice().starts_with("No udev rule"));
+
+        let details = PermissionDetails {
+            mode: Some(0o660),
+            group: Some("plugdev".to_string()),
+            in_group: Some(false),
+            listed_in_group: Some(false),
+            udev_rules: vec!["/etc/udev/rules.d/99-razer.rules".to_string()],
+            rule_grants: vec![r#"GROUP="plugdev""#.to_string()],
+            ..details
+        };
+        assert!(details.advice().contains("usermod -aG plugdev"));
+        assert!(details
+            .lines()
+            .contains(&"Group plugdev: not a member".to_string()));
+
+        let relogin = PermissionDetails {
+            listed_in_group: Some(true),
+            ..details.clone()
+        };
+        assert!(relogin.advice().contains("log out and back in"));
+
+        let root_only = PermissionDetails {
+            mode: Some(0o600),
+            ..details
+        };
+        assert!(root_only.advice().contains("udevadm"));
+        assert_eq!(
+            root_only.lines()[0],
+            "Node: /dev/hidraw3 (mode 600, owner ?:plugdev)"
+        );
+    }
+}
@@ -0,0 +1,399 @@
+//! Settings saved by name and written back after the EC forgot them.
+//!
+//! The EC drops the manual fan speed and the boosts on every cold boot.
+//! [`SavedSettings`] keeps the settings of a [`DeviceState`] by field name and
+//! parses each value on its own, so a hand-edited or outdated entry only loses
+//! that setting. [`SavedSettings::restore_to`] writes them in an order that meets
+//! the mode requirements and reports what became of each one.
+
+use crate::descriptor::Descriptor;
+use crate::device::CommandTarget;
+use crate::error::{ErrorCode, RazerError, Result};
+use crate::plan;
+use crate::state::{DeviceState, Field};
+use crate::types::FanMode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Settings by [`Field`] name. Values stay untyped until restored, so loading a
+/// file never fails over a single bad entry.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SavedSettings(BTreeMap<String, serde_json::Value>);
+
+impl SavedSettings {
+    /// Keeps every setting `state` has a value for; `zone_mismatch` isn't a setting.
+    pub fn from_state(state: &DeviceState) -> SavedSettings {
+        let settings = Field::ALL
+            .iter()
+            .filter_map(|&field| {
+                let value = serde_json::to_value(state.get(field)?).ok()?;
+                Some((field.name().to_string(), value))
+            })
+            .collect();
+        SavedSettings(settings)
+    }
+
+    /// Returns whether nothing is saved.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the number of saved entries, valid or not.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Keeps only the entries whose name `keep` accepts.
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.0.retain(|name, _| keep(name));
+    }
+
+    /// Returns the settings that parse, and a [`Restored::Skipped`] outcome for
+    /// unknown names and a [`Restored::Failed`] one for invalid values.
+    pub fn parse(&self) -> (DeviceState, Vec<FieldOutcome>) {
+        let mut state = DeviceState::default();
+        let mut report = Vec::new();
+        for (name, value) in &self.0 {
+            let Ok(field) = name.parse::<Field>() else {
+                report.push(FieldOutcome::new(
+                    name,
+                    Restored::skipped("unknown setting"),
+                ));
+                continue;
+            };
+            let single = serde_json::Value::Object(
+                [(field.name().to_string(), value.clone())]
+                    .into_iter()
+                    .collect(),
+            );
+            match serde_json::from_value::<DeviceState>(single) {
+                Ok(parsed) if parsed.get(field).is_some() => state.copy_field(&parsed, field),
+                Ok(_) => report.push(FieldOutcome::new(name, Restored::skipped("no value"))),
+                Err(e) => report.push(FieldOutcome::new(
+                    name,
+                    Restored::Failed(format!("invalid value {}: {}", value, e)),
+                )),
+            }
+        }
+        (state, report)
+    }
+
+    /// Checks every entry against `descriptor` without a device, returning those
+    /// [`restore_to`](Self::restore_to) would skip or fail before sending anything.
+    pub fn check(&self, descriptor: &Descriptor) -> Vec<FieldOutcome> {
+        let (state, mut report) = self.parse();
+        for &field in Field::ALL {
+            if let Some(Err(e)) = state.get(field).map(|value| descriptor.check_value(value)) {
+                report.push(FieldOutcome::new(field.name(), Restored::from(Err(e))));
+            }
+        }
+        report
+    }
+
+    /// Writes every setting that parses and differs from what `device` reports,
+    /// continuing past failures; see [`DeviceState::diff_against`].
+    ///
+    /// The performance mode goes first; if it fails, the fan and boost settings
+    /// that depend on it are skipped. Settings the model doesn't take (see
+    /// [`Descriptor::check_value`]) or that the EC rejects as unsupported are
+    /// skipped rather than failed, except fan speeds outside the model's range.
+    /// Settings the device already has count as restored. Entries that don't
+    /// parse are reported first, the rest in the order they are written.
+    pub fn restore_to(&self, device: &impl CommandTarget) -> Vec<FieldOutcome> {
+        let (parsed, mut report) = self.parse();
+        let info = device.info();
+        let mut outcomes: Vec<(Field, Restored)> = Vec::new();
+        let mut target = DeviceState::default();
+        for &field in Field::ALL {
+            let Some(value) = parsed.get(field) else {
+                continue;
+            };
+            let result = info.check_value(value).and_then(|()| match field {
+                Field::FanRpm if parsed.fan_mode == Some(FanMode::Auto) => Err(
+                    RazerError::PreconditionFailed("fan_mode is saved as Auto".to_string()),
+                ),
+                _ => Ok(()),
+            });
+            match result {
+                Ok(()) => target.copy_field(&parsed, field),
+                Err(e) => outcomes.push((field, Restored::from(Err(e)))),
+            }
+        }
+
+        let current = plan::read_current(device, &target);
+        // a mode that couldn't be set, and why its dependents weren't written
+        let mut blocked: Option<(Field, Restored)> = None;
+        for &value in target.diff_against(&current).writes() {
+            let field = value.field();
+            let outcome = match &blocked {
+                Some((mode, outcome)) if depends_on(field, *mode) => outcome.clone(),
+                _ => Restored::from(value.write_to(device)),
+            };
+            if outcome != Restored::Restored && matches!(field, Field::PerfMode | Field::FanMode) {
+                let wanted = target.get(field).is_some();
+                let blocking = if wanted {
+                    Restored::Skipped(format!("{} wasn't restored", field))
+                } else {
+                    // a mode switch the plan added for another setting
+                    outcome.clone()
+                };
+                blocked = blocked.or(Some((field, blocking)));
+            }
+            if target.get(field).is_some() {
+                outcomes.push((field, outcome));
+            }
+        }
+
+        for field in RESTORE_ORDER {
+            let outcome = match outcomes.iter().position(|(f, _)| *f == field) {
+                Some(index) => outcomes.remove(index).1,
+                None if target.get(field).is_some() => Restored::Restored,
+                None => continue,
+            };
+            report.push(FieldOutcome::new(field.name(), outcome));
+        }
+        report
+    }
+}
+
+/// The order [`SavedSettings::restore_to`] reports in, the order of the writes.
+const RESTORE_ORDER: [Field; 10] = [
+    Field::PerfMode,
+    Field::FanMode,
+    Field::FanRpm,
+    Field::CpuBoost,
+    Field::GpuBoost,
+    Field::MaxFanSpeed,
+    Field::KeyboardBrightness,
+    Field::LogoMode,
+    Field::BatteryCare,
+    Field::LightsAlwaysOn,
+];
+
+/// Whether writing `field` needs `mode`, the performance or fan mode, to be set.
+fn depends_on(field: Field, mode: Field) -> bool {
+    match mode {
+        Field::PerfMode => matches!(
+            field,
+            Field::FanMode | Field::FanRpm | Field::CpuBoost | Field::GpuBoost | Field::MaxFanSpeed
+        ),
+        Field::FanMode => field == Field::FanRpm,
+        _ => false,
+    }
+}
+
+/// What [`SavedSettings::restore_to`] did with one saved entry.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FieldOutcome {
+    /// The entry's name, a [`Field`] name unless it was unknown.
+    pub field: String,
+    #[serde(flatten)]
+    pub outcome: Restored,
+}
+
+impl FieldOutcome {
+    fn new(field: &str, outcome: Restored) -> FieldOutcome {
+        FieldOutcome {
+            field: field.to_string(),
+            outcome,
+        }
+    }
+}
+
+impl fmt::Display for FieldOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.outcome)
+    }
+}
+
+/// Outcome of restoring one setting.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "outcome", content = "reason", rename_all = "snake_case")]
+pub enum Restored {
+    /// The device has the saved value again.
+    Restored,
+    /// Not written, e.g. because the model lacks the feature.
+    Skipped(String),
+    /// Invalid, or rejected by the device.
+    Failed(String),
+}
+
+impl Restored {
+    fn skipped(reason: &str) -> Restored {
+        Restored::Skipped(reason.to_string())
+    }
+
+    /// Returns whether the setting was written or deliberately left alone.
+    pub fn is_ok(&self) -> bool {
+        !matches!(self, Restored::Failed(_))
+    }
+}
+
+impl From<Result<()>> for Restored {
+    fn from(result: Result<()>) -> Restored {
+        match result {
+            Ok(()) => Restored::Restored,
+            Err(e) if e.code() == ErrorCode::Unsupported => Restored::Skipped(e.to_string()),
+            Err(e) => Restored::Failed(e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Restored {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Restored::Restored => f.write_str("restored"),
+            Restored::Skipped(reason) => write!(f, "skipped ({})", reason),
+            Restored::Failed(reason) => write!(f, "failed ({})", reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::cmd;
+    use crate::descriptor::SUPPORTED;
+    use crate::device::Device;
+    use crate::feature;
+    use crate::transport::MockTransport;
+    use crate::types::{
+        BatteryCare, Brightness, CpuBoost, FanRpm, MaxFanSpeedMode, MaybeKnown, PerfMode,
+    };
+
+    fn outcome(report: &[FieldOutcome], field: &str) -> Restored {
+        report
+            .iter()
+            .find(|entry| entry.field == field)
+            .map(|entry| entry.outcome.clone())
+            .unwrap_or_else(|| panic!("{} not in {:?}", field, report))
+    }
+
+    #[test]
+    fn test_roundtrip_keeps_set_fields() {
+        let state = DeviceState {
+            perf_mode: Some(PerfMode::Custom),
+            cpu_boost: Some(MaybeKnown::Known(CpuBoost::Boost)),
+            gpu_boost: Some(MaybeKnown::Unknown(9)),
+            fan_rpm: Some(FanRpm::new(3500).unwrap()),
+            keyboard_brightness: Some(Brightness::new(128)),
+            zone_mismatch: true,
+            ..Default::default()
+        };
+        let saved = SavedSettings::from_state(&state);
+        assert_eq!(saved.len(), 5);
+        let json = serde_json::to_string(&saved).unwrap();
+        let saved: SavedSettings = serde_json::from_str(&json).unwrap();
+        let (parsed, report) = saved.parse();
+        assert!(report.is_empty(), "{:?}", report);
+        assert_eq!(
+            parsed,
+            DeviceState {
+                zone_mismatch: false,
+                ..state
+            }
+        );
+    }
+
+    #[test]
+    fn test_corrupt_entries_are_reported() {
+        let saved: SavedSettings = serde_json::from_str(
+            r#"{"perf_mode": "Turbo", "fan_rpm": 99999, "fan_speed": 1, "logo_mode": null,
+                "keyboard_brightness": 40}"#,
+        )
+        .unwrap();
+        let (state, report) = saved.parse();
+        assert_eq!(state.keyboard_brightness, Some(Brightness::new(40)));
+        assert_eq!(state.perf_mode, None);
+        assert!(matches!(outcome(&report, "perf_mode"), Restored::Failed(_)));
+        assert!(matches!(outcome(&report, "fan_rpm"), Restored::Failed(_)));
+        assert_eq!(
+            outcome(&report, "fan_speed"),
+            Restored::skipped("unknown setting")
+        );
+        assert_eq!(outcome(&report, "logo_mode"), Restored::skipped("no value"));
+    }
+
+    fn mocked(features: &[&'static str]) -> (Device, MockTransport) {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].with_features(features));
+        (device, mock)
+    }
+
+    #[test]
+    fn test_restore_skips_missing_features() {
+        let (device, mock) = mocked(&[feature::PERF, feature::FAN]);
+        mock.push_response(&[0, 0x01, PerfMode::Custom.raw(), FanMode::Auto as u8]);
+        mock.push_response(&[0, 0x02, PerfMode::Custom.raw(), FanMode::Auto as u8]);
+        mock.push_response(&[MaxFanSpeedMode::Enable as u8]);
+        let state = DeviceState {
+            perf_mode: Some(PerfMode::Balanced),
+            fan_mode: Some(FanMode::Auto),
+            max_fan_speed: Some(MaxFanSpeedMode::Disable),
+            keyboard_brightness: Some(Brightness::new(10)),
+            battery_care: Some(BatteryCare::Enable),
+            ..Default::default()
+        };
+        let report = SavedSettings::from_state(&state).restore_to(&device);
+        let fields: Vec<&str> = report.iter().map(|entry| entry.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            [
+                "perf_mode",
+                "fan_mode",
+                "max_fan_speed",
+                "keyboard_brightness",
+                "battery_care"
+            ]
+        );
+        assert!(report[..3].iter().all(|e| e.outcome == Restored::Restored));
+        assert!(report[3..]
+            .iter()
+            .all(|e| matches!(e.outcome, Restored::Skipped(_))));
+        // the performance mode resets the fan and max fan speed on its own
+        let sent: Vec<u16> = mock.sent_commands().into_iter().map(|(c, _)| c).collect();
+        assert_eq!(
+            sent,
+            [
+                cmd::GET_PERF_MODE,
+                cmd::GET_PERF_MODE,
+                cmd::GET_MAX_FAN_SPEED,
+                cmd::SET_PERF_MODE,
+                cmd::SET_PERF_MODE
+            ]
+        );
+
+        // nothing to write the second time
+        let report = SavedSettings::from_state(&state).restore_to(&device);
+        assert!(report[..3].iter().all(|e| e.outcome == Restored::Restored));
+        let sent = mock.sent_commands();
+        assert!(sent[5..].iter().all(|(c, _)| c & 0x80 != 0), "{:?}", sent);
+    }
+
+    #[test]
+    fn test_restore_continues_past_failures() {
+        let (device, mock) = mocked(feature::ALL_FEATURES);
+        mock.push_response(&[0, 0x01, PerfMode::Balanced.raw(), FanMode::Auto as u8]);
+        mock.push_response(&[0, 0x02, PerfMode::Balanced.raw(), FanMode::Auto as u8]);
+        mock.push_response(&[1, 5, 0]);
+        mock.push_status(0x03); // perf mode rejected
+        let state = DeviceState {
+            perf_mode: Some(PerfMode::Custom),
+            cpu_boost: Some(MaybeKnown::Known(CpuBoost::Boost)),
+            fan_rpm: Some(FanRpm::new(4000).unwrap()),
+            keyboard_brightness: Some(Brightness::new(10)),
+            ..Default::default()
+        };
+        let report = SavedSettings::from_state(&state).restore_to(&device);
+        assert!(matches!(outcome(&report, "perf_mode"), Restored::Failed(_)));
+        let skipped = Restored::skipped("perf_mode wasn't restored");
+        assert_eq!(outcome(&report, "cpu_boost"), skipped);
+        assert_eq!(outcome(&report, "fan_rpm"), skipped);
+        assert_eq!(outcome(&report, "keyboard_brightness"), Restored::Restored);
+        let sent: Vec<u16> = mock.sent_commands().into_iter().map(|(c, _)| c).collect();
+        assert!(!sent.contains(&cmd::SET_BOOST));
+        assert_eq!(sent.last(), Some(&cmd::SET_KBD_BRIGHTNESS));
+    }
+}
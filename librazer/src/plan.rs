@@ -0,0 +1,296 @@
+//! The fewest writes that take a device from its current settings to others.
+//!
+//! Re-sending a setting the device already has costs an exchange and, for the
+//! performance mode, resets the fan and the boosts the EC had. A [`ChangePlan`]
+//! holds only the writes that change something, in an order that meets the mode
+//! requirements. A new performance mode is planned first and whatever it resets
+//! is written again after it, and a fan speed switches the fan to Manual. The
+//! performance mode itself is only changed when the target sets it, so a boost
+//! outside Custom mode still fails with [`ModeRequired`](crate::error::RazerError::ModeRequired).
+
+use crate::device::CommandTarget;
+use crate::error::Result;
+use crate::state::{DeviceState, Field, FieldValue};
+use crate::types::{FanMode, MaxFanSpeedMode, PerfMode};
+use serde::Serialize;
+
+/// Ordered writes from one [`DeviceState`] to another, see
+/// [`DeviceState::diff_against`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct ChangePlan(Vec<FieldValue>);
+
+impl ChangePlan {
+    /// Plans the writes from `current` to the settings `target` has a value for.
+    pub(crate) fn new(target: &DeviceState, current: &DeviceState) -> ChangePlan {
+        let mut writes = Vec::new();
+        let mut now = current.clone();
+        let differs = |now: &DeviceState, field: Field| {
+            target
+                .get(field)
+                .is_some_and(|value| now.get(field) != Some(value))
+        };
+        let fan_rpm_differs = |now: &DeviceState| {
+            target.fan_mode != Some(FanMode::Auto) && differs(now, Field::FanRpm)
+        };
+
+        if let Some(mode) = target
+            .perf_mode
+            .filter(|&mode| now.perf_mode != Some(mode) || now.zone_mismatch)
+        {
+            writes.push(FieldValue::PerfMode(mode));
+            // what setting the performance mode leaves behind; max fan speed is
+            // off outside Custom mode, so also right after entering it
+            let was_custom = now.perf_mode.is_none_or(|mode| mode == PerfMode::Custom);
+            now.max_fan_speed =
+                (mode != PerfMode::Custom || !was_custom).then_some(MaxFanSpeedMode::Disable);
+            now.perf_mode = Some(mode);
+            now.fan_mode = Some(FanMode::Auto);
+            now.fan_rpm = None;
+            now.cpu_boost = None;
+            now.gpu_boost = None;
+            now.zone_mismatch = false;
+        }
+
+        if let Some(mode) = target.fan_mode.filter(|_| differs(&now, Field::FanMode)) {
+            writes.push(FieldValue::FanMode(mode));
+            now.fan_mode = Some(mode);
+        }
+        if let Some(rpm) = target.fan_rpm.filter(|_| fan_rpm_differs(&now)) {
+            if now.fan_mode != Some(FanMode::Manual) {
+                writes.push(FieldValue::FanMode(FanMode::Manual));
+            }
+            writes.push(FieldValue::FanRpm(rpm));
+        }
+
+        for field in [
+            Field::CpuBoost,
+            Field::GpuBoost,
+            Field::MaxFanSpeed,
+            Field::KeyboardBrightness,
+            Field::LogoMode,
+            Field::BatteryCare,
+            Field::LightsAlwaysOn,
+        ] {
+            if let Some(value) = target.get(field).filter(|_| differs(&now, field)) {
+                writes.push(value);
+            }
+        }
+        ChangePlan(writes)
+    }
+
+    /// Returns the writes in the order they are sent.
+    pub fn writes(&self) -> &[FieldValue] {
+        &self.0
+    }
+
+    /// Returns whether the device already has every setting.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the number of writes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Sends the writes in order, stopping at the first error.
+    pub fn apply_to(&self, device: &impl CommandTarget) -> Result<()> {
+        self.0.iter().try_for_each(|value| value.write_to(device))
+    }
+}
+
+/// Reads the settings of `device` that planning the way to `target` compares:
+/// those `target` has a value for, and the modes whenever the performance or
+/// fan settings are among them.
+pub(crate) fn read_current(device: &impl CommandTarget, target: &DeviceState) -> DeviceState {
+    let mut fields: Vec<Field> = Field::ALL
+        .iter()
+        .copied()
+        .filter(|&field| target.get(field).is_some())
+        .collect();
+    if fields.iter().any(|&field| THERMAL.contains(&field)) {
+        fields.extend([Field::PerfMode, Field::FanMode]);
+    }
+    DeviceState::read_fields_from(device, device.info().features, &fields)
+}
+
+/// Fields whose writes depend on the performance mode, or are it.
+const THERMAL: [Field; 6] = [
+    Field::PerfMode,
+    Field::FanMode,
+    Field::FanRpm,
+    Field::CpuBoost,
+    Field::GpuBoost,
+    Field::MaxFanSpeed,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Brightness, CpuBoost, FanRpm, GpuBoost, LogoMode, MaybeKnown};
+    use FieldValue as V;
+
+    fn rpm(value: u16) -> FanRpm {
+        FanRpm::new(value).unwrap()
+    }
+
+    fn modes(perf_mode: PerfMode, fan_mode: FanMode) -> DeviceState {
+        DeviceState {
+            perf_mode: Some(perf_mode),
+            fan_mode: Some(fan_mode),
+            max_fan_speed: Some(MaxFanSpeedMode::Disable),
+            ..Default::default()
+        }
+    }
+
+    fn custom(cpu: CpuBoost, gpu: GpuBoost) -> DeviceState {
+        DeviceState {
+            cpu_boost: Some(MaybeKnown::Known(cpu)),
+            gpu_boost: Some(MaybeKnown::Known(gpu)),
+            ..modes(PerfMode::Custom, FanMode::Auto)
+        }
+    }
+
+    #[test]
+    fn test_plan_matrix() {
+        let boost = |cpu| V::CpuBoost(MaybeKnown::Known(cpu));
+        let gpu = |gpu| V::GpuBoost(MaybeKnown::Known(gpu));
+        let manual = |value| DeviceState {
+            fan_rpm: Some(rpm(value)),
+            ..modes(PerfMode::Balanced, FanMode::Manual)
+        };
+        let cases: Vec<(&str, DeviceState, DeviceState, Vec<FieldValue>)> = vec![
+            (
+                "custom with boosts from silent",
+                modes(PerfMode::Silent, FanMode::Auto),
+                custom(CpuBoost::Boost, GpuBoost::High),
+                vec![
+                    V::PerfMode(PerfMode::Custom),
+                    boost(CpuBoost::Boost),
+                    gpu(GpuBoost::High),
+                ],
+            ),
+            (
+                "one boost in custom",
+                custom(CpuBoost::Boost, GpuBoost::High),
+                custom(CpuBoost::Overclock, GpuBoost::High),
+                vec![boost(CpuBoost::Overclock)],
+            ),
+            (
+                "only the brightness",
+                DeviceState {
+                    keyboard_brightness: Some(Brightness::new(10)),
+                    logo_mode: Some(LogoMode::Static),
+                    ..custom(CpuBoost::Boost, GpuBoost::High)
+                },
+                DeviceState {
+                    keyboard_brightness: Some(Brightness::new(20)),
+                    logo_mode: Some(LogoMode::Static),
+                    ..custom(CpuBoost::Boost, GpuBoost::High)
+                },
+                vec![V::KeyboardBrightness(Brightness::new(20))],
+            ),
+            ("nothing differs", manual(3500), manual(3500), vec![]),
+            (
+                "a boost without a mode leaves the mode alone",
+                modes(PerfMode::Balanced, FanMode::Auto),
+                DeviceState {
+                    cpu_boost: Some(MaybeKnown::Known(CpuBoost::Boost)),
+                    ..Default::default()
+                },
+                vec![boost(CpuBoost::Boost)],
+            ),
+            (
+                "a fan speed without modes",
+                modes(PerfMode::Balanced, FanMode::Auto),
+                DeviceState {
+                    fan_rpm: Some(rpm(4000)),
+                    ..Default::default()
+                },
+                vec![V::FanMode(FanMode::Manual), V::FanRpm(rpm(4000))],
+            ),
+            (
+                "a fan speed from custom",
+                custom(CpuBoost::Boost, GpuBoost::High),
+                DeviceState {
+                    fan_rpm: Some(rpm(4000)),
+                    ..modes(PerfMode::Balanced, FanMode::Manual)
+                },
+                vec![
+                    V::PerfMode(PerfMode::Balanced),
+                    V::FanMode(FanMode::Manual),
+                    V::FanRpm(rpm(4000)),
+                ],
+            ),
+            (
+                "another fan speed",
+                manual(3500),
+                manual(4000),
+                vec![V::FanRpm(rpm(4000))],
+            ),
+            (
+                "back to the auto fan",
+                manual(3500),
+                modes(PerfMode::Balanced, FanMode::Auto),
+                vec![V::FanMode(FanMode::Auto)],
+            ),
+            (
+                "a fan speed with the auto fan is left out",
+                manual(3500),
+                DeviceState {
+                    fan_rpm: Some(rpm(4000)),
+                    ..modes(PerfMode::Balanced, FanMode::Auto)
+                },
+                vec![V::FanMode(FanMode::Auto)],
+            ),
+            (
+                "leaving custom turns max fan speed off",
+                DeviceState {
+                    max_fan_speed: Some(MaxFanSpeedMode::Enable),
+                    ..custom(CpuBoost::Boost, GpuBoost::High)
+                },
+                modes(PerfMode::Silent, FanMode::Auto),
+                vec![V::PerfMode(PerfMode::Silent)],
+            ),
+            (
+                "a mode switch forgets the fan speed",
+                manual(3500),
+                DeviceState {
+                    perf_mode: Some(PerfMode::Silent),
+                    ..Default::default()
+                },
+                vec![V::PerfMode(PerfMode::Silent)],
+            ),
+            (
+                "mismatched zones are resynced",
+                DeviceState {
+                    zone_mismatch: true,
+                    ..modes(PerfMode::Balanced, FanMode::Auto)
+                },
+                modes(PerfMode::Balanced, FanMode::Auto),
+                vec![V::PerfMode(PerfMode::Balanced)],
+            ),
+            (
+                "unread settings are written",
+                DeviceState::default(),
+                DeviceState {
+                    perf_mode: Some(PerfMode::Custom),
+                    max_fan_speed: Some(MaxFanSpeedMode::Enable),
+                    logo_mode: Some(LogoMode::Off),
+                    ..Default::default()
+                },
+                vec![
+                    V::PerfMode(PerfMode::Custom),
+                    V::MaxFanSpeed(MaxFanSpeedMode::Enable),
+                    V::LogoMode(LogoMode::Off),
+                ],
+            ),
+        ];
+        for (name, current, target, writes) in cases {
+            let plan = target.diff_against(&current);
+            assert_eq!(plan.writes(), writes, "{}", name);
+            assert_eq!(plan.is_empty(), writes.is_empty(), "{}", name);
+        }
+    }
+}
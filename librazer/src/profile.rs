@@ -0,0 +1,118 @@
+//! Named sets of settings, shared by the CLI, the services and GUIs.
+//!
+//! A [`Profile`] keeps only the settings it was given, by name like
+//! [`SavedSettings`], so it can be captured on one model and applied to another:
+//! [`Profile::validate`] reports what the other model can't take before anything
+//! is sent, [`Profile::strip_unsupported`] drops those settings for good, and
+//! [`Profile::apply`] skips them instead of letting the EC reject them.
+
+use crate::descriptor::Descriptor;
+use crate::device::CommandTarget;
+use crate::persist::{FieldOutcome, SavedSettings};
+use crate::state::DeviceState;
+use serde::{Deserialize, Serialize};
+
+/// A named, partial set of settings.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    /// Model number prefix of the laptop the profile was captured on, empty if
+    /// unknown.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub model: String,
+    /// The settings to apply; the others are left alone.
+    #[serde(default)]
+    pub settings: SavedSettings,
+}
+
+impl Profile {
+    /// A profile of every setting `state` has a value for.
+    pub fn new(name: impl Into<String>, state: &DeviceState) -> Profile {
+        Profile {
+            name: name.into(),
+            model: String::new(),
+            settings: SavedSettings::from_state(state),
+        }
+    }
+
+    /// Reads every setting the device's features cover into a new profile.
+    pub fn capture(name: impl Into<String>, device: &impl CommandTarget) -> Profile {
+        let state = DeviceState::read_from(device, device.info().features);
+        Profile {
+            model: device.info().model_number_prefix().to_string(),
+            ..Profile::new(name, &state)
+        }
+    }
+
+    /// Returns the settings that parse; entries that don't are left out.
+    pub fn state(&self) -> DeviceState {
+        self.settings.parse().0
+    }
+
+    /// Returns the settings `descriptor`'s model can't take and the entries that
+    /// don't parse, empty if the whole profile applies.
+    pub fn validate(&self, descriptor: &Descriptor) -> Vec<FieldOutcome> {
+        self.settings.check(descriptor)
+    }
+
+    /// Removes the settings [`validate`](Self::validate) reports, returning the
+    /// report.
+    pub fn strip_unsupported(&mut self, descriptor: &Descriptor) -> Vec<FieldOutcome> {
+        let report = self.validate(descriptor);
+        self.settings
+            .retain(|name| report.iter().all(|entry| entry.field != name));
+        report
+    }
+
+    /// Applies the settings the device doesn't already have, in an order that
+    /// meets the mode requirements, continuing past failures, and reports each
+    /// setting; see [`SavedSettings::restore_to`].
+    pub fn apply(&self, device: &impl CommandTarget) -> Vec<FieldOutcome> {
+        self.settings.restore_to(device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::SUPPORTED;
+    use crate::feature;
+    use crate::persist::Restored;
+    use crate::types::{CpuBoost, FanRpm, LogoMode, MaybeKnown, PerfMode};
+
+    #[test]
+    fn test_validate_against_another_model() {
+        let profile = Profile::new(
+            "gaming",
+            &DeviceState {
+                perf_mode: Some(PerfMode::Custom),
+                cpu_boost: Some(MaybeKnown::Known(CpuBoost::Overclock)),
+                fan_rpm: Some(FanRpm::new(5000).unwrap()),
+                logo_mode: Some(LogoMode::Static),
+                ..Default::default()
+            },
+        );
+        let text = toml::to_string(&profile).unwrap();
+        assert_eq!(toml::from_str::<Profile>(&text).unwrap(), profile);
+
+        let mut full = SUPPORTED[0].with_features(feature::ALL_FEATURES);
+        full.cpu_boosts = &CpuBoost::ALL;
+        full.fan_rpm_range = 0..=FanRpm::MAX;
+        assert_eq!(profile.validate(&full), []);
+
+        let mut small = SUPPORTED[0].with_features(&[feature::PERF, feature::FAN]);
+        small.cpu_boosts = &[CpuBoost::Low, CpuBoost::Medium];
+        small.fan_rpm_range = 2000..=4500;
+        let report = profile.validate(&small);
+        let fields: Vec<&str> = report.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["cpu_boost", "fan_rpm", "logo_mode"]);
+        assert!(matches!(report[0].outcome, Restored::Skipped(_)));
+        assert!(matches!(report[1].outcome, Restored::Failed(_)));
+        assert!(matches!(report[2].outcome, Restored::Skipped(_)));
+
+        let mut stripped = profile.clone();
+        assert_eq!(stripped.strip_unsupported(&small), report);
+        assert_eq!(stripped.settings.len(), 1);
+        assert_eq!(stripped.validate(&small), []);
+    }
+}
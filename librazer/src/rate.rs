@@ -0,0 +1,370 @@
+//! Token-bucket pacing of the commands sent to a device.
+//!
+//! The EC copes with a few writes a second, but a script setting the keyboard
+//! brightness many times a second for hours can leave it answering Failure
+//! until a reboot. A [`Device`](crate::device::Device) with a [`RateLimiter`]
+//! takes a token for every send, batch or sequence that writes; when the bucket
+//! is empty it waits for the next token, or fails with
+//! [`RazerError::RateLimited`] if that is further off than the limit allows.
+//! Reads are exempt unless given a budget of their own with
+//! [`RateLimiter::with_reads`].
+
+use crate::error::{RazerError, Result};
+use log::{debug, trace};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How fast commands may be sent, and how long a command waits for its turn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Commands per second in the long run. Anything but a positive number
+    /// never refills the bucket, so commands beyond the burst are rejected.
+    pub per_second: f64,
+    /// Commands that may be sent back to back after a quiet period.
+    pub burst: u32,
+    /// Longest a command waits for a token; one that would wait longer fails
+    /// with [`RazerError::RateLimited`]. Zero rejects instead of waiting.
+    pub max_wait: Duration,
+}
+
+impl RateLimit {
+    /// Writes for the services: 10 a second in bursts of 20, waiting up to a second.
+    pub const DEFAULT: RateLimit = RateLimit {
+        per_second: 10.0,
+        burst: 20,
+        max_wait: Duration::from_secs(1),
+    };
+
+    /// `per_second` commands in bursts of `burst`, waiting as long as it takes.
+    pub fn new(per_second: f64, burst: u32) -> RateLimit {
+        RateLimit {
+            per_second,
+            burst,
+            max_wait: Duration::MAX,
+        }
+    }
+
+    /// Sets how long a command waits for a token before failing.
+    pub fn with_max_wait(mut self, max_wait: Duration) -> RateLimit {
+        self.max_wait = max_wait;
+        self
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit::DEFAULT
+    }
+}
+
+/// Source of time for a [`RateLimiter`], replaced in tests.
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+
+    /// Waits for `duration` to pass.
+    fn sleep(&self, duration: Duration);
+}
+
+/// The real clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    limit: RateLimit,
+    /// Below zero while commands wait for tokens they already took.
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit, now: Instant) -> Bucket {
+        Bucket {
+            limit,
+            tokens: f64::from(limit.burst.max(1)),
+            updated: now,
+        }
+    }
+
+    /// Takes a token, returning how long to wait before it may be used.
+    fn take(&mut self, now: Instant) -> Result<Duration> {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        let burst = f64::from(self.limit.burst.max(1));
+        // also false for NaN, whether from the rate or from no time at an infinite one
+        let refill = elapsed * self.limit.per_second;
+        if refill > 0.0 {
+            self.tokens = (self.tokens + refill).min(burst);
+        }
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(Duration::ZERO);
+        }
+        // `None` if the next token never comes or is too far off to wait for
+        let wait = (self.limit.per_second > 0.0)
+            .then(|| Duration::try_from_secs_f64((1.0 - self.tokens) / self.limit.per_second))
+            .and_then(|wait| wait.ok());
+        match wait {
+            Some(wait) if wait <= self.limit.max_wait => {
+                self.tokens -= 1.0;
+                Ok(wait)
+            }
+            _ => Err(RazerError::RateLimited {
+                retry_after: wait.unwrap_or(Duration::MAX),
+            }),
+        }
+    }
+}
+
+/// Token buckets for the writes, and optionally the reads, of one device.
+pub struct RateLimiter {
+    writes: Mutex<Bucket>,
+    reads: Option<Mutex<Bucket>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RateLimiter {
+    /// Limits writes to `writes`; reads are exempt.
+    pub fn new(writes: RateLimit) -> RateLimiter {
+        RateLimiter::with_clock(writes, Arc::new(SystemClock))
+    }
+
+    /// Like [`new`](Self::new), reading the time from `clock`.
+    pub fn with_clock(writes: RateLimit, clock: Arc<dyn Clock>) -> RateLimiter {
+        RateLimiter {
+            writes: Mutex::new(Bucket::new(writes, clock.now())),
+            reads: None,
+            clock,
+        }
+    }
+
+    /// Limits reads to `reads` as well, in a bucket of their own.
+    pub fn with_reads(mut self, reads: RateLimit) -> RateLimiter {
+        self.reads = Some(Mutex::new(Bucket::new(reads, self.clock.now())));
+        self
+    }
+
+    /// Returns the write limit.
+    pub fn writes(&self) -> RateLimit {
+        self.writes.lock().unwrap_or_else(|e| e.into_inner()).limit
+    }
+
+    /// Returns the read limit, `None` if reads are exempt.
+    pub fn reads(&self) -> Option<RateLimit> {
+        let bucket = self.reads.as_ref()?;
+        Some(bucket.lock().unwrap_or_else(|e| e.into_inner()).limit)
+    }
+
+    /// Takes a token for a write, or a read if `write` is false, waiting until
+    /// it may be used.
+    pub fn acquire(&self, write: bool) -> Result<()> {
+        let bucket = match (write, &self.reads) {
+            (true, _) => &self.writes,
+            (false, Some(reads)) => reads,
+            (false, None) => return Ok(()),
+        };
+        let wait = bucket
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take(self.clock.now())
+            .inspect_err(|e| debug!("{}", e))?;
+        if !wait.is_zero() {
+            trace!("Rate limit delays a command by {:?}", wait);
+            self.clock.sleep(wait);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("writes", &self.writes())
+            .field("reads", &self.reads())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command;
+    use crate::descriptor::SUPPORTED;
+    use crate::device::Device;
+    use crate::transport::MockTransport;
+
+    /// Time that only moves when slept through.
+    struct MockClock {
+        now: Mutex<Instant>,
+        slept: Mutex<Vec<Duration>>,
+    }
+
+    impl MockClock {
+        fn new() -> Arc<MockClock> {
+            Arc::new(MockClock {
+                now: Mutex::new(Instant::now()),
+                slept: Mutex::new(Vec::new()),
+            })
+        }
+
+        fn advance(&self, duration: Duration) {
+            *self.now.lock().unwrap() += duration;
+        }
+
+        fn slept(&self) -> Vec<Duration> {
+            std::mem::take(&mut self.slept.lock().unwrap())
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            *self.now.lock().unwrap()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.slept.lock().unwrap().push(duration);
+            self.advance(duration);
+        }
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_writes_are_paced_after_a_burst() {
+        let clock = MockClock::new();
+        let limiter = RateLimiter::with_clock(RateLimit::new(10.0, 3), clock.clone());
+
+        for _ in 0..3 {
+            limiter.acquire(true).unwrap();
+        }
+        assert_eq!(clock.slept(), []);
+        for _ in 0..3 {
+            limiter.acquire(true).unwrap();
+        }
+        assert_eq!(clock.slept(), [ms(100); 3]);
+
+        // a quiet second refills the burst, but no more
+        clock.advance(Duration::from_secs(1));
+        for _ in 0..4 {
+            limiter.acquire(true).unwrap();
+        }
+        assert_eq!(clock.slept(), [ms(100)]);
+
+        // reads are exempt
+        for _ in 0..100 {
+            limiter.acquire(false).unwrap();
+        }
+        assert_eq!(clock.slept(), []);
+    }
+
+    #[test]
+    fn test_excess_writes_are_rejected() {
+        let clock = MockClock::new();
+        let limit = RateLimit::new(4.0, 1).with_max_wait(ms(100));
+        let limiter = RateLimiter::with_clock(limit, clock.clone());
+        limiter.acquire(true).unwrap();
+        let error = limiter.acquire(true).unwrap_err();
+        assert!(
+            matches!(error, RazerError::RateLimited { retry_after } if retry_after == ms(250)),
+            "{}",
+            error
+        );
+        assert!(error.is_retryable());
+        assert_eq!(clock.slept(), []);
+
+        clock.advance(ms(200));
+        limiter.acquire(true).unwrap();
+        assert_eq!(clock.slept(), [ms(50)]);
+    }
+
+    #[test]
+    fn test_rates_that_never_refill_reject_instead_of_waiting() {
+        for per_second in [0.0, -1.0, f64::NAN] {
+            let clock = MockClock::new();
+            let limiter = RateLimiter::with_clock(RateLimit::new(per_second, 2), clock.clone());
+            limiter.acquire(true).unwrap();
+            clock.advance(Duration::from_secs(60));
+            limiter.acquire(true).unwrap();
+            let error = limiter.acquire(true).unwrap_err();
+            assert!(
+                matches!(error, RazerError::RateLimited { retry_after } if retry_after == Duration::MAX),
+                "{}: {}",
+                per_second,
+                error
+            );
+            assert_eq!(clock.slept(), []);
+        }
+
+        // an infinite rate never runs out
+        let clock = MockClock::new();
+        let limiter = RateLimiter::with_clock(RateLimit::new(f64::INFINITY, 1), clock.clone());
+        for _ in 0..3 {
+            limiter.acquire(true).unwrap();
+        }
+        assert_eq!(clock.slept(), []);
+    }
+
+    #[test]
+    fn test_reads_have_their_own_budget() {
+        let clock = MockClock::new();
+        let limiter = RateLimiter::with_clock(RateLimit::new(1.0, 1), clock.clone())
+            .with_reads(RateLimit::new(20.0, 2));
+        limiter.acquire(true).unwrap();
+        for _ in 0..3 {
+            limiter.acquire(false).unwrap();
+        }
+        assert_eq!(clock.slept(), [ms(50)]);
+        limiter.acquire(true).unwrap();
+        assert_eq!(clock.slept(), [ms(950)]);
+    }
+
+    #[test]
+    fn test_device_takes_a_token_per_write_command() {
+        let clock = MockClock::new();
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone()).with_rate_limit(
+            RateLimiter::with_clock(RateLimit::new(5.0, 1), clock.clone()),
+        );
+
+        // two reports in one batch are one command
+        command::set_perf_mode(&device, crate::types::PerfMode::Silent).unwrap();
+        command::get_keyboard_brightness(&device).unwrap();
+        command::set_keyboard_brightness(&device, 10).unwrap();
+        command::set_keyboard_brightness(&device, 20).unwrap();
+        assert_eq!(clock.slept(), [ms(200); 2]);
+        assert_eq!(mock.sent_commands().len(), 5);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_async_sends_are_paced() {
+        use crate::async_device::AsyncDevice;
+        use crate::packet::Packet;
+
+        let clock = MockClock::new();
+        let device = AsyncDevice::new(
+            Device::with_transport(MockTransport::new(), SUPPORTED[0].clone()).with_rate_limit(
+                RateLimiter::with_clock(RateLimit::new(5.0, 1), clock.clone()),
+            ),
+        );
+        for brightness in [10, 20, 30] {
+            let report = Packet::new(0x0303, &[1, 5, brightness]);
+            device.send(report).await.unwrap();
+        }
+        assert_eq!(clock.slept(), [ms(200); 2]);
+    }
+}
@@ -0,0 +1,356 @@
+//! Recorded HID sessions.
+//!
+//! [`Device::start_recording`](crate::device::Device::start_recording) logs every
+//! report/response pair into a [`Recording`], which is saved as JSON. A
+//! [`ReplayTransport`] answers reports from such a file, so a session captured on
+//! someone else's laptop can be run through the command layer locally.
+
+use crate::error::{RazerError, Result};
+use crate::transport::Transport;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// A recorded session, as stored on disk.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Recording {
+    /// Descriptor name of the recorded device.
+    pub device: String,
+    /// USB PID of the recorded device.
+    pub pid: u16,
+    pub exchanges: Vec<Exchange>,
+}
+
+/// One report sent to the device and what came back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exchange {
+    /// Milliseconds since the recording started.
+    pub elapsed_ms: u64,
+    /// Report sent, as hex without the report id byte.
+    pub report: String,
+    /// Response read back, as hex without the report id byte.
+    pub response: Option<String>,
+    /// Status byte of the response.
+    pub status: Option<u8>,
+    /// Transport error, if the report could not be sent or read.
+    pub error: Option<String>,
+}
+
+impl Exchange {
+    /// Returns the command code of the report.
+    pub fn command(&self) -> Option<u16> {
+        let report = from_hex(&self.report).ok()?;
+        Some(u16::from_be_bytes([*report.get(6)?, *report.get(7)?]))
+    }
+}
+
+impl Recording {
+    /// Reads a recording from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Recording> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| RazerError::Other(format!("Failed to read {}: {}", path.display(), e)))?;
+        serde_json::from_str(&json)
+            .map_err(|e| RazerError::Other(format!("Invalid recording {}: {}", path.display(), e)))
+    }
+
+    /// Writes the recording as pretty-printed JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| RazerError::Other(format!("Failed to encode recording: {}", e)))?;
+        std::fs::write(path, json)
+            .map_err(|e| RazerError::Other(format!("Failed to write {}: {}", path.display(), e)))
+    }
+}
+
+/// Handle to a recording in progress. Clones share the recording.
+#[derive(Clone)]
+pub struct Recorder {
+    recording: Arc<Mutex<Recording>>,
+    start: Instant,
+}
+
+impl Recorder {
+    /// Starts an empty recording of `device`. Filled by
+    /// [`Device::start_recording`](crate::device::Device::start_recording), or by
+    /// the exchange events when used as a `tracing` layer.
+    pub fn new(device: &str, pid: u16) -> Self {
+        Self {
+            recording: Arc::new(Mutex::new(Recording {
+                device: device.to_string(),
+                pid,
+                exchanges: Vec::new(),
+            })),
+            start: Instant::now(),
+        }
+    }
+
+    /// Returns a snapshot of everything recorded so far.
+    pub fn recording(&self) -> Recording {
+        self.lock().clone()
+    }
+
+    /// Writes everything recorded so far to `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.lock().save(path)
+    }
+
+    pub(crate) fn record_report(&self, report: &[u8], error: Option<String>) {
+        let elapsed_ms = self.start.elapsed().as_millis() as u64;
+        self.lock().exchanges.push(Exchange {
+            elapsed_ms,
+            report: to_hex(report),
+            response: None,
+            status: None,
+            error,
+        });
+    }
+
+    pub(crate) fn record_response(&self, response: std::result::Result<&[u8], String>) {
+        if let Some(exchange) = self.lock().exchanges.last_mut() {
+            match response {
+                Ok(bytes) => {
+                    exchange.status = bytes.first().copied();
+                    exchange.response = Some(to_hex(bytes));
+                }
+                Err(e) => exchange.error = Some(e),
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Recording> {
+        self.recording.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Records the exchange events of [`instrument`](crate::instrument), so a
+/// recording can be taken without access to the [`Device`](crate::device::Device).
+#[cfg(feature = "tracing")]
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Recorder {
+    fn on_event(&self, event: &tracing::Event<'_>, _: tracing_subscriber::layer::Context<'_, S>) {
+        if event.metadata().target() != crate::instrument::EXCHANGE_TARGET {
+            return;
+        }
+        let mut fields = ExchangeFields::default();
+        event.record(&mut fields);
+        let Some(report) = fields.report else {
+            return;
+        };
+        let status = fields
+            .response
+            .as_ref()
+            .and_then(|response| u8::from_str_radix(response.get(..2)?, 16).ok());
+        let elapsed_ms = self.start.elapsed().as_millis() as u64;
+        self.lock().exchanges.push(Exchange {
+            elapsed_ms,
+            report,
+            response: fields.response,
+            status,
+            error: fields.error,
+        });
+    }
+}
+
+/// Fields of an exchange event.
+#[cfg(feature = "tracing")]
+#[derive(Default)]
+struct ExchangeFields {
+    report: Option<String>,
+    response: Option<String>,
+    error: Option<String>,
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::field::Visit for ExchangeFields {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        let value = Some(format!("{:?}", value));
+        match field.name() {
+            "report" => self.report = value,
+            "response" => self.response = value,
+            "error" => self.error = value,
+            _ => {}
+        }
+    }
+}
+
+struct ReplayState {
+    remaining: VecDeque<Exchange>,
+    last_report: Option<Vec<u8>>,
+}
+
+/// Transport that answers reports with the responses from a [`Recording`].
+///
+/// Each read is answered by the next unused exchange with the same command, with
+/// the transaction id rewritten to match the report just sent.
+pub struct ReplayTransport {
+    state: Mutex<ReplayState>,
+}
+
+impl ReplayTransport {
+    pub fn new(recording: &Recording) -> Self {
+        Self {
+            state: Mutex::new(ReplayState {
+                remaining: recording.exchanges.iter().cloned().collect(),
+                last_report: None,
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ReplayState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send_feature_report(&self, data: &[u8]) -> Result<()> {
+        // skip report id byte
+        self.lock().last_report = Some(data[1..].to_vec());
+        Ok(())
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut state = self.lock();
+        let report = state
+            .last_report
+            .clone()
+            .ok_or_else(|| RazerError::Other("No report sent to replay transport".to_string()))?;
+        let command = u16::from_be_bytes([report[6], report[7]]);
+
+        let exchange = state
+            .remaining
+            .iter()
+            .position(|exchange| exchange.command() == Some(command))
+            .and_then(|index| state.remaining.remove(index))
+            .ok_or_else(|| {
+                RazerError::Other(format!(
+                    "No recorded response for command 0x{:04X}",
+                    command
+                ))
+            })?;
+
+        let mut response = match (exchange.response, exchange.error) {
+            (Some(response), _) => from_hex(&response)?,
+            (None, Some(error)) => return Err(RazerError::Other(error)),
+            (None, None) => {
+                return Err(RazerError::Other(format!(
+                    "Recorded command 0x{:04X} has no response",
+                    command
+                )))
+            }
+        };
+        // transaction id is not covered by the CRC
+        response[1] = report[1];
+
+        buf[1..=response.len()].copy_from_slice(&response);
+        Ok(1 + response.len())
+    }
+
+    fn sleep(&self, _duration: Duration) {}
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(RazerError::Other(format!("Invalid hex string: {}", hex)));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| RazerError::Other(format!("Invalid hex string: {}", hex)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command;
+    use crate::descriptor::SUPPORTED;
+    use crate::device::Device;
+    use crate::transport::MockTransport;
+    use crate::types::{BatteryCare, PerfMode};
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let bytes = [0x00, 0x1f, 0xa0, 0xff];
+        assert_eq!(to_hex(&bytes), "001fa0ff");
+        assert_eq!(from_hex("001fa0ff").unwrap(), bytes);
+        assert!(from_hex("0g").is_err());
+        assert!(from_hex("abc").is_err());
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let mock = MockTransport::new();
+        let mut device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        let recorder = device.start_recording();
+
+        mock.push_response(&[0xd0]);
+        mock.push_response(&[0, 1, 4, 0]);
+        mock.push_response(&[0, 2, 4, 0]);
+        assert_eq!(
+            command::get_battery_care(&device).unwrap(),
+            BatteryCare::Enable
+        );
+        command::get_perf_mode(&device).unwrap();
+
+        let recording = recorder.recording();
+        assert_eq!(recording.pid, SUPPORTED[0].pid);
+        assert_eq!(recording.exchanges.len(), 3);
+        assert_eq!(recording.exchanges[0].command(), Some(0x0792));
+        assert_eq!(recording.exchanges[0].status, Some(0x02));
+
+        let path =
+            std::env::temp_dir().join(format!("librazer-replay-{}.json", std::process::id()));
+        recording.save(&path).unwrap();
+        let loaded = Recording::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // replayed out of order, answered by command
+        let replay = Device::with_transport(ReplayTransport::new(&loaded), SUPPORTED[0].clone());
+        assert_eq!(command::get_perf_mode(&replay).unwrap().0, PerfMode::Custom);
+        assert_eq!(
+            command::get_battery_care(&replay).unwrap(),
+            BatteryCare::Enable
+        );
+        assert!(command::get_battery_care(&replay).is_err());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_record_from_trace_events() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        let recorder = Recorder::new(SUPPORTED[0].name, SUPPORTED[0].pid);
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+
+        mock.push_response(&[0xd0]);
+        mock.push_response(&[0, 1, 4, 0]);
+        mock.push_response(&[0, 2, 4, 0]);
+        tracing::subscriber::with_default(subscriber, || {
+            command::get_battery_care(&device).unwrap();
+            command::get_perf_mode(&device).unwrap();
+        });
+
+        let recording = recorder.recording();
+        assert_eq!(recording.exchanges.len(), 3);
+        assert_eq!(recording.exchanges[0].command(), Some(0x0792));
+        assert_eq!(recording.exchanges[0].status, Some(0x02));
+
+        let replay = Device::with_transport(ReplayTransport::new(&recording), SUPPORTED[0].clone());
+        assert_eq!(command::get_perf_mode(&replay).unwrap().0, PerfMode::Custom);
+        assert_eq!(
+            command::get_battery_care(&replay).unwrap(),
+            BatteryCare::Enable
+        );
+    }
+}
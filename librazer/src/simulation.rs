@@ -0,0 +1,407 @@
+//! In-memory simulated laptop, enabled by the `simulation` feature.
+//!
+//! [`SimulatedTransport`] keeps the state a real Blade would (performance mode per
+//! thermal zone, boosts, fans, lighting, battery care) and answers every command in
+//! [`command`](crate::command) like the firmware does, including Failure for
+//! unmet preconditions, NotSupported for features the descriptor doesn't declare
+//! and an occasional Busy. Setting `RAZER_SIMULATE=1` makes
+//! [`Device::detect`] return [`device()`] so the CLI runs without hardware.
+
+use crate::command::cmd;
+use crate::descriptor::{
+    command_feature, Descriptor, HealthCheck, LightingCapability, Quirks, Timing,
+    DEFAULT_FAN_RPM_RANGE,
+};
+use crate::device::Device;
+use crate::error::{RazerError, Result};
+use crate::feature;
+use crate::packet::{CommandStatus, Packet};
+use crate::transport::Transport;
+use crate::types::{ChargeLimit, ChargeLimitEncoding, CpuBoost, GpuBoost, PerfMode};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Environment variable that switches detection to the simulated device.
+pub const SIMULATE_ENV: &str = "RAZER_SIMULATE";
+
+/// Descriptor of the simulated laptop, declaring every feature.
+pub const SIMULATED: Descriptor = Descriptor {
+    model_number_prefixes: &["SIMULATED"],
+    name: "Razer Blade (simulated)",
+    pid: 0x0000,
+    features: feature::ALL_FEATURES,
+    timing: Timing {
+        pre_send_us: 0,
+        pre_read_us: 0,
+    },
+    health_check: HealthCheck::DEFAULT,
+    quirks: Quirks::NONE,
+    report_id: 0x00,
+    fan_rpm_range: DEFAULT_FAN_RPM_RANGE,
+    perf_modes: &PerfMode::ALL,
+    cpu_boosts: &CpuBoost::ALL,
+    gpu_boosts: &GpuBoost::ALL,
+    lighting: LightingCapability::BrightnessOnly,
+    charge_limit_encoding: ChargeLimitEncoding::FlaggedPercent,
+    notes: "In-memory simulation",
+    source_url: "",
+};
+
+/// Returns whether `RAZER_SIMULATE` asks for the simulated device.
+pub fn enabled() -> bool {
+    std::env::var(SIMULATE_ENV).is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Opens the simulated laptop.
+pub fn device() -> Device {
+    Device::with_transport(SimulatedTransport::new(SIMULATED.features), SIMULATED)
+}
+
+const PERF_BALANCED: u8 = 0;
+const PERF_CUSTOM: u8 = 4;
+const PERF_SILENT: u8 = 5;
+const FAN_AUTO: u8 = 0;
+const FAN_MANUAL: u8 = 1;
+/// Reported speed in automatic fan mode, in hundreds of RPM.
+const AUTO_FAN_RPM: u8 = 32;
+
+struct SimState {
+    features: &'static [&'static str],
+    /// (perf mode, fan mode) per thermal zone
+    perf: [(u8, u8); 2],
+    /// CPU and GPU boost
+    boost: [u8; 2],
+    /// Manual fan speed per zone, in hundreds of RPM
+    fan_rpm: [u8; 2],
+    max_fan_speed: u8,
+    logo_power: u8,
+    logo_mode: u8,
+    kbd_brightness: u8,
+    lights_always_on: u8,
+    battery_care: u8,
+    /// Response to the last report, returned by the next read.
+    response: Option<Packet>,
+    /// Whether the next read reports Busy before the real response.
+    busy: bool,
+    exchanges: u32,
+    busy_every: u32,
+}
+
+/// Transport backed by an in-memory laptop.
+pub struct SimulatedTransport {
+    state: Mutex<SimState>,
+}
+
+impl SimulatedTransport {
+    /// Creates a laptop in Balanced mode that supports `features`.
+    ///
+    /// Every 25th command first answers Busy, like firmware that is read too early.
+    pub fn new(features: &'static [&'static str]) -> Self {
+        Self {
+            state: Mutex::new(SimState {
+                features,
+                perf: [(PERF_BALANCED, FAN_AUTO); 2],
+                boost: [0; 2],
+                fan_rpm: [AUTO_FAN_RPM; 2],
+                max_fan_speed: 0,
+                logo_power: 1,
+                logo_mode: 0,
+                kbd_brightness: 128,
+                lights_always_on: 0,
+                battery_care: 0x50,
+                response: None,
+                busy: false,
+                exchanges: 0,
+                busy_every: 25,
+            }),
+        }
+    }
+
+    /// Answers Busy once every `n` commands, or never if `n` is 0.
+    pub fn with_busy_every(self, n: u32) -> Self {
+        self.lock().busy_every = n;
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SimState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Index of a thermal/fan zone or boost cluster argument (1 or 2).
+fn zone_index(zone: u8) -> Option<usize> {
+    matches!(zone, 1 | 2).then(|| zone as usize - 1)
+}
+
+impl SimState {
+    /// Applies `request` and returns the status and the arguments to reply with.
+    fn handle(&mut self, request: &Packet) -> (CommandStatus, Vec<u8>) {
+        use CommandStatus::{Failure, NotSupported, Successful};
+
+        let command = request.command();
+        if let Some(feature) = command_feature(command) {
+            if !self.features.contains(&feature) {
+                return (NotSupported, vec![]);
+            }
+        } else if !matches!(command, cmd::GET_FIRMWARE_VERSION | cmd::GET_SERIAL) {
+            return (NotSupported, vec![]);
+        }
+
+        let args = request.get_args();
+        let arg = |i: usize| args.get(i).copied().unwrap_or(0);
+        let all_custom = self.perf.iter().all(|&(perf, _)| perf == PERF_CUSTOM);
+
+        match command {
+            cmd::GET_FIRMWARE_VERSION => (Successful, vec![1, 0]),
+            cmd::GET_SERIAL => (Successful, b"SIMULATED000001".to_vec()),
+            cmd::GET_PERF_MODE => match zone_index(arg(1)) {
+                Some(zone) => (
+                    Successful,
+                    vec![0, arg(1), self.perf[zone].0, self.perf[zone].1],
+                ),
+                None => (Failure, vec![]),
+            },
+            cmd::SET_PERF_MODE => {
+                let (perf, fan) = (arg(2), arg(3));
+                let valid = matches!(perf, PERF_BALANCED | PERF_CUSTOM | PERF_SILENT)
+                    && matches!(fan, FAN_AUTO | FAN_MANUAL)
+                    && (fan == FAN_AUTO || perf == PERF_BALANCED);
+                match zone_index(arg(1)) {
+                    Some(zone) if valid => {
+                        self.perf[zone] = (perf, fan);
+                        if fan == FAN_AUTO {
+                            self.fan_rpm[zone] = AUTO_FAN_RPM;
+                        }
+                        if perf != PERF_CUSTOM {
+                            self.max_fan_speed = 0;
+                        }
+                        (Successful, vec![])
+                    }
+                    _ => (Failure, vec![]),
+                }
+            }
+            cmd::GET_BOOST => match zone_index(arg(1)) {
+                Some(cluster) => (Successful, vec![0, arg(1), self.boost[cluster]]),
+                None => (Failure, vec![]),
+            },
+            cmd::SET_BOOST => {
+                let max = if arg(1) == 1 { 4 } else { 2 };
+                match zone_index(arg(1)) {
+                    Some(cluster) if all_custom && arg(2) <= max => {
+                        self.boost[cluster] = arg(2);
+                        (Successful, vec![])
+                    }
+                    _ => (Failure, vec![]),
+                }
+            }
+            cmd::GET_FAN_RPM => match zone_index(arg(1)) {
+                Some(zone) => (Successful, vec![0, arg(1), self.fan_rpm[zone]]),
+                None => (Failure, vec![]),
+            },
+            cmd::SET_FAN_RPM => match zone_index(arg(1)) {
+                Some(zone)
+                    if self.perf[zone] == (PERF_BALANCED, FAN_MANUAL)
+                        && (20..=50).contains(&arg(2)) =>
+                {
+                    self.fan_rpm[zone] = arg(2);
+                    (Successful, vec![])
+                }
+                _ => (Failure, vec![]),
+            },
+            cmd::GET_MAX_FAN_SPEED => (Successful, vec![self.max_fan_speed]),
+            cmd::SET_MAX_FAN_SPEED if all_custom && matches!(arg(0), 0 | 2) => {
+                self.max_fan_speed = arg(0);
+                (Successful, vec![])
+            }
+            cmd::GET_LOGO_POWER => (Successful, vec![1, 4, self.logo_power]),
+            cmd::SET_LOGO_POWER if matches!(arg(2), 0 | 1) => {
+                self.logo_power = arg(2);
+                (Successful, vec![])
+            }
+            cmd::GET_LOGO_MODE => (Successful, vec![1, 4, self.logo_mode]),
+            cmd::SET_LOGO_MODE if matches!(arg(2), 0 | 2) => {
+                self.logo_mode = arg(2);
+                (Successful, vec![])
+            }
+            cmd::GET_KBD_BRIGHTNESS => (Successful, vec![1, 5, self.kbd_brightness]),
+            cmd::SET_KBD_BRIGHTNESS => {
+                self.kbd_brightness = arg(2);
+                (Successful, vec![])
+            }
+            cmd::GET_LIGHTS_ALWAYS_ON => (Successful, vec![self.lights_always_on, 0]),
+            cmd::SET_LIGHTS_ALWAYS_ON if matches!(arg(0), 0x00 | 0x03) => {
+                self.lights_always_on = arg(0);
+                (Successful, vec![])
+            }
+            cmd::GET_BATTERY_CARE => (Successful, vec![self.battery_care]),
+            cmd::SET_BATTERY_CARE
+                if ChargeLimit::from_wire(arg(0), ChargeLimitEncoding::FlaggedPercent).is_ok() =>
+            {
+                self.battery_care = arg(0);
+                (Successful, vec![])
+            }
+            _ => (Failure, vec![]),
+        }
+    }
+}
+
+impl Transport for SimulatedTransport {
+    fn send_feature_report(&self, data: &[u8]) -> Result<()> {
+        // skip report id byte
+        let request = Packet::try_from(&data[1..])?;
+        let mut state = self.lock();
+        let (status, args) = state.handle(&request);
+        state.response = Some(request.into_response(status as u8, &args));
+        state.exchanges += 1;
+        state.busy = state.busy_every != 0 && state.exchanges.is_multiple_of(state.busy_every);
+        Ok(())
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut state = self.lock();
+        let response = state
+            .response
+            .clone()
+            .ok_or_else(|| RazerError::Other("No report sent to simulated device".to_string()))?;
+        let response = if std::mem::take(&mut state.busy) {
+            response.into_response(CommandStatus::Busy as u8, &[])
+        } else {
+            response
+        };
+
+        let bytes: Vec<u8> = (&response).into();
+        buf[1..=bytes.len()].copy_from_slice(&bytes);
+        Ok(1 + bytes.len())
+    }
+
+    fn sleep(&self, _duration: Duration) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command;
+    use crate::device::SendOptions;
+    use crate::types::{
+        BatteryCare, Brightness, CpuBoost, FanMode, FanRpm, FanZone, GpuBoost, LightsAlwaysOn,
+        LogoMode, MaxFanSpeedMode, PerfMode,
+    };
+
+    fn simulated(features: &'static [&'static str]) -> Device {
+        let transport = SimulatedTransport::new(features).with_busy_every(0);
+        Device::with_transport(transport, SIMULATED)
+    }
+
+    #[test]
+    fn test_perf_and_boosts() {
+        let device = simulated(SIMULATED.features);
+        assert_eq!(
+            command::get_perf_mode(&device).unwrap(),
+            (PerfMode::Balanced, FanMode::Auto)
+        );
+
+        assert!(matches!(
+            command::set_cpu_boost(&device, CpuBoost::Boost),
+            Err(RazerError::ModeRequired { .. })
+        ));
+        // the firmware enforces the precondition too
+        assert!(matches!(
+            device
+                .send(Packet::new(cmd::SET_BOOST, &[0, 1, 3]))
+                .map_err(RazerError::into_root),
+            Err(RazerError::CommandFailed)
+        ));
+
+        command::set_perf_mode(&device, PerfMode::Custom).unwrap();
+        command::set_cpu_boost(&device, CpuBoost::Overclock).unwrap();
+        command::set_gpu_boost(&device, GpuBoost::High).unwrap();
+        assert_eq!(
+            command::get_boosts(&device).unwrap(),
+            (CpuBoost::Overclock.into(), GpuBoost::High.into())
+        );
+
+        command::set_max_fan_speed_mode(&device, MaxFanSpeedMode::Enable).unwrap();
+        assert_eq!(
+            command::get_max_fan_speed_mode(&device).unwrap(),
+            MaxFanSpeedMode::Enable
+        );
+    }
+
+    #[test]
+    fn test_fan_rpm() {
+        let device = simulated(SIMULATED.features);
+        assert_eq!(
+            command::get_fan_rpm(&device, FanZone::Zone1).unwrap(),
+            FanRpm::new(3200).unwrap()
+        );
+        command::set_fan_mode(&device, FanMode::Manual).unwrap();
+        command::set_fan_rpm(&device, FanRpm::new(4500).unwrap()).unwrap();
+        assert_eq!(
+            command::get_fan_rpm(&device, FanZone::Zone2).unwrap(),
+            FanRpm::new(4500).unwrap()
+        );
+
+        command::set_perf_mode(&device, PerfMode::Silent).unwrap();
+        assert_eq!(
+            command::get_fan_rpm(&device, FanZone::Zone1).unwrap(),
+            FanRpm::new(3200).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_lighting_and_battery() {
+        let device = simulated(SIMULATED.features);
+        command::set_keyboard_brightness(&device, 42).unwrap();
+        assert_eq!(
+            command::get_keyboard_brightness(&device).unwrap(),
+            Brightness::new(42)
+        );
+
+        for mode in [LogoMode::Breathing, LogoMode::Off, LogoMode::Static] {
+            command::set_logo_mode(&device, mode).unwrap();
+            assert_eq!(command::get_logo_mode(&device).unwrap(), mode);
+        }
+
+        command::set_lights_always_on(&device, LightsAlwaysOn::Enable).unwrap();
+        assert_eq!(
+            command::get_lights_always_on(&device).unwrap(),
+            LightsAlwaysOn::Enable
+        );
+
+        command::set_battery_care(&device, BatteryCare::Enable).unwrap();
+        assert_eq!(
+            command::get_battery_care(&device).unwrap(),
+            BatteryCare::Enable
+        );
+    }
+
+    #[test]
+    fn test_undeclared_feature_not_supported() {
+        let device = simulated(&[feature::PERF]);
+        assert!(matches!(
+            command::get_keyboard_brightness(&device),
+            Err(RazerError::CommandNotSupported)
+        ));
+        assert_eq!(device.probe_features().supported, vec![feature::PERF]);
+    }
+
+    #[test]
+    fn test_occasional_busy() {
+        let transport = SimulatedTransport::new(SIMULATED.features).with_busy_every(2);
+        let mut device = Device::with_transport(transport, SIMULATED);
+        device.set_send_options(SendOptions {
+            busy_budget: Duration::ZERO,
+            ..SendOptions::DEFAULT
+        });
+        command::get_battery_care(&device).unwrap();
+        assert!(matches!(
+            command::get_battery_care(&device),
+            Err(RazerError::DeviceBusy)
+        ));
+
+        // the default budget reads the response again
+        device.set_send_options(SendOptions::DEFAULT);
+        command::get_battery_care(&device).unwrap();
+        command::get_battery_care(&device).unwrap();
+    }
+}
@@ -0,0 +1,482 @@
+//! Snapshot of every readable setting, shared by the helpers so a new setting
+//! is added in one place.
+
+use crate::command;
+use crate::device::CommandTarget;
+use crate::error::{RazerError, Result};
+use crate::feature;
+use crate::plan::{self, ChangePlan};
+use crate::types::{
+    BatteryCare, Brightness, CpuBoost, FanMode, FanRpm, FanZone, GpuBoost, LightsAlwaysOn,
+    LogoMode, MaxFanSpeedMode, MaybeKnown, PerfMode,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Declares [`Field`], [`FieldValue`] and [`DeviceState::get`] from the
+/// `Option` members of [`DeviceState`].
+macro_rules! fields {
+    ($($variant:ident($value:ty) => $member:ident;)*) => {
+        /// A setting of [`DeviceState`], named like its member.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        pub enum Field {
+            $($variant,)*
+        }
+
+        impl Field {
+            /// Every field, in [`DeviceState`] order.
+            pub const ALL: &'static [Field] = &[$(Field::$variant,)*];
+
+            /// Returns the member name, e.g. `keyboard_brightness`.
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $(Field::$variant => stringify!($member),)*
+                }
+            }
+        }
+
+        /// The value of one [`Field`].
+        #[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+        #[serde(untagged)]
+        pub enum FieldValue {
+            $($variant($value),)*
+        }
+
+        impl FieldValue {
+            /// Returns the field this is a value of.
+            pub const fn field(&self) -> Field {
+                match self {
+                    $(FieldValue::$variant(_) => Field::$variant,)*
+                }
+            }
+        }
+
+        impl fmt::Display for FieldValue {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    $(FieldValue::$variant(value) => value.fmt(f),)*
+                }
+            }
+        }
+
+        impl DeviceState {
+            /// Returns the value of `field`, `None` where it wasn't read.
+            pub fn get(&self, field: Field) -> Option<FieldValue> {
+                match field {
+                    $(Field::$variant => self.$member.map(FieldValue::$variant),)*
+                }
+            }
+
+            /// Copies the value of `field` from `other`.
+            pub(crate) fn copy_field(&mut self, other: &DeviceState, field: Field) {
+                match field {
+                    $(Field::$variant => self.$member = other.$member,)*
+                }
+            }
+        }
+    };
+}
+
+fields! {
+    PerfMode(PerfMode) => perf_mode;
+    FanMode(FanMode) => fan_mode;
+    CpuBoost(MaybeKnown<CpuBoost>) => cpu_boost;
+    GpuBoost(MaybeKnown<GpuBoost>) => gpu_boost;
+    FanRpm(FanRpm) => fan_rpm;
+    MaxFanSpeed(MaxFanSpeedMode) => max_fan_speed;
+    KeyboardBrightness(Brightness) => keyboard_brightness;
+    LogoMode(LogoMode) => logo_mode;
+    BatteryCare(BatteryCare) => battery_care;
+    LightsAlwaysOn(LightsAlwaysOn) => lights_always_on;
+}
+
+impl FieldValue {
+    /// Sends the command that sets this value, without switching modes first.
+    pub fn write_to(self, device: &impl CommandTarget) -> Result<()> {
+        match self {
+            FieldValue::PerfMode(mode) => command::set_perf_mode(device, mode),
+            FieldValue::FanMode(mode) => command::set_fan_mode(device, mode),
+            FieldValue::CpuBoost(boost) => command::set_cpu_boost(device, boost.into_known()?),
+            FieldValue::GpuBoost(boost) => command::set_gpu_boost(device, boost.into_known()?),
+            FieldValue::FanRpm(rpm) => command::set_fan_rpm(device, rpm),
+            FieldValue::MaxFanSpeed(mode) => command::set_max_fan_speed_mode(device, mode),
+            FieldValue::KeyboardBrightness(brightness) => {
+                command::set_keyboard_brightness(device, brightness)
+            }
+            FieldValue::LogoMode(mode) => command::set_logo_mode(device, mode),
+            FieldValue::BatteryCare(mode) => command::set_battery_care(device, mode),
+            FieldValue::LightsAlwaysOn(mode) => command::set_lights_always_on(device, mode),
+        }
+    }
+}
+
+impl Field {
+    /// Returns the [`feature`] a model needs for this field.
+    pub const fn feature(self) -> &'static str {
+        match self {
+            Field::PerfMode | Field::CpuBoost | Field::GpuBoost => feature::PERF,
+            Field::FanMode | Field::FanRpm | Field::MaxFanSpeed => feature::FAN,
+            Field::KeyboardBrightness => feature::KBDBACKLIGHT,
+            Field::LogoMode => feature::LIDLOGO,
+            Field::BatteryCare => feature::BATTERYCARE,
+            Field::LightsAlwaysOn => feature::LIGHTSALWAYSON,
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl std::str::FromStr for Field {
+    type Err = RazerError;
+
+    /// Parses a member name; dashes may replace the underscores.
+    fn from_str(name: &str) -> Result<Self> {
+        let name = name.trim().replace('-', "_");
+        Field::ALL
+            .iter()
+            .copied()
+            .find(|field| field.name().eq_ignore_ascii_case(&name))
+            .ok_or(RazerError::InvalidName {
+                name,
+                type_name: "Field",
+            })
+    }
+}
+
+/// Settings read from a device, `None` where a read failed or the model lacks the
+/// feature. When the thermal zones disagree the first zone's modes are reported and
+/// `zone_mismatch` is set. Serializes losslessly, so it can be written to JSON or TOML and read
+/// back; `status --json` prints it as is.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceState {
+    pub perf_mode: Option<PerfMode>,
+    pub fan_mode: Option<FanMode>,
+    pub cpu_boost: Option<MaybeKnown<CpuBoost>>,
+    pub gpu_boost: Option<MaybeKnown<GpuBoost>>,
+    pub fan_rpm: Option<FanRpm>,
+    pub max_fan_speed: Option<MaxFanSpeedMode>,
+    pub keyboard_brightness: Option<Brightness>,
+    pub logo_mode: Option<LogoMode>,
+    pub battery_care: Option<BatteryCare>,
+    pub lights_always_on: Option<LightsAlwaysOn>,
+    /// The thermal zones reported different modes; setting the performance mode
+    /// resyncs them.
+    #[serde(default)]
+    pub zone_mismatch: bool,
+}
+
+impl DeviceState {
+    /// Reads every setting `features` covers. Boosts are only read in Custom mode
+    /// and the fan speed only in Manual fan mode, where they mean something.
+    pub fn read_from(device: &impl CommandTarget, features: &[&str]) -> DeviceState {
+        DeviceState::read_fields_from(device, features, Field::ALL)
+    }
+
+    /// Reads `fields` like [`read_from`](Self::read_from), leaving the others
+    /// `None`. The modes are read once for every field that needs them, but only
+    /// reported when requested; `zone_mismatch` is set whenever they were read.
+    pub(crate) fn read_fields_from(
+        device: &impl CommandTarget,
+        features: &[&str],
+        fields: &[Field],
+    ) -> DeviceState {
+        let mut state = DeviceState::default();
+        let supports = |name: &str| features.contains(&name);
+        let wants = |field: Field| fields.contains(&field);
+
+        let needs_modes = [
+            Field::PerfMode,
+            Field::FanMode,
+            Field::CpuBoost,
+            Field::GpuBoost,
+            Field::FanRpm,
+        ]
+        .into_iter()
+        .any(wants);
+        let modes = match needs_modes.then(|| command::get_perf_mode(device)) {
+            Some(Err(RazerError::ZoneMismatch { zone1, .. })) => {
+                state.zone_mismatch = true;
+                Some(zone1)
+            }
+            modes => modes.and_then(Result::ok),
+        };
+        if let Some((perf_mode, fan_mode)) = modes {
+            state.perf_mode = wants(Field::PerfMode).then_some(perf_mode);
+            state.fan_mode = wants(Field::FanMode).then_some(fan_mode);
+
+            if perf_mode == PerfMode::Custom {
+                match (wants(Field::CpuBoost), wants(Field::GpuBoost)) {
+                    (true, true) => {
+                        if let Ok((cpu_boost, gpu_boost)) = command::get_boosts(device) {
+                            state.cpu_boost = Some(cpu_boost);
+                            state.gpu_boost = Some(gpu_boost);
+                        }
+                    }
+                    (true, false) => state.cpu_boost = command::get_cpu_boost(device).ok(),
+                    (false, true) => state.gpu_boost = command::get_gpu_boost(device).ok(),
+                    (false, false) => {}
+                }
+            }
+
+            if fan_mode == FanMode::Manual && wants(Field::FanRpm) {
+                state.fan_rpm = command::get_fan_rpm(device, FanZone::Zone1).ok();
+            }
+        }
+
+        if wants(Field::MaxFanSpeed) {
+            state.max_fan_speed = command::get_max_fan_speed_mode(device).ok();
+        }
+        if supports(feature::KBDBACKLIGHT) && wants(Field::KeyboardBrightness) {
+            state.keyboard_brightness = command::get_keyboard_brightness(device).ok();
+        }
+        if supports(feature::BATTERYCARE) && wants(Field::BatteryCare) {
+            state.battery_care = command::get_battery_care(device).ok();
+        }
+        if supports(feature::LIDLOGO) && wants(Field::LogoMode) {
+            state.logo_mode = command::get_logo_mode(device).ok();
+        }
+        if supports(feature::LIGHTSALWAYSON) && wants(Field::LightsAlwaysOn) {
+            state.lights_always_on = command::get_lights_always_on(device).ok();
+        }
+
+        state
+    }
+
+    /// Returns the changes from `self` to `new` in `fields`, in `fields` order.
+    pub fn diff(&self, new: &DeviceState, fields: &[Field]) -> Vec<StateChange> {
+        fields
+            .iter()
+            .map(|&field| StateChange {
+                field,
+                old: self.get(field),
+                new: new.get(field),
+            })
+            .filter(|change| change.old != change.new)
+            .collect()
+    }
+
+    /// Plans the writes that take a device with the `current` settings to the
+    /// ones `self` has a value for, skipping those it already has; see
+    /// [`ChangePlan`]. `current` should have every field `self` sets and the
+    /// modes, `None` where they couldn't be read, which are then written.
+    pub fn diff_against(&self, current: &DeviceState) -> ChangePlan {
+        ChangePlan::new(self, current)
+    }
+
+    /// Reads the settings that are set back from `device` and writes those that
+    /// differ, in an order that meets the mode requirements: the performance mode
+    /// first, then the fan, the boosts and the rest. A fan speed switches the fan
+    /// to Manual. Stops at the first error; `zone_mismatch` is ignored.
+    pub fn apply_to(&self, device: &impl CommandTarget) -> Result<()> {
+        let current = plan::read_current(device, self);
+        self.diff_against(&current).apply_to(device)
+    }
+}
+
+/// Result of [`command::read_fields`]: the requested fields, `None` where a read
+/// failed, the model lacks the feature or the modes make the value meaningless.
+///
+/// Serializes as an object of the requested fields, named like in [`DeviceState`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PartialState {
+    fields: Vec<Field>,
+    state: DeviceState,
+}
+
+impl PartialState {
+    pub(crate) fn new(fields: &[Field], state: DeviceState) -> Self {
+        let mut requested = Vec::with_capacity(fields.len());
+        for &field in fields {
+            if !requested.contains(&field) {
+                requested.push(field);
+            }
+        }
+        PartialState {
+            fields: requested,
+            state,
+        }
+    }
+
+    /// Returns the requested fields, without duplicates.
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Returns the value of `field`, `None` unless it was requested and read.
+    pub fn get(&self, field: Field) -> Option<FieldValue> {
+        self.state.get(field)
+    }
+
+    /// Returns each requested field with its value.
+    pub fn iter(&self) -> impl Iterator<Item = (Field, Option<FieldValue>)> + '_ {
+        self.fields.iter().map(|&field| (field, self.get(field)))
+    }
+
+    /// Whether the thermal zones disagreed, when the modes were read.
+    pub fn zone_mismatch(&self) -> bool {
+        self.state.zone_mismatch
+    }
+
+    /// Returns the values as a [`DeviceState`], unrequested fields `None`.
+    pub fn into_state(self) -> DeviceState {
+        self.state
+    }
+}
+
+impl Serialize for PartialState {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+        for (field, value) in self.iter() {
+            map.serialize_entry(field.name(), &value)?;
+        }
+        map.end()
+    }
+}
+
+/// A field whose value changed between two reads, `None` where it wasn't read.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct StateChange {
+    pub field: Field,
+    pub old: Option<FieldValue>,
+    pub new: Option<FieldValue>,
+}
+
+impl fmt::Display for StateChange {
+    /// Formats like `perf_mode: Balanced -> Silent`, with `-` for unread values.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: Option<FieldValue>| value.map_or("-".to_string(), |v| v.to_string());
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field,
+            show(self.old),
+            show(self.new)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::SUPPORTED;
+    use crate::device::Device;
+    use crate::transport::MockTransport;
+
+    #[test]
+    fn test_zone_mismatch_keeps_reading() {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        mock.push_response(&[0, 0x01, PerfMode::Balanced.raw(), FanMode::Auto as u8]);
+        mock.push_response(&[0, 0x02, PerfMode::Silent.raw(), FanMode::Auto as u8]);
+        mock.push_response(&[MaxFanSpeedMode::Enable as u8]);
+
+        let state = DeviceState::read_from(&device, &[]);
+        assert!(state.zone_mismatch);
+        assert_eq!(state.perf_mode, Some(PerfMode::Balanced));
+        assert_eq!(state.fan_mode, Some(FanMode::Auto));
+        assert_eq!(state.max_fan_speed, Some(MaxFanSpeedMode::Enable));
+    }
+
+    #[test]
+    fn test_diff_lists_changed_fields() {
+        let old = DeviceState {
+            perf_mode: Some(PerfMode::Balanced),
+            keyboard_brightness: Some(Brightness::new(10)),
+            ..Default::default()
+        };
+        let new = DeviceState {
+            perf_mode: Some(PerfMode::Silent),
+            battery_care: Some(BatteryCare::Enable),
+            keyboard_brightness: Some(Brightness::new(10)),
+            ..Default::default()
+        };
+        let changes = old.diff(&new, Field::ALL);
+        assert_eq!(
+            changes.iter().map(|c| c.field).collect::<Vec<_>>(),
+            [Field::PerfMode, Field::BatteryCare]
+        );
+        assert_eq!(changes[0].to_string(), "perf_mode: Balanced -> Silent");
+        assert_eq!(changes[1].to_string(), "battery_care: - -> Enable");
+        assert!(old.diff(&new, &[Field::KeyboardBrightness]).is_empty());
+    }
+
+    #[test]
+    fn test_field_names() {
+        for &field in Field::ALL {
+            assert_eq!(field.to_string().parse::<Field>().unwrap(), field);
+            assert_eq!(
+                serde_json::to_value(field).unwrap(),
+                serde_json::Value::String(field.name().to_string())
+            );
+        }
+        assert_eq!("Fan-RPM".parse::<Field>().unwrap(), Field::FanRpm);
+        assert!("temps".parse::<Field>().is_err());
+    }
+
+    #[test]
+    fn test_apply_writes_only_what_differs() {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        mock.push_response(&[0, 0x01, PerfMode::Silent.raw(), FanMode::Auto as u8]);
+        mock.push_response(&[0, 0x02, PerfMode::Silent.raw(), FanMode::Auto as u8]);
+        mock.push_response(&[1, 5, 128]);
+        let profile = DeviceState {
+            keyboard_brightness: Some(Brightness::new(128)),
+            fan_mode: Some(FanMode::Auto),
+            perf_mode: Some(PerfMode::Balanced),
+            ..Default::default()
+        };
+        profile.apply_to(&device).unwrap();
+
+        let writes: Vec<u16> = mock
+            .sent_commands()
+            .into_iter()
+            .map(|(command, _)| command)
+            .filter(|command| command & 0x80 == 0)
+            .collect();
+        // the performance mode leaves the fan in Auto, the brightness matches
+        assert_eq!(writes, [0x0d02, 0x0d02]);
+    }
+
+    #[test]
+    fn test_json_field_names() {
+        // status --json prints this, so its names are part of the CLI's output
+        let state = DeviceState {
+            perf_mode: Some(PerfMode::Custom),
+            fan_mode: Some(FanMode::Manual),
+            cpu_boost: Some(CpuBoost::Boost.into()),
+            gpu_boost: Some(MaybeKnown::Unknown(3)),
+            fan_rpm: Some(FanRpm::new(3500).unwrap()),
+            max_fan_speed: Some(MaxFanSpeedMode::Disable),
+            keyboard_brightness: Some(Brightness::new(128)),
+            logo_mode: Some(LogoMode::Static),
+            battery_care: Some(BatteryCare::Enable),
+            lights_always_on: Some(LightsAlwaysOn::Disable),
+            zone_mismatch: false,
+        };
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/snapshots/device_state.json"
+        );
+        let json = serde_json::to_string_pretty(&state).unwrap() + "\n";
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(path, &json).unwrap();
+        }
+        assert_eq!(
+            json,
+            include_str!("snapshots/device_state.json"),
+            "the status JSON changed, rerun with UPDATE_SNAPSHOTS=1 if intended"
+        );
+        assert_eq!(serde_json::from_str::<DeviceState>(&json).unwrap(), state);
+    }
+}
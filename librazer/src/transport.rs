@@ -0,0 +1,429 @@
+//! Transport layer between [`Device`](crate::device::Device) and the hardware.
+//!
+//! The real backend wraps a hidapi device. [`MockTransport`] records outgoing
+//! reports and answers with scripted responses so command logic can be tested
+//! without a Razer laptop attached.
+
+use crate::error::{RazerError, Result};
+use crate::packet::{CommandStatus, Packet};
+use crate::replay::Recorder;
+use log::debug;
+use std::collections::VecDeque;
+use std::ffi::{CStr, CString};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Raw feature report I/O used by [`Device`](crate::device::Device).
+///
+/// Buffers include the leading report id byte, matching hidapi conventions.
+pub trait Transport: Send {
+    /// Sends a feature report to the device.
+    fn send_feature_report(&self, data: &[u8]) -> Result<()>;
+
+    /// Reads a feature report into `buf`, returning the number of bytes read.
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize>;
+
+    /// Waits between protocol steps. Mocks can record the delay instead of sleeping.
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+
+    /// Returns the HID path the transport talks to, if it has one.
+    fn path(&self) -> Option<&CStr> {
+        None
+    }
+
+    /// Re-opens the underlying device after a disconnect.
+    ///
+    /// Takes `&self` so a [`Device`](crate::device::Device) can recover in the
+    /// middle of a send. The default does nothing, for transports that cannot go away.
+    fn reopen(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Transport backed by a hidapi device handle.
+pub struct HidTransport {
+    device: Mutex<hidapi::HidDevice>,
+    path: CString,
+}
+
+impl HidTransport {
+    /// Wraps an already opened hidapi device, remembering its path for [`reopen`](Transport::reopen).
+    pub fn new(device: hidapi::HidDevice, path: &CStr) -> Self {
+        Self {
+            device: Mutex::new(device),
+            path: path.to_owned(),
+        }
+    }
+
+    fn device(&self) -> MutexGuard<'_, hidapi::HidDevice> {
+        self.device.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Returns whether a hidapi error is EACCES/EPERM or the platform equivalent.
+pub(crate) fn is_permission_error(e: &hidapi::HidError) -> bool {
+    if let hidapi::HidError::IoError { error } = e {
+        return error.kind() == std::io::ErrorKind::PermissionDenied;
+    }
+    let message = e.to_string().to_lowercase();
+    [
+        "permission denied",
+        "operation not permitted",
+        "access denied",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+/// Classifies hidapi errors that mean the device is gone rather than misbehaving.
+fn map_hid_error(e: hidapi::HidError) -> RazerError {
+    if is_permission_error(&e) {
+        return RazerError::permission_denied(None);
+    }
+    let message = e.to_string().to_lowercase();
+    if [
+        "no such device",
+        "not connected",
+        "disconnected",
+        "no such file",
+        // stale handle after suspend/resume
+        "broken pipe",
+        "input/output error",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+    {
+        debug!("Treating HID error as disconnect: {}", e);
+        RazerError::Disconnected
+    } else {
+        e.into()
+    }
+}
+
+impl Transport for HidTransport {
+    fn send_feature_report(&self, data: &[u8]) -> Result<()> {
+        self.device()
+            .send_feature_report(data)
+            .map_err(map_hid_error)
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize> {
+        self.device().get_feature_report(buf).map_err(map_hid_error)
+    }
+
+    fn path(&self) -> Option<&CStr> {
+        Some(&self.path)
+    }
+
+    fn reopen(&self) -> Result<()> {
+        debug!("Re-opening HID device at {:?}", self.path);
+        let device = crate::device::with_hid_api(false, |api| {
+            api.open_path(&self.path).map_err(map_hid_error)
+        })?;
+        *self.device() = device;
+        Ok(())
+    }
+}
+
+/// Wraps another transport and logs every exchange to a [`Recorder`].
+pub struct RecordingTransport {
+    inner: Box<dyn Transport>,
+    recorder: Recorder,
+}
+
+impl RecordingTransport {
+    pub fn new(inner: Box<dyn Transport>, recorder: Recorder) -> Self {
+        Self { inner, recorder }
+    }
+
+    /// Returns the wrapped transport.
+    pub fn into_inner(self) -> Box<dyn Transport> {
+        self.inner
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn send_feature_report(&self, data: &[u8]) -> Result<()> {
+        let result = self.inner.send_feature_report(data);
+        let error = result.as_ref().err().map(|e| e.to_string());
+        // skip report id byte
+        self.recorder.record_report(&data[1..], error);
+        result
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize> {
+        let result = self.inner.get_feature_report(buf);
+        self.recorder.record_response(match &result {
+            Ok(read) => Ok(&buf[1..(*read).max(1)]),
+            Err(e) => Err(e.to_string()),
+        });
+        result
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.inner.sleep(duration);
+    }
+
+    fn path(&self) -> Option<&CStr> {
+        self.inner.path()
+    }
+
+    fn reopen(&self) -> Result<()> {
+        self.inner.reopen()
+    }
+}
+
+/// Scripted reply for the next report sent to a [`MockTransport`].
+#[derive(Debug, Clone)]
+enum MockResponse {
+    /// Successful reply with these bytes written over the request arguments.
+    Args(Vec<u8>),
+    /// Reply carrying the given status code and the echoed request arguments.
+    Status(u8),
+    /// The read fails with a HID error.
+    HidError,
+    /// Successful echo that announces this many remaining packets.
+    RemainingPackets(u16),
+    /// Successful echo of which only this many bytes, report id included, are read.
+    ShortRead(usize),
+    /// Successful echo whose data_size is cut to this many argument bytes.
+    Truncated(u8),
+    /// Successful echo with a wrong CRC byte.
+    BadCrc,
+}
+
+#[derive(Default)]
+struct MockState {
+    sent: Vec<Vec<u8>>,
+    report_ids: Vec<u8>,
+    responses: VecDeque<MockResponse>,
+    sleeps: Vec<Duration>,
+    disconnected: bool,
+    reopen_fails: bool,
+    reopen_count: usize,
+}
+
+/// In-memory transport for tests.
+///
+/// Every report sent is recorded. Each read answers the most recent report:
+/// scripted responses are consumed in order, and once the script is exhausted
+/// the request is echoed back with a successful status. Delays are recorded rather
+/// than slept. Clones share state, so a test can keep a handle after moving the
+/// transport into a device.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+    /// Creates a mock with an empty script.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a successful response whose arguments start with `args`.
+    pub fn push_response(&self, args: &[u8]) {
+        self.lock()
+            .responses
+            .push_back(MockResponse::Args(args.to_vec()));
+    }
+
+    /// Queues a response carrying the raw packet status code `status`.
+    pub fn push_status(&self, status: u8) {
+        self.lock()
+            .responses
+            .push_back(MockResponse::Status(status));
+    }
+
+    /// Queues a successful response whose remaining-packets count is `remaining`,
+    /// whatever the report's was.
+    pub fn push_remaining_packets(&self, remaining: u16) {
+        self.lock()
+            .responses
+            .push_back(MockResponse::RemainingPackets(remaining));
+    }
+
+    /// Queues a successful response of which only `len` bytes, report id
+    /// included, are read.
+    pub fn push_short_read(&self, len: usize) {
+        self.lock()
+            .responses
+            .push_back(MockResponse::ShortRead(len));
+    }
+
+    /// Queues a successful response that only carries `data_size` argument bytes.
+    pub fn push_truncated(&self, data_size: u8) {
+        self.lock()
+            .responses
+            .push_back(MockResponse::Truncated(data_size));
+    }
+
+    /// Queues a successful response whose CRC byte is wrong.
+    pub fn push_bad_crc(&self) {
+        self.lock().responses.push_back(MockResponse::BadCrc);
+    }
+
+    /// Queues a HID-level failure for the next read.
+    pub fn push_hid_error(&self) {
+        self.lock().responses.push_back(MockResponse::HidError);
+    }
+
+    /// Returns every report sent so far, without the report id byte.
+    pub fn sent(&self) -> Vec<Vec<u8>> {
+        self.lock().sent.clone()
+    }
+
+    /// Returns the report id byte of every report sent so far.
+    pub fn sent_report_ids(&self) -> Vec<u8> {
+        self.lock().report_ids.clone()
+    }
+
+    /// Returns the command code and arguments of every report sent so far.
+    pub fn sent_commands(&self) -> Vec<(u16, Vec<u8>)> {
+        self.lock()
+            .sent
+            .iter()
+            .filter_map(|bytes| Packet::try_from(bytes.as_slice()).ok())
+            .map(|packet| (packet.command(), packet.get_args().to_vec()))
+            .collect()
+    }
+
+    /// Returns every delay requested so far.
+    pub fn sleeps(&self) -> Vec<Duration> {
+        self.lock().sleeps.clone()
+    }
+
+    /// Simulates unplugging the device: I/O fails with
+    /// [`RazerError::Disconnected`] until the transport is re-opened.
+    pub fn disconnect(&self) {
+        self.lock().disconnected = true;
+    }
+
+    /// Makes [`reopen`](Transport::reopen) fail, as if the device had not come back.
+    pub fn fail_reopen(&self, fail: bool) {
+        self.lock().reopen_fails = fail;
+    }
+
+    /// Returns how many times the transport has been re-opened.
+    pub fn reopen_count(&self) -> usize {
+        self.lock().reopen_count
+    }
+
+    /// Forgets recorded reports and any unused scripted responses.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.sent.clear();
+        state.report_ids.clear();
+        state.responses.clear();
+        state.sleeps.clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Transport for MockTransport {
+    fn send_feature_report(&self, data: &[u8]) -> Result<()> {
+        let mut state = self.lock();
+        if state.disconnected {
+            return Err(RazerError::Disconnected);
+        }
+        state.report_ids.push(data[0]);
+        state.sent.push(data[1..].to_vec());
+        Ok(())
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut state = self.lock();
+        if state.disconnected {
+            return Err(RazerError::Disconnected);
+        }
+        let request = state
+            .sent
+            .last()
+            .ok_or_else(|| RazerError::Other("No report sent to mock transport".to_string()))?;
+        let request = Packet::try_from(request.as_slice())?;
+
+        let mut len = None;
+        let mut bad_crc = false;
+        let response = match state.responses.pop_front() {
+            Some(MockResponse::Args(args)) => {
+                request.into_response(CommandStatus::Successful as u8, &args)
+            }
+            Some(MockResponse::Status(status)) => request.into_response(status, &[]),
+            Some(MockResponse::RemainingPackets(remaining)) => request
+                .into_response(CommandStatus::Successful as u8, &[])
+                .with_remaining_packets(remaining),
+            Some(MockResponse::ShortRead(bytes)) => {
+                len = Some(bytes);
+                request.into_response(CommandStatus::Successful as u8, &[])
+            }
+            Some(MockResponse::Truncated(data_size)) => request
+                .into_response(CommandStatus::Successful as u8, &[])
+                .truncated(data_size),
+            Some(MockResponse::BadCrc) => {
+                bad_crc = true;
+                request.into_response(CommandStatus::Successful as u8, &[])
+            }
+            Some(MockResponse::HidError) => {
+                return Err(hidapi::HidError::HidApiError {
+                    message: "Simulated HID failure".to_string(),
+                }
+                .into())
+            }
+            None => request.into_response(CommandStatus::Successful as u8, &[]),
+        };
+
+        let mut bytes: Vec<u8> = (&response).into();
+        if bad_crc {
+            bytes[88] ^= 0xff;
+        }
+        buf[1..=bytes.len()].copy_from_slice(&bytes);
+        Ok(len.unwrap_or(1 + bytes.len()))
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.lock().sleeps.push(duration);
+    }
+
+    fn reopen(&self) -> Result<()> {
+        let mut state = self.lock();
+        if state.reopen_fails {
+            return Err(RazerError::Disconnected);
+        }
+        state.disconnected = false;
+        state.reopen_count += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_errors() {
+        let hid_error = |message: &str| hidapi::HidError::HidApiError {
+            message: message.to_string(),
+        };
+        assert!(is_permission_error(&hid_error(
+            "Failed to open a device with path '/dev/hidraw3': Permission denied"
+        )));
+        assert!(is_permission_error(&hidapi::HidError::IoError {
+            error: std::io::Error::from_raw_os_error(1), // EPERM
+        }));
+        assert!(!is_permission_error(&hid_error("No such device")));
+
+        assert!(matches!(
+            map_hid_error(hid_error("Operation not permitted")),
+            RazerError::PermissionDenied(_)
+        ));
+        assert!(matches!(
+            map_hid_error(hid_error("Broken pipe")),
+            RazerError::Disconnected
+        ));
+    }
+}
@@ -1,7 +1,10 @@
 use crate::error::RazerError;
+use crate::limits;
+use clap::builder::PossibleValue;
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
-use strum_macros::{EnumIter, EnumString};
+use std::ops::RangeInclusive;
+use strum_macros::EnumIter;
 
 /// Generates TryFrom<u8> implementation for enums with explicit discriminants.
 macro_rules! impl_try_from_u8 {
@@ -22,13 +25,109 @@ macro_rules! impl_try_from_u8 {
     };
 }
 
-#[derive(Clone, Copy)]
+/// Generates canonical `Display`, case-insensitive `FromStr` and clap `ValueEnum`
+/// implementations. The first name of each variant is canonical, the others are
+/// accepted aliases.
+macro_rules! impl_names {
+    ($enum_type:ident { $($variant:ident => [$name:literal $(, $alias:literal)*]),+ $(,)? }) => {
+        impl std::fmt::Display for $enum_type {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.pad(match self {
+                    $(Self::$variant => $name,)+
+                })
+            }
+        }
+
+        impl std::str::FromStr for $enum_type {
+            type Err = RazerError;
+
+            fn from_str(name: &str) -> Result<Self, Self::Err> {
+                $(
+                    if name.eq_ignore_ascii_case($name) $(|| name.eq_ignore_ascii_case($alias))* {
+                        return Ok(Self::$variant);
+                    }
+                )+
+                Err(RazerError::InvalidName {
+                    name: name.to_string(),
+                    type_name: stringify!($enum_type),
+                })
+            }
+        }
+
+        impl ValueEnum for $enum_type {
+            fn value_variants<'a>() -> &'a [Self] {
+                &[$(Self::$variant),+]
+            }
+
+            fn to_possible_value(&self) -> Option<PossibleValue> {
+                Some(match self {
+                    $(Self::$variant => PossibleValue::new(
+                        const_str::convert_ascii_case!(lower, $name)
+                    )
+                    .aliases({ let aliases: [&str; _] = [$($alias),*]; aliases }),)+
+                })
+            }
+        }
+    };
+}
+
+/// Generates saturating `next`/`prev` and wrapping `cycle` helpers that step
+/// through `$order`, lowest first. Values missing from `$order` step to its
+/// first entry.
+macro_rules! impl_steps {
+    ($enum_type:ident, $order:expr) => {
+        impl $enum_type {
+            fn step(self, offset: fn(usize, usize) -> usize) -> Self {
+                let order = &$order;
+                match order.iter().position(|value| *value == self) {
+                    Some(index) => order[offset(index, order.len())],
+                    None => order[0],
+                }
+            }
+
+            /// The next value up, staying at the highest.
+            pub fn next(self) -> Self {
+                self.step(|index, len| (index + 1).min(len - 1))
+            }
+
+            /// The next value down, staying at the lowest.
+            pub fn prev(self) -> Self {
+                self.step(|index, _| index.saturating_sub(1))
+            }
+
+            /// The next value up, wrapping around to the lowest.
+            pub fn cycle(self) -> Self {
+                self.step(|index, len| (index + 1) % len)
+            }
+
+            /// The next value in `supported` after `self` in cycle order, or
+            /// `self` if `supported` lists nothing else.
+            pub fn cycle_in(self, supported: &[Self]) -> Self {
+                let mut value = self;
+                for _ in 0..$order.len() {
+                    value = value.cycle();
+                    if supported.contains(&value) {
+                        return value;
+                    }
+                }
+                self
+            }
+        }
+    };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Cluster {
     Cpu = 0x01,
     Gpu = 0x02,
 }
 
-#[derive(Clone, Copy)]
+impl Cluster {
+    /// Both boost clusters
+    pub const ALL: [Cluster; 2] = [Cluster::Cpu, Cluster::Gpu];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum FanZone {
     Zone1 = 0x01,
     Zone2 = 0x02,
@@ -40,7 +139,7 @@ impl FanZone {
 }
 
 /// Thermal zones for performance mode operations
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ThermalZone {
     Zone1 = 0x01,
     Zone2 = 0x02,
@@ -51,26 +150,133 @@ impl ThermalZone {
     pub const ALL: [ThermalZone; 2] = [ThermalZone::Zone1, ThermalZone::Zone2];
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, EnumIter, ValueEnum)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter)]
 pub enum PerfMode {
-    Balanced = 0,
-    Silent = 5,
-    Custom = 4,
+    #[serde(rename = "balanced", alias = "Balanced")]
+    Balanced,
+    #[serde(rename = "silent", alias = "Silent")]
+    Silent,
+    #[serde(rename = "custom", alias = "Custom")]
+    Custom,
+    /// A mode without a variant, as read from a device where other software
+    /// such as Synapse set it. Named like `Unknown(6)`, serialized as
+    /// `{"unknown": 6}`.
+    #[strum(disabled)]
+    #[serde(rename = "unknown", alias = "Unknown")]
+    Unknown(u8),
+}
+
+impl PerfMode {
+    /// Every known mode
+    pub const ALL: [PerfMode; 3] = [PerfMode::Balanced, PerfMode::Silent, PerfMode::Custom];
+
+    /// Order [`next`](PerfMode::next), [`prev`](PerfMode::prev) and
+    /// [`cycle`](PerfMode::cycle) step through, quietest first.
+    pub const CYCLE: [PerfMode; 3] = [PerfMode::Silent, PerfMode::Balanced, PerfMode::Custom];
+
+    /// Value of the mode in performance mode commands.
+    pub const fn raw(self) -> u8 {
+        match self {
+            PerfMode::Balanced => 0,
+            PerfMode::Silent => 5,
+            PerfMode::Custom => 4,
+            PerfMode::Unknown(value) => value,
+        }
+    }
+
+    /// Parses a mode read from the device, keeping values without a variant as
+    /// [`Unknown`](PerfMode::Unknown).
+    pub fn from_raw(value: u8) -> PerfMode {
+        PerfMode::try_from(value).unwrap_or(PerfMode::Unknown(value))
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, EnumIter, ValueEnum)]
+/// Names of the known modes; `Unknown` is handled by the impls below.
+#[derive(Clone, Copy, PartialEq)]
+enum KnownPerfMode {
+    Balanced,
+    Silent,
+    Custom,
+}
+
+impl_names!(KnownPerfMode {
+    Balanced => ["Balanced"],
+    Silent => ["Silent", "quiet"],
+    Custom => ["Custom"],
+});
+
+impl KnownPerfMode {
+    fn new(mode: PerfMode) -> Option<KnownPerfMode> {
+        match mode {
+            PerfMode::Balanced => Some(KnownPerfMode::Balanced),
+            PerfMode::Silent => Some(KnownPerfMode::Silent),
+            PerfMode::Custom => Some(KnownPerfMode::Custom),
+            PerfMode::Unknown(_) => None,
+        }
+    }
+
+    fn mode(self) -> PerfMode {
+        match self {
+            KnownPerfMode::Balanced => PerfMode::Balanced,
+            KnownPerfMode::Silent => PerfMode::Silent,
+            KnownPerfMode::Custom => PerfMode::Custom,
+        }
+    }
+}
+
+impl std::fmt::Display for PerfMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (KnownPerfMode::new(*self), self) {
+            (Some(known), _) => known.fmt(f),
+            (None, mode) => f.pad(&format!("Unknown({})", mode.raw())),
+        }
+    }
+}
+
+impl std::str::FromStr for PerfMode {
+    type Err = RazerError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if let Ok(known) = name.parse::<KnownPerfMode>() {
+            return Ok(known.mode());
+        }
+        let raw = name
+            .get(..8)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("unknown("))
+            .and_then(|_| name[8..].strip_suffix(')'))
+            .and_then(|value| value.parse().ok());
+        raw.map(PerfMode::Unknown).ok_or(RazerError::InvalidName {
+            name: name.to_string(),
+            type_name: "PerfMode",
+        })
+    }
+}
+
+impl ValueEnum for PerfMode {
+    fn value_variants<'a>() -> &'a [Self] {
+        &PerfMode::ALL
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        KnownPerfMode::new(*self).and_then(|known| known.to_possible_value())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter)]
 pub enum MaxFanSpeedMode {
     Enable = 2,
     Disable = 0,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, EnumIter, ValueEnum)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter)]
 pub enum FanMode {
     Auto = 0,
     Manual = 1,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, EnumIter, ValueEnum)]
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, EnumIter,
+)]
 pub enum CpuBoost {
     Low = 0,
     Medium = 1,
@@ -79,34 +285,96 @@ pub enum CpuBoost {
     Overclock = 4,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, EnumIter, ValueEnum)]
+impl CpuBoost {
+    /// Every level, lowest first
+    pub const ALL: [CpuBoost; 5] = [
+        CpuBoost::Low,
+        CpuBoost::Medium,
+        CpuBoost::High,
+        CpuBoost::Boost,
+        CpuBoost::Overclock,
+    ];
+}
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, EnumIter,
+)]
 pub enum GpuBoost {
     Low = 0,
     Medium = 1,
     High = 2,
 }
 
-#[derive(
-    Clone, Copy, Debug, PartialEq, Serialize, Deserialize, EnumIter, EnumString, ValueEnum,
-)]
+impl GpuBoost {
+    /// Every level, lowest first
+    pub const ALL: [GpuBoost; 3] = [GpuBoost::Low, GpuBoost::Medium, GpuBoost::High];
+
+    /// Marketing name shown by Synapse; both spellings parse.
+    pub fn ui_label(self) -> &'static str {
+        match self {
+            GpuBoost::Low => "Eco",
+            GpuBoost::Medium => "Standard",
+            GpuBoost::High => "Ultimate",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter)]
 pub enum LogoMode {
     Off,
     Breathing,
     Static,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, EnumString, ValueEnum)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum LightsAlwaysOn {
     Enable = 0x03,
     Disable = 0x00,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BatteryCare {
     Disable = 0x50,
     Enable = 0xd0,
 }
 
+impl_steps!(PerfMode, PerfMode::CYCLE);
+impl_steps!(CpuBoost, CpuBoost::ALL);
+impl_steps!(GpuBoost, GpuBoost::ALL);
+
+impl_names!(Cluster { Cpu => ["Cpu"], Gpu => ["Gpu"] });
+impl_names!(FanZone { Zone1 => ["Zone1"], Zone2 => ["Zone2"] });
+impl_names!(ThermalZone { Zone1 => ["Zone1"], Zone2 => ["Zone2"] });
+impl_names!(MaxFanSpeedMode {
+    Enable => ["Enable", "on", "enabled"],
+    Disable => ["Disable", "off", "disabled"],
+});
+impl_names!(FanMode { Auto => ["Auto", "automatic"], Manual => ["Manual"] });
+impl_names!(CpuBoost {
+    Low => ["Low", "eco"],
+    Medium => ["Medium"],
+    High => ["High"],
+    Boost => ["Boost"],
+    Overclock => ["Overclock"],
+});
+impl_names!(GpuBoost {
+    Low => ["Low", "eco"],
+    Medium => ["Medium", "standard"],
+    High => ["High", "ultimate"],
+});
+impl_names!(LogoMode { Off => ["Off"], Breathing => ["Breathing"], Static => ["Static"] });
+impl_names!(LightsAlwaysOn {
+    Enable => ["Enable", "on", "enabled"],
+    Disable => ["Disable", "off", "disabled"],
+});
+impl_names!(BatteryCare {
+    Enable => ["Enable", "on", "enabled"],
+    Disable => ["Disable", "off", "disabled"],
+});
+
+impl_try_from_u8!(Cluster { 0x01 => Cpu, 0x02 => Gpu });
+impl_try_from_u8!(FanZone { 0x01 => Zone1, 0x02 => Zone2 });
+impl_try_from_u8!(ThermalZone { 0x01 => Zone1, 0x02 => Zone2 });
 impl_try_from_u8!(GpuBoost { 0 => Low, 1 => Medium, 2 => High });
 impl_try_from_u8!(PerfMode { 0 => Balanced, 5 => Silent, 4 => Custom });
 impl_try_from_u8!(FanMode { 0 => Auto, 1 => Manual });
@@ -115,9 +383,1174 @@ impl_try_from_u8!(LightsAlwaysOn { 0 => Disable, 3 => Enable });
 impl_try_from_u8!(BatteryCare { 0x50 => Disable, 0xd0 => Enable });
 impl_try_from_u8!(MaxFanSpeedMode { 0x00 => Disable, 0x02 => Enable });
 
+/// A value read from a device, which firmware or Synapse may have set to one this
+/// crate doesn't know, like a new boost level. Keeps the raw byte instead of
+/// failing the read. Setters take `T`, so an unknown value can't be written back.
+///
+/// Displays and serializes like `T`, and like [`PerfMode::Unknown`] otherwise:
+/// `Unknown(6)` and `{"unknown":6}`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MaybeKnown<T> {
+    Known(T),
+    Unknown(u8),
+}
+
+impl<T: TryFrom<u8>> MaybeKnown<T> {
+    pub fn from_raw(value: u8) -> MaybeKnown<T> {
+        T::try_from(value).map_or(MaybeKnown::Unknown(value), MaybeKnown::Known)
+    }
+}
+
+impl<T> MaybeKnown<T> {
+    pub fn known(self) -> Option<T> {
+        match self {
+            MaybeKnown::Known(value) => Some(value),
+            MaybeKnown::Unknown(_) => None,
+        }
+    }
+
+    /// The known value, or [`RazerError::InvalidValue`] with the raw byte.
+    pub fn into_known(self) -> Result<T, RazerError> {
+        match self {
+            MaybeKnown::Known(value) => Ok(value),
+            MaybeKnown::Unknown(value) => Err(RazerError::InvalidValue {
+                value,
+                type_name: std::any::type_name::<T>()
+                    .rsplit("::")
+                    .next()
+                    .unwrap_or_default(),
+            }),
+        }
+    }
+}
+
+impl<T> From<T> for MaybeKnown<T> {
+    fn from(value: T) -> MaybeKnown<T> {
+        MaybeKnown::Known(value)
+    }
+}
+
+impl<T: PartialEq> PartialEq<T> for MaybeKnown<T> {
+    fn eq(&self, other: &T) -> bool {
+        matches!(self, MaybeKnown::Known(value) if value == other)
+    }
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for MaybeKnown<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MaybeKnown::Known(value) => value.fmt(f),
+            MaybeKnown::Unknown(value) => f.pad(&format!("Unknown({})", value)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum MaybeKnownRepr<T> {
+    Unknown { unknown: u8 },
+    Known(T),
+}
+
+impl<T: Serialize> Serialize for MaybeKnown<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MaybeKnown::Known(value) => value.serialize(serializer),
+            MaybeKnown::Unknown(unknown) => {
+                MaybeKnownRepr::<()>::Unknown { unknown: *unknown }.serialize(serializer)
+            }
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for MaybeKnown<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match MaybeKnownRepr::deserialize(deserializer)? {
+            MaybeKnownRepr::Unknown { unknown } => MaybeKnown::Unknown(unknown),
+            MaybeKnownRepr::Known(value) => MaybeKnown::Known(value),
+        })
+    }
+}
+
+/// Manual fan speed, sent to the device in hundreds of RPM.
+///
+/// [`FanRpm::new`] only checks that the speed can be encoded; use
+/// [`FanRpm::new_in`] or [`Descriptor::check_fan_rpm`](crate::descriptor::Descriptor::check_fan_rpm)
+/// for the range a model accepts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "u16", into = "u16")]
+pub struct FanRpm(u16);
+
+impl FanRpm {
+    /// Fastest speed the one-byte encoding can carry.
+    pub const MAX: u16 = u8::MAX as u16 * 100;
+
+    /// Fails with [`RazerError::FanRpmOutOfRange`] above [`FanRpm::MAX`].
+    pub fn new(rpm: u16) -> Result<FanRpm, RazerError> {
+        FanRpm::new_in(rpm, &(0..=FanRpm::MAX))
+    }
+
+    /// Fails with [`RazerError::FanRpmOutOfRange`] unless `rpm` is in `range`.
+    pub fn new_in(rpm: u16, range: &RangeInclusive<u16>) -> Result<FanRpm, RazerError> {
+        if !range.contains(&rpm) || rpm > FanRpm::MAX {
+            return Err(RazerError::FanRpmOutOfRange {
+                rpm,
+                min: *range.start(),
+                max: (*range.end()).min(FanRpm::MAX),
+            });
+        }
+        Ok(FanRpm(rpm))
+    }
+
+    /// Decodes a speed read from the device.
+    pub const fn from_raw_byte(value: u8) -> FanRpm {
+        FanRpm(value as u16 * 100)
+    }
+
+    /// Encodes the speed for fan commands, dropping anything below 100 RPM.
+    pub const fn as_raw_byte(self) -> u8 {
+        (self.0 / 100) as u8
+    }
+
+    pub const fn get(self) -> u16 {
+        self.0
+    }
+}
+
+impl From<FanRpm> for u16 {
+    fn from(rpm: FanRpm) -> u16 {
+        rpm.0
+    }
+}
+
+impl TryFrom<u16> for FanRpm {
+    type Error = RazerError;
+
+    fn try_from(rpm: u16) -> Result<Self, Self::Error> {
+        FanRpm::new(rpm)
+    }
+}
+
+impl std::fmt::Display for FanRpm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} RPM", self.0)
+    }
+}
+
+impl std::str::FromStr for FanRpm {
+    type Err = RazerError;
+
+    /// Parses a speed like `3500`, with or without an `RPM` suffix.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let trimmed = text.trim();
+        let number = trimmed
+            .get(trimmed.len().saturating_sub(3)..)
+            .filter(|suffix| suffix.eq_ignore_ascii_case("rpm"))
+            .map_or(trimmed, |_| &trimmed[..trimmed.len() - 3]);
+        let rpm = number.trim().parse().map_err(|_| RazerError::InvalidName {
+            name: text.to_string(),
+            type_name: "FanRpm",
+        })?;
+        FanRpm::new(rpm)
+    }
+}
+
+/// Fan speeds by temperature, as `(°C, RPM)` points with strictly increasing
+/// temperatures. Serialized as a list of `[temp, rpm]` pairs.
+///
+/// Construction only checks that each speed can be encoded; use
+/// [`FanCurve::check_range`] or
+/// [`Descriptor::check_fan_curve`](crate::descriptor::Descriptor::check_fan_curve)
+/// for the range a model accepts.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<(u8, u16)>", into = "Vec<(u8, u16)>")]
+pub struct FanCurve {
+    points: Vec<(u8, FanRpm)>,
+}
+
+impl FanCurve {
+    /// Fails with [`RazerError::InvalidFanCurve`] for fewer than two points or
+    /// temperatures that don't strictly increase, and with
+    /// [`RazerError::FanRpmOutOfRange`] for speeds that can't be encoded.
+    pub fn try_from_points(
+        points: impl IntoIterator<Item = (u8, u16)>,
+    ) -> Result<FanCurve, RazerError> {
+        let points = points
+            .into_iter()
+            .map(|(temp, rpm)| FanRpm::new(rpm).map(|rpm| (temp, rpm)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if points.len() < 2 {
+            return Err(RazerError::InvalidFanCurve(format!(
+                "a fan curve needs at least two points, got {}",
+                points.len()
+            )));
+        }
+        if let Some(pair) = points.windows(2).find(|pair| pair[0].0 >= pair[1].0) {
+            return Err(RazerError::InvalidFanCurve(format!(
+                "temperatures must increase, but {}°C follows {}°C",
+                pair[1].0, pair[0].0
+            )));
+        }
+        Ok(FanCurve { points })
+    }
+
+    pub fn points(&self) -> &[(u8, FanRpm)] {
+        &self.points
+    }
+
+    /// Fails with [`RazerError::FanRpmOutOfRange`] unless every speed is in `range`.
+    pub fn check_range(&self, range: &RangeInclusive<u16>) -> Result<(), RazerError> {
+        for (_, rpm) in &self.points {
+            FanRpm::new_in(rpm.get(), range)?;
+        }
+        Ok(())
+    }
+
+    /// Speed for `temp`, interpolated linearly between the surrounding points and
+    /// held at the first and last points' speeds outside the curve.
+    pub fn rpm_for(&self, temp: u8) -> FanRpm {
+        let (first, last) = (self.points[0], self.points[self.points.len() - 1]);
+        if temp <= first.0 {
+            return first.1;
+        }
+        if temp >= last.0 {
+            return last.1;
+        }
+        let upper = self.points.iter().position(|&(t, _)| t >= temp).unwrap();
+        let ((t0, r0), (t1, r1)) = (self.points[upper - 1], self.points[upper]);
+        let (r0, r1) = (r0.get() as i32, r1.get() as i32);
+        let rpm = r0 + (r1 - r0) * (temp - t0) as i32 / (t1 - t0) as i32;
+        // between r0 and r1, so still encodable
+        FanRpm(rpm as u16)
+    }
+}
+
+impl TryFrom<Vec<(u8, u16)>> for FanCurve {
+    type Error = RazerError;
+
+    fn try_from(points: Vec<(u8, u16)>) -> Result<Self, Self::Error> {
+        FanCurve::try_from_points(points)
+    }
+}
+
+impl From<FanCurve> for Vec<(u8, u16)> {
+    fn from(curve: FanCurve) -> Vec<(u8, u16)> {
+        curve
+            .points
+            .into_iter()
+            .map(|(t, rpm)| (t, rpm.get()))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for FanCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (temp, rpm)) in self.points.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}°C→{}", temp, rpm.get())?;
+        }
+        Ok(())
+    }
+}
+
+/// How a model's firmware encodes a charge limit in battery care commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChargeLimitEncoding {
+    /// The limit in percent, 100 for none.
+    Percent,
+    /// The limit in percent in the low 7 bits, with the high bit set while the
+    /// limit applies. With it clear the battery charges fully. Used by the 2023
+    /// Blades, where battery care is `0xd0`: a limit of 80%.
+    FlaggedPercent,
+}
+
+/// Highest charge in percent the battery charges to, from
+/// [`MIN`](ChargeLimit::MIN) to [`MAX`](ChargeLimit::MAX) (no limit). Serialized
+/// as the plain percentage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct ChargeLimit(u8);
+
+impl ChargeLimit {
+    pub const MIN: ChargeLimit = ChargeLimit(50);
+    pub const MAX: ChargeLimit = ChargeLimit(100);
+    /// Granularity [`new`](ChargeLimit::new) rounds to, in percent.
+    pub const STEP: u8 = 5;
+
+    /// Clamps `percent` to [`MIN`](ChargeLimit::MIN)..=[`MAX`](ChargeLimit::MAX)
+    /// and rounds it to the nearest [`STEP`](ChargeLimit::STEP), halves up.
+    pub const fn new(percent: u8) -> ChargeLimit {
+        let clamped = if percent < ChargeLimit::MIN.0 {
+            ChargeLimit::MIN.0
+        } else if percent > ChargeLimit::MAX.0 {
+            ChargeLimit::MAX.0
+        } else {
+            percent
+        };
+        ChargeLimit((clamped + ChargeLimit::STEP / 2) / ChargeLimit::STEP * ChargeLimit::STEP)
+    }
+
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+
+    /// Encodes the limit for battery care commands.
+    pub const fn to_wire(self, encoding: ChargeLimitEncoding) -> u8 {
+        match encoding {
+            ChargeLimitEncoding::Percent => self.0,
+            // firmware reports 80 in the low bits while the limit is off
+            ChargeLimitEncoding::FlaggedPercent if self.0 == ChargeLimit::MAX.0 => 0x50,
+            ChargeLimitEncoding::FlaggedPercent => 0x80 | self.0,
+        }
+    }
+
+    /// Decodes a battery care response, keeping limits between steps as they are.
+    /// Fails with [`RazerError::InvalidValue`] for limits outside the range.
+    pub fn from_wire(value: u8, encoding: ChargeLimitEncoding) -> Result<ChargeLimit, RazerError> {
+        let percent = match encoding {
+            ChargeLimitEncoding::Percent => value,
+            ChargeLimitEncoding::FlaggedPercent if value & 0x80 == 0 => ChargeLimit::MAX.0,
+            ChargeLimitEncoding::FlaggedPercent => value & 0x7f,
+        };
+        if !(ChargeLimit::MIN.0..=ChargeLimit::MAX.0).contains(&percent) {
+            return Err(RazerError::InvalidValue {
+                value,
+                type_name: "ChargeLimit",
+            });
+        }
+        Ok(ChargeLimit(percent))
+    }
+}
+
+impl From<ChargeLimit> for u8 {
+    fn from(limit: ChargeLimit) -> u8 {
+        limit.0
+    }
+}
+
+impl TryFrom<u8> for ChargeLimit {
+    type Error = RazerError;
+
+    /// Rounds like [`ChargeLimit::new`], but fails outside the range instead of
+    /// clamping.
+    fn try_from(percent: u8) -> Result<Self, Self::Error> {
+        if !(ChargeLimit::MIN.0..=ChargeLimit::MAX.0).contains(&percent) {
+            return Err(RazerError::PreconditionFailed(format!(
+                "Charge limit must be between {} and {}, got {}%",
+                ChargeLimit::MIN,
+                ChargeLimit::MAX,
+                percent
+            )));
+        }
+        Ok(ChargeLimit::new(percent))
+    }
+}
+
+impl std::fmt::Display for ChargeLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+impl std::str::FromStr for ChargeLimit {
+    type Err = RazerError;
+
+    /// Parses a percentage like `80` or `80%`, failing outside the range.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let trimmed = text.trim();
+        let percent: u8 = trimmed
+            .strip_suffix('%')
+            .unwrap_or(trimmed)
+            .trim()
+            .parse()
+            .map_err(|_| RazerError::InvalidName {
+                name: text.to_string(),
+                type_name: "ChargeLimit",
+            })?;
+        ChargeLimit::try_from(percent)
+    }
+}
+
+/// Keyboard backlight brightness, from [`OFF`](Brightness::OFF) to
+/// [`MAX`](Brightness::MAX). Displayed as a percentage with the raw value, like
+/// `50% (128)`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Brightness(u8);
+
+impl Brightness {
+    pub const OFF: Brightness = Brightness(0);
+    pub const MAX: Brightness = Brightness(limits::BRIGHTNESS_MAX);
+
+    pub const fn new(value: u8) -> Brightness {
+        Brightness(value)
+    }
+
+    /// Nearest brightness to `percent`, clamped to 100.
+    pub const fn from_percent(percent: u8) -> Brightness {
+        let percent = if percent > 100 { 100 } else { percent } as u16;
+        Brightness(((percent * 255 + 50) / 100) as u8)
+    }
+
+    /// Brightness as a rounded percentage of [`MAX`](Brightness::MAX).
+    pub const fn as_percent(self) -> u8 {
+        ((self.0 as u16 * 100 + 127) / 255) as u8
+    }
+
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+
+    /// Steps up by `step`, stopping at [`MAX`](Brightness::MAX).
+    pub const fn saturating_add(self, step: u8) -> Brightness {
+        Brightness(self.0.saturating_add(step))
+    }
+
+    /// Steps down by `step`, stopping at [`OFF`](Brightness::OFF).
+    pub const fn saturating_sub(self, step: u8) -> Brightness {
+        Brightness(self.0.saturating_sub(step))
+    }
+}
+
+impl From<u8> for Brightness {
+    fn from(value: u8) -> Brightness {
+        Brightness(value)
+    }
+}
+
+impl From<Brightness> for u8 {
+    fn from(brightness: Brightness) -> u8 {
+        brightness.0
+    }
+}
+
+impl std::fmt::Display for Brightness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}% ({})", self.as_percent(), self.0)
+    }
+}
+
+impl std::str::FromStr for Brightness {
+    type Err = RazerError;
+
+    /// Parses a raw value like `128` or a percentage like `50%`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || RazerError::InvalidName {
+            name: text.to_string(),
+            type_name: "Brightness",
+        };
+        match text.trim().strip_suffix('%') {
+            Some(percent) => match percent.trim().parse() {
+                Ok(percent @ 0..=100) => Ok(Brightness::from_percent(percent)),
+                _ => Err(invalid()),
+            },
+            None => text.trim().parse().map(Brightness).map_err(|_| invalid()),
+        }
+    }
+}
+
+/// A color, written as lowercase hex like `#44d62c`.
+///
+/// Parses `#rrggbb`, `rrggbb` or one of [`Rgb::NAMED`], and serializes as the hex
+/// string.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb::new(0x00, 0x00, 0x00);
+    pub const WHITE: Rgb = Rgb::new(0xff, 0xff, 0xff);
+    pub const RAZER_GREEN: Rgb = Rgb::new(0x44, 0xd6, 0x2c);
+
+    /// Names accepted when parsing, matched case-insensitively.
+    pub const NAMED: &[(&'static str, Rgb)] = &[
+        ("black", Rgb::BLACK),
+        ("white", Rgb::WHITE),
+        ("red", Rgb::new(0xff, 0x00, 0x00)),
+        ("green", Rgb::new(0x00, 0xff, 0x00)),
+        ("blue", Rgb::new(0x00, 0x00, 0xff)),
+        ("yellow", Rgb::new(0xff, 0xff, 0x00)),
+        ("cyan", Rgb::new(0x00, 0xff, 0xff)),
+        ("magenta", Rgb::new(0xff, 0x00, 0xff)),
+        ("orange", Rgb::new(0xff, 0x80, 0x00)),
+        ("purple", Rgb::new(0x80, 0x00, 0xff)),
+        ("razer-green", Rgb::RAZER_GREEN),
+    ];
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Rgb {
+        Rgb { r, g, b }
+    }
+
+    /// The color as sent in lighting commands.
+    pub const fn to_bytes(self) -> [u8; 3] {
+        [self.r, self.g, self.b]
+    }
+}
+
+impl From<[u8; 3]> for Rgb {
+    fn from([r, g, b]: [u8; 3]) -> Rgb {
+        Rgb { r, g, b }
+    }
+}
+
+impl From<Rgb> for [u8; 3] {
+    fn from(color: Rgb) -> [u8; 3] {
+        color.to_bytes()
+    }
+}
+
+impl From<Rgb> for String {
+    fn from(color: Rgb) -> String {
+        color.to_string()
+    }
+}
+
+impl TryFrom<String> for Rgb {
+    type Error = RazerError;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl std::fmt::Display for Rgb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+impl std::str::FromStr for Rgb {
+    type Err = RazerError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: String| RazerError::InvalidColor {
+            color: text.to_string(),
+            reason,
+        };
+        let trimmed = text.trim();
+        if let Some((_, color)) = Rgb::NAMED
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(trimmed))
+        {
+            return Ok(*color);
+        }
+        let hex = trimmed.strip_prefix('#').unwrap_or(trimmed);
+        if !hex.is_ascii() || hex.len() != 6 {
+            return Err(invalid(format!(
+                "expected 6 hex digits like #44d62c or a color name, got {} characters",
+                hex.chars().count()
+            )));
+        }
+        let channel = |i: usize| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| invalid(format!("'{}' is not a hex number", &hex[i..i + 2])))
+        };
+        Ok(Rgb::new(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+/// How long something like the keyboard backlight stays on without input, in
+/// whole seconds, or [`NEVER`](Timeout::NEVER).
+///
+/// Parses `never`, `off`, or a number with an optional `s`, `m` or `h` unit (`90`,
+/// `30s`, `5m`). Serializes as seconds with 0 for never; the string form also
+/// deserializes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "TimeoutRepr", into = "u16")]
+pub struct Timeout(u16);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TimeoutRepr {
+    Seconds(u16),
+    Text(String),
+}
+
+impl Timeout {
+    pub const NEVER: Timeout = Timeout(0);
+    pub const MAX: Timeout = Timeout(limits::TIMEOUT_MAX_SECS);
+
+    /// Fails with [`RazerError::InvalidTimeout`] for 0, use
+    /// [`NEVER`](Timeout::NEVER) instead.
+    pub fn from_secs(secs: u16) -> Result<Timeout, RazerError> {
+        if secs == 0 {
+            return Err(RazerError::InvalidTimeout {
+                timeout: "0s".to_string(),
+                reason: "must be at least 1s, use \"never\" to disable".to_string(),
+            });
+        }
+        Ok(Timeout(secs))
+    }
+
+    /// Seconds until the timeout, `None` for never.
+    pub const fn as_secs(self) -> Option<u16> {
+        if self.0 == 0 {
+            None
+        } else {
+            Some(self.0)
+        }
+    }
+
+    pub const fn is_never(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn as_duration(self) -> Option<std::time::Duration> {
+        self.as_secs()
+            .map(|secs| std::time::Duration::from_secs(secs.into()))
+    }
+}
+
+impl From<Timeout> for u16 {
+    fn from(timeout: Timeout) -> u16 {
+        timeout.0
+    }
+}
+
+impl TryFrom<TimeoutRepr> for Timeout {
+    type Error = RazerError;
+
+    fn try_from(repr: TimeoutRepr) -> Result<Self, Self::Error> {
+        match repr {
+            TimeoutRepr::Seconds(0) => Ok(Timeout::NEVER),
+            TimeoutRepr::Seconds(secs) => Timeout::from_secs(secs),
+            TimeoutRepr::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl std::fmt::Display for Timeout {
+    /// Uses the largest unit that divides evenly, like `5m` or `90s`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            0 => f.write_str("never"),
+            secs if secs % 3600 == 0 => write!(f, "{}h", secs / 3600),
+            secs if secs % 60 == 0 => write!(f, "{}m", secs / 60),
+            secs => write!(f, "{}s", secs),
+        }
+    }
+}
+
+impl std::str::FromStr for Timeout {
+    type Err = RazerError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: String| RazerError::InvalidTimeout {
+            timeout: text.to_string(),
+            reason,
+        };
+        let trimmed = text.trim().to_ascii_lowercase();
+        if trimmed == "never" || trimmed == "off" {
+            return Ok(Timeout::NEVER);
+        }
+        if trimmed.ends_with("ms") || trimmed.contains('.') {
+            return Err(invalid("sub-second timeouts are not supported".to_string()));
+        }
+        let (number, unit) = match trimmed.find(|c: char| !c.is_ascii_digit()) {
+            Some(i) => trimmed.split_at(i),
+            None => (trimmed.as_str(), ""),
+        };
+        let scale: u64 = match unit.trim() {
+            "" | "s" => 1,
+            "m" => 60,
+            "h" => 3600,
+            other => {
+                return Err(invalid(format!(
+                    "unknown unit {:?}, expected s, m, h or \"never\"",
+                    other
+                )))
+            }
+        };
+        let count: u64 = number
+            .parse()
+            .map_err(|_| invalid("expected a number like 30s or 5m".to_string()))?;
+        let secs = count.saturating_mul(scale);
+        if secs > limits::TIMEOUT_MAX_SECS.into() {
+            return Err(invalid(format!(
+                "longer than the maximum of {}s",
+                limits::TIMEOUT_MAX_SECS
+            )));
+        }
+        if secs == 0 {
+            return Err(invalid(
+                "must be at least 1s, use \"never\" to disable".to_string(),
+            ));
+        }
+        Ok(Timeout(secs as u16))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use std::fmt::Debug;
+    use std::str::FromStr;
+
+    fn assert_names_round_trip<T>()
+    where
+        T: ValueEnum + FromStr<Err = RazerError> + std::fmt::Display + Debug + PartialEq,
+    {
+        for value in T::value_variants() {
+            let name = value.to_string();
+            assert_eq!(name.parse::<T>().unwrap(), *value);
+            assert_eq!(name.to_uppercase().parse::<T>().unwrap(), *value);
+            assert_eq!(name.to_lowercase().parse::<T>().unwrap(), *value);
+            let possible = value.to_possible_value().unwrap();
+            for cli_name in possible.get_name_and_aliases() {
+                assert_eq!(cli_name.parse::<T>().unwrap(), *value, "{}", cli_name);
+                assert_eq!(<T as ValueEnum>::from_str(cli_name, false).unwrap(), *value);
+            }
+        }
+        assert!(matches!(
+            "nonsense".parse::<T>(),
+            Err(RazerError::InvalidName { .. })
+        ));
+    }
+
+    #[test]
+    fn test_names_round_trip() {
+        assert_names_round_trip::<Cluster>();
+        assert_names_round_trip::<FanZone>();
+        assert_names_round_trip::<ThermalZone>();
+        assert_names_round_trip::<PerfMode>();
+        assert_names_round_trip::<MaxFanSpeedMode>();
+        assert_names_round_trip::<FanMode>();
+        assert_names_round_trip::<CpuBoost>();
+        assert_names_round_trip::<GpuBoost>();
+        assert_names_round_trip::<LogoMode>();
+        assert_names_round_trip::<LightsAlwaysOn>();
+        assert_names_round_trip::<BatteryCare>();
+    }
+
+    #[test]
+    fn test_zone_try_from_u8() {
+        for cluster in Cluster::ALL {
+            assert_eq!(Cluster::try_from(cluster as u8).unwrap(), cluster);
+        }
+        for zone in FanZone::ALL {
+            assert_eq!(FanZone::try_from(zone as u8).unwrap(), zone);
+        }
+        for zone in ThermalZone::ALL {
+            assert_eq!(ThermalZone::try_from(zone as u8).unwrap(), zone);
+        }
+        assert!(Cluster::try_from(0).is_err());
+        assert!(FanZone::try_from(3).is_err());
+        assert!(ThermalZone::try_from(0xff).is_err());
+    }
+
+    #[test]
+    fn test_name_aliases() {
+        assert_eq!("eco".parse::<GpuBoost>().unwrap(), GpuBoost::Low);
+        assert_eq!("Ultimate".parse::<GpuBoost>().unwrap(), GpuBoost::High);
+        assert_eq!("ECO".parse::<CpuBoost>().unwrap(), CpuBoost::Low);
+        assert_eq!("quiet".parse::<PerfMode>().unwrap(), PerfMode::Silent);
+        assert_eq!("on".parse::<BatteryCare>().unwrap(), BatteryCare::Enable);
+        assert_eq!(
+            "Disabled".parse::<LightsAlwaysOn>().unwrap(),
+            LightsAlwaysOn::Disable
+        );
+        assert_eq!(GpuBoost::High.to_string(), "High");
+        assert_eq!(format!("{:>6}", FanMode::Auto), "  Auto");
+    }
+
+    #[test]
+    fn test_gpu_boost_ui_labels_parse() {
+        for boost in GpuBoost::ALL {
+            assert_eq!(boost.ui_label().parse::<GpuBoost>().unwrap(), boost);
+        }
+    }
+
+    #[test]
+    fn test_boost_steps() {
+        assert!(CpuBoost::Low < CpuBoost::Overclock);
+        assert!(GpuBoost::Medium < GpuBoost::High);
+        assert!(CpuBoost::ALL.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(CpuBoost::Low.next(), CpuBoost::Medium);
+        assert_eq!(CpuBoost::Overclock.next(), CpuBoost::Overclock);
+        assert_eq!(CpuBoost::Low.prev(), CpuBoost::Low);
+        assert_eq!(CpuBoost::Overclock.cycle(), CpuBoost::Low);
+        assert_eq!(GpuBoost::High.prev(), GpuBoost::Medium);
+        assert_eq!(GpuBoost::High.cycle(), GpuBoost::Low);
+        let supported = [
+            CpuBoost::Low,
+            CpuBoost::Medium,
+            CpuBoost::High,
+            CpuBoost::Boost,
+        ];
+        assert_eq!(CpuBoost::Boost.cycle_in(&supported), CpuBoost::Low);
+        assert_eq!(CpuBoost::High.cycle_in(&[CpuBoost::High]), CpuBoost::High);
+    }
+
+    #[test]
+    fn test_perf_mode_steps() {
+        assert_eq!(PerfMode::Silent.cycle(), PerfMode::Balanced);
+        assert_eq!(PerfMode::Balanced.cycle(), PerfMode::Custom);
+        assert_eq!(PerfMode::Custom.cycle(), PerfMode::Silent);
+        assert_eq!(PerfMode::Custom.next(), PerfMode::Custom);
+        assert_eq!(PerfMode::Silent.prev(), PerfMode::Silent);
+        assert_eq!(PerfMode::Unknown(6).cycle(), PerfMode::Silent);
+        let supported = [PerfMode::Balanced, PerfMode::Custom];
+        assert_eq!(PerfMode::Custom.cycle_in(&supported), PerfMode::Balanced);
+        assert_eq!(
+            PerfMode::Unknown(6).cycle_in(&supported),
+            PerfMode::Balanced
+        );
+    }
+
+    #[test]
+    fn test_rgb() {
+        let green = Rgb::new(0x44, 0xd6, 0x2c);
+        assert_eq!("#44d62c".parse::<Rgb>().unwrap(), green);
+        assert_eq!("44D62C".parse::<Rgb>().unwrap(), green);
+        assert_eq!("Razer-Green".parse::<Rgb>().unwrap(), green);
+        assert_eq!("red".parse::<Rgb>().unwrap().to_bytes(), [0xff, 0, 0]);
+        assert_eq!(green.to_string(), "#44d62c");
+        assert_eq!(Rgb::from(green.to_bytes()), green);
+        for (name, color) in Rgb::NAMED {
+            assert_eq!(name.parse::<Rgb>().unwrap(), *color);
+            assert_eq!(color.to_string().parse::<Rgb>().unwrap(), *color);
+        }
+        assert_eq!(serde_json::to_string(&green).unwrap(), "\"#44d62c\"");
+        assert_eq!(serde_json::from_str::<Rgb>("\"#44d62c\"").unwrap(), green);
+        assert!(serde_json::from_str::<Rgb>("\"#44d6\"").is_err());
+    }
+
+    #[test]
+    fn test_maybe_known() {
+        let known = MaybeKnown::<CpuBoost>::from_raw(3);
+        let unknown = MaybeKnown::<CpuBoost>::from_raw(6);
+        assert_eq!(known, CpuBoost::Boost);
+        assert_eq!(unknown, MaybeKnown::Unknown(6));
+        assert_ne!(unknown, CpuBoost::Low);
+        assert_eq!(known.to_string(), "Boost");
+        assert_eq!(unknown.to_string(), "Unknown(6)");
+        assert_eq!(unknown.known(), None);
+        assert!(matches!(
+            unknown.into_known(),
+            Err(RazerError::InvalidValue {
+                value: 6,
+                type_name: "CpuBoost"
+            })
+        ));
+
+        assert_eq!(serde_json::to_string(&known).unwrap(), "\"Boost\"");
+        assert_eq!(serde_json::to_string(&unknown).unwrap(), r#"{"unknown":6}"#);
+        for value in [known, unknown] {
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(
+                serde_json::from_str::<MaybeKnown<CpuBoost>>(&json).unwrap(),
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_timeout_parse() {
+        assert_eq!("never".parse::<Timeout>().unwrap(), Timeout::NEVER);
+        assert_eq!("Off".parse::<Timeout>().unwrap(), Timeout::NEVER);
+        assert_eq!("90".parse::<Timeout>().unwrap().as_secs(), Some(90));
+        assert_eq!("30s".parse::<Timeout>().unwrap().as_secs(), Some(30));
+        assert_eq!(" 5m ".parse::<Timeout>().unwrap().as_secs(), Some(300));
+        assert_eq!("5 M".parse::<Timeout>().unwrap().as_secs(), Some(300));
+        assert_eq!("2h".parse::<Timeout>().unwrap().as_secs(), Some(7200));
+        for text in ["never", "90s", "5m", "2h"] {
+            assert_eq!(text.parse::<Timeout>().unwrap().to_string(), text);
+        }
+        assert_eq!(Timeout::MAX.to_string(), "65535s");
+    }
+
+    #[test]
+    fn test_timeout_rejects() {
+        for text in [
+            "",
+            "s",
+            "0",
+            "0s",
+            "500ms",
+            "0.5s",
+            "1.5m",
+            "-1s",
+            "5d",
+            "19h",
+            "99999999999999999999",
+        ] {
+            assert!(
+                matches!(
+                    text.parse::<Timeout>(),
+                    Err(RazerError::InvalidTimeout { .. })
+                ),
+                "{:?}",
+                text
+            );
+        }
+        assert!(Timeout::from_secs(0).is_err());
+    }
+
+    #[test]
+    fn test_timeout_serde() {
+        let timeout: Timeout = serde_json::from_str("300").unwrap();
+        assert_eq!(timeout.as_secs(), Some(300));
+        assert_eq!(serde_json::from_str::<Timeout>("\"5m\"").unwrap(), timeout);
+        assert_eq!(
+            serde_json::from_str::<Timeout>("0").unwrap(),
+            Timeout::NEVER
+        );
+        assert_eq!(serde_json::to_string(&timeout).unwrap(), "300");
+        assert_eq!(serde_json::to_string(&Timeout::NEVER).unwrap(), "0");
+        assert!(serde_json::from_str::<Timeout>("\"1ms\"").is_err());
+    }
+
+    #[test]
+    fn test_rgb_parse_errors() {
+        let reason = |text: &str| match text.parse::<Rgb>() {
+            Err(RazerError::InvalidColor { reason, .. }) => reason,
+            other => panic!("{:?}", other),
+        };
+        assert!(reason("#44d6").contains("got 4 characters"));
+        assert!(reason("#44d62c00").contains("got 8 characters"));
+        assert!(reason("").contains("got 0 characters"));
+        assert!(reason("#44z62c").contains("'z6' is not a hex number"));
+        assert!(reason("#44d6é").contains("got 5 characters"));
+        assert!(reason("chartreuse").contains("color name"));
+
+        // clap shows the reason when a color argument doesn't parse
+        let command = clap::Command::new("test")
+            .arg(clap::Arg::new("color").value_parser(clap::value_parser!(Rgb)));
+        let error = command
+            .try_get_matches_from(["test", "#12345"])
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("expected 6 hex digits"),
+            "{}",
+            error
+        );
+    }
+
+    fn curve() -> FanCurve {
+        FanCurve::try_from_points([(40, 2200), (60, 3500), (80, 5000)]).unwrap()
+    }
+
+    #[test]
+    fn test_fan_curve() {
+        let curve = curve();
+        assert_eq!(curve.to_string(), "40°C→2200, 60°C→3500, 80°C→5000");
+        assert_eq!(curve.rpm_for(0).get(), 2200);
+        assert_eq!(curve.rpm_for(40).get(), 2200);
+        assert_eq!(curve.rpm_for(50).get(), 2850);
+        assert_eq!(curve.rpm_for(60).get(), 3500);
+        assert_eq!(curve.rpm_for(79).get(), 4925);
+        assert_eq!(curve.rpm_for(80).get(), 5000);
+        assert_eq!(curve.rpm_for(u8::MAX).get(), 5000);
+        assert!(curve.check_range(&(2000..=5000)).is_ok());
+        assert!(matches!(
+            curve.check_range(&(2000..=4500)),
+            Err(RazerError::FanRpmOutOfRange { rpm: 5000, .. })
+        ));
+
+        let json = serde_json::to_string(&curve).unwrap();
+        assert_eq!(json, "[[40,2200],[60,3500],[80,5000]]");
+        assert_eq!(serde_json::from_str::<FanCurve>(&json).unwrap(), curve);
+    }
+
+    #[test]
+    fn test_fan_curve_rejects_invalid_points() {
+        let invalid = |points: &[(u8, u16)]| match FanCurve::try_from_points(points.iter().copied())
+        {
+            Err(RazerError::InvalidFanCurve(reason)) => reason,
+            other => panic!("{:?}", other),
+        };
+        assert!(invalid(&[]).contains("got 0"));
+        assert!(invalid(&[(40, 2200)]).contains("got 1"));
+        assert!(invalid(&[(60, 2200), (60, 3000)]).contains("60°C follows 60°C"));
+        assert!(invalid(&[(60, 2200), (40, 3000)]).contains("40°C follows 60°C"));
+        assert!(matches!(
+            FanCurve::try_from_points([(40, 2200), (60, 30000)]),
+            Err(RazerError::FanRpmOutOfRange { .. })
+        ));
+        assert!(serde_json::from_str::<FanCurve>("[[60,2200],[40,3000]]").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_fan_curve_interpolation_is_monotone(
+            temps in proptest::collection::btree_set(any::<u8>(), 2..8),
+            steps in proptest::collection::vec(0u16..3000, 8),
+        ) {
+            // non-decreasing speeds, capped at the encodable maximum
+            let mut rpm = 0u16;
+            let points: Vec<(u8, u16)> = temps
+                .into_iter()
+                .zip(steps)
+                .map(|(temp, step)| {
+                    rpm = (rpm + step).min(FanRpm::MAX);
+                    (temp, rpm)
+                })
+                .collect();
+            let curve = FanCurve::try_from_points(points.clone()).unwrap();
+            for temp in 0..u8::MAX {
+                prop_assert!(curve.rpm_for(temp) <= curve.rpm_for(temp + 1));
+            }
+            for (temp, rpm) in points {
+                prop_assert_eq!(curve.rpm_for(temp).get(), rpm);
+            }
+        }
+    }
+
+    #[test]
+    fn test_charge_limit_rounding() {
+        assert_eq!(ChargeLimit::new(80).get(), 80);
+        assert_eq!(ChargeLimit::new(72).get(), 70);
+        assert_eq!(ChargeLimit::new(73).get(), 75);
+        assert_eq!(ChargeLimit::new(0), ChargeLimit::MIN);
+        assert_eq!(ChargeLimit::new(49), ChargeLimit::MIN);
+        assert_eq!(ChargeLimit::new(99), ChargeLimit::MAX);
+        assert_eq!(ChargeLimit::new(u8::MAX), ChargeLimit::MAX);
+        assert_eq!(ChargeLimit::new(80).to_string(), "80%");
+        assert_eq!("75%".parse::<ChargeLimit>().unwrap().get(), 75);
+        assert_eq!("77".parse::<ChargeLimit>().unwrap().get(), 75);
+        assert!("49".parse::<ChargeLimit>().is_err());
+        assert!("101%".parse::<ChargeLimit>().is_err());
+        assert_eq!(serde_json::to_string(&ChargeLimit::new(80)).unwrap(), "80");
+        assert_eq!(
+            serde_json::from_str::<ChargeLimit>("60").unwrap(),
+            ChargeLimit::new(60)
+        );
+        assert!(serde_json::from_str::<ChargeLimit>("30").is_err());
+    }
+
+    #[test]
+    fn test_charge_limit_wire() {
+        use ChargeLimitEncoding::*;
+        for encoding in [Percent, FlaggedPercent] {
+            for percent in ChargeLimit::MIN.get()..=ChargeLimit::MAX.get() {
+                let limit = ChargeLimit::new(percent);
+                let wire = limit.to_wire(encoding);
+                assert_eq!(ChargeLimit::from_wire(wire, encoding).unwrap(), limit);
+            }
+        }
+        // battery care's values on the 2023 Blades
+        assert_eq!(ChargeLimit::new(80).to_wire(FlaggedPercent), 0xd0);
+        assert_eq!(ChargeLimit::MAX.to_wire(FlaggedPercent), 0x50);
+        assert_eq!(
+            ChargeLimit::from_wire(0x50, FlaggedPercent).unwrap(),
+            ChargeLimit::MAX
+        );
+        assert_eq!(
+            ChargeLimit::from_wire(0xc9, FlaggedPercent).unwrap().get(),
+            73
+        );
+        assert!(ChargeLimit::from_wire(0x80 | 20, FlaggedPercent).is_err());
+        assert_eq!(ChargeLimit::new(60).to_wire(Percent), 60);
+        assert!(ChargeLimit::from_wire(0xd0, Percent).is_err());
+    }
+
+    #[test]
+    fn test_fan_rpm() {
+        let rpm = FanRpm::new(3500).unwrap();
+        assert_eq!(rpm.as_raw_byte(), 35);
+        assert_eq!(FanRpm::from_raw_byte(35), rpm);
+        assert_eq!(u16::from(rpm), 3500);
+        assert_eq!(rpm.to_string(), "3500 RPM");
+        assert_eq!("3500".parse::<FanRpm>().unwrap(), rpm);
+        assert_eq!("3500 rpm".parse::<FanRpm>().unwrap(), rpm);
+        assert!("fast".parse::<FanRpm>().is_err());
+        assert_eq!(FanRpm::new(FanRpm::MAX).unwrap().as_raw_byte(), u8::MAX);
+        assert!(matches!(
+            FanRpm::new(FanRpm::MAX + 100),
+            Err(RazerError::FanRpmOutOfRange {
+                max: FanRpm::MAX,
+                ..
+            })
+        ));
+        assert!(FanRpm::new_in(5300, &(2000..=5000)).is_err());
+        assert!(FanRpm::new_in(1900, &(2000..=5000)).is_err());
+        assert!(FanRpm::new_in(5000, &(2000..=5000)).is_ok());
+        assert_eq!(serde_json::to_string(&rpm).unwrap(), "3500");
+        assert_eq!(serde_json::from_str::<FanRpm>("3500").unwrap(), rpm);
+        assert!(serde_json::from_str::<FanRpm>("30000").is_err());
+    }
+
+    #[test]
+    fn test_brightness() {
+        assert_eq!(Brightness::from_percent(0), Brightness::OFF);
+        assert_eq!(Brightness::from_percent(100), Brightness::MAX);
+        assert_eq!(Brightness::from_percent(150), Brightness::MAX);
+        assert_eq!(Brightness::from_percent(50).get(), 128);
+        for percent in 0..=100 {
+            assert_eq!(Brightness::from_percent(percent).as_percent(), percent);
+        }
+        assert_eq!(Brightness::new(128).to_string(), "50% (128)");
+        assert_eq!(Brightness::MAX.saturating_add(10), Brightness::MAX);
+        assert_eq!(Brightness::new(5).saturating_sub(10), Brightness::OFF);
+        assert_eq!(Brightness::new(5).saturating_add(10).get(), 15);
+        assert_eq!("128".parse::<Brightness>().unwrap().get(), 128);
+        assert_eq!("50%".parse::<Brightness>().unwrap().get(), 128);
+        assert!("101%".parse::<Brightness>().is_err());
+        assert!("256".parse::<Brightness>().is_err());
+        assert_eq!(serde_json::to_string(&Brightness::new(77)).unwrap(), "77");
+    }
+
+    #[test]
+    fn test_perf_mode_serde() {
+        let modes = [
+            PerfMode::Balanced,
+            PerfMode::Silent,
+            PerfMode::Custom,
+            PerfMode::Unknown(6),
+        ];
+        // Guards the on-disk names against accidental renames
+        let fixture = include_str!("snapshots/perf_modes.json");
+        assert_eq!(
+            serde_json::to_string_pretty(&modes).unwrap() + "\n",
+            fixture
+        );
+        assert_eq!(
+            serde_json::from_str::<Vec<PerfMode>>(fixture).unwrap(),
+            modes
+        );
+        // Names written before the renames still load
+        assert_eq!(
+            serde_json::from_str::<PerfMode>("\"Silent\"").unwrap(),
+            PerfMode::Silent
+        );
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Profile {
+            perf_mode: PerfMode,
+        }
+        for perf_mode in modes {
+            let profile = Profile { perf_mode };
+            let text = toml::to_string(&profile).unwrap();
+            assert_eq!(toml::from_str::<Profile>(&text).unwrap(), profile);
+        }
+    }
+
+    #[test]
+    fn test_unknown_perf_mode_names() {
+        assert_eq!(PerfMode::Unknown(6).to_string(), "Unknown(6)");
+        assert_eq!(
+            "unknown(6)".parse::<PerfMode>().unwrap(),
+            PerfMode::Unknown(6)
+        );
+        assert!("Unknown(256)".parse::<PerfMode>().is_err());
+        assert!("Unknown(".parse::<PerfMode>().is_err());
+        assert!(PerfMode::Unknown(6).to_possible_value().is_none());
+        assert!(matches!(
+            "turbo".parse::<PerfMode>(),
+            Err(RazerError::InvalidName {
+                type_name: "PerfMode",
+                ..
+            })
+        ));
+    }
 
     #[test]
     fn test_perf_mode_try_from() {
@@ -125,6 +1558,10 @@ mod tests {
         assert_eq!(PerfMode::try_from(5).unwrap(), PerfMode::Silent);
         assert_eq!(PerfMode::try_from(4).unwrap(), PerfMode::Custom);
         assert!(PerfMode::try_from(99).is_err());
+        assert_eq!(PerfMode::from_raw(6), PerfMode::Unknown(6));
+        for mode in PerfMode::ALL {
+            assert_eq!(PerfMode::from_raw(mode.raw()), mode);
+        }
     }
 
     #[test]
@@ -181,4 +1618,51 @@ mod tests {
         );
         assert!(MaxFanSpeedMode::try_from(0x01).is_err());
     }
+
+    /// Checks `TryFrom<u8>` of fieldless enums against their discriminants: a
+    /// byte converts iff a variant is declared with it, and to that variant.
+    macro_rules! prop_try_from_discriminants {
+        ($($name:ident: $type:ty),+ $(,)?) => {
+            proptest! {
+                $(
+                    #[test]
+                    fn $name(value: u8) {
+                        let declared = <$type as ValueEnum>::value_variants()
+                            .iter()
+                            .find(|variant| **variant as u8 == value);
+                        match <$type>::try_from(value) {
+                            Ok(variant) => prop_assert_eq!(Some(&variant), declared),
+                            Err(e) => {
+                                prop_assert_eq!(declared, None);
+                                let rejected = matches!(e, RazerError::InvalidValue { value: v, .. } if v == value);
+                                prop_assert!(rejected);
+                            }
+                        }
+                    }
+                )+
+            }
+        };
+    }
+
+    prop_try_from_discriminants! {
+        prop_cluster_try_from: Cluster,
+        prop_fan_zone_try_from: FanZone,
+        prop_thermal_zone_try_from: ThermalZone,
+        prop_cpu_boost_try_from: CpuBoost,
+        prop_gpu_boost_try_from: GpuBoost,
+        prop_fan_mode_try_from: FanMode,
+        prop_lights_always_on_try_from: LightsAlwaysOn,
+        prop_battery_care_try_from: BatteryCare,
+        prop_max_fan_speed_mode_try_from: MaxFanSpeedMode,
+    }
+
+    proptest! {
+        #[test]
+        fn prop_perf_mode_try_from(value: u8) {
+            let declared = PerfMode::ALL.into_iter().find(|mode| mode.raw() == value);
+            prop_assert_eq!(PerfMode::try_from(value).ok(), declared);
+            // from_raw keeps every byte
+            prop_assert_eq!(PerfMode::from_raw(value).raw(), value);
+        }
+    }
 }
@@ -0,0 +1,324 @@
+//! Background polling that reports setting changes instead of snapshots.
+//!
+//! [`StateWatcher`] reads the watched fields of a device on its own thread every
+//! interval, sending only their packets (see [`read_fields`](command::read_fields)),
+//! and sends a [`StateChange`] for each field that differs from the previous read,
+//! so a UI only redraws what changed. The first read reports every watched field
+//! that could be read, from `None`.
+
+use crate::command;
+use crate::device::CommandTarget;
+use crate::error::{RazerError, Result};
+use crate::state::{DeviceState, Field, StateChange};
+use log::{debug, trace};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What a [`StateWatcher`] reads and how often.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchOptions {
+    /// Time between reads.
+    pub interval: Duration,
+    /// Fields to read and report changes of.
+    pub fields: Vec<Field>,
+    /// Changes that can wait in the channel. Further changes are held back and
+    /// merged per field until the receiver catches up.
+    pub buffer: usize,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        WatchOptions {
+            interval: Duration::from_secs(2),
+            fields: Field::ALL.to_vec(),
+            buffer: 64,
+        }
+    }
+}
+
+#[derive(Default)]
+struct ControlState {
+    stopped: bool,
+    paused: bool,
+}
+
+/// Shared between the watcher, its [`StopHandle`]s and the polling thread.
+#[derive(Default)]
+struct Control {
+    state: Mutex<ControlState>,
+    wake: Condvar,
+}
+
+impl Control {
+    fn lock(&self) -> MutexGuard<'_, ControlState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ControlState)) {
+        f(&mut self.lock());
+        self.wake.notify_all();
+    }
+
+    /// Waits until the next read is due, returning `false` once stopped. Time
+    /// spent paused doesn't count, and resuming reads right away.
+    fn wait(&self, interval: Duration) -> bool {
+        let deadline = Instant::now() + interval;
+        let mut state = self.lock();
+        let mut was_paused = false;
+        loop {
+            if state.stopped {
+                return false;
+            }
+            if state.paused {
+                was_paused = true;
+                state = self.wake.wait(state).unwrap_or_else(|e| e.into_inner());
+                continue;
+            }
+            let now = Instant::now();
+            if was_paused || now >= deadline {
+                return true;
+            }
+            state = self
+                .wake
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+}
+
+/// Stops a [`StateWatcher`] from another thread.
+#[derive(Clone)]
+pub struct StopHandle(Arc<Control>);
+
+impl StopHandle {
+    /// Stops polling. The receiver gets nothing more once the current read ends.
+    pub fn stop(&self) {
+        self.0.update(|state| state.stopped = true);
+    }
+}
+
+/// Thread polling a device and sending the changes it sees.
+///
+/// Dropping the watcher stops the thread, as does dropping the receiver.
+pub struct StateWatcher {
+    control: Arc<Control>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl StateWatcher {
+    /// Moves `device`, e.g. a [`Device`](crate::device::Device) or a
+    /// [`SharedDevice`](crate::device::SharedDevice), to a new polling thread.
+    pub fn spawn<D>(
+        device: D,
+        options: WatchOptions,
+    ) -> Result<(StateWatcher, mpsc::Receiver<StateChange>)>
+    where
+        D: CommandTarget + Send + 'static,
+    {
+        let (events, receiver) = mpsc::sync_channel(options.buffer);
+        let control = Arc::new(Control::default());
+        let thread = thread::Builder::new()
+            .name("razer-watch".to_string())
+            .spawn({
+                let control = control.clone();
+                move || poll(&device, &options, &control, &events)
+            })
+            .map_err(|e| RazerError::Other(format!("Failed to start state watcher: {}", e)))?;
+        let watcher = StateWatcher {
+            control,
+            thread: Some(thread),
+        };
+        Ok((watcher, receiver))
+    }
+
+    /// Stops reading until [`resume`](Self::resume), e.g. while the UI is hidden.
+    pub fn pause(&self) {
+        self.control.update(|state| state.paused = true);
+    }
+
+    /// Reads right away and then every interval again. Changes made while paused
+    /// are reported against the last read before the pause.
+    pub fn resume(&self) {
+        self.control.update(|state| state.paused = false);
+    }
+
+    /// Returns whether the watcher is paused.
+    pub fn is_paused(&self) -> bool {
+        self.control.lock().paused
+    }
+
+    /// Returns a handle that stops the watcher from elsewhere.
+    pub fn stop_handle(&self) -> StopHandle {
+        StopHandle(self.control.clone())
+    }
+
+    /// Stops polling and waits for the thread to finish.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop_handle().stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for StateWatcher {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn poll(
+    device: &impl CommandTarget,
+    options: &WatchOptions,
+    control: &Control,
+    events: &mpsc::SyncSender<StateChange>,
+) {
+    let mut previous = DeviceState::default();
+    let mut pending = Vec::new();
+    loop {
+        let state = command::read_fields(device, &options.fields).into_state();
+        if state == DeviceState::default() {
+            // nothing answered, e.g. while disconnected; don't report every field as gone
+            debug!("State watcher read nothing, keeping the previous state");
+        } else {
+            coalesce(&mut pending, previous.diff(&state, &options.fields));
+            previous = state;
+        }
+
+        while let Some(&change) = pending.first() {
+            match events.try_send(change) {
+                Ok(()) => {
+                    pending.remove(0);
+                }
+                Err(mpsc::TrySendError::Full(_)) => {
+                    trace!("State watcher holding back {} changes", pending.len());
+                    break;
+                }
+                Err(mpsc::TrySendError::Disconnected(_)) => return,
+            }
+        }
+
+        if !control.wait(options.interval) {
+            return;
+        }
+    }
+}
+
+/// Adds `changes` to the unsent `pending` ones, merging changes of the same field
+/// and dropping those that end where they started.
+fn coalesce(pending: &mut Vec<StateChange>, changes: Vec<StateChange>) {
+    for change in changes {
+        match pending.iter().position(|p| p.field == change.field) {
+            Some(index) if pending[index].old == change.new => {
+                pending.remove(index);
+            }
+            Some(index) => pending[index].new = change.new,
+            None => pending.push(change),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::cmd;
+    use crate::descriptor::{Descriptor, SUPPORTED};
+    use crate::packet::Packet;
+    use crate::state::FieldValue;
+    use crate::types::Brightness;
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    /// Answers the keyboard brightness from `brightness` and echoes the rest.
+    struct Target {
+        info: Descriptor,
+        brightness: Arc<AtomicU8>,
+    }
+
+    impl CommandTarget for Target {
+        fn info(&self) -> &Descriptor {
+            &self.info
+        }
+
+        fn send(&self, report: Packet) -> Result<Packet> {
+            if report.command() == cmd::GET_KBD_BRIGHTNESS {
+                let brightness = self.brightness.load(Ordering::SeqCst);
+                return Ok(Packet::new(cmd::GET_KBD_BRIGHTNESS, &[1, 5, brightness]));
+            }
+            Ok(report)
+        }
+    }
+
+    fn brightness(value: u8) -> Option<FieldValue> {
+        Some(FieldValue::KeyboardBrightness(Brightness::new(value)))
+    }
+
+    #[test]
+    fn test_watcher_reports_changes() {
+        let level = Arc::new(AtomicU8::new(10));
+        let target = Target {
+            info: SUPPORTED[0].clone(),
+            brightness: level.clone(),
+        };
+        let options = WatchOptions {
+            interval: Duration::from_millis(5),
+            fields: vec![Field::KeyboardBrightness],
+            ..Default::default()
+        };
+        let (watcher, events) = StateWatcher::spawn(target, options).unwrap();
+        let next = || events.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        let first = next();
+        assert_eq!((first.old, first.new), (None, brightness(10)));
+
+        level.store(20, Ordering::SeqCst);
+        let change = next();
+        assert_eq!(change.field, Field::KeyboardBrightness);
+        assert_eq!((change.old, change.new), (brightness(10), brightness(20)));
+
+        watcher.pause();
+        assert!(watcher.is_paused());
+        // let a read that started before the pause finish
+        thread::sleep(Duration::from_millis(50));
+        while events.try_recv().is_ok() {}
+        level.store(30, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(50));
+        assert!(events.try_recv().is_err());
+
+        watcher.resume();
+        let change = next();
+        assert_eq!(change.new, brightness(30));
+
+        watcher.stop_handle().stop();
+        watcher.stop();
+        assert!(events.recv().is_err());
+    }
+
+    #[test]
+    fn test_coalesce_merges_per_field() {
+        let change = |field, old, new| StateChange {
+            field,
+            old: brightness(old),
+            new: brightness(new),
+        };
+        let mut pending = vec![change(Field::KeyboardBrightness, 0, 50)];
+        coalesce(
+            &mut pending,
+            vec![change(Field::KeyboardBrightness, 50, 60)],
+        );
+        assert_eq!(pending, [change(Field::KeyboardBrightness, 0, 60)]);
+
+        coalesce(&mut pending, vec![change(Field::LogoMode, 1, 2)]);
+        assert_eq!(pending.len(), 2);
+
+        // back where it started
+        coalesce(&mut pending, vec![change(Field::KeyboardBrightness, 60, 0)]);
+        assert_eq!(pending, [change(Field::LogoMode, 1, 2)]);
+    }
+}
@@ -0,0 +1,356 @@
+//! Background thread that owns a [`Device`] and coalesces redundant writes.
+//!
+//! Each command takes a few milliseconds, so a UI firing a write per slider step
+//! falls behind. [`DeviceWorker`] queues requests for a dedicated thread; before
+//! executing a batch it drops writes that a later write of the same setting
+//! overrides, unless something in between depends on them (e.g. a boost that
+//! needs the preceding performance mode).
+
+use crate::command;
+use crate::device::Device;
+use crate::error::{RazerError, Result};
+use crate::types::{
+    BatteryCare, Brightness, CpuBoost, FanMode, FanRpm, GpuBoost, LightsAlwaysOn, LogoMode,
+    MaxFanSpeedMode, PerfMode,
+};
+use log::trace;
+use std::mem::discriminant;
+use std::sync::mpsc;
+use std::thread;
+
+/// A setting change handled by [`DeviceWorker::write`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Write {
+    PerfMode(PerfMode),
+    CpuBoost(CpuBoost),
+    GpuBoost(GpuBoost),
+    FanMode(FanMode),
+    FanRpm(FanRpm),
+    MaxFanSpeedMode(MaxFanSpeedMode),
+    KeyboardBrightness(Brightness),
+    LogoMode(LogoMode),
+    LightsAlwaysOn(LightsAlwaysOn),
+    BatteryCare(BatteryCare),
+}
+
+impl Write {
+    fn apply(self, device: &Device) -> Result<()> {
+        match self {
+            Write::PerfMode(mode) => command::set_perf_mode(device, mode),
+            Write::CpuBoost(boost) => command::set_cpu_boost(device, boost),
+            Write::GpuBoost(boost) => command::set_gpu_boost(device, boost),
+            Write::FanMode(mode) => command::set_fan_mode(device, mode),
+            Write::FanRpm(rpm) => command::set_fan_rpm(device, rpm),
+            Write::MaxFanSpeedMode(mode) => command::set_max_fan_speed_mode(device, mode),
+            Write::KeyboardBrightness(brightness) => {
+                command::set_keyboard_brightness(device, brightness)
+            }
+            Write::LogoMode(mode) => command::set_logo_mode(device, mode),
+            Write::LightsAlwaysOn(mode) => command::set_lights_always_on(device, mode),
+            Write::BatteryCare(mode) => command::set_battery_care(device, mode),
+        }
+    }
+
+    /// Performance mode, boosts and fan settings constrain each other, so their
+    /// relative order must be kept.
+    fn is_thermal(&self) -> bool {
+        matches!(
+            self,
+            Write::PerfMode(_)
+                | Write::CpuBoost(_)
+                | Write::GpuBoost(_)
+                | Write::FanMode(_)
+                | Write::FanRpm(_)
+                | Write::MaxFanSpeedMode(_)
+        )
+    }
+
+    fn same_setting(&self, other: &Write) -> bool {
+        discriminant(self) == discriminant(other)
+    }
+
+    /// Whether dropping one of the two writes could change what the other does.
+    fn interacts_with(&self, other: &Write) -> bool {
+        self.is_thermal() && other.is_thermal()
+    }
+}
+
+type Job = Box<dyn FnOnce(&Device) + Send>;
+
+enum Message {
+    /// A write and the replies of every request it stands for, oldest first.
+    Write(Write, Vec<mpsc::Sender<Result<()>>>),
+    /// Arbitrary work; never reordered or dropped.
+    Job(Job),
+}
+
+impl Message {
+    fn execute(self, device: &Device) {
+        match self {
+            Message::Write(write, replies) => {
+                trace!("Worker applying {:?}", write);
+                let result = write.apply(device);
+                for reply in replies {
+                    let _ = reply.send(result.clone());
+                }
+            }
+            Message::Job(job) => job(device),
+        }
+    }
+}
+
+/// Drops writes overridden by a later write of the same setting, moving their
+/// replies to the later write.
+fn coalesce(queue: impl IntoIterator<Item = Message>) -> Vec<Message> {
+    let mut coalesced: Vec<Message> = Vec::new();
+    for message in queue {
+        let Message::Write(write, mut replies) = message else {
+            coalesced.push(message);
+            continue;
+        };
+        // the closest earlier request that constrains this write, if it is a
+        // write of the same setting it can be replaced
+        let superseded = coalesced
+            .iter()
+            .rposition(|earlier| match earlier {
+                Message::Write(earlier, _) => {
+                    earlier.same_setting(&write) || earlier.interacts_with(&write)
+                }
+                Message::Job(_) => true,
+            })
+            .filter(|&index| {
+                matches!(&coalesced[index], Message::Write(earlier, _) if earlier.same_setting(&write))
+            });
+        if let Some(index) = superseded {
+            if let Message::Write(_, mut earlier_replies) = coalesced.remove(index) {
+                earlier_replies.append(&mut replies);
+                replies = earlier_replies;
+            }
+        }
+        coalesced.push(Message::Write(write, replies));
+    }
+    coalesced
+}
+
+/// Result of a request queued on a [`DeviceWorker`].
+pub struct Pending<T> {
+    reply: mpsc::Receiver<Result<T>>,
+}
+
+impl<T> Pending<T> {
+    /// Blocks until the request has been handled.
+    pub fn wait(self) -> Result<T> {
+        self.reply.recv().unwrap_or_else(|_| Err(stopped()))
+    }
+
+    /// Returns the result if the request has been handled, without blocking.
+    pub fn try_get(&self) -> Option<Result<T>> {
+        match self.reply.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err(stopped())),
+        }
+    }
+}
+
+fn stopped() -> RazerError {
+    RazerError::Other("Device worker stopped".to_string())
+}
+
+/// Thread owning a [`Device`] that runs queued requests in order.
+///
+/// Requests queued while the device is busy are coalesced: of several pending
+/// writes to the same setting only the latest is sent, and the earlier ones
+/// share its result. Dropping the worker finishes the queue and stops the thread.
+pub struct DeviceWorker {
+    requests: Option<mpsc::Sender<Message>>,
+    thread: Option<thread::JoinHandle<Device>>,
+}
+
+impl DeviceWorker {
+    /// Moves `device` to a new worker thread.
+    pub fn spawn(device: Device) -> Result<Self> {
+        let (requests, queue) = mpsc::channel::<Message>();
+        let thread = thread::Builder::new()
+            .name("razer-worker".to_string())
+            .spawn(move || {
+                while let Ok(first) = queue.recv() {
+                    let batch: Vec<_> = std::iter::once(first).chain(queue.try_iter()).collect();
+                    let received = batch.len();
+                    let batch = coalesce(batch);
+                    trace!("Worker running {} of {} requests", batch.len(), received);
+                    for message in batch {
+                        message.execute(&device);
+                    }
+                }
+                device
+            })
+            .map_err(|e| RazerError::Other(format!("Failed to start device worker: {}", e)))?;
+        Ok(DeviceWorker {
+            requests: Some(requests),
+            thread: Some(thread),
+        })
+    }
+
+    /// Queues a setting change.
+    pub fn write(&self, write: Write) -> Pending<()> {
+        let (reply, pending) = mpsc::channel();
+        self.send(Message::Write(write, vec![reply]));
+        Pending { reply: pending }
+    }
+
+    /// Queues `f` to run with the device, e.g. a read. Jobs are never coalesced
+    /// and writes are not coalesced across them.
+    pub fn run<T, F>(&self, f: F) -> Pending<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Device) -> Result<T> + Send + 'static,
+    {
+        let (reply, pending) = mpsc::channel();
+        self.send(Message::Job(Box::new(move |device| {
+            let _ = reply.send(f(device));
+        })));
+        Pending { reply: pending }
+    }
+
+    /// Finishes the queued requests and returns the device.
+    pub fn shutdown(mut self) -> Result<Device> {
+        self.stop().ok_or_else(stopped)
+    }
+
+    fn send(&self, message: Message) {
+        // if the thread is gone the reply sender is dropped, which Pending reports
+        if let Some(requests) = &self.requests {
+            let _ = requests.send(message);
+        }
+    }
+
+    fn stop(&mut self) -> Option<Device> {
+        self.requests.take();
+        self.thread.take()?.join().ok()
+    }
+}
+
+impl Drop for DeviceWorker {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::cmd;
+    use crate::descriptor::SUPPORTED;
+    use crate::transport::MockTransport;
+
+    fn worker() -> (DeviceWorker, MockTransport) {
+        let mock = MockTransport::new();
+        let device = Device::with_transport(mock.clone(), SUPPORTED[0].clone());
+        (DeviceWorker::spawn(device).unwrap(), mock)
+    }
+
+    /// Keeps the worker busy until the returned sender is dropped, so that
+    /// requests queued meanwhile end up in one batch.
+    fn block(worker: &DeviceWorker) -> mpsc::Sender<()> {
+        let (started, running) = mpsc::channel();
+        let (release, released) = mpsc::channel::<()>();
+        worker.run(move |_| {
+            started.send(()).unwrap();
+            let _ = released.recv();
+            Ok(())
+        });
+        running.recv().unwrap();
+        release
+    }
+
+    #[test]
+    fn test_rapid_writes_are_coalesced() {
+        let (worker, mock) = worker();
+        let release = block(&worker);
+        let pending: Vec<_> = (0..100)
+            .map(|brightness| worker.write(Write::KeyboardBrightness(Brightness::new(brightness))))
+            .collect();
+        drop(release);
+
+        for pending in pending {
+            pending.wait().unwrap();
+        }
+        let commands = mock.sent_commands();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].1[2], 99);
+    }
+
+    #[test]
+    fn test_thermal_order_is_kept() {
+        let (worker, mock) = worker();
+        let release = block(&worker);
+        let writes = [
+            Write::GpuBoost(GpuBoost::Low),
+            Write::PerfMode(PerfMode::Custom),
+            Write::GpuBoost(GpuBoost::High),
+            Write::KeyboardBrightness(Brightness::new(10)),
+            Write::GpuBoost(GpuBoost::Medium),
+        ];
+        let pending: Vec<_> = writes.iter().map(|&write| worker.write(write)).collect();
+        let read = worker.run(command::get_keyboard_brightness);
+        let after_read = worker.write(Write::KeyboardBrightness(Brightness::new(20)));
+        drop(release);
+
+        for pending in pending {
+            // the mock echoes a Balanced perf mode, so boosts fail their precondition
+            let _ = pending.wait();
+        }
+        read.wait().unwrap();
+        after_read.wait().unwrap();
+
+        let device = worker.shutdown().unwrap();
+        assert_eq!(device.info().pid, SUPPORTED[0].pid);
+        let commands: Vec<u16> = mock.sent_commands().iter().map(|c| c.0).collect();
+        let expected = [
+            // GPU boost Low: perf mode check on both zones
+            &[cmd::GET_PERF_MODE, cmd::GET_PERF_MODE][..],
+            &[cmd::SET_PERF_MODE, cmd::SET_PERF_MODE],
+            &[cmd::SET_KBD_BRIGHTNESS],
+            // GPU boost Medium replaced High, still after the perf mode
+            &[cmd::GET_PERF_MODE, cmd::GET_PERF_MODE],
+            &[cmd::GET_KBD_BRIGHTNESS],
+            // brightness 20, not merged across the read
+            &[cmd::SET_KBD_BRIGHTNESS],
+        ]
+        .concat();
+        assert_eq!(commands, expected);
+    }
+
+    #[test]
+    fn test_coalesced_writes_share_the_error() {
+        let (worker, mock) = worker();
+        let release = block(&worker);
+        let pending: Vec<_> = [GpuBoost::Low, GpuBoost::High]
+            .map(|boost| worker.write(Write::GpuBoost(boost)))
+            .into_iter()
+            .collect();
+        drop(release);
+
+        // the mock echoes a Balanced perf mode, which boosts don't accept
+        for pending in pending {
+            let error = pending.wait().unwrap_err();
+            assert!(
+                matches!(error.root(), RazerError::ModeRequired { .. }),
+                "{:?}",
+                error
+            );
+        }
+        assert_eq!(mock.sent_commands().len(), 2);
+    }
+
+    #[test]
+    fn test_pending_after_stop() {
+        let (mut worker, _mock) = worker();
+        worker.stop();
+        assert!(worker
+            .write(Write::KeyboardBrightness(Brightness::new(1)))
+            .wait()
+            .is_err());
+    }
+}
@@ -0,0 +1,17 @@
+//! Checks that descriptor tables are validated at compile time.
+
+#[test]
+fn descriptor_tables() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/duplicate_prefix.rs");
+    t.compile_fail("tests/ui/shadowed_prefix.rs");
+    t.pass("tests/ui/ordered_prefixes.rs");
+}
+
+#[test]
+fn descriptor_macro() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/descriptor_unknown_feature.rs");
+    t.compile_fail("tests/ui/descriptor_field_order.rs");
+    t.compile_fail("tests/ui/descriptor_bad_quirks.rs");
+}
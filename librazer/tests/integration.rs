@@ -0,0 +1,560 @@
+//! Scenario tests: whole command sequences against a scripted EC that enforces
+//! the same preconditions as the firmware, with failures injected mid-sequence.
+//!
+//! Unlike `MockTransport`, which echoes whatever it is sent, [`Ec`] keeps the
+//! state of both thermal zones and rejects commands the real EC would reject,
+//! so a regression in the order commands are sent fails here even when every
+//! unit test of the single commands passes.
+
+use librazer::command::{self, cmd};
+use librazer::descriptor::{Descriptor, SUPPORTED};
+use librazer::device::{Device, SendOptions};
+use librazer::error::{RazerError, Remedy, Result};
+use librazer::feature;
+use librazer::packet::Packet;
+use librazer::persist::{Restored, SavedSettings};
+use librazer::state::DeviceState;
+use librazer::transport::Transport;
+use librazer::types::{Brightness, CpuBoost, FanMode, FanRpm, GpuBoost, PerfMode};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+const BUSY: u8 = 0x01;
+const SUCCESSFUL: u8 = 0x02;
+const FAILURE: u8 = 0x03;
+const NOT_SUPPORTED: u8 = 0x05;
+
+const PERF_BALANCED: u8 = 0;
+const PERF_CUSTOM: u8 = 4;
+const PERF_SILENT: u8 = 5;
+const FAN_AUTO: u8 = 0;
+const FAN_MANUAL: u8 = 1;
+/// Speed the EC reports in automatic fan mode, in hundreds of RPM.
+const AUTO_FAN_RPM: u8 = 32;
+
+/// Failure the EC answers a future report with.
+#[derive(Clone, Copy)]
+enum Fault {
+    /// Applies the report but answers Busy for this many reads first.
+    Busy(u32),
+    /// Answers Failure without applying the report.
+    Failure,
+}
+
+struct EcState {
+    /// (perf mode, fan mode) per thermal zone
+    perf: [(u8, u8); 2],
+    /// CPU and GPU boost
+    boost: [u8; 2],
+    /// Fan speed per zone, in hundreds of RPM
+    fan_rpm: [u8; 2],
+    max_fan_speed: u8,
+    kbd_brightness: u8,
+    /// Commands answered with NotSupported.
+    unsupported: Vec<u16>,
+    /// Every report received, as (command, arguments).
+    log: Vec<(u16, Vec<u8>)>,
+    /// Faults for upcoming reports, by how many reports from now they hit.
+    faults: Vec<(usize, Fault)>,
+    /// Response to the last report and the Busy reads still to answer first.
+    response: Option<([u8; Packet::SIZE], u32)>,
+}
+
+/// Scripted EC behind the [`Transport`] trait. Clones share state, so a test
+/// keeps a handle after moving one into a [`Device`].
+#[derive(Clone)]
+struct Ec(Arc<Mutex<EcState>>);
+
+impl Ec {
+    /// A laptop in Balanced mode with automatic fans.
+    fn new() -> Self {
+        Ec(Arc::new(Mutex::new(EcState {
+            perf: [(PERF_BALANCED, FAN_AUTO); 2],
+            boost: [0; 2],
+            fan_rpm: [AUTO_FAN_RPM; 2],
+            max_fan_speed: 0,
+            kbd_brightness: 128,
+            unsupported: Vec::new(),
+            log: Vec::new(),
+            faults: Vec::new(),
+            response: None,
+        })))
+    }
+
+    /// Answers `commands` with NotSupported, like a model without that hardware.
+    fn without(self, commands: &[u16]) -> Self {
+        self.lock().unsupported.extend_from_slice(commands);
+        self
+    }
+
+    /// Makes the report `after` reports from now, 0 being the next, hit `fault`.
+    fn inject(&self, after: usize, fault: Fault) {
+        self.lock().faults.push((after, fault));
+    }
+
+    /// Returns and forgets the reports received so far.
+    fn take_log(&self) -> Vec<(u16, Vec<u8>)> {
+        std::mem::take(&mut self.lock().log)
+    }
+
+    /// Returns the commands of the reports received so far, forgetting them.
+    fn take_commands(&self) -> Vec<u16> {
+        self.take_log()
+            .into_iter()
+            .map(|(command, _)| command)
+            .collect()
+    }
+
+    fn perf(&self) -> [(u8, u8); 2] {
+        self.lock().perf
+    }
+
+    fn lock(&self) -> MutexGuard<'_, EcState> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Index of a thermal/fan zone or boost cluster argument (1 or 2).
+fn zone_index(zone: u8) -> Option<usize> {
+    matches!(zone, 1 | 2).then(|| zone as usize - 1)
+}
+
+impl EcState {
+    /// Applies `request` and returns the status and the arguments to reply with.
+    fn handle(&mut self, command: u16, args: &[u8]) -> (u8, Vec<u8>) {
+        if self.unsupported.contains(&command) {
+            return (NOT_SUPPORTED, vec![]);
+        }
+        let arg = |i: usize| args.get(i).copied().unwrap_or(0);
+        let all_custom = self.perf.iter().all(|&(perf, _)| perf == PERF_CUSTOM);
+
+        match command {
+            cmd::GET_FIRMWARE_VERSION => (SUCCESSFUL, vec![1, 0]),
+            cmd::GET_PERF_MODE => match zone_index(arg(1)) {
+                Some(zone) => (
+                    SUCCESSFUL,
+                    vec![0, arg(1), self.perf[zone].0, self.perf[zone].1],
+                ),
+                None => (FAILURE, vec![]),
+            },
+            cmd::SET_PERF_MODE => {
+                let (perf, fan) = (arg(2), arg(3));
+                let valid = matches!(perf, PERF_BALANCED | PERF_CUSTOM | PERF_SILENT)
+                    && matches!(fan, FAN_AUTO | FAN_MANUAL)
+                    && (fan == FAN_AUTO || perf == PERF_BALANCED);
+                match zone_index(arg(1)) {
+                    Some(zone) if valid => {
+                        self.perf[zone] = (perf, fan);
+                        if fan == FAN_AUTO {
+                            self.fan_rpm[zone] = AUTO_FAN_RPM;
+                        }
+                        if perf != PERF_CUSTOM {
+                            self.max_fan_speed = 0;
+                        }
+                        (SUCCESSFUL, vec![])
+                    }
+                    _ => (FAILURE, vec![]),
+                }
+            }
+            cmd::GET_BOOST => match zone_index(arg(1)) {
+                Some(cluster) => (SUCCESSFUL, vec![0, arg(1), self.boost[cluster]]),
+                None => (FAILURE, vec![]),
+            },
+            cmd::SET_BOOST => {
+                let max = if arg(1) == 1 { 4 } else { 2 };
+                match zone_index(arg(1)) {
+                    Some(cluster) if all_custom && arg(2) <= max => {
+                        self.boost[cluster] = arg(2);
+                        (SUCCESSFUL, vec![])
+                    }
+                    _ => (FAILURE, vec![]),
+                }
+            }
+            cmd::GET_FAN_RPM => match zone_index(arg(1)) {
+                Some(zone) => (SUCCESSFUL, vec![0, arg(1), self.fan_rpm[zone]]),
+                None => (FAILURE, vec![]),
+            },
+            cmd::SET_FAN_RPM => match zone_index(arg(1)) {
+                Some(zone)
+                    if self.perf[zone] == (PERF_BALANCED, FAN_MANUAL)
+                        && (20..=50).contains(&arg(2)) =>
+                {
+                    self.fan_rpm[zone] = arg(2);
+                    (SUCCESSFUL, vec![])
+                }
+                _ => (FAILURE, vec![]),
+            },
+            cmd::GET_MAX_FAN_SPEED => (SUCCESSFUL, vec![self.max_fan_speed]),
+            cmd::SET_MAX_FAN_SPEED if all_custom && matches!(arg(0), 0 | 2) => {
+                self.max_fan_speed = arg(0);
+                (SUCCESSFUL, vec![])
+            }
+            cmd::GET_KBD_BRIGHTNESS => (SUCCESSFUL, vec![1, 5, self.kbd_brightness]),
+            cmd::SET_KBD_BRIGHTNESS => {
+                self.kbd_brightness = arg(2);
+                (SUCCESSFUL, vec![])
+            }
+            cmd::GET_LOGO_POWER => (SUCCESSFUL, vec![1, 4, 1]),
+            cmd::GET_LOGO_MODE => (SUCCESSFUL, vec![1, 4, 0]),
+            cmd::GET_LIGHTS_ALWAYS_ON => (SUCCESSFUL, vec![0, 0]),
+            cmd::GET_BATTERY_CARE => (SUCCESSFUL, vec![0x50]),
+            _ => (FAILURE, vec![]),
+        }
+    }
+
+    /// Takes the fault for the report being received, if any, and moves the
+    /// others one report closer.
+    fn next_fault(&mut self) -> Option<Fault> {
+        let fault = self
+            .faults
+            .iter()
+            .position(|&(after, _)| after == 0)
+            .map(|index| self.faults.remove(index).1);
+        for (after, _) in &mut self.faults {
+            *after -= 1;
+        }
+        fault
+    }
+}
+
+/// `request` with its status, leading arguments and CRC replaced.
+fn respond(request: &[u8], status: u8, args: &[u8]) -> [u8; Packet::SIZE] {
+    let mut bytes: [u8; Packet::SIZE] = request.try_into().unwrap();
+    bytes[0] = status;
+    bytes[5] = bytes[5].max(args.len() as u8);
+    bytes[8..8 + args.len()].copy_from_slice(args);
+    bytes[88] = bytes[2..88].iter().fold(0, |crc, byte| crc ^ byte);
+    bytes
+}
+
+impl Transport for Ec {
+    fn send_feature_report(&self, data: &[u8]) -> Result<()> {
+        // skip report id byte
+        let request = Packet::try_from(&data[1..])?;
+        let (command, args) = (request.command(), request.get_args().to_vec());
+        let mut state = self.lock();
+        state.log.push((command, args.clone()));
+        let (status, reply, busy) = match state.next_fault() {
+            Some(Fault::Failure) => (FAILURE, vec![], 0),
+            Some(Fault::Busy(reads)) => {
+                let (status, reply) = state.handle(command, &args);
+                (status, reply, reads)
+            }
+            None => {
+                let (status, reply) = state.handle(command, &args);
+                (status, reply, 0)
+            }
+        };
+        state.response = Some((respond(&data[1..], status, &reply), busy));
+        Ok(())
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut state = self.lock();
+        let (response, busy) = state
+            .response
+            .as_mut()
+            .ok_or_else(|| RazerError::Other("No report sent to the EC".to_string()))?;
+        let bytes = if *busy > 0 {
+            *busy -= 1;
+            respond(response, BUSY, &[])
+        } else {
+            *response
+        };
+        buf[1..=Packet::SIZE].copy_from_slice(&bytes);
+        Ok(1 + Packet::SIZE)
+    }
+
+    fn sleep(&self, _duration: Duration) {}
+}
+
+fn descriptor() -> Descriptor {
+    SUPPORTED[0].with_features(feature::ALL_FEATURES)
+}
+
+fn laptop(ec: &Ec, descriptor: Descriptor) -> Device {
+    Device::with_transport(ec.clone(), descriptor)
+}
+
+fn rpm(rpm: u16) -> FanRpm {
+    FanRpm::new(rpm).unwrap()
+}
+
+#[test]
+fn test_custom_boosts_then_manual_fan() {
+    let ec = Ec::new();
+    let device = laptop(&ec, descriptor());
+
+    // detect: the firmware answers and every declared feature probes fine
+    assert_eq!(command::get_firmware_version(&device).unwrap(), (1, 0));
+    assert!(device.probe_features().unsupported.is_empty());
+    ec.take_log();
+
+    let state = DeviceState::read_from(&device, device.info().features);
+    assert_eq!(state.perf_mode, Some(PerfMode::Balanced));
+    assert_eq!(state.fan_mode, Some(FanMode::Auto));
+    assert_eq!((state.cpu_boost, state.fan_rpm), (None, None));
+    assert_eq!(state.keyboard_brightness, Some(Brightness::new(128)));
+    assert!(!state.zone_mismatch);
+    // boosts and fan speed mean nothing in Balanced/Auto and aren't read
+    let commands = ec.take_commands();
+    assert!(!commands.contains(&cmd::GET_BOOST));
+    assert!(!commands.contains(&cmd::GET_FAN_RPM));
+
+    command::set_perf_mode(&device, PerfMode::Custom).unwrap();
+    assert_eq!(
+        ec.take_log(),
+        [
+            (cmd::SET_PERF_MODE, vec![1, 1, PERF_CUSTOM, FAN_AUTO]),
+            (cmd::SET_PERF_MODE, vec![1, 2, PERF_CUSTOM, FAN_AUTO]),
+        ]
+    );
+
+    command::set_cpu_boost(&device, CpuBoost::Boost).unwrap();
+    command::set_gpu_boost(&device, GpuBoost::High).unwrap();
+    assert_eq!(
+        ec.take_commands(),
+        [
+            cmd::GET_PERF_MODE,
+            cmd::GET_PERF_MODE,
+            cmd::SET_BOOST,
+            cmd::GET_PERF_MODE,
+            cmd::GET_PERF_MODE,
+            cmd::SET_BOOST,
+        ]
+    );
+    assert_eq!(ec.lock().boost, [3, 2]);
+
+    command::set_perf_mode(&device, PerfMode::Balanced).unwrap();
+    command::set_fan_mode(&device, FanMode::Manual).unwrap();
+    command::set_fan_rpm(&device, rpm(4000)).unwrap();
+    assert_eq!(
+        ec.take_log(),
+        [
+            (cmd::SET_PERF_MODE, vec![1, 1, PERF_BALANCED, FAN_AUTO]),
+            (cmd::SET_PERF_MODE, vec![1, 2, PERF_BALANCED, FAN_AUTO]),
+            (cmd::GET_PERF_MODE, vec![0, 1, 0, 0]),
+            (cmd::GET_PERF_MODE, vec![0, 2, 0, 0]),
+            (cmd::SET_PERF_MODE, vec![1, 1, PERF_BALANCED, FAN_MANUAL]),
+            (cmd::SET_PERF_MODE, vec![1, 2, PERF_BALANCED, FAN_MANUAL]),
+            (cmd::GET_PERF_MODE, vec![0, 1, 0, 0]),
+            (cmd::GET_PERF_MODE, vec![0, 2, 0, 0]),
+            (cmd::SET_FAN_RPM, vec![0, 1, 40]),
+            (cmd::SET_FAN_RPM, vec![0, 2, 40]),
+        ]
+    );
+
+    let state = DeviceState::read_from(&device, device.info().features);
+    assert_eq!(state.perf_mode, Some(PerfMode::Balanced));
+    assert_eq!(state.fan_mode, Some(FanMode::Manual));
+    assert_eq!(state.fan_rpm, Some(rpm(4000)));
+}
+
+#[test]
+fn test_apply_orders_commands_for_the_preconditions() {
+    let ec = Ec::new();
+    let device = laptop(&ec, descriptor());
+
+    // fields in the order a naive apply would get wrong
+    let profile = DeviceState {
+        cpu_boost: Some(CpuBoost::Overclock.into()),
+        perf_mode: Some(PerfMode::Custom),
+        ..Default::default()
+    };
+    profile.apply_to(&device).unwrap();
+    assert_eq!(ec.perf(), [(PERF_CUSTOM, FAN_AUTO); 2]);
+    assert_eq!(ec.lock().boost[0], 4);
+
+    let profile = DeviceState {
+        perf_mode: Some(PerfMode::Balanced),
+        fan_rpm: Some(rpm(3500)),
+        ..Default::default()
+    };
+    profile.apply_to(&device).unwrap();
+    assert_eq!(ec.perf(), [(PERF_BALANCED, FAN_MANUAL); 2]);
+    assert_eq!(ec.lock().fan_rpm, [35, 35]);
+}
+
+#[test]
+fn test_fan_mode_outside_balanced_keeps_perf_mode() {
+    let ec = Ec::new();
+    let device = laptop(&ec, descriptor());
+    command::set_perf_mode(&device, PerfMode::Custom).unwrap();
+    command::set_cpu_boost(&device, CpuBoost::High).unwrap();
+    ec.take_log();
+
+    // switching the fan would silently drop Custom mode and its boosts
+    let error = command::set_fan_mode(&device, FanMode::Manual)
+        .unwrap_err()
+        .into_root();
+    assert!(matches!(
+        error,
+        RazerError::ModeRequired {
+            remedy: Remedy::PerfMode(PerfMode::Balanced),
+            ..
+        }
+    ));
+    assert_eq!(ec.take_commands(), [cmd::GET_PERF_MODE, cmd::GET_PERF_MODE]);
+    assert_eq!(ec.perf(), [(PERF_CUSTOM, FAN_AUTO); 2]);
+    assert_eq!(ec.lock().boost[0], 2);
+
+    // and a fan speed needs Manual first
+    command::set_perf_mode(&device, PerfMode::Balanced).unwrap();
+    ec.take_log();
+    let error = command::set_fan_rpm(&device, rpm(4000))
+        .unwrap_err()
+        .into_root();
+    assert!(matches!(
+        error,
+        RazerError::ModeRequired {
+            remedy: Remedy::FanMode(FanMode::Manual),
+            ..
+        }
+    ));
+    assert!(!ec.take_commands().contains(&cmd::SET_FAN_RPM));
+}
+
+#[test]
+fn test_busy_mid_sequence() {
+    let ec = Ec::new();
+    let mut device = laptop(&ec, descriptor());
+
+    // a short Busy on the second zone is read out, not re-sent
+    ec.inject(1, Fault::Busy(2));
+    command::set_perf_mode(&device, PerfMode::Custom).unwrap();
+    assert_eq!(ec.take_commands(), [cmd::SET_PERF_MODE; 2]);
+    assert_eq!(ec.perf(), [(PERF_CUSTOM, FAN_AUTO); 2]);
+
+    // Busy past the budget fails the sequence after the first zone
+    ec.inject(1, Fault::Busy(u32::MAX));
+    let error = command::set_perf_mode(&device, PerfMode::Silent)
+        .unwrap_err()
+        .into_root();
+    assert!(matches!(error, RazerError::DeviceBusy));
+    assert_eq!(ec.take_commands(), [cmd::SET_PERF_MODE; 2]);
+    ec.lock().response = None;
+
+    // with a retry the report is sent again and the sequence completes
+    device.set_send_options(SendOptions {
+        retries: 1,
+        ..SendOptions::DEFAULT
+    });
+    ec.inject(1, Fault::Busy(100));
+    command::set_perf_mode(&device, PerfMode::Balanced).unwrap();
+    assert_eq!(ec.take_commands(), [cmd::SET_PERF_MODE; 3]);
+    assert_eq!(ec.perf(), [(PERF_BALANCED, FAN_AUTO); 2]);
+}
+
+#[test]
+fn test_failure_leaves_zones_mismatched() {
+    let ec = Ec::new();
+    let device = laptop(&ec, descriptor());
+
+    ec.inject(1, Fault::Failure);
+    let error = command::set_perf_mode(&device, PerfMode::Custom)
+        .unwrap_err()
+        .into_root();
+    assert!(matches!(error, RazerError::CommandFailed));
+    assert_eq!(
+        ec.perf(),
+        [(PERF_CUSTOM, FAN_AUTO), (PERF_BALANCED, FAN_AUTO)]
+    );
+
+    assert!(matches!(
+        command::get_perf_mode(&device).unwrap_err().into_root(),
+        RazerError::ZoneMismatch { .. }
+    ));
+    let state = DeviceState::read_from(&device, device.info().features);
+    assert!(state.zone_mismatch);
+    assert_eq!(state.perf_mode, Some(PerfMode::Custom));
+
+    // nothing that needs a mode is sent while the zones disagree
+    ec.take_log();
+    assert!(command::set_cpu_boost(&device, CpuBoost::Boost).is_err());
+    assert_eq!(ec.take_commands(), [cmd::GET_PERF_MODE, cmd::GET_PERF_MODE]);
+
+    // setting the mode again brings them back in sync
+    command::set_perf_mode(&device, PerfMode::Custom).unwrap();
+    assert_eq!(
+        command::get_perf_mode(&device).unwrap(),
+        (PerfMode::Custom, FanMode::Auto)
+    );
+    command::set_cpu_boost(&device, CpuBoost::Boost).unwrap();
+}
+
+#[test]
+fn test_unsupported_features() {
+    let ec = Ec::new().without(&[cmd::GET_KBD_BRIGHTNESS, cmd::SET_KBD_BRIGHTNESS]);
+
+    // a descriptor without the feature never sends its commands
+    let device = laptop(
+        &ec,
+        SUPPORTED[0].with_features(&[feature::PERF, feature::FAN]),
+    );
+    let state = DeviceState::read_from(&device, device.info().features);
+    assert_eq!(state.perf_mode, Some(PerfMode::Balanced));
+    assert_eq!(state.keyboard_brightness, None);
+    assert!(!ec.take_commands().contains(&cmd::GET_KBD_BRIGHTNESS));
+
+    // one that declares it learns from the EC
+    let device = laptop(&ec, descriptor());
+    assert!(matches!(
+        command::get_keyboard_brightness(&device)
+            .unwrap_err()
+            .into_root(),
+        RazerError::CommandNotSupported
+    ));
+    assert!(command::set_keyboard_brightness(&device, 10).is_err());
+    assert_eq!(ec.lock().kbd_brightness, 128);
+
+    let probed = device.probe_features();
+    assert!(!probed.supported.contains(&feature::KBDBACKLIGHT));
+    assert!(probed.supported.contains(&feature::PERF));
+
+    // the rest of the state still reads
+    let state = DeviceState::read_from(&device, device.info().features);
+    assert_eq!(state.keyboard_brightness, None);
+    assert_eq!(state.fan_mode, Some(FanMode::Auto));
+}
+
+#[test]
+fn test_restore_after_cold_boot() {
+    let features = [feature::PERF, feature::FAN, feature::KBDBACKLIGHT];
+    let ec = Ec::new();
+    let device = laptop(&ec, SUPPORTED[0].with_features(&features));
+    command::set_perf_mode(&device, PerfMode::Custom).unwrap();
+    command::set_cpu_boost(&device, CpuBoost::Boost).unwrap();
+    command::set_gpu_boost(&device, GpuBoost::High).unwrap();
+    command::set_keyboard_brightness(&device, 40).unwrap();
+    let saved = SavedSettings::from_state(&DeviceState::read_from(&device, &features));
+
+    // the EC forgot everything, and this one rejects the keyboard backlight
+    let ec = Ec::new().without(&[cmd::SET_KBD_BRIGHTNESS]);
+    let device = laptop(&ec, SUPPORTED[0].with_features(&features));
+    let report = saved.restore_to(&device);
+    assert_eq!(ec.perf(), [(PERF_CUSTOM, FAN_AUTO); 2]);
+    assert_eq!(ec.lock().boost, [3, 2]);
+    let outcome = |field: &str| {
+        let entry = report.iter().find(|entry| entry.field == field).unwrap();
+        entry.outcome.clone()
+    };
+    for field in ["perf_mode", "fan_mode", "cpu_boost", "gpu_boost"] {
+        assert_eq!(outcome(field), Restored::Restored, "{}", field);
+    }
+    assert!(matches!(
+        outcome("keyboard_brightness"),
+        Restored::Skipped(_)
+    ));
+
+    // a manual fan speed in Balanced mode
+    let state = DeviceState {
+        perf_mode: Some(PerfMode::Balanced),
+        fan_mode: Some(FanMode::Manual),
+        fan_rpm: Some(rpm(4000)),
+        ..Default::default()
+    };
+    let report = SavedSettings::from_state(&state).restore_to(&device);
+    assert!(report.iter().all(|e| e.outcome == Restored::Restored));
+    assert_eq!(ec.perf(), [(PERF_BALANCED, FAN_MANUAL); 2]);
+    assert_eq!(ec.lock().fan_rpm, [40, 40]);
+}
@@ -0,0 +1,83 @@
+//! Runs the service protocol end to end: a simulated laptop served on one side
+//! of a socket, the client on the other.
+
+#![cfg(feature = "simulation")]
+
+use librazer::device::SharedDevice;
+use librazer::error::{ErrorCode, RazerError};
+use librazer::ipc::{self, Client};
+use librazer::simulation;
+use librazer::state::DeviceState;
+use librazer::types::{CpuBoost, FanMode, FanRpm, PerfMode};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+/// Serves the simulated laptop to one client and connects it.
+fn connect() -> (Client<TcpStream>, thread::JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let device = SharedDevice::new(simulation::device());
+        ipc::serve(&device, stream).unwrap();
+    });
+    (Client::new(TcpStream::connect(address).unwrap()), server)
+}
+
+#[test]
+fn test_status_and_apply() {
+    let (mut client, server) = connect();
+
+    let status = client.status().unwrap();
+    assert_eq!(status.name, simulation::SIMULATED.name);
+    assert_eq!(status.model, "SIMULATED");
+    assert_eq!(status.state.perf_mode, Some(PerfMode::Balanced));
+    assert_eq!(status.state.fan_mode, Some(FanMode::Auto));
+
+    let profile = DeviceState {
+        fan_rpm: Some(FanRpm::new(4000).unwrap()),
+        ..Default::default()
+    };
+    client.apply(&profile).unwrap();
+    let state = client.status().unwrap().state;
+    assert_eq!(state.fan_mode, Some(FanMode::Manual));
+    assert_eq!(state.fan_rpm, Some(FanRpm::new(4000).unwrap()));
+
+    let profile = DeviceState {
+        perf_mode: Some(PerfMode::Custom),
+        cpu_boost: Some(CpuBoost::Boost.into()),
+        ..Default::default()
+    };
+    client.apply(&profile).unwrap();
+    let state = client.status().unwrap().state;
+    assert_eq!(state.perf_mode, Some(PerfMode::Custom));
+    assert_eq!(state.cpu_boost, Some(CpuBoost::Boost.into()));
+
+    drop(client);
+    server.join().unwrap();
+}
+
+#[test]
+fn test_errors_keep_their_code() {
+    let (mut client, server) = connect();
+
+    let profile = DeviceState {
+        cpu_boost: Some(CpuBoost::Boost.into()),
+        ..Default::default()
+    };
+    let error = client.apply(&profile).unwrap_err();
+    assert!(matches!(
+        error,
+        RazerError::Remote {
+            code: ErrorCode::Precondition,
+            ..
+        }
+    ));
+    assert!(error.to_string().contains("Custom"), "{}", error);
+
+    // the connection survives a failed request
+    assert!(client.status().is_ok());
+
+    drop(client);
+    server.join().unwrap();
+}
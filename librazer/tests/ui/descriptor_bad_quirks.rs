@@ -0,0 +1,16 @@
+use librazer::descriptor::{Descriptor, Quirks};
+
+const BLADE: Descriptor = librazer::descriptor! {
+    name: "Razer Blade 15\" (2022)",
+    prefix: "RZ09-0421",
+    pid: 0x028a,
+    features: [FAN],
+    quirks: Quirks {
+        lenient_short_reads: true,
+        ..Quirks::NONE
+    },
+};
+
+fn main() {
+    let _ = BLADE;
+}
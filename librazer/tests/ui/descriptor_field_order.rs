@@ -0,0 +1,12 @@
+use librazer::descriptor::Descriptor;
+
+const BLADE: Descriptor = librazer::descriptor! {
+    prefix: "RZ09-0421",
+    name: "Razer Blade 15\" (2022)",
+    pid: 0x028a,
+    features: [FAN],
+};
+
+fn main() {
+    let _ = BLADE;
+}
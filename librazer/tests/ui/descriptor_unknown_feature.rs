@@ -0,0 +1,12 @@
+use librazer::descriptor::Descriptor;
+
+const BLADE: Descriptor = librazer::descriptor! {
+    name: "Razer Blade 15\" (2022)",
+    prefix: "RZ09-0421",
+    pid: 0x028a,
+    features: [FAN, RGB],
+};
+
+fn main() {
+    let _ = BLADE;
+}
@@ -0,0 +1,18 @@
+use librazer::descriptor::{validate_model_prefixes, Descriptor, GENERIC};
+
+const DESCRIPTORS: &[Descriptor] = &[
+    Descriptor {
+        model_number_prefixes: &["RZ09-0421"],
+        pid: 0x0270,
+        ..GENERIC
+    },
+    Descriptor {
+        model_number_prefixes: &["RZ09-0421"],
+        pid: 0x0276,
+        ..GENERIC
+    },
+];
+
+const _: () = validate_model_prefixes(DESCRIPTORS);
+
+fn main() {}
@@ -0,0 +1,38 @@
+use librazer::error::{ErrorCode, RazerError};
+
+/// Errors returned over D-Bus. The names, like `org.razerctl.Error.Precondition`,
+/// follow [`ErrorCode`], so clients can map them back to a category.
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "org.razerctl.Error")]
+pub enum Error {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    Other(String),
+    DeviceNotFound(String),
+    Permission(String),
+    Unsupported(String),
+    Precondition(String),
+    Busy(String),
+    Protocol(String),
+    /// The caller isn't authorized by polkit.
+    AccessDenied(String),
+    /// An argument didn't parse.
+    InvalidArgs(String),
+}
+
+impl From<RazerError> for Error {
+    fn from(error: RazerError) -> Self {
+        let message = error.to_string();
+        match error.code() {
+            ErrorCode::Other => Error::Other(message),
+            ErrorCode::DeviceNotFound => Error::DeviceNotFound(message),
+            ErrorCode::Permission => Error::Permission(message),
+            ErrorCode::Unsupported => Error::Unsupported(message),
+            ErrorCode::Precondition => Error::Precondition(message),
+            ErrorCode::Busy => Error::Busy(message),
+            ErrorCode::Protocol => Error::Protocol(message),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
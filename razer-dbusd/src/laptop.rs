@@ -0,0 +1,267 @@
+use crate::error::{Error, Result};
+use crate::polkit;
+use clap::ValueEnum;
+use librazer::async_device::AsyncDevice;
+use librazer::persist::{Restored, SavedSettings};
+use librazer::state::DeviceState;
+use librazer::types::{FanRpm, PerfMode};
+use log::{debug, info, warn};
+use serde::Serialize;
+use zbus::object_server::SignalEmitter;
+
+/// Object path the laptop is served at.
+pub const PATH: &str = "/org/razerctl/Laptop1";
+
+/// The `org.razerctl.Laptop1` interface.
+///
+/// Properties mirror [`DeviceState`]: enums are their JSON names, like
+/// `"balanced"`, and settings that couldn't be read are empty or 0. Every
+/// change goes through polkit unless the service runs on the session bus.
+pub struct Laptop {
+    device: AsyncDevice,
+    state: DeviceState,
+    authorize: bool,
+}
+
+impl Laptop {
+    pub async fn new(device: AsyncDevice, authorize: bool) -> Result<Laptop> {
+        let mut laptop = Laptop {
+            device,
+            state: DeviceState::default(),
+            authorize,
+        };
+        laptop.state = laptop.read_state().await?;
+        Ok(laptop)
+    }
+
+    async fn read_state(&self) -> Result<DeviceState> {
+        let features = self.device.info().features;
+        Ok(self
+            .device
+            .run(move |device| Ok(DeviceState::read_from(device, features)))
+            .await?)
+    }
+
+    /// Re-reads the device and emits `PropertiesChanged` for what differs.
+    pub async fn refresh(&mut self, emitter: &SignalEmitter<'_>) -> Result<()> {
+        let state = self.read_state().await?;
+        let old = std::mem::replace(&mut self.state, state);
+        let new = &self.state;
+        if old == *new {
+            return Ok(());
+        }
+        debug!("State changed: {:?}", new);
+        if old.perf_mode != new.perf_mode {
+            self.perf_mode_changed(emitter).await?;
+        }
+        if old.fan_mode != new.fan_mode {
+            self.fan_mode_changed(emitter).await?;
+        }
+        if old.fan_rpm != new.fan_rpm {
+            self.fan_rpm_changed(emitter).await?;
+        }
+        if old.cpu_boost != new.cpu_boost {
+            self.cpu_boost_changed(emitter).await?;
+        }
+        if old.gpu_boost != new.gpu_boost {
+            self.gpu_boost_changed(emitter).await?;
+        }
+        if old.max_fan_speed != new.max_fan_speed {
+            self.max_fan_speed_changed(emitter).await?;
+        }
+        if old.keyboard_brightness != new.keyboard_brightness {
+            self.keyboard_brightness_changed(emitter).await?;
+        }
+        if old.logo_mode != new.logo_mode {
+            self.logo_mode_changed(emitter).await?;
+        }
+        if old.battery_care != new.battery_care {
+            self.battery_care_changed(emitter).await?;
+        }
+        if old.lights_always_on != new.lights_always_on {
+            self.lights_always_on_changed(emitter).await?;
+        }
+        if old.zone_mismatch != new.zone_mismatch {
+            self.zone_mismatch_changed(emitter).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes back `settings`, logging each one that couldn't be restored, and
+    /// publishes the result.
+    pub async fn restore(
+        &mut self,
+        settings: SavedSettings,
+        emitter: &SignalEmitter<'_>,
+    ) -> Result<()> {
+        let report = self
+            .device
+            .run(move |device| Ok(settings.restore_to(device)))
+            .await?;
+        for entry in &report {
+            match &entry.outcome {
+                Restored::Restored => debug!("Restored {}", entry.field),
+                Restored::Skipped(reason) => info!("Skipped {}: {}", entry.field, reason),
+                Restored::Failed(reason) => warn!("Failed to restore {}: {}", entry.field, reason),
+            }
+        }
+        info!(
+            "Restored {} of {} saved settings",
+            report
+                .iter()
+                .filter(|e| e.outcome == Restored::Restored)
+                .count(),
+            report.len()
+        );
+        self.refresh(emitter).await
+    }
+
+    /// Checks the caller with polkit, applies `profile` and publishes the result.
+    async fn apply(
+        &mut self,
+        profile: DeviceState,
+        connection: &zbus::Connection,
+        header: &zbus::message::Header<'_>,
+        emitter: &SignalEmitter<'_>,
+    ) -> Result<()> {
+        if self.authorize {
+            polkit::authorize(connection, header, polkit::CONFIGURE_ACTION).await?;
+        }
+        info!("Applying {:?}", profile);
+        let result = self
+            .device
+            .run(move |device| profile.apply_to(device))
+            .await;
+        // a failed profile may have been applied in part
+        self.refresh(emitter).await?;
+        Ok(result?)
+    }
+}
+
+/// JSON name of a setting, like `balanced`, or an empty string if it wasn't read.
+fn name<T: Serialize>(value: &Option<T>) -> String {
+    match value.as_ref().map(serde_json::to_value) {
+        Some(Ok(serde_json::Value::String(name))) => name,
+        Some(Ok(value)) => value.to_string(),
+        _ => String::new(),
+    }
+}
+
+#[zbus::interface(name = "org.razerctl.Laptop1")]
+impl Laptop {
+    #[zbus(property)]
+    fn name(&self) -> String {
+        self.device.info().name.to_string()
+    }
+
+    #[zbus(property)]
+    fn model(&self) -> String {
+        self.device.info().model_number_prefix().to_string()
+    }
+
+    #[zbus(property)]
+    fn perf_mode(&self) -> String {
+        name(&self.state.perf_mode)
+    }
+
+    #[zbus(property)]
+    fn fan_mode(&self) -> String {
+        name(&self.state.fan_mode)
+    }
+
+    /// Manual fan speed, 0 in automatic mode.
+    #[zbus(property)]
+    fn fan_rpm(&self) -> u16 {
+        self.state.fan_rpm.map_or(0, u16::from)
+    }
+
+    #[zbus(property)]
+    fn cpu_boost(&self) -> String {
+        name(&self.state.cpu_boost)
+    }
+
+    #[zbus(property)]
+    fn gpu_boost(&self) -> String {
+        name(&self.state.gpu_boost)
+    }
+
+    #[zbus(property)]
+    fn max_fan_speed(&self) -> String {
+        name(&self.state.max_fan_speed)
+    }
+
+    #[zbus(property)]
+    fn keyboard_brightness(&self) -> u8 {
+        self.state.keyboard_brightness.map_or(0, u8::from)
+    }
+
+    #[zbus(property)]
+    fn logo_mode(&self) -> String {
+        name(&self.state.logo_mode)
+    }
+
+    #[zbus(property)]
+    fn battery_care(&self) -> String {
+        name(&self.state.battery_care)
+    }
+
+    #[zbus(property)]
+    fn lights_always_on(&self) -> String {
+        name(&self.state.lights_always_on)
+    }
+
+    #[zbus(property)]
+    fn zone_mismatch(&self) -> bool {
+        self.state.zone_mismatch
+    }
+
+    /// The whole state as `status --json` prints it.
+    fn get_state(&self) -> Result<String> {
+        serde_json::to_string(&self.state).map_err(|e| Error::Other(e.to_string()))
+    }
+
+    /// Sets the performance mode by name, like `balanced`.
+    async fn set_perf_mode(
+        &mut self,
+        mode: &str,
+        #[zbus(connection)] connection: &zbus::Connection,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<()> {
+        let mode = PerfMode::from_str(mode, true).map_err(Error::InvalidArgs)?;
+        let profile = DeviceState {
+            perf_mode: Some(mode),
+            ..Default::default()
+        };
+        self.apply(profile, connection, &header, &emitter).await
+    }
+
+    /// Switches the fan to manual mode at `rpm`.
+    async fn set_fan_rpm(
+        &mut self,
+        rpm: u16,
+        #[zbus(connection)] connection: &zbus::Connection,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<()> {
+        let profile = DeviceState {
+            fan_rpm: Some(FanRpm::new(rpm)?),
+            ..Default::default()
+        };
+        self.apply(profile, connection, &header, &emitter).await
+    }
+
+    /// Applies a JSON object in the format of [`get_state`](Self::get_state);
+    /// settings it leaves out are not changed.
+    async fn apply_profile(
+        &mut self,
+        profile: &str,
+        #[zbus(connection)] connection: &zbus::Connection,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<()> {
+        let profile: DeviceState =
+            serde_json::from_str(profile).map_err(|e| Error::InvalidArgs(e.to_string()))?;
+        self.apply(profile, connection, &header, &emitter).await
+    }
+}
@@ -0,0 +1,138 @@
+//! D-Bus system service that owns a Razer laptop and exposes its settings as
+//! `org.razerctl.Laptop1`, so desktop integrations need neither root nor the CLI.
+
+mod error;
+mod laptop;
+mod polkit;
+
+use laptop::{Laptop, PATH};
+use librazer::async_device::AsyncDevice;
+use librazer::device::Device;
+use librazer::error::RazerError;
+use librazer::persist::SavedSettings;
+use librazer::rate::{RateLimit, RateLimiter};
+use log::{error, info, warn};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Well-known bus name the service owns.
+const BUS_NAME: &str = "org.razerctl";
+
+/// How often the device is re-read to notice changes made behind the service's
+/// back, like the Fn keys or another tool.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How much longer than [`POLL_INTERVAL`] the wall clock may move between polls
+/// before the system is taken to have been suspended. The poll timer doesn't run
+/// while suspended, the wall clock does.
+const RESUME_GAP: Duration = Duration::from_secs(30);
+
+#[tokio::main]
+async fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format_timestamp(None)
+        .init();
+
+    if let Err(e) = run().await {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Opens the laptop with writes rate limited, so a client setting a value in a
+/// loop can't wear the EC out.
+async fn open_device(no_lock: bool) -> librazer::error::Result<AsyncDevice> {
+    #[cfg(feature = "simulation")]
+    let simulated = librazer::simulation::enabled().then(librazer::simulation::device);
+    #[cfg(not(feature = "simulation"))]
+    let simulated = None;
+    let mut device = match simulated {
+        Some(device) => device,
+        None => tokio::task::spawn_blocking(Device::detect)
+            .await
+            .map_err(|e| RazerError::Other(format!("Blocking task failed: {}", e)))??,
+    };
+    if no_lock {
+        device.set_lock(None);
+    }
+    device.set_rate_limit(Some(RateLimiter::new(RateLimit::DEFAULT)));
+    Ok(AsyncDevice::new(device))
+}
+
+/// Reads the settings `blade_helper save --file` wrote, `None` with a warning if
+/// the file can't be read; entries that don't parse are reported by the restore.
+fn load_saved(path: &Path) -> Option<SavedSettings> {
+    let loaded = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| serde_json::from_str(&contents).map_err(|e| e.to_string()));
+    match loaded {
+        Ok(settings) => Some(settings),
+        Err(e) => {
+            warn!("Not restoring settings from {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    // the session bus has no polkit agent to ask; it is for trying the service out
+    let session = args.iter().any(|arg| arg == "--session");
+    // for setups where nothing else talks to the laptop, or the lock file can't be shared
+    let no_lock = args.iter().any(|arg| arg == "--no-lock");
+    // settings to write back on startup and after every resume
+    let restore = args
+        .iter()
+        .position(|arg| arg == "--restore")
+        .map(|index| args.get(index + 1).map(PathBuf::from))
+        .map(|path| path.ok_or("--restore needs a file"))
+        .transpose()?;
+
+    let device = open_device(no_lock).await?;
+    info!("Serving {} at {}", device.info().name, PATH);
+    let laptop = Laptop::new(device, !session).await?;
+
+    let builder = if session {
+        zbus::connection::Builder::session()?
+    } else {
+        zbus::connection::Builder::system()?
+    };
+    let connection = builder
+        .name(BUS_NAME)?
+        .serve_at(PATH, laptop)?
+        .build()
+        .await?;
+
+    let laptop = connection
+        .object_server()
+        .interface::<_, Laptop>(PATH)
+        .await?;
+    let restore_saved = || async {
+        let Some(settings) = restore.as_deref().and_then(load_saved) else {
+            return;
+        };
+        let emitter = laptop.signal_emitter();
+        if let Err(e) = laptop.get_mut().await.restore(settings, emitter).await {
+            warn!("Failed to restore the saved settings: {}", e);
+        }
+    };
+    restore_saved().await;
+
+    loop {
+        let slept_from = SystemTime::now();
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let slept = slept_from.elapsed().unwrap_or_default();
+        if slept > POLL_INTERVAL + RESUME_GAP {
+            info!("Resumed after {}s asleep", slept.as_secs());
+            restore_saved().await;
+        }
+        if let Err(e) = laptop
+            .get_mut()
+            .await
+            .refresh(laptop.signal_emitter())
+            .await
+        {
+            warn!("Failed to refresh the state: {}", e);
+        }
+    }
+}
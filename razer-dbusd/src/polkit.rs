@@ -0,0 +1,59 @@
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+use zbus::zvariant::Value;
+
+/// polkit action that guards every setting change, see `data/org.razerctl.policy`.
+pub const CONFIGURE_ACTION: &str = "org.razerctl.laptop.configure";
+
+/// Lets polkit ask the user for a password if the action requires one.
+const ALLOW_USER_INTERACTION: u32 = 1;
+
+#[zbus::proxy(
+    interface = "org.freedesktop.PolicyKit1.Authority",
+    default_service = "org.freedesktop.PolicyKit1",
+    default_path = "/org/freedesktop/PolicyKit1/Authority"
+)]
+trait Authority {
+    fn check_authorization(
+        &self,
+        subject: &(&str, HashMap<&str, Value<'_>>),
+        action_id: &str,
+        details: &HashMap<&str, &str>,
+        flags: u32,
+        cancellation_id: &str,
+    ) -> zbus::Result<(bool, bool, HashMap<String, String>)>;
+}
+
+/// Fails with [`Error::AccessDenied`] unless polkit allows the sender of the
+/// current call to perform `action`.
+pub async fn authorize(
+    connection: &zbus::Connection,
+    header: &zbus::message::Header<'_>,
+    action: &str,
+) -> Result<()> {
+    let sender = header
+        .sender()
+        .ok_or_else(|| Error::AccessDenied("The call has no sender".to_string()))?;
+    let subject = (
+        "system-bus-name",
+        HashMap::from([("name", Value::from(sender.as_str()))]),
+    );
+    let authority = AuthorityProxy::new(connection).await?;
+    let (authorized, _, _) = authority
+        .check_authorization(
+            &subject,
+            action,
+            &HashMap::new(),
+            ALLOW_USER_INTERACTION,
+            "",
+        )
+        .await?;
+    if authorized {
+        Ok(())
+    } else {
+        Err(Error::AccessDenied(format!(
+            "Not authorized for {}",
+            action
+        )))
+    }
+}
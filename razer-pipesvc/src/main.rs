@@ -0,0 +1,74 @@
+//! Windows service that owns a Razer laptop and serves its settings on the
+//! named pipe `\\.\pipe\razer-ctl`, so the CLI works without administrator
+//! rights. The protocol is [`librazer::ipc`].
+
+#[cfg(windows)]
+mod pipe;
+
+use log::error;
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format_timestamp(None)
+        .init();
+
+    if let Err(e) = run() {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(windows)]
+/// Opens the laptop with writes rate limited, so a client setting a value in a
+/// loop can't wear the EC out.
+fn open_device(no_lock: bool) -> librazer::error::Result<librazer::device::Device> {
+    use librazer::rate::{RateLimit, RateLimiter};
+
+    #[cfg(feature = "simulation")]
+    let simulated = librazer::simulation::enabled().then(librazer::simulation::device);
+    #[cfg(not(feature = "simulation"))]
+    let simulated = None;
+    let mut device = match simulated {
+        Some(device) => device,
+        None => librazer::device::Device::detect()?,
+    };
+    if no_lock {
+        device.set_lock(None);
+    }
+    device.set_rate_limit(Some(RateLimiter::new(RateLimit::DEFAULT)));
+    Ok(device)
+}
+
+#[cfg(windows)]
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    use librazer::device::SharedDevice;
+    use librazer::ipc;
+    use log::{debug, info, warn};
+
+    let no_lock = std::env::args().any(|arg| arg == "--no-lock");
+    let device = SharedDevice::new(open_device(no_lock)?);
+    let mut listener = pipe::Listener::new()?;
+    info!("Serving {} at {}", device.info().name, ipc::PIPE_NAME);
+    loop {
+        let stream = match listener.accept() {
+            Ok(stream) => stream,
+            Err(pipe::AcceptError::Client(e)) => {
+                warn!("Client failed to connect: {}", e);
+                continue;
+            }
+            Err(pipe::AcceptError::Listen(e)) => return Err(e.into()),
+        };
+        debug!("Client connected");
+        let device = device.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = ipc::serve(&device, stream) {
+                warn!("Dropped a client: {}", e);
+            }
+        });
+    }
+}
+
+#[cfg(not(windows))]
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    Err("razer-pipesvc only runs on Windows; use razer-dbusd on Linux".into())
+}
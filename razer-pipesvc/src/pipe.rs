@@ -0,0 +1,115 @@
+//! Server end of the named pipe. std can only open pipes, so instances are
+//! created with the Win32 API and handed out as [`File`]s.
+
+use librazer::ipc::PIPE_NAME;
+use std::fs::File;
+use std::io;
+use std::os::windows::io::FromRawHandle;
+use std::ptr;
+use windows_sys::Win32::Foundation::{ERROR_PIPE_CONNECTED, INVALID_HANDLE_VALUE};
+use windows_sys::Win32::Security::Authorization::{
+    ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+};
+use windows_sys::Win32::Security::SECURITY_ATTRIBUTES;
+use windows_sys::Win32::Storage::FileSystem::{FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX};
+use windows_sys::Win32::System::Pipes::{
+    ConnectNamedPipe, CreateNamedPipeW, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE,
+    PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+};
+
+/// Full access for SYSTEM and administrators, read and write for interactive
+/// users. Network logons get nothing, and remote clients are rejected anyway.
+const SDDL: &str = "D:(A;;GA;;;SY)(A;;GA;;;BA)(A;;GRGW;;;IU)";
+
+const BUFFER_SIZE: u32 = 4096;
+
+/// Why [`Listener::accept`] returned no client.
+#[derive(Debug)]
+pub enum AcceptError {
+    /// No pipe instance could be created, e.g. because another process already
+    /// serves the pipe. Accepting again won't help.
+    Listen(io::Error),
+    /// A client went away while connecting, e.g. with `ERROR_NO_DATA`. Only
+    /// that client is affected.
+    Client(io::Error),
+}
+
+/// Creates an instance of the pipe for each client.
+pub struct Listener {
+    name: Vec<u16>,
+    attributes: SECURITY_ATTRIBUTES,
+    first: bool,
+}
+
+impl Listener {
+    pub fn new() -> io::Result<Listener> {
+        let sddl = wide(SDDL);
+        let mut descriptor = ptr::null_mut();
+        // SAFETY: `sddl` is NUL-terminated and `descriptor` receives a
+        // LocalAlloc'ed descriptor, kept for the life of the process.
+        let converted = unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.as_ptr(),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                ptr::null_mut(),
+            )
+        };
+        if converted == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Listener {
+            name: wide(PIPE_NAME),
+            attributes: SECURITY_ATTRIBUTES {
+                nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+                lpSecurityDescriptor: descriptor,
+                bInheritHandle: 0,
+            },
+            first: true,
+        })
+    }
+
+    /// Waits for the next client. The first instance fails if another process
+    /// already serves the pipe, so nobody can squat on it before the service.
+    pub fn accept(&mut self) -> Result<File, AcceptError> {
+        let mut open_mode = PIPE_ACCESS_DUPLEX;
+        if self.first {
+            open_mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
+        }
+        // SAFETY: the name is NUL-terminated and the attributes outlive the call.
+        let handle = unsafe {
+            CreateNamedPipeW(
+                self.name.as_ptr(),
+                open_mode,
+                PIPE_TYPE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                &self.attributes,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(AcceptError::Listen(io::Error::last_os_error()));
+        }
+        self.first = false;
+        // SAFETY: `handle` is a new pipe instance nothing else owns.
+        let pipe = unsafe { File::from_raw_handle(handle) };
+
+        // SAFETY: `handle` is open, owned by `pipe`, and not overlapped.
+        if unsafe { ConnectNamedPipe(handle, ptr::null_mut()) } == 0 {
+            // the client may connect between creating and waiting
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() != Some(ERROR_PIPE_CONNECTED as i32) {
+                // dropping `pipe` closes the instance
+                return Err(AcceptError::Client(error));
+            }
+        }
+        Ok(pipe)
+    }
+}
+
+/// `s` as a NUL-terminated UTF-16 string.
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}